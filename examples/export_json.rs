@@ -0,0 +1,21 @@
+//! Write the capability matrix JSON that web-based diagnostics pages accept,
+//! to stdout or the file given as the first argument.
+//!
+//! ```sh
+//! cargo run --example export_json --features schema -- gpus.json
+//! ```
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let gpus = gpu_info::GpuQuery::all().run()?;
+    let json = gpu_info::export::to_capability_json(&gpus);
+    match std::env::args().nth(1).filter(|_| !cfg!(test)) {
+        Some(path) => std::fs::write(path, json)?,
+        None => println!("{json}"),
+    }
+    Ok(())
+}
+
+#[test]
+fn test_export_json() {
+    main().unwrap();
+}
@@ -0,0 +1,28 @@
+//! Print every GPU with the fields most applications show.
+//!
+//! ```sh
+//! cargo run --example list_gpus
+//! ```
+
+fn main() -> Result<(), gpu_info::Error> {
+    let gpus = gpu_info::retrieve_gpu_info()?;
+    if gpus.is_empty() {
+        println!("No GPUs found");
+    }
+    for gpu in &gpus {
+        println!("{}", gpu.display_name());
+        println!("  vendor:  {}", gpu.vendor);
+        println!("  kind:    {}", gpu.kind.as_str());
+        println!("  VRAM:    {}", gpu.vram);
+        println!("  driver:  {}", gpu.driver_version);
+        if let Some(address) = gpu.pci_address {
+            println!("  PCI:     {address}");
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_list_gpus() {
+    main().unwrap();
+}
@@ -0,0 +1,34 @@
+//! Pick the GPU to run on: the one the user chose last time if it is still
+//! there, the fastest one otherwise.
+//!
+//! ```sh
+//! cargo run --example pick_best_gpu
+//! ```
+
+use gpu_info::{GpuList, GpuPreference};
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let path = std::env::temp_dir().join("gpu-info-example/preferred-gpu");
+    let gpus = gpu_info::retrieve_gpu_info()?;
+
+    let preference = GpuPreference::load(&path)?.unwrap_or_default();
+    let Some(gpu) = preference.resolve_or_fastest(&gpus) else {
+        println!("No GPUs found");
+        return Ok(());
+    };
+    println!("Using {}", gpu.display_name());
+    // In an application, save once the user picks a GPU in the settings
+    GpuPreference::save(gpu, &path)?;
+
+    let mut ranked = GpuList::from(gpus.clone());
+    ranked.sort_by_performance();
+    for (rank, gpu) in ranked.iter().enumerate() {
+        println!("{}. {} ({})", rank + 1, gpu.display_name(), gpu.vram);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_pick_best_gpu() {
+    main().unwrap();
+}
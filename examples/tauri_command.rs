@@ -0,0 +1,31 @@
+//! The shape of a Tauri command returning the GPUs to a web frontend. With
+//! the `specta` feature the frontend can use the generated TypeScript types.
+//!
+//! In a Tauri app, annotate `gpus` with `#[tauri::command]` and register it
+//! with `tauri::generate_handler![gpus]`. Tauri serializes the result with
+//! serde, as `main` does here, and runs it off the main thread.
+//!
+//! ```sh
+//! cargo run --example tauri_command
+//! ```
+
+use gpu_info::{GpuQuery, GPU};
+
+/// Errors cross to the frontend as strings
+fn gpus() -> Result<Vec<GPU>, String> {
+    GpuQuery::new()
+        .with_displays()
+        .run()
+        .map_err(|e| e.to_string())
+}
+
+fn main() -> Result<(), serde_json::Error> {
+    let response = gpus();
+    println!("{}", serde_json::to_string_pretty(&response)?);
+    Ok(())
+}
+
+#[test]
+fn test_tauri_command() {
+    main().unwrap();
+}
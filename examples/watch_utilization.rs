@@ -0,0 +1,35 @@
+//! Sample how busy each engine of every GPU is, like a task manager.
+//!
+//! ```sh
+//! cargo run --example watch_utilization
+//! ```
+
+use std::time::Duration;
+
+const SAMPLES: usize = 3;
+const INTERVAL: Duration = Duration::from_millis(500);
+
+fn main() -> Result<(), gpu_info::Error> {
+    let gpus = gpu_info::retrieve_gpu_info()?;
+    for _ in 0..SAMPLES {
+        for gpu in &gpus {
+            // Blocks for `INTERVAL` where the OS only reports busy time
+            let classes = gpu_info::engine_utilization(gpu, INTERVAL)?;
+            let line = classes
+                .iter()
+                .map(|class| format!("{} {:.0}%", class.class.as_str(), class.busy * 100.0))
+                .collect::<Vec<_>>()
+                .join(", ");
+            println!("{}: {}", gpu.display_name(), line);
+        }
+        if gpus.is_empty() {
+            std::thread::sleep(INTERVAL);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_watch_utilization() {
+    main().unwrap();
+}
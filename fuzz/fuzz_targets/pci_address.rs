@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &str| {
+    if let Ok(address) = data.parse::<gpu_info::PciAddress>() {
+        // Whatever parses must survive a round trip
+        assert_eq!(address.to_string().parse(), Ok(address));
+    }
+});
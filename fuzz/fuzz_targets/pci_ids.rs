@@ -0,0 +1,8 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: (u16, u16, &str)| {
+    let (vendor_id, device_id, pci_ids) = input;
+    let _ = gpu_info::fuzzing::lookup_device_name(pci_ids, vendor_id.into(), device_id.into());
+});
@@ -0,0 +1,223 @@
+//! NPUs and other inference accelerators, which graphics APIs do not expose
+
+use crate::PciAddress;
+
+/// Peak INT8 TOPS from vendor spec sheets, keyed by PCI vendor and device id
+#[cfg(not(target_os = "macos"))]
+const KNOWN_TOPS: &[(u32, u32, u32)] = &[
+    (0x8086, 0x7D1D, 11), // Intel Meteor Lake NPU
+    (0x8086, 0xAD1D, 13), // Intel Arrow Lake NPU
+    (0x8086, 0x643E, 48), // Intel Lunar Lake NPU
+    (0x1022, 0x17F0, 50), // AMD XDNA 2 (Strix Point)
+];
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct Accelerator {
+    pub name: String,
+    pub vendor: String,
+    /// Kernel driver on Linux (e.g. `intel_vpu`), driver service on Windows
+    pub driver: Option<String>,
+    pub pci_address: Option<PciAddress>,
+    /// Estimated peak INT8 throughput, `None` if the model is not known
+    pub tops: Option<u32>,
+    /// Hardware generation, e.g. `h14` for the Apple M2 Neural Engine
+    pub generation: Option<String>,
+    pub core_count: Option<u32>,
+    /// 0 is means the accelerator shares system memory
+    pub memory: u64, // MB
+}
+
+pub fn retrieve_accelerators() -> Vec<Accelerator> {
+    #[cfg(target_os = "linux")]
+    let accelerators = sysfs::retrieve_accelerators();
+    #[cfg(windows)]
+    let accelerators = setupapi::retrieve_accelerators();
+    #[cfg(target_os = "macos")]
+    let accelerators = iokit::retrieve_accelerators();
+    #[cfg(not(any(target_os = "linux", windows, target_os = "macos")))]
+    let accelerators = vec![];
+
+    accelerators
+}
+
+#[cfg(not(target_os = "macos"))]
+fn known_tops(vendor_id: u32, device_id: u32) -> Option<u32> {
+    KNOWN_TOPS
+        .iter()
+        .find(|(vendor, device, _)| *vendor == vendor_id && *device == device_id)
+        .map(|(_, _, tops)| *tops)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn vendor_name(vendor_id: u32) -> String {
+    match vendor_id {
+        0x1022 => "AMD".to_string(),
+        0x17CB => "Qualcomm".to_string(),
+        0x1DA3 => "Habana".to_string(),
+        _ => crate::vendor_from_id(vendor_id).to_string(),
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod sysfs {
+    use std::fs;
+    use std::path::Path;
+
+    use super::{known_tops, vendor_name, Accelerator};
+    use crate::pci::{lookup_device_name, read_driver, read_hex, read_pci_ids};
+
+    /// Compute accelerators register here, e.g. `intel_vpu`, `amdxdna`, `qaic`
+    const SYSFS_ACCEL: &str = "/sys/class/accel";
+
+    pub fn retrieve_accelerators() -> Vec<Accelerator> {
+        let Ok(entries) = fs::read_dir(SYSFS_ACCEL) else {
+            return vec![];
+        };
+        let pci_ids = read_pci_ids();
+        let mut accelerators = Vec::new();
+
+        for entry in entries.flatten() {
+            let device = entry.path().join("device");
+            let Ok(device) = fs::canonicalize(device) else {
+                continue;
+            };
+            accelerators.push(read_accelerator(&device, pci_ids.as_deref()));
+        }
+
+        accelerators.sort_by_key(|accelerator| accelerator.pci_address);
+        accelerators
+    }
+
+    fn read_accelerator(device: &Path, pci_ids: Option<&str>) -> Accelerator {
+        let vendor_id = read_hex(&device.join("vendor")).unwrap_or_default();
+        let device_id = read_hex(&device.join("device")).unwrap_or_default();
+        let name = pci_ids
+            .and_then(|pci_ids| lookup_device_name(pci_ids, vendor_id, device_id))
+            .unwrap_or_else(|| format!("PCI device {vendor_id:04x}:{device_id:04x}"));
+
+        Accelerator {
+            name,
+            vendor: vendor_name(vendor_id),
+            driver: read_driver(device),
+            pci_address: device
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.parse().ok()),
+            tops: known_tops(vendor_id, device_id),
+            generation: None,
+            core_count: None,
+            memory: 0,
+        }
+    }
+}
+
+#[cfg(windows)]
+mod setupapi {
+    use super::{known_tops, vendor_name, Accelerator};
+    use crate::dxgi::class_devices;
+    use windows::Win32::Devices::DeviceAndDriverInstallation::GUID_DEVCLASS_COMPUTEACCELERATOR;
+
+    /// NPUs install under the ComputeAccelerator device class, invisible to DXGI
+    pub fn retrieve_accelerators() -> Vec<Accelerator> {
+        class_devices(&GUID_DEVCLASS_COMPUTEACCELERATOR)
+            .into_iter()
+            .map(|device| {
+                let ids = device.pci_ids();
+                // Qualcomm NPUs are ACPI devices, e.g. `ACPI\VEN_QCOM&DEV_0D0A`
+                let vendor = match ids {
+                    Some((vendor_id, _)) => vendor_name(vendor_id),
+                    None if device.hardware_id.contains("VEN_QCOM") => "Qualcomm".to_string(),
+                    None => "Unknown".to_string(),
+                };
+                Accelerator {
+                    name: device.description.unwrap_or(device.hardware_id),
+                    vendor,
+                    driver: device.service,
+                    pci_address: None,
+                    tops: ids.and_then(|(vendor_id, device_id)| known_tops(vendor_id, device_id)),
+                    generation: None,
+                    core_count: None,
+                    memory: 0,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod iokit {
+    use objc2_core_foundation::{CFDictionary, CFNumber, CFString, CFType};
+    use objc2_metal::{MTLCreateSystemDefaultDevice, MTLDevice};
+
+    use super::Accelerator;
+    use crate::metal::get_iokit_service_properties;
+
+    /// Neural Engine cores and TOPS by chip, from Apple's spec sheets.
+    /// Ultra chips are listed first as their names contain the base chip's.
+    const ANE_SPECS: &[(&str, u32, u32)] = &[
+        ("M1 Ultra", 32, 22),
+        ("M2 Ultra", 32, 32),
+        ("M3 Ultra", 32, 36),
+        ("M1", 16, 11),
+        ("M2", 16, 16),
+        ("M3", 16, 18),
+        ("M4", 16, 38),
+    ];
+
+    pub fn retrieve_accelerators() -> Vec<Accelerator> {
+        let Some(properties) = get_iokit_service_properties(c"H11ANEIn") else {
+            return vec![];
+        };
+        let properties = unsafe { properties.cast_unchecked::<CFString, CFType>() };
+        let device_properties = properties
+            .get(&CFString::from_str("DeviceProperties"))
+            .and_then(|value| value.downcast::<CFDictionary>().ok());
+        let device_property = |key: &str| {
+            let dict = unsafe {
+                device_properties
+                    .as_ref()?
+                    .cast_unchecked::<CFString, CFType>()
+            };
+            dict.get(&CFString::from_str(key))
+        };
+
+        // The Metal device of Apple Silicon is named after the chip, e.g. `Apple M2 Max`
+        let chip = MTLCreateSystemDefaultDevice()
+            .map(|device| device.name().to_string())
+            .unwrap_or_default();
+        let spec = ANE_SPECS.iter().find(|(name, _, _)| chip.contains(name));
+
+        vec![Accelerator {
+            name: "Apple Neural Engine".to_string(),
+            vendor: "Apple".to_string(),
+            driver: None,
+            pci_address: None,
+            tops: spec.map(|(_, _, tops)| *tops),
+            generation: device_property("ANEDevicePropertyTypeANEArchitectureTypeStr")
+                .and_then(|value| value.downcast::<CFString>().ok())
+                .map(|value| value.to_string()),
+            core_count: device_property("ANEDevicePropertyNumANECores")
+                .and_then(|value| value.downcast::<CFNumber>().ok())
+                .and_then(|value| value.as_i64())
+                .map(|cores| cores as u32)
+                .or(spec.map(|(_, cores, _)| *cores)),
+            memory: 0,
+        }]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retrieve_accelerators() {
+        let accelerators = retrieve_accelerators();
+        eprintln!("{accelerators:#?}");
+        for accelerator in accelerators {
+            assert!(!accelerator.name.is_empty());
+        }
+    }
+}
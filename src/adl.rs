@@ -0,0 +1,417 @@
+use std::ffi::{c_char, c_int, c_void, CStr};
+
+use crate::{Celsius, MegaHertz, PciAddress, TemperatureLocation, Watts, GPU};
+
+#[cfg(target_pointer_width = "64")]
+const ADL_LIB: &str = "atiadlxx.dll";
+#[cfg(not(target_pointer_width = "64"))]
+const ADL_LIB: &str = "atiadlxy.dll";
+
+const ADL_OK: c_int = 0;
+const ADL_MAX_PATH: usize = 256;
+const ADL_PMLOG_MAX_SENSORS: usize = 256;
+/// The AMD PCI vendor id, as ADL lists other adapters too
+const AMD_VENDOR_ID: c_int = 0x1002;
+
+// Indices into `ADLPMLogDataOutput::sensors`, from `ADL_PMLOG_SENSORS`
+const PMLOG_CLK_GFXCLK: usize = 1;
+const PMLOG_CLK_MEMCLK: usize = 2;
+const PMLOG_TEMPERATURE_EDGE: usize = 8;
+const PMLOG_TEMPERATURE_MEM: usize = 9;
+const PMLOG_TEMPERATURE_VRVDDC: usize = 10;
+const PMLOG_FAN_RPM: usize = 14;
+const PMLOG_FAN_PERCENTAGE: usize = 15;
+const PMLOG_INFO_ACTIVITY_GFX: usize = 19;
+const PMLOG_INFO_ACTIVITY_MEM: usize = 20;
+const PMLOG_ASIC_POWER: usize = 23;
+const PMLOG_TEMPERATURE_HOTSPOT: usize = 27;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AdlError {
+    #[error("ADL is not available: {0}")]
+    NotSupported(String),
+    #[error("Failed to perform ADL operation: {0}")]
+    OperationFailed(String),
+}
+
+impl AdlError {
+    pub fn is_not_supported(&self) -> bool {
+        matches!(self, AdlError::NotSupported(_))
+    }
+}
+
+/// The sensors the AMD driver's performance overlay shows. Unsupported
+/// sensors, which vary by GPU generation, are `None`.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct AdlMetrics {
+    /// Load of the graphics engine, from 0.0 to 1.0
+    pub gpu_utilization: Option<f32>,
+    /// Load of the memory controller, from 0.0 to 1.0
+    pub memory_utilization: Option<f32>,
+    pub clock_speed: Option<MegaHertz>,
+    pub memory_clock: Option<MegaHertz>,
+    /// Edge temperature, the one older tools report
+    pub temperature: Option<Celsius>,
+    /// Hottest spot of the die, RDNA and later
+    pub hotspot_temperature: Option<Celsius>,
+    pub memory_temperature: Option<Celsius>,
+    /// Voltage regulator of the core
+    pub vrm_temperature: Option<Celsius>,
+    pub fan_rpm: Option<u32>,
+    /// Fan speed in percent of its maximum
+    pub fan_percent: Option<u32>,
+    /// Power drawn by the chip
+    pub power: Option<Watts>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AdlGpu {
+    pub name: String,
+    pub pci_address: PciAddress,
+    pub metrics: AdlMetrics,
+}
+
+/// `AdapterInfo` with its Windows only fields
+#[repr(C)]
+struct AdapterInfo {
+    size: c_int,
+    adapter_index: c_int,
+    udid: [c_char; ADL_MAX_PATH],
+    bus_number: c_int,
+    device_number: c_int,
+    function_number: c_int,
+    vendor_id: c_int,
+    adapter_name: [c_char; ADL_MAX_PATH],
+    display_name: [c_char; ADL_MAX_PATH],
+    present: c_int,
+    exist: c_int,
+    driver_path: [c_char; ADL_MAX_PATH],
+    driver_path_ext: [c_char; ADL_MAX_PATH],
+    pnp_string: [c_char; ADL_MAX_PATH],
+    os_display_index: c_int,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SingleSensorData {
+    supported: c_int,
+    value: c_int,
+}
+
+#[repr(C)]
+struct PmLogDataOutput {
+    size: c_int,
+    sensors: [SingleSensorData; ADL_PMLOG_MAX_SENSORS],
+}
+
+type MallocCallback = unsafe extern "system" fn(c_int) -> *mut c_void;
+type ControlCreate = unsafe extern "system" fn(MallocCallback, c_int, *mut *mut c_void) -> c_int;
+type ControlDestroy = unsafe extern "system" fn(*mut c_void) -> c_int;
+type NumberOfAdapters = unsafe extern "system" fn(*mut c_void, *mut c_int) -> c_int;
+type AdapterInfoGet = unsafe extern "system" fn(*mut c_void, *mut AdapterInfo, c_int) -> c_int;
+type PmLogDataGet = unsafe extern "system" fn(*mut c_void, c_int, *mut PmLogDataOutput) -> c_int;
+
+/// ADL only allocates through this for the output of calls not made here,
+/// whatever it allocates is leaked
+unsafe extern "system" fn adl_malloc(size: c_int) -> *mut c_void {
+    match std::alloc::Layout::from_size_align(size.max(1) as usize, 16) {
+        Ok(layout) => unsafe { std::alloc::alloc(layout).cast() },
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// A loaded ADL library and context
+struct Adl {
+    context: *mut c_void,
+    destroy: ControlDestroy,
+    number_of_adapters: NumberOfAdapters,
+    adapter_info: AdapterInfoGet,
+    pmlog_data: PmLogDataGet,
+    // Unloaded after the context is destroyed
+    _lib: libloading::Library,
+}
+
+impl Adl {
+    fn open() -> Result<Self, AdlError> {
+        let lib = unsafe { libloading::Library::new(ADL_LIB) }
+            .map_err(|e| AdlError::NotSupported(e.to_string()))?;
+        let create: ControlCreate = symbol(&lib, b"ADL2_Main_Control_Create\0")?;
+        let destroy: ControlDestroy = symbol(&lib, b"ADL2_Main_Control_Destroy\0")?;
+        let number_of_adapters: NumberOfAdapters =
+            symbol(&lib, b"ADL2_Adapter_NumberOfAdapters_Get\0")?;
+        let adapter_info: AdapterInfoGet = symbol(&lib, b"ADL2_Adapter_AdapterInfo_Get\0")?;
+        // Added with Adrenalin 19.x for Navi
+        let pmlog_data: PmLogDataGet = symbol(&lib, b"ADL2_New_QueryPMLogData_Get\0")?;
+
+        let mut context = std::ptr::null_mut();
+        // 1 lists only adapters present in the system
+        check(
+            unsafe { create(adl_malloc, 1, &mut context) },
+            "control create",
+        )?;
+        Ok(Self {
+            context,
+            destroy,
+            number_of_adapters,
+            adapter_info,
+            pmlog_data,
+            _lib: lib,
+        })
+    }
+
+    /// One adapter index per AMD GPU, ADL lists one per display output
+    fn gpus(&self) -> Result<Vec<(c_int, String, PciAddress)>, AdlError> {
+        let mut count = 0;
+        check(
+            unsafe { (self.number_of_adapters)(self.context, &mut count) },
+            "number of adapters",
+        )?;
+        if count <= 0 {
+            return Ok(vec![]);
+        }
+        let mut infos: Vec<AdapterInfo> = (0..count)
+            .map(|_| AdapterInfo {
+                size: std::mem::size_of::<AdapterInfo>() as c_int,
+                ..unsafe { std::mem::zeroed() }
+            })
+            .collect();
+        check(
+            unsafe {
+                (self.adapter_info)(
+                    self.context,
+                    infos.as_mut_ptr(),
+                    (infos.len() * std::mem::size_of::<AdapterInfo>()) as c_int,
+                )
+            },
+            "adapter info",
+        )?;
+
+        let mut gpus: Vec<(c_int, String, PciAddress)> = Vec::new();
+        for info in &infos {
+            if info.vendor_id != AMD_VENDOR_ID {
+                continue;
+            }
+            let address = PciAddress {
+                domain: 0,
+                bus: info.bus_number as u8,
+                device: info.device_number as u8,
+                function: info.function_number as u8,
+            };
+            if gpus.iter().any(|(_, _, other)| *other == address) {
+                continue;
+            }
+            let name = unsafe { CStr::from_ptr(info.adapter_name.as_ptr()) }
+                .to_string_lossy()
+                .trim()
+                .to_string();
+            gpus.push((info.adapter_index, name, address));
+        }
+        Ok(gpus)
+    }
+
+    fn metrics(&self, adapter_index: c_int) -> Result<AdlMetrics, AdlError> {
+        let mut output: PmLogDataOutput = unsafe { std::mem::zeroed() };
+        output.size = std::mem::size_of::<PmLogDataOutput>() as c_int;
+        check(
+            unsafe { (self.pmlog_data)(self.context, adapter_index, &mut output) },
+            "pmlog data",
+        )?;
+        Ok(metrics_from_sensors(&output.sensors))
+    }
+}
+
+impl Drop for Adl {
+    fn drop(&mut self) {
+        unsafe { (self.destroy)(self.context) };
+    }
+}
+
+fn symbol<T: Copy>(lib: &libloading::Library, name: &[u8]) -> Result<T, AdlError> {
+    unsafe { lib.get::<T>(name) }
+        .map(|symbol| *symbol)
+        .map_err(|e| AdlError::NotSupported(e.to_string()))
+}
+
+fn check(result: c_int, call: &str) -> Result<(), AdlError> {
+    match result {
+        ADL_OK => Ok(()),
+        code => Err(AdlError::OperationFailed(format!(
+            "{call} failed with error {code}"
+        ))),
+    }
+}
+
+fn metrics_from_sensors(sensors: &[SingleSensorData]) -> AdlMetrics {
+    let sensor = |index: usize| {
+        sensors
+            .get(index)
+            .filter(|sensor| sensor.supported != 0 && sensor.value >= 0)
+            .map(|sensor| sensor.value as u32)
+    };
+    let fraction = |index: usize| sensor(index).map(|percent| percent.min(100) as f32 / 100.0);
+    AdlMetrics {
+        gpu_utilization: fraction(PMLOG_INFO_ACTIVITY_GFX),
+        memory_utilization: fraction(PMLOG_INFO_ACTIVITY_MEM),
+        clock_speed: sensor(PMLOG_CLK_GFXCLK).map(MegaHertz),
+        memory_clock: sensor(PMLOG_CLK_MEMCLK).map(MegaHertz),
+        temperature: sensor(PMLOG_TEMPERATURE_EDGE).map(Celsius),
+        hotspot_temperature: sensor(PMLOG_TEMPERATURE_HOTSPOT).map(Celsius),
+        memory_temperature: sensor(PMLOG_TEMPERATURE_MEM).map(Celsius),
+        vrm_temperature: sensor(PMLOG_TEMPERATURE_VRVDDC).map(Celsius),
+        fan_rpm: sensor(PMLOG_FAN_RPM),
+        fan_percent: sensor(PMLOG_FAN_PERCENTAGE),
+        power: sensor(PMLOG_ASIC_POWER).map(Watts),
+    }
+}
+
+/// Every AMD GPU ADL sees with its current metrics. ADL ships with the AMD
+/// driver; ADLX, its C++ successor, reads the same sensors.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", err))]
+pub fn retrieve_gpu_info_via_adl() -> Result<Vec<AdlGpu>, AdlError> {
+    let adl = Adl::open()?;
+    adl.gpus()?
+        .into_iter()
+        .map(|(adapter_index, name, pci_address)| {
+            Ok(AdlGpu {
+                name,
+                pci_address,
+                metrics: adl.metrics(adapter_index)?,
+            })
+        })
+        .collect()
+}
+
+/// The current [`AdlMetrics`] of one AMD GPU, `None` for other GPUs or
+/// when ADL does not list it
+pub fn adl_metrics(gpu: &GPU) -> Result<Option<AdlMetrics>, AdlError> {
+    if gpu.vendor != "AMD" {
+        return Ok(None);
+    }
+    let adl = Adl::open()?;
+    let gpus = adl.gpus()?;
+    match find_adapter(gpu, &gpus) {
+        Some(adapter_index) => Ok(Some(adl.metrics(adapter_index)?)),
+        None => Ok(None),
+    }
+}
+
+/// Match by PCI address, or by name when ADL lists that name once
+fn find_adapter(gpu: &GPU, gpus: &[(c_int, String, PciAddress)]) -> Option<c_int> {
+    if let Some(address) = gpu.pci_address {
+        return gpus
+            .iter()
+            .find(|(_, _, other)| *other == address)
+            .map(|&(index, _, _)| index);
+    }
+    let mut same_name = gpus.iter().filter(|(_, name, _)| *name == gpu.name);
+    match (same_name.next(), same_name.next()) {
+        (Some(&(index, _, _)), None) => Some(index),
+        _ => None,
+    }
+}
+
+/// Fill in the temperature and clock of AMD GPUs, which Vulkan and DXGI do
+/// not report
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+pub(crate) fn attach_adl_info(mut gpus: Vec<GPU>) -> Vec<GPU> {
+    if !gpus.iter().any(|gpu| gpu.vendor == "AMD") {
+        return gpus;
+    }
+    let adl_gpus = match retrieve_gpu_info_via_adl() {
+        Ok(adl_gpus) => adl_gpus,
+        Err(e) => {
+            log::debug!("skipping adl: {e}");
+            return gpus;
+        }
+    };
+    let adapters: Vec<_> = adl_gpus
+        .iter()
+        .enumerate()
+        .map(|(index, adl_gpu)| (index as c_int, adl_gpu.name.clone(), adl_gpu.pci_address))
+        .collect();
+
+    for gpu in gpus.iter_mut().filter(|gpu| gpu.vendor == "AMD") {
+        if let Some(index) = find_adapter(gpu, &adapters) {
+            apply_metrics(gpu, &adl_gpus[index as usize].metrics);
+        }
+    }
+    gpus
+}
+
+/// Re-read the temperature and clock of one AMD GPU
+pub(crate) fn refresh_adl_info(gpu: &mut GPU) {
+    match adl_metrics(gpu) {
+        Ok(Some(metrics)) => apply_metrics(gpu, &metrics),
+        Ok(None) => {}
+        Err(e) => log::debug!("skipping adl refresh of {}: {e}", gpu.name),
+    }
+}
+
+fn apply_metrics(gpu: &mut GPU, metrics: &AdlMetrics) {
+    gpu.temperatures = crate::temperature_sensors(
+        [
+            (TemperatureLocation::Edge, metrics.temperature),
+            (TemperatureLocation::Hotspot, metrics.hotspot_temperature),
+            (TemperatureLocation::Memory, metrics.memory_temperature),
+            (TemperatureLocation::Vrm, metrics.vrm_temperature),
+        ]
+        .map(|(location, celsius)| (location, celsius.map(|celsius| celsius.0 as f32))),
+    );
+    gpu.clock_speed = metrics.clock_speed;
+    #[cfg(feature = "extra")]
+    for (key, value) in [
+        ("adl.fan_rpm", metrics.fan_rpm),
+        ("adl.power", metrics.power.map(|watts| watts.0)),
+    ] {
+        if let Some(value) = value {
+            gpu.extra.insert(key.to_string(), value.into());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics_from_sensors() {
+        let mut sensors = [SingleSensorData {
+            supported: 0,
+            value: 0,
+        }; ADL_PMLOG_MAX_SENSORS];
+        sensors[PMLOG_CLK_GFXCLK] = SingleSensorData {
+            supported: 1,
+            value: 2430,
+        };
+        sensors[PMLOG_INFO_ACTIVITY_GFX] = SingleSensorData {
+            supported: 1,
+            value: 87,
+        };
+        sensors[PMLOG_TEMPERATURE_EDGE] = SingleSensorData {
+            supported: 1,
+            value: 64,
+        };
+        // Reported but unsupported on this GPU
+        sensors[PMLOG_FAN_RPM] = SingleSensorData {
+            supported: 0,
+            value: 1200,
+        };
+
+        let metrics = metrics_from_sensors(&sensors);
+        assert_eq!(metrics.clock_speed, Some(MegaHertz(2430)));
+        assert_eq!(metrics.gpu_utilization, Some(0.87));
+        assert_eq!(metrics.temperature, Some(Celsius(64)));
+        assert_eq!(metrics.fan_rpm, None);
+        assert_eq!(metrics.power, None);
+    }
+
+    #[test]
+    fn test_retrieve_gpu_info_via_adl() {
+        match retrieve_gpu_info_via_adl() {
+            Ok(gpus) => eprintln!("{gpus:#?}"),
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use std::time::Duration;
+
+use crate::{Error, GPU};
+
+/// Size of the buffers copied for the bandwidth measurement
+pub(crate) const COPY_BYTES: u64 = 64 * 1024 * 1024;
+/// Floats updated by each compute dispatch
+pub(crate) const FMA_ELEMENTS: u64 = 1024 * 1024;
+pub(crate) const FMA_GROUP_SIZE: u32 = 64;
+/// Multiply-adds each invocation performs on its float
+pub(crate) const FMA_ITERATIONS: u32 = 256;
+
+/// Time spent on each of the two measurements, after one warm-up run
+const TARGET: Duration = Duration::from_millis(50);
+
+/// Measured throughput of one GPU. Only meaningful relative to other results
+/// from this crate, as the workloads are far smaller than real ones.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct Benchmark {
+    /// Device local to device local copy, counting bytes read and written
+    pub bandwidth_gbps: f64,
+    /// Single precision, counting a multiply-add as two operations
+    pub gflops: f64,
+}
+
+impl Benchmark {
+    pub(crate) fn new(
+        copies: u32,
+        copy_time: Duration,
+        dispatches: u32,
+        compute_time: Duration,
+    ) -> Self {
+        let bytes = 2 * COPY_BYTES * copies as u64;
+        let flops = 2 * FMA_ELEMENTS * FMA_ITERATIONS as u64 * dispatches as u64;
+        Self {
+            bandwidth_gbps: bytes as f64 / copy_time.as_secs_f64() / 1e9,
+            gflops: flops as f64 / compute_time.as_secs_f64() / 1e9,
+        }
+    }
+}
+
+/// Run a GPU returned by [`retrieve_gpu_info`](crate::retrieve_gpu_info)
+/// through a copy and a multiply-add workload for about 100 ms, to rank GPUs
+/// where spec tables are missing or misleading. `None` when the GPU cannot
+/// be matched.
+pub fn benchmark(gpu: &GPU) -> Result<Option<Benchmark>, Error> {
+    #[cfg(target_os = "macos")]
+    return match crate::find_metal_gpu(gpu)? {
+        Some(metal_gpu) => Ok(Some(crate::metal::benchmark_via_metal(
+            metal_gpu.registry_id,
+        )?)),
+        None => Ok(None),
+    };
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let Some(uuid) = gpu.uuid.as_deref().and_then(crate::parse_uuid) else {
+            return Ok(None);
+        };
+        Ok(crate::vulkan::benchmark_via_vk(&uuid)?)
+    }
+}
+
+/// Call `run` with a repeat count once to warm up and estimate, then with
+/// enough repeats to take about [`TARGET`]
+pub(crate) fn measure<E>(
+    mut run: impl FnMut(u32) -> Result<Duration, E>,
+) -> Result<(u32, Duration), E> {
+    let estimate = run(1)?.max(Duration::from_micros(1));
+    let count = (TARGET.as_secs_f64() / estimate.as_secs_f64()).clamp(1.0, 4096.0) as u32;
+    Ok((count, run(count)?))
+}
+
+/// SPIR-V 1.0 module equivalent to
+///
+/// ```glsl
+/// layout(local_size_x = 64) in;
+/// layout(binding = 0) buffer Data { float data[]; };
+/// void main() {
+///     float value = data[gl_GlobalInvocationID.x];
+///     // unrolled FMA_ITERATIONS times
+///     value = value * 0.999 + 0.001;
+///     data[gl_GlobalInvocationID.x] = value;
+/// }
+/// ```
+///
+/// assembled here so the crate needs no shader compiler
+#[cfg(not(target_os = "macos"))]
+pub(crate) fn fma_shader() -> Vec<u32> {
+    const VOID: u32 = 1;
+    const FUNCTION: u32 = 2;
+    const UINT: u32 = 3;
+    const FLOAT: u32 = 4;
+    const UVEC3: u32 = 5;
+    const UVEC3_INPUT: u32 = 6;
+    const INVOCATION_ID: u32 = 7;
+    const FLOAT_ARRAY: u32 = 8;
+    const DATA_BLOCK: u32 = 9;
+    const DATA_BLOCK_UNIFORM: u32 = 10;
+    const DATA: u32 = 11;
+    const FLOAT_UNIFORM: u32 = 12;
+    const ZERO: u32 = 13;
+    const A: u32 = 14;
+    const B: u32 = 15;
+    const MAIN: u32 = 16;
+    const ENTRY: u32 = 17;
+    const ID3: u32 = 18;
+    const ID: u32 = 19;
+    const ELEMENT: u32 = 20;
+    const VALUE: u32 = 21;
+
+    fn op(code: &mut Vec<u32>, opcode: u32, operands: &[u32]) {
+        code.push((operands.len() as u32 + 1) << 16 | opcode);
+        code.extend_from_slice(operands);
+    }
+
+    // Magic, version, generator, id bound (patched below), schema
+    let mut code = vec![0x0723_0203, 0x0001_0000, 0, 0, 0];
+    let code = &mut code;
+    op(code, 17, &[1]); // OpCapability Shader
+    op(code, 14, &[0, 1]); // OpMemoryModel Logical GLSL450
+    let name = u32::from_le_bytes(*b"main");
+    op(code, 15, &[5, MAIN, name, 0, INVOCATION_ID]); // OpEntryPoint GLCompute
+    op(code, 16, &[MAIN, 17, FMA_GROUP_SIZE, 1, 1]); // OpExecutionMode LocalSize
+    op(code, 71, &[INVOCATION_ID, 11, 28]); // OpDecorate BuiltIn GlobalInvocationId
+    op(code, 71, &[FLOAT_ARRAY, 6, 4]); // OpDecorate ArrayStride
+    op(code, 72, &[DATA_BLOCK, 0, 35, 0]); // OpMemberDecorate Offset
+    op(code, 71, &[DATA_BLOCK, 3]); // OpDecorate BufferBlock
+    op(code, 71, &[DATA, 34, 0]); // OpDecorate DescriptorSet
+    op(code, 71, &[DATA, 33, 0]); // OpDecorate Binding
+    op(code, 19, &[VOID]); // OpTypeVoid
+    op(code, 33, &[FUNCTION, VOID]); // OpTypeFunction
+    op(code, 21, &[UINT, 32, 0]); // OpTypeInt
+    op(code, 22, &[FLOAT, 32]); // OpTypeFloat
+    op(code, 23, &[UVEC3, UINT, 3]); // OpTypeVector
+    op(code, 32, &[UVEC3_INPUT, 1, UVEC3]); // OpTypePointer Input
+    op(code, 59, &[UVEC3_INPUT, INVOCATION_ID, 1]); // OpVariable Input
+    op(code, 29, &[FLOAT_ARRAY, FLOAT]); // OpTypeRuntimeArray
+    op(code, 30, &[DATA_BLOCK, FLOAT_ARRAY]); // OpTypeStruct
+    op(code, 32, &[DATA_BLOCK_UNIFORM, 2, DATA_BLOCK]); // OpTypePointer Uniform
+    op(code, 59, &[DATA_BLOCK_UNIFORM, DATA, 2]); // OpVariable Uniform
+    op(code, 32, &[FLOAT_UNIFORM, 2, FLOAT]); // OpTypePointer Uniform
+    op(code, 43, &[UINT, ZERO, 0]); // OpConstant
+    op(code, 43, &[FLOAT, A, 0.999f32.to_bits()]); // OpConstant
+    op(code, 43, &[FLOAT, B, 0.001f32.to_bits()]); // OpConstant
+    op(code, 54, &[VOID, MAIN, 0, FUNCTION]); // OpFunction
+    op(code, 248, &[ENTRY]); // OpLabel
+    op(code, 61, &[UVEC3, ID3, INVOCATION_ID]); // OpLoad
+    op(code, 81, &[UINT, ID, ID3, 0]); // OpCompositeExtract
+    op(code, 65, &[FLOAT_UNIFORM, ELEMENT, DATA, ZERO, ID]); // OpAccessChain
+    op(code, 61, &[FLOAT, VALUE, ELEMENT]); // OpLoad
+
+    let mut value = VALUE;
+    for _ in 0..FMA_ITERATIONS {
+        op(code, 133, &[FLOAT, value + 1, value, A]); // OpFMul
+        op(code, 129, &[FLOAT, value + 2, value + 1, B]); // OpFAdd
+        value += 2;
+    }
+
+    op(code, 62, &[ELEMENT, value]); // OpStore
+    op(code, 253, &[]); // OpReturn
+    op(code, 56, &[]); // OpFunctionEnd
+    code[3] = value + 1;
+    std::mem::take(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_benchmark_new() {
+        let benchmark = Benchmark::new(10, Duration::from_secs(1), 100, Duration::from_secs(2));
+        assert!((benchmark.bandwidth_gbps - 1.342_177_28).abs() < 1e-9);
+        assert!((benchmark.gflops - 26.843_545_6).abs() < 1e-9);
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn test_fma_shader() {
+        let code = fma_shader();
+        assert_eq!(code[0], 0x0723_0203);
+
+        // Instruction word counts must add up to the module length
+        let mut offset = 5;
+        let mut instructions = 0;
+        while offset < code.len() {
+            let words = (code[offset] >> 16) as usize;
+            assert!(words > 0);
+            offset += words;
+            instructions += 1;
+        }
+        assert_eq!(offset, code.len());
+        assert_eq!(instructions, 34 + 2 * FMA_ITERATIONS as usize);
+        assert_eq!(code[3], 22 + 2 * FMA_ITERATIONS);
+    }
+
+    #[test]
+    fn test_benchmark() {
+        for gpu in crate::retrieve_gpu_info().unwrap() {
+            match benchmark(&gpu) {
+                Ok(result) => eprintln!("{}: {result:?}", gpu.name),
+                Err(e) => eprintln!("{}: {e}", gpu.name),
+            }
+        }
+    }
+}
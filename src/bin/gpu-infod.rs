@@ -0,0 +1,40 @@
+//! Shares one GPU sampling loop between the processes of a machine, see
+//! `gpu_info::serve`.
+//!
+//! Usage: `gpu-infod [--interval-ms N] [SOCKET]`
+
+#[cfg(unix)]
+use std::path::PathBuf;
+#[cfg(unix)]
+use std::time::Duration;
+
+#[cfg(unix)]
+fn main() {
+    let mut interval = gpu_info::DEFAULT_INTERVAL;
+    let mut path = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--interval-ms" => {
+                let Some(ms) = args.next().and_then(|ms| ms.parse().ok()) else {
+                    eprintln!("--interval-ms expects a number of milliseconds");
+                    std::process::exit(2);
+                };
+                interval = Duration::from_millis(ms);
+            }
+            _ => path = Some(PathBuf::from(arg)),
+        }
+    }
+
+    let path = path.unwrap_or_else(gpu_info::default_socket_path);
+    if let Err(e) = gpu_info::serve(&path, interval) {
+        eprintln!("gpu-infod: {}: {e}", path.display());
+        std::process::exit(1);
+    }
+}
+
+#[cfg(not(unix))]
+fn main() {
+    eprintln!("gpu-infod: unix only, Windows named pipes are not supported, see serve_http");
+    std::process::exit(1);
+}
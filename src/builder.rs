@@ -0,0 +1,259 @@
+use crate::{
+    Bytes, Celsius, DisplayOutput, GPUKind, GpuPartition, HybridRole, MegaHertz, OverclockSetting,
+    PciAddress, ProductSegment, TemperatureLocation, TemperatureSensor, Watts, GPU,
+};
+
+/// Builds a [`GPU`] field by field, for fixtures in tests and demos that keep
+/// compiling when fields are added. Unset fields keep their default.
+///
+/// ```
+/// use gpu_info::{GPUKind, GPU};
+///
+/// let gpu = GPU::builder()
+///     .name("NVIDIA GeForce RTX 4090")
+///     .vendor("NVIDIA")
+///     .kind(GPUKind::Discrete)
+///     .vram_gib(24)
+///     .build();
+/// assert_eq!(gpu.vram.as_mb(), 24 * 1024);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct GpuBuilder(GPU);
+
+/// One setter per field, taking the value itself for `Option` fields
+macro_rules! setters {
+    ($($field:ident: $ty:ty),* $(,)?) => {
+        $(
+            pub fn $field(mut self, $field: impl Into<$ty>) -> Self {
+                self.0.$field = $field.into();
+                self
+            }
+        )*
+    };
+}
+
+macro_rules! optional_setters {
+    ($($field:ident: $ty:ty),* $(,)?) => {
+        $(
+            pub fn $field(mut self, $field: impl Into<$ty>) -> Self {
+                self.0.$field = Some($field.into());
+                self
+            }
+        )*
+    };
+}
+
+/// Getters returning each field by value or as a borrowed view
+macro_rules! getters {
+    (copy { $($field:ident: $ty:ty),* $(,)? }
+     str { $($str_field:ident),* $(,)? }
+     optional_str { $($optional_field:ident),* $(,)? }) => {
+        $(
+            pub fn $field(&self) -> $ty {
+                self.$field
+            }
+        )*
+        $(
+            pub fn $str_field(&self) -> &str {
+                &self.$str_field
+            }
+        )*
+        $(
+            pub fn $optional_field(&self) -> Option<&str> {
+                self.$optional_field.as_deref()
+            }
+        )*
+    };
+}
+
+impl GPU {
+    pub fn builder() -> GpuBuilder {
+        GpuBuilder::default()
+    }
+
+    pub fn kind(&self) -> &GPUKind {
+        &self.kind
+    }
+
+    pub fn displays(&self) -> &[DisplayOutput] {
+        &self.displays
+    }
+
+    pub fn partitions(&self) -> &[GpuPartition] {
+        &self.partitions
+    }
+
+    /// VRAM in MB, the unit [`GPU::vram`] held before it became [`Bytes`]
+    #[deprecated(since = "0.3.0", note = "use `vram.as_mb()`, `vram` now holds `Bytes`")]
+    pub fn vram_mb(&self) -> u64 {
+        self.vram.as_mb()
+    }
+
+    pub fn overclock_settings(&self) -> &[OverclockSetting] {
+        &self.overclock_settings
+    }
+
+    pub fn temperatures(&self) -> &[TemperatureSensor] {
+        &self.temperatures
+    }
+
+    /// The edge temperature rounded to a degree, the single value GPUs had
+    /// before [`GPU::temperatures`]
+    pub fn temperature(&self) -> Option<Celsius> {
+        self.temperature_at(TemperatureLocation::Edge)
+            .map(|celsius| Celsius(celsius.round() as u32))
+    }
+
+    pub fn temperature_at(&self, location: TemperatureLocation) -> Option<f32> {
+        self.temperatures
+            .iter()
+            .find(|sensor| sensor.location == location)
+            .map(|sensor| sensor.celsius)
+    }
+
+    getters! {
+        copy {
+            vram: Bytes,
+            clock_speed: Option<MegaHertz>,
+            compute_queue_count: Option<u32>,
+            async_compute: Option<bool>,
+            luid: Option<u64>,
+            registry_id: Option<u64>,
+            max_displays: Option<u32>,
+            active_displays: Option<u32>,
+            hybrid_role: Option<HybridRole>,
+            pci_address: Option<PciAddress>,
+            has_display_output: Option<bool>,
+            tdp_watts: Option<Watts>,
+            min_power_limit: Option<Watts>,
+            max_power_limit: Option<Watts>,
+            shared_memory: Option<Bytes>,
+            recommended_working_set: Option<Bytes>,
+            carve_out: Option<Bytes>,
+            tile_count: Option<u32>,
+            passthrough: Option<bool>,
+            hardware_ray_tracing: Option<bool>,
+            mesh_shading: Option<bool>,
+            hardware_scheduling: Option<bool>,
+            linked_node_count: Option<u32>,
+            instance_index: Option<u32>,
+            is_overclocked: Option<bool>,
+            product_segment: Option<ProductSegment>,
+        }
+        str { name, vendor, driver_version }
+        optional_str {
+            driver_model,
+            kernel_driver,
+            uuid,
+            partition_mode,
+            memory_partition_mode,
+            cloud_instance,
+            product_line,
+            translation_layer,
+        }
+    }
+}
+
+impl GpuBuilder {
+    setters! {
+        kind: GPUKind,
+        name: String,
+        vendor: String,
+        driver_version: String,
+        vram: Bytes,
+        displays: Vec<DisplayOutput>,
+        partitions: Vec<GpuPartition>,
+        overclock_settings: Vec<OverclockSetting>,
+        temperatures: Vec<TemperatureSensor>,
+    }
+
+    optional_setters! {
+        clock_speed: MegaHertz,
+        compute_queue_count: u32,
+        async_compute: bool,
+        luid: u64,
+        registry_id: u64,
+        max_displays: u32,
+        active_displays: u32,
+        hybrid_role: HybridRole,
+        driver_model: String,
+        pci_address: PciAddress,
+        kernel_driver: String,
+        has_display_output: bool,
+        uuid: String,
+        partition_mode: String,
+        memory_partition_mode: String,
+        tdp_watts: Watts,
+        min_power_limit: Watts,
+        max_power_limit: Watts,
+        shared_memory: Bytes,
+        recommended_working_set: Bytes,
+        carve_out: Bytes,
+        tile_count: u32,
+        passthrough: bool,
+        cloud_instance: String,
+        hardware_ray_tracing: bool,
+        mesh_shading: bool,
+        hardware_scheduling: bool,
+        linked_node_count: u32,
+        instance_index: u32,
+        is_overclocked: bool,
+        product_line: String,
+        product_segment: ProductSegment,
+        translation_layer: String,
+    }
+
+    pub fn vram_mb(self, mb: u64) -> Self {
+        self.vram(Bytes::from_mb(mb))
+    }
+
+    pub fn vram_gib(self, gib: u64) -> Self {
+        self.vram(Bytes::from_mb(gib * 1024))
+    }
+
+    /// Add an edge sensor, see [`GPU::temperature`]
+    pub fn temperature(mut self, celsius: Celsius) -> Self {
+        self.0.temperatures.push(TemperatureSensor {
+            location: TemperatureLocation::Edge,
+            celsius: celsius.0 as f32,
+        });
+        self
+    }
+
+    /// Add one backend specific value, see [`GPU::extra`]
+    #[cfg(feature = "extra")]
+    pub fn extra(mut self, key: impl Into<String>, value: impl Into<serde_json::Value>) -> Self {
+        self.0.extra.insert(key.into(), value.into());
+        self
+    }
+
+    pub fn build(self) -> GPU {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder() {
+        let gpu = GPU::builder()
+            .name("Intel Arc A770")
+            .kind(GPUKind::Discrete)
+            .vram_mb(16 * 1024)
+            .temperature(Celsius(40))
+            .uuid("00000000-0000-0000-0000-000000000000")
+            .build();
+        assert_eq!(gpu.name, "Intel Arc A770");
+        assert_eq!(gpu.vram, Bytes::from_mb(16384));
+        assert_eq!(gpu.temperatures.len(), 1);
+        assert!(gpu.uuid.is_some());
+        assert_eq!(gpu.clock_speed, None);
+        assert_eq!(gpu.name(), "Intel Arc A770");
+        assert_eq!(gpu.kind(), &GPUKind::Discrete);
+        assert_eq!(gpu.temperature(), Some(Celsius(40)));
+        assert_eq!(gpu.temperature_at(TemperatureLocation::Memory), None);
+        assert_eq!(gpu.kernel_driver(), None);
+    }
+}
@@ -0,0 +1,200 @@
+use std::sync::OnceLock;
+
+use crate::{GPUKind, GPU};
+
+/// Asset tag Azure sets on every VM, telling it apart from a local Hyper-V VM
+const AZURE_ASSET_TAG: &str = "7783-7084-3265-9085-8269-3286-77";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub enum CloudProvider {
+    Aws,
+    Gcp,
+    Azure,
+}
+
+/// The cloud VM this process runs on, from the firmware (DMI) strings.
+/// Metadata services are not contacted, so enumeration stays offline.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct CloudInstance {
+    pub provider: CloudProvider,
+    /// e.g. `g5.xlarge`. Only EC2 puts the instance type in the DMI strings.
+    pub instance_type: Option<String>,
+}
+
+/// GPU models offered by each provider and the instance family they come
+/// in. All tokens of the GPU name must match, more specific entries first.
+const INSTANCE_FAMILIES: &[(CloudProvider, &[&str], &str)] = &[
+    (CloudProvider::Aws, &["A10G"], "g5"),
+    (CloudProvider::Aws, &["T4G"], "g5g"),
+    (CloudProvider::Aws, &["T4"], "g4dn"),
+    (CloudProvider::Aws, &["L4"], "g6"),
+    (CloudProvider::Aws, &["L40S"], "g6e"),
+    (CloudProvider::Aws, &["A100", "80GB"], "p4de"),
+    (CloudProvider::Aws, &["A100"], "p4d"),
+    (CloudProvider::Aws, &["H100"], "p5"),
+    (CloudProvider::Aws, &["H200"], "p5e"),
+    (CloudProvider::Aws, &["V100"], "p3"),
+    (CloudProvider::Aws, &["K80"], "p2"),
+    (CloudProvider::Aws, &["M60"], "g3"),
+    (CloudProvider::Aws, &["V520"], "g4ad"),
+    (CloudProvider::Gcp, &["T4"], "n1"),
+    (CloudProvider::Gcp, &["P4"], "n1"),
+    (CloudProvider::Gcp, &["P100"], "n1"),
+    (CloudProvider::Gcp, &["V100"], "n1"),
+    (CloudProvider::Gcp, &["L4"], "g2"),
+    (CloudProvider::Gcp, &["A100", "80GB"], "a2-ultragpu"),
+    (CloudProvider::Gcp, &["A100"], "a2-highgpu"),
+    (CloudProvider::Gcp, &["H100"], "a3"),
+    (CloudProvider::Gcp, &["H200"], "a3-ultragpu"),
+    (CloudProvider::Azure, &["T4"], "NCasT4_v3"),
+    (CloudProvider::Azure, &["A10"], "NVadsA10_v5"),
+    (CloudProvider::Azure, &["A100", "PCIe"], "NC_A100_v4"),
+    (CloudProvider::Azure, &["A100"], "ND_A100_v4"),
+    (CloudProvider::Azure, &["H100", "NVL"], "NCads_H100_v5"),
+    (CloudProvider::Azure, &["H100"], "ND_H100_v5"),
+    (CloudProvider::Azure, &["V100"], "NCv3"),
+    (CloudProvider::Azure, &["K80"], "NC"),
+    (CloudProvider::Azure, &["M60"], "NV"),
+    (CloudProvider::Azure, &["MI300X"], "ND_MI300X_v5"),
+];
+
+/// `None` outside of EC2, Compute Engine and Azure
+pub fn detect_cloud_instance() -> Option<CloudInstance> {
+    static CLOUD_INSTANCE: OnceLock<Option<CloudInstance>> = OnceLock::new();
+    CLOUD_INSTANCE
+        .get_or_init(|| {
+            let (vendor, product, asset_tag) = read_dmi();
+            from_dmi(&vendor?, product.as_deref(), asset_tag.as_deref())
+        })
+        .clone()
+}
+
+/// Set `GPU::cloud_instance` on the hardware GPUs of a cloud VM
+pub(crate) fn attach_cloud_instance(gpus: &mut [GPU]) {
+    let Some(cloud) = detect_cloud_instance() else {
+        return;
+    };
+    for gpu in gpus {
+        if matches!(gpu.kind, GPUKind::Software | GPUKind::Virtual) {
+            continue;
+        }
+        gpu.cloud_instance = cloud.instance_type.clone().or_else(|| {
+            instance_family(cloud.provider, &gpu.name).map(|family| family.to_string())
+        });
+    }
+}
+
+fn from_dmi(vendor: &str, product: Option<&str>, asset_tag: Option<&str>) -> Option<CloudInstance> {
+    let provider = match vendor.trim() {
+        "Amazon EC2" => CloudProvider::Aws,
+        "Google" => CloudProvider::Gcp,
+        "Microsoft Corporation" if asset_tag.map(str::trim) == Some(AZURE_ASSET_TAG) => {
+            CloudProvider::Azure
+        }
+        _ => return None,
+    };
+    Some(CloudInstance {
+        provider,
+        instance_type: product
+            .map(str::trim)
+            .filter(|product| provider == CloudProvider::Aws && product.contains('.'))
+            .map(str::to_string),
+    })
+}
+
+fn instance_family(provider: CloudProvider, gpu_name: &str) -> Option<&'static str> {
+    let tokens = gpu_name
+        .split(|c: char| c.is_whitespace() || c == '-')
+        .collect::<Vec<_>>();
+    INSTANCE_FAMILIES
+        .iter()
+        .filter(|(entry_provider, _, _)| *entry_provider == provider)
+        .find(|(_, model, _)| {
+            model
+                .iter()
+                .all(|part| tokens.iter().any(|token| token.eq_ignore_ascii_case(part)))
+        })
+        .map(|(_, _, family)| *family)
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn read_dmi() -> (Option<String>, Option<String>, Option<String>) {
+    let read = |name: &str| std::fs::read_to_string(format!("/sys/class/dmi/id/{name}")).ok();
+    (
+        read("sys_vendor"),
+        read("product_name"),
+        read("chassis_asset_tag"),
+    )
+}
+
+#[cfg(windows)]
+pub(crate) fn read_dmi() -> (Option<String>, Option<String>, Option<String>) {
+    let (vendor, product, is_azure) = crate::dxgi::read_bios_strings();
+    (
+        vendor,
+        product,
+        is_azure.then(|| AZURE_ASSET_TAG.to_string()),
+    )
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+pub(crate) fn read_dmi() -> (Option<String>, Option<String>, Option<String>) {
+    (None, None, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_dmi() {
+        assert_eq!(
+            from_dmi("Amazon EC2\n", Some("g5.xlarge\n"), None),
+            Some(CloudInstance {
+                provider: CloudProvider::Aws,
+                instance_type: Some("g5.xlarge".to_string()),
+            })
+        );
+        assert_eq!(
+            from_dmi("Google", Some("Google Compute Engine"), None).map(|c| c.instance_type),
+            Some(None)
+        );
+        // A local Hyper-V VM has the same vendor and product as Azure
+        assert_eq!(
+            from_dmi("Microsoft Corporation", Some("Virtual Machine"), None),
+            None
+        );
+        assert_eq!(
+            from_dmi("Microsoft Corporation", None, Some(AZURE_ASSET_TAG)).map(|c| c.provider),
+            Some(CloudProvider::Azure)
+        );
+    }
+
+    #[test]
+    fn test_instance_family() {
+        assert_eq!(
+            instance_family(CloudProvider::Aws, "NVIDIA A10G"),
+            Some("g5")
+        );
+        assert_eq!(
+            instance_family(CloudProvider::Aws, "Tesla T4"),
+            Some("g4dn")
+        );
+        assert_eq!(
+            instance_family(CloudProvider::Aws, "NVIDIA L40S"),
+            Some("g6e")
+        );
+        assert_eq!(
+            instance_family(CloudProvider::Gcp, "NVIDIA A100-SXM4-80GB"),
+            Some("a2-ultragpu")
+        );
+        assert_eq!(
+            instance_family(CloudProvider::Azure, "NVIDIA GeForce RTX 4090"),
+            None
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use std::fs;
+use std::path::Path;
+
+use crate::{Error, PciAddress, GPU};
+
+pub(crate) const SYSFS_DRM: &str = "/sys/class/drm";
+const UDEV_DATA: &str = "/run/udev/data";
+
+/// Device lists of KWin and wlroots compositors, the first device renders
+const DRM_DEVICE_VARIABLES: &[&str] = &["KWIN_DRM_DEVICES", "WLR_DRM_DEVICES"];
+
+/// udev tag mutter (GNOME) renders on when set
+const MUTTER_PRIMARY_TAG: &str = "mutter-device-preferred-primary";
+
+/// The GPU the Wayland compositor or X server renders on, so applications can
+/// default to the same device and skip a copy between GPUs every frame.
+///
+/// Checked in order: the first device of `KWIN_DRM_DEVICES` or
+/// `WLR_DRM_DEVICES`, a card tagged `mutter-device-preferred-primary` in udev,
+/// then the boot VGA device among the cards driving a display, which is also
+/// the GPU Mesa picks when `DRI_PRIME` is unset. `DRI_PRIME` itself is
+/// ignored, as it only moves the calling process off that GPU. `None` when no
+/// PCI GPU drives a display.
+pub fn compositor_gpu() -> Result<Option<GPU>, Error> {
+    let Some(address) =
+        compositor_pci_address(Path::new(SYSFS_DRM), Path::new(UDEV_DATA), |name| {
+            std::env::var(name).ok()
+        })
+    else {
+        return Ok(None);
+    };
+    Ok(crate::retrieve_gpu_info()?
+        .into_iter()
+        .find(|gpu| gpu.pci_address == Some(address)))
+}
+
+fn compositor_pci_address(
+    drm: &Path,
+    udev_data: &Path,
+    var: impl Fn(&str) -> Option<String>,
+) -> Option<PciAddress> {
+    // Device paths are `:` separated, like `/dev/dri/card1:/dev/dri/card0`
+    let configured = DRM_DEVICE_VARIABLES
+        .iter()
+        .filter_map(|name| var(name))
+        .find_map(|devices| {
+            let device = Path::new(devices.split(':').next()?);
+            let device = fs::canonicalize(device).unwrap_or_else(|_| device.to_path_buf());
+            card_pci_address(drm, device.file_name()?.to_str()?)
+        });
+    if configured.is_some() {
+        return configured;
+    }
+
+    let cards = read_cards(drm);
+    if let Some(card) = cards.iter().find(|card| has_udev_tag(drm, udev_data, card)) {
+        return card_pci_address(drm, card);
+    }
+
+    let boot_vga = |card: &&String| is_boot_vga(drm, card);
+    let displaying = cards
+        .iter()
+        .filter(|card| has_connected_display(&drm.join(card)))
+        .collect::<Vec<_>>();
+    displaying
+        .iter()
+        .copied()
+        .find(boot_vga)
+        .or(displaying.first().copied())
+        .or_else(|| cards.iter().find(boot_vga))
+        .and_then(|card| card_pci_address(drm, card))
+}
+
+/// `cardN` entries, without their connectors such as `card0-DP-1`
+pub(crate) fn read_cards(drm: &Path) -> Vec<String> {
+    let mut cards = fs::read_dir(drm)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .filter(|name| {
+                    name.strip_prefix("card")
+                        .is_some_and(|index| index.parse::<u32>().is_ok())
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    cards.sort();
+    cards
+}
+
+/// `device` links to the PCI device, or to a platform device on SoCs
+pub(crate) fn card_pci_address(drm: &Path, card: &str) -> Option<PciAddress> {
+    let device = fs::canonicalize(drm.join(card).join("device")).ok()?;
+    device.file_name()?.to_str()?.parse().ok()
+}
+
+/// The GPU the firmware initialized, which Mesa treats as the default
+pub(crate) fn is_boot_vga(drm: &Path, card: &str) -> bool {
+    fs::read_to_string(drm.join(card).join("device/boot_vga")).is_ok_and(|v| v.trim() == "1")
+}
+
+/// udev keeps the tags of a device in `c<major>:<minor>` as `G:` lines
+fn has_udev_tag(drm: &Path, udev_data: &Path, card: &str) -> bool {
+    let Ok(dev) = fs::read_to_string(drm.join(card).join("dev")) else {
+        return false;
+    };
+    fs::read_to_string(udev_data.join(format!("c{}", dev.trim()))).is_ok_and(|data| {
+        data.lines()
+            .any(|line| line.strip_prefix("G:") == Some(MUTTER_PRIMARY_TAG))
+    })
+}
+
+fn has_connected_display(card: &Path) -> bool {
+    let Some(name) = card.file_name().and_then(|name| name.to_str()) else {
+        return false;
+    };
+    let prefix = format!("{name}-");
+    fs::read_dir(card).is_ok_and(|entries| {
+        entries.flatten().any(|entry| {
+            entry.file_name().to_string_lossy().starts_with(&prefix)
+                && fs::read_to_string(entry.path().join("status"))
+                    .is_ok_and(|status| status.trim() == "connected")
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compositor_pci_address() {
+        let fixture = tempfile::tempdir().unwrap();
+        let root = fixture.path();
+        let (drm, udev_data) = (root.join("drm"), root.join("udev"));
+        fs::create_dir_all(&udev_data).unwrap();
+        let card = |card: &str, address: &str, minor: u32, boot_vga: bool, connected: bool| {
+            let device = root.join("pci").join(address);
+            fs::create_dir_all(&device).unwrap();
+            fs::write(
+                device.join("boot_vga"),
+                if boot_vga { "1\n" } else { "0\n" },
+            )
+            .unwrap();
+            let connector = drm.join(card).join(format!("{card}-DP-1"));
+            fs::create_dir_all(&connector).unwrap();
+            let status = if connected {
+                "connected\n"
+            } else {
+                "disconnected\n"
+            };
+            fs::write(connector.join("status"), status).unwrap();
+            fs::write(drm.join(card).join("dev"), format!("226:{minor}\n")).unwrap();
+            std::os::unix::fs::symlink(&device, drm.join(card).join("device")).unwrap();
+        };
+        // The iGPU boots, the dGPU has the monitor
+        card("card0", "0000:00:02.0", 0, true, false);
+        card("card1", "0000:03:00.0", 1, false, true);
+
+        let no_env = |_: &str| None;
+        let displaying = compositor_pci_address(&drm, &udev_data, no_env);
+
+        let kwin = |name: &str| {
+            (name == "KWIN_DRM_DEVICES").then(|| "/dev/dri/card0:/dev/dri/card1".to_string())
+        };
+        let configured = compositor_pci_address(&drm, &udev_data, kwin);
+
+        fs::write(
+            udev_data.join("c226:0"),
+            "E:ID_PATH=pci-0000:00:02.0\nG:seat\nG:mutter-device-preferred-primary\n",
+        )
+        .unwrap();
+        let tagged = compositor_pci_address(&drm, &udev_data, no_env);
+        fixture.close().unwrap();
+
+        assert_eq!(displaying, "0000:03:00.0".parse().ok());
+        assert_eq!(configured, "0000:00:02.0".parse().ok());
+        assert_eq!(tagged, "0000:00:02.0".parse().ok());
+    }
+}
@@ -0,0 +1,576 @@
+//! Changing power limits and fans, for thermal management daemons.
+//!
+//! Every call checks what the GPU supports first and fails with
+//! [`ControlError::NotSupported`] instead of writing blindly. Writing needs
+//! root on Linux and administrator rights on Windows; [`GpuControl::dry_run`]
+//! validates a change and describes it without writing anything.
+//!
+//! NVIDIA GPUs are controlled through NVML and other GPUs through hwmon on
+//! Linux. AMD GPUs on Windows have no control path, as changing their
+//! limits needs ADLX, which this crate does not bind: they report no
+//! [`ControlCapabilities::backend`] and every change fails with
+//! [`ControlError::NotSupported`], as on macOS.
+//!
+//! ```no_run
+//! use gpu_info::{GpuControl, Watts};
+//!
+//! let gpus = gpu_info::retrieve_gpu_info()?;
+//! let control = GpuControl::new(&gpus[0]).dry_run(true);
+//! if control.capabilities()?.power_limit_range.is_some() {
+//!     println!("{}", control.set_power_limit(Watts(250))?.description);
+//! }
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+
+#[cfg(target_os = "linux")]
+use std::path::{Path, PathBuf};
+
+use crate::{Watts, GPU};
+
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ControlError {
+    #[error("{0} is not supported on this GPU")]
+    NotSupported(String),
+    #[error("no permission to {0}, run as root or administrator")]
+    PermissionDenied(String),
+    #[error("{value} is outside of the supported range {min}..={max}")]
+    OutOfRange { value: u32, min: u32, max: u32 },
+    #[error("invalid fan curve: {0}")]
+    InvalidFanCurve(String),
+    #[error("failed to {0}")]
+    OperationFailed(String),
+}
+
+/// The interface changes go through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[non_exhaustive]
+pub enum ControlBackend {
+    /// The sysfs attributes of the kernel driver on Linux, e.g. amdgpu's
+    Hwmon,
+    Nvml,
+}
+
+/// What [`GpuControl`] can change on a GPU
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ControlCapabilities {
+    /// `None` when nothing can be changed
+    pub backend: Option<ControlBackend>,
+    /// The limits [`GpuControl::set_power_limit`] accepts
+    pub power_limit_range: Option<(Watts, Watts)>,
+    /// What [`GpuControl::reset_power_limit`] restores
+    pub default_power_limit: Option<Watts>,
+    /// Whether [`GpuControl::set_fan_speed`] is supported
+    pub fan_speed: bool,
+    /// Whether [`GpuControl::set_fan_curve`] is supported, with amdgpu on
+    /// RDNA 3 and later
+    pub fan_curve: bool,
+    /// Whether this process may write, `None` when the backend only tells by
+    /// trying
+    pub privileged: Option<bool>,
+}
+
+/// Fan speed at a temperature, amdgpu interpolates between the points
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct FanCurvePoint {
+    pub celsius: u32,
+    pub percent: u32,
+}
+
+/// A change written, or in a dry run the change that would be
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ControlAction {
+    pub backend: ControlBackend,
+    /// e.g. `power1_cap = 250000000`
+    pub description: String,
+    /// `false` in a dry run
+    pub applied: bool,
+}
+
+/// Changes the power limit and fans of a GPU returned by
+/// [`retrieve_gpu_info`](crate::retrieve_gpu_info). NVIDIA GPUs go through
+/// NVML, which needs the `nvml` feature, others through hwmon on Linux.
+/// Elsewhere, e.g. on macOS, nothing can be changed.
+#[derive(Debug, Clone, Copy)]
+pub struct GpuControl<'a> {
+    #[cfg_attr(
+        not(any(target_os = "linux", all(feature = "nvml", not(target_os = "macos")))),
+        allow(dead_code)
+    )]
+    gpu: &'a GPU,
+    dry_run: bool,
+}
+
+/// The device a GPU is controlled through
+enum Target {
+    #[cfg(target_os = "linux")]
+    Hwmon(PathBuf),
+    #[cfg(all(feature = "nvml", not(target_os = "macos")))]
+    Nvml,
+}
+
+impl<'a> GpuControl<'a> {
+    pub fn new(gpu: &'a GPU) -> Self {
+        Self {
+            gpu,
+            dry_run: false,
+        }
+    }
+
+    /// Check and describe changes without writing them
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    pub fn capabilities(&self) -> Result<ControlCapabilities, ControlError> {
+        match self.target() {
+            #[cfg(target_os = "linux")]
+            Some(Target::Hwmon(hwmon)) => Ok(hwmon_capabilities(&hwmon)),
+            #[cfg(all(feature = "nvml", not(target_os = "macos")))]
+            Some(Target::Nvml) => crate::nvml::nvml_control_capabilities(self.gpu),
+            None => Ok(ControlCapabilities::default()),
+        }
+    }
+
+    /// Set the sustained power limit, within
+    /// [`ControlCapabilities::power_limit_range`]
+    pub fn set_power_limit(&self, limit: Watts) -> Result<ControlAction, ControlError> {
+        self.change_power_limit(Some(limit))
+    }
+
+    /// Restore [`ControlCapabilities::default_power_limit`]
+    pub fn reset_power_limit(&self) -> Result<ControlAction, ControlError> {
+        self.change_power_limit(None)
+    }
+
+    /// Run every fan at a fixed speed until [`GpuControl::reset_fan`]
+    pub fn set_fan_speed(&self, percent: u32) -> Result<ControlAction, ControlError> {
+        check_range(percent, 0, 100)?;
+        self.change_fan_speed(Some(percent))
+    }
+
+    /// Hand the fans back to the driver's automatic control
+    pub fn reset_fan(&self) -> Result<ControlAction, ControlError> {
+        self.change_fan_speed(None)
+    }
+
+    /// Replace the points of the driver's fan curve, in order of rising
+    /// temperature
+    pub fn set_fan_curve(&self, curve: &[FanCurvePoint]) -> Result<ControlAction, ControlError> {
+        validate_fan_curve(curve)?;
+        match self.target() {
+            #[cfg(target_os = "linux")]
+            Some(Target::Hwmon(hwmon)) => {
+                hwmon_fan_curve_writes(&hwmon, curve)?.apply(self.dry_run)
+            }
+            #[cfg(all(feature = "nvml", not(target_os = "macos")))]
+            Some(Target::Nvml) => Err(not_supported("fan curve")),
+            None => Err(not_supported("fan curve")),
+        }
+    }
+
+    #[cfg_attr(
+        not(any(target_os = "linux", all(feature = "nvml", not(target_os = "macos")))),
+        allow(unused_variables)
+    )]
+    fn change_power_limit(&self, limit: Option<Watts>) -> Result<ControlAction, ControlError> {
+        match self.target() {
+            #[cfg(target_os = "linux")]
+            Some(Target::Hwmon(hwmon)) => {
+                hwmon_power_limit_writes(&hwmon, limit)?.apply(self.dry_run)
+            }
+            #[cfg(all(feature = "nvml", not(target_os = "macos")))]
+            Some(Target::Nvml) => crate::nvml::nvml_set_power_limit(self.gpu, limit, self.dry_run),
+            None => Err(not_supported("power limit")),
+        }
+    }
+
+    #[cfg_attr(
+        not(any(target_os = "linux", all(feature = "nvml", not(target_os = "macos")))),
+        allow(unused_variables)
+    )]
+    fn change_fan_speed(&self, percent: Option<u32>) -> Result<ControlAction, ControlError> {
+        match self.target() {
+            #[cfg(target_os = "linux")]
+            Some(Target::Hwmon(hwmon)) => {
+                hwmon_fan_speed_writes(&hwmon, percent)?.apply(self.dry_run)
+            }
+            #[cfg(all(feature = "nvml", not(target_os = "macos")))]
+            Some(Target::Nvml) => crate::nvml::nvml_set_fan_speed(self.gpu, percent, self.dry_run),
+            None => Err(not_supported("fan control")),
+        }
+    }
+
+    /// NVML for NVIDIA GPUs, as the proprietary driver has no hwmon
+    /// attributes, the hwmon directory of the PCI device otherwise
+    fn target(&self) -> Option<Target> {
+        #[cfg(all(feature = "nvml", not(target_os = "macos")))]
+        if self.gpu.vendor == "NVIDIA" {
+            return Some(Target::Nvml);
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(address) = self.gpu.pci_address {
+            let device = Path::new(crate::pci::SYSFS_PCI_DEVICES).join(address.to_string());
+            return crate::pci::find_hwmon(&device).map(Target::Hwmon);
+        }
+        None
+    }
+}
+
+pub(crate) fn not_supported(what: &str) -> ControlError {
+    ControlError::NotSupported(what.to_string())
+}
+
+pub(crate) fn check_range(value: u32, min: u32, max: u32) -> Result<(), ControlError> {
+    if (min..=max).contains(&value) {
+        Ok(())
+    } else {
+        Err(ControlError::OutOfRange { value, min, max })
+    }
+}
+
+fn validate_fan_curve(curve: &[FanCurvePoint]) -> Result<(), ControlError> {
+    if curve.is_empty() {
+        return Err(ControlError::InvalidFanCurve("no points".to_string()));
+    }
+    for point in curve {
+        check_range(point.percent, 0, 100)?;
+    }
+    if curve
+        .windows(2)
+        .any(|pair| pair[0].celsius >= pair[1].celsius)
+    {
+        return Err(ControlError::InvalidFanCurve(
+            "temperatures must rise from point to point".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Values to write to sysfs attributes, in order
+#[cfg(target_os = "linux")]
+struct SysfsWrites(Vec<(PathBuf, String)>);
+
+#[cfg(target_os = "linux")]
+impl SysfsWrites {
+    fn apply(self, dry_run: bool) -> Result<ControlAction, ControlError> {
+        let description = self
+            .0
+            .iter()
+            .map(|(path, value)| format!("{} = {value}", path.display()))
+            .collect::<Vec<_>>()
+            .join("; ");
+        if !dry_run {
+            for (path, value) in &self.0 {
+                std::fs::write(path, value).map_err(|e| {
+                    let action = format!("write {}", path.display());
+                    match e.kind() {
+                        std::io::ErrorKind::PermissionDenied => {
+                            ControlError::PermissionDenied(action)
+                        }
+                        _ => ControlError::OperationFailed(format!("{action}: {e}")),
+                    }
+                })?;
+            }
+        }
+        Ok(ControlAction {
+            backend: ControlBackend::Hwmon,
+            description,
+            applied: !dry_run,
+        })
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn hwmon_capabilities(hwmon: &Path) -> ControlCapabilities {
+    // Whole watts within the limits, which need not be whole watts themselves
+    let power_limit_range = hwmon_power_limit_range(hwmon).and_then(|(min, max)| {
+        let min = u32::try_from(min.div_ceil(1_000_000)).ok()?;
+        let max = u32::try_from(max / 1_000_000).ok()?;
+        (min <= max).then_some((Watts(min), Watts(max)))
+    });
+    let fan_speed = hwmon.join("pwm1").is_file() && hwmon.join("pwm1_enable").is_file();
+    let privileged = ["power1_cap", "pwm1_enable"]
+        .into_iter()
+        .map(|name| hwmon.join(name))
+        .find(|path| path.is_file())
+        .map(|path| std::fs::OpenOptions::new().write(true).open(path).is_ok());
+    ControlCapabilities {
+        backend: Some(ControlBackend::Hwmon),
+        power_limit_range,
+        default_power_limit: crate::pci::read_watts(&hwmon.join("power1_cap_default")).map(Watts),
+        fan_speed,
+        fan_curve: fan_curve_path(hwmon).is_some_and(|path| path.is_file()),
+        privileged,
+    }
+}
+
+/// `power1_cap_min` and `power1_cap_max` in microwatts, when `power1_cap`
+/// can be written
+#[cfg(target_os = "linux")]
+fn hwmon_power_limit_range(hwmon: &Path) -> Option<(u64, u64)> {
+    let microwatts = |name: &str| crate::pci::read_microwatts(&hwmon.join(name));
+    hwmon
+        .join("power1_cap")
+        .is_file()
+        .then(|| microwatts("power1_cap_min").zip(microwatts("power1_cap_max")))
+        .flatten()
+}
+
+/// `power1_cap` in microwatts, checked against the limits in microwatts so
+/// a limit of e.g. 150.5 W does not let 150 W through
+#[cfg(target_os = "linux")]
+fn hwmon_power_limit_writes(
+    hwmon: &Path,
+    limit: Option<Watts>,
+) -> Result<SysfsWrites, ControlError> {
+    let Some((min, max)) = hwmon_power_limit_range(hwmon) else {
+        return Err(not_supported("power limit"));
+    };
+    let microwatts = match limit {
+        Some(limit) => {
+            let microwatts = limit.0 as u64 * 1_000_000;
+            if !(min..=max).contains(&microwatts) {
+                return Err(ControlError::OutOfRange {
+                    value: limit.0,
+                    min: u32::try_from(min.div_ceil(1_000_000)).unwrap_or(u32::MAX),
+                    max: u32::try_from(max / 1_000_000).unwrap_or(u32::MAX),
+                });
+            }
+            microwatts
+        }
+        // Restored as is rather than through whole watts
+        None => crate::pci::read_microwatts(&hwmon.join("power1_cap_default"))
+            .filter(|&default| default > 0)
+            .ok_or_else(|| not_supported("default power limit"))?,
+    };
+    Ok(SysfsWrites(vec![(
+        hwmon.join("power1_cap"),
+        microwatts.to_string(),
+    )]))
+}
+
+/// `pwm1_enable` is 1 for manual and 2 for automatic control, `pwm1` ranges
+/// from 0 to 255
+#[cfg(target_os = "linux")]
+fn hwmon_fan_speed_writes(hwmon: &Path, percent: Option<u32>) -> Result<SysfsWrites, ControlError> {
+    if !hwmon_capabilities(hwmon).fan_speed {
+        return Err(not_supported("fan control"));
+    }
+    Ok(SysfsWrites(match percent {
+        Some(percent) => vec![
+            (hwmon.join("pwm1_enable"), "1".to_string()),
+            (hwmon.join("pwm1"), (percent * 255 / 100).to_string()),
+        ],
+        None => vec![(hwmon.join("pwm1_enable"), "2".to_string())],
+    }))
+}
+
+/// amdgpu's `fan_curve` lists its points as `0: 35C 20%` and the limits
+/// under `OD_RANGE`, each point is set with `index celsius percent` and the
+/// curve committed with `c`
+#[cfg(target_os = "linux")]
+fn hwmon_fan_curve_writes(
+    hwmon: &Path,
+    curve: &[FanCurvePoint],
+) -> Result<SysfsWrites, ControlError> {
+    let Some((path, content)) = fan_curve_path(hwmon).and_then(|path| {
+        let content = std::fs::read_to_string(&path).ok()?;
+        Some((path, content))
+    }) else {
+        return Err(not_supported("fan curve"));
+    };
+    let points = content
+        .lines()
+        .take_while(|line| !line.starts_with("OD_RANGE"))
+        .filter(|line| {
+            line.split_once(':')
+                .is_some_and(|(index, _)| index.trim().parse::<u32>().is_ok())
+        })
+        .count();
+    if curve.len() > points {
+        return Err(ControlError::InvalidFanCurve(format!(
+            "{} points, the driver supports {points}",
+            curve.len()
+        )));
+    }
+    // The driver rejects points outside its range only once some are written
+    let celsius_range = od_range(&content, "temp");
+    let percent_range = od_range(&content, "fan speed");
+    for point in curve {
+        if let Some((min, max)) = celsius_range {
+            check_range(point.celsius, min, max)?;
+        }
+        if let Some((min, max)) = percent_range {
+            check_range(point.percent, min, max)?;
+        }
+    }
+    Ok(SysfsWrites(
+        curve
+            .iter()
+            .enumerate()
+            .map(|(index, point)| {
+                (
+                    path.clone(),
+                    format!("{index} {} {}", point.celsius, point.percent),
+                )
+            })
+            .chain([(path.clone(), "c".to_string())])
+            .collect(),
+    ))
+}
+
+/// The limits of the `OD_RANGE` line whose label contains `label`, e.g.
+/// `FAN_CURVE(hotspot temp): 25C 100C` or `FAN_CURVE(fan speed): 15% 100%`
+#[cfg(target_os = "linux")]
+fn od_range(content: &str, label: &str) -> Option<(u32, u32)> {
+    let (_, values) = content
+        .lines()
+        .skip_while(|line| !line.starts_with("OD_RANGE"))
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)| name.contains(label))?;
+    let mut values = values
+        .split_whitespace()
+        .map(|value| value.trim_end_matches(['C', '%']).parse::<u32>().ok());
+    values.next()?.zip(values.next()?)
+}
+
+/// `gpu_od/fan_ctrl/fan_curve` of the PCI device `hwmon/hwmonN` belongs to
+#[cfg(target_os = "linux")]
+fn fan_curve_path(hwmon: &Path) -> Option<PathBuf> {
+    let device = hwmon.parent()?.parent()?;
+    Some(device.join("gpu_od/fan_ctrl/fan_curve"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_fan_curve() {
+        let point = |celsius, percent| FanCurvePoint { celsius, percent };
+        assert!(validate_fan_curve(&[point(40, 20), point(70, 60), point(90, 100)]).is_ok());
+        assert!(validate_fan_curve(&[]).is_err());
+        assert!(validate_fan_curve(&[point(70, 60), point(40, 20)]).is_err());
+        assert!(matches!(
+            validate_fan_curve(&[point(40, 120)]),
+            Err(ControlError::OutOfRange { value: 120, .. })
+        ));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_hwmon_control() {
+        use std::fs;
+
+        let fixture = tempfile::tempdir().unwrap();
+        let root = fixture.path();
+        let hwmon = root.join("hwmon/hwmon2");
+        fs::create_dir_all(&hwmon).unwrap();
+        fs::create_dir_all(root.join("gpu_od/fan_ctrl")).unwrap();
+        for (name, value) in [
+            ("power1_cap", "300000000"),
+            ("power1_cap_min", "150000000"),
+            ("power1_cap_max", "350000000"),
+            ("power1_cap_default", "300000000"),
+            ("pwm1", "80"),
+            ("pwm1_enable", "2"),
+        ] {
+            fs::write(hwmon.join(name), format!("{value}\n")).unwrap();
+        }
+        let fan_curve = root.join("gpu_od/fan_ctrl/fan_curve");
+        fs::write(
+            &fan_curve,
+            "OD_FAN_CURVE:\n0: 0C 0%\n1: 0C 0%\n2: 0C 0%\n3: 0C 0%\n4: 0C 0%\n\
+             OD_RANGE:\nFAN_CURVE(hotspot temp): 25C 100C\nFAN_CURVE(fan speed): 15% 100%\n",
+        )
+        .unwrap();
+
+        let capabilities = hwmon_capabilities(&hwmon);
+        let dry_run = hwmon_power_limit_writes(&hwmon, Some(Watts(250)))
+            .unwrap()
+            .apply(true)
+            .unwrap();
+        let dry_run_value = fs::read_to_string(hwmon.join("power1_cap")).unwrap();
+        let out_of_range = hwmon_power_limit_writes(&hwmon, Some(Watts(400))).err();
+        let applied = hwmon_fan_speed_writes(&hwmon, Some(50))
+            .unwrap()
+            .apply(false)
+            .unwrap();
+        let pwm = fs::read_to_string(hwmon.join("pwm1")).unwrap();
+        let pwm_enable = fs::read_to_string(hwmon.join("pwm1_enable")).unwrap();
+        let point = |celsius, percent| FanCurvePoint { celsius, percent };
+        let curve = hwmon_fan_curve_writes(&hwmon, &[point(40, 20), point(80, 70)])
+            .unwrap()
+            .0;
+        let too_long = hwmon_fan_curve_writes(&hwmon, &[point(0, 0); 6]).err();
+        let too_cold = hwmon_fan_curve_writes(&hwmon, &[point(20, 20), point(80, 70)]).err();
+        let too_slow = hwmon_fan_curve_writes(&hwmon, &[point(40, 10), point(80, 70)]).err();
+        // Limits of fractional watts round inwards
+        fs::write(hwmon.join("power1_cap_min"), "150500000\n").unwrap();
+        let fractional_range = hwmon_capabilities(&hwmon).power_limit_range;
+        let below_fractional_min = hwmon_power_limit_writes(&hwmon, Some(Watts(150))).err();
+        fs::write(hwmon.join("power1_cap_default"), "300500000\n").unwrap();
+        let reset = hwmon_power_limit_writes(&hwmon, None).unwrap().0;
+        fixture.close().unwrap();
+
+        assert_eq!(
+            capabilities.power_limit_range,
+            Some((Watts(150), Watts(350)))
+        );
+        assert_eq!(capabilities.default_power_limit, Some(Watts(300)));
+        assert!(capabilities.fan_speed);
+        assert!(capabilities.fan_curve);
+        assert!(!dry_run.applied);
+        assert!(dry_run.description.ends_with("power1_cap = 250000000"));
+        assert_eq!(dry_run_value, "300000000\n");
+        assert!(matches!(
+            out_of_range,
+            Some(ControlError::OutOfRange {
+                value: 400,
+                min: 150,
+                max: 350
+            })
+        ));
+        assert!(applied.applied);
+        assert_eq!((pwm.as_str(), pwm_enable.as_str()), ("127", "1"));
+        let values: Vec<_> = curve.iter().map(|(_, value)| value.as_str()).collect();
+        assert_eq!(values, ["0 40 20", "1 80 70", "c"]);
+        assert!(matches!(too_long, Some(ControlError::InvalidFanCurve(_))));
+        assert!(matches!(
+            too_cold,
+            Some(ControlError::OutOfRange {
+                value: 20,
+                min: 25,
+                max: 100
+            })
+        ));
+        assert!(matches!(
+            too_slow,
+            Some(ControlError::OutOfRange {
+                value: 10,
+                min: 15,
+                max: 100
+            })
+        ));
+        assert_eq!(fractional_range, Some((Watts(151), Watts(350))));
+        assert!(matches!(
+            below_fractional_min,
+            Some(ControlError::OutOfRange {
+                value: 150,
+                min: 151,
+                max: 350
+            })
+        ));
+        assert_eq!(reset[0].1, "300500000");
+    }
+}
@@ -0,0 +1,137 @@
+//! Hardware performance counters, from `VK_KHR_performance_query` and Metal
+//! counter sets, for profiling tools that need more than
+//! [`engine_utilization`](crate::engine_utilization)
+//!
+//! Counters only count work submitted between the start and end of their
+//! sample, so [`sample_counters`] measures a copy of its own. Sampling the
+//! work of an application needs the application's own device and command
+//! buffers; the names listed here are the ones to ask its API for.
+
+use crate::{Error, GPU};
+
+/// Bytes copied through device memory while [`sample_counters`] samples
+pub(crate) const SAMPLE_COPY_BYTES: u64 = 16 * 1024 * 1024;
+
+/// Counters the driver can sample together: a Metal counter set, or a
+/// Vulkan counter category
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct CounterGroup {
+    /// e.g. `timestamp` or `statistic` on Metal, driver defined on Vulkan
+    pub name: String,
+    pub counters: Vec<Counter>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct Counter {
+    pub name: String,
+    /// What the counter measures, only given by Vulkan drivers
+    pub description: Option<String>,
+    pub unit: CounterUnit,
+}
+
+/// Unit of a counter, as the Vulkan drivers define them. Metal counters are
+/// event counts, [`CounterUnit::Generic`], or GPU timestamps in
+/// [`CounterUnit::Ticks`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[non_exhaustive]
+pub enum CounterUnit {
+    Generic,
+    Percentage,
+    Nanoseconds,
+    Bytes,
+    BytesPerSecond,
+    Kelvin,
+    Watts,
+    Volts,
+    Amps,
+    Hertz,
+    Cycles,
+    Ticks,
+}
+
+/// One counter's value over a [`sample_counters`] run
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct CounterValue {
+    pub name: String,
+    pub unit: CounterUnit,
+    /// The difference between the end and the start of the sample for
+    /// counting counters, otherwise what the driver reports for the range
+    pub value: f64,
+}
+
+/// The counter groups of a GPU returned by
+/// [`retrieve_gpu_info`](crate::retrieve_gpu_info). Empty when the driver
+/// exposes none, as most Vulkan drivers outside of Intel, AMD (RADV) and
+/// Arm Mali do, and Apple GPUs do beyond timestamps. `None` when the GPU
+/// cannot be matched.
+pub fn counter_groups(gpu: &GPU) -> Result<Option<Vec<CounterGroup>>, Error> {
+    #[cfg(target_os = "macos")]
+    return Ok(crate::find_metal_gpu(gpu)?
+        .map(|metal_gpu| crate::metal::counter_groups_via_metal(metal_gpu.registry_id)));
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let Some(uuid) = gpu.uuid.as_deref().and_then(crate::parse_uuid) else {
+            return Ok(None);
+        };
+        Ok(crate::vulkan::counter_groups_via_vk(&uuid)?)
+    }
+}
+
+/// Sample every counter of `group` around a copy through device memory, to
+/// check that the counters work and see how a GPU reports a known
+/// workload. Vulkan drivers may need several passes of the copy for one
+/// group, and some restrict counters to privileged processes, e.g. Intel's
+/// to root unless `dev.i915.perf_stream_paranoid` is 0. `None` when the GPU
+/// or the group cannot be found.
+pub fn sample_counters(gpu: &GPU, group: &str) -> Result<Option<Vec<CounterValue>>, Error> {
+    #[cfg(target_os = "macos")]
+    return match crate::find_metal_gpu(gpu)? {
+        Some(metal_gpu) => Ok(crate::metal::sample_counters_via_metal(
+            metal_gpu.registry_id,
+            group,
+        )?),
+        None => Ok(None),
+    };
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let Some(uuid) = gpu.uuid.as_deref().and_then(crate::parse_uuid) else {
+            return Ok(None);
+        };
+        Ok(crate::vulkan::sample_counters_via_vk(&uuid, group)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_counters() {
+        for gpu in crate::retrieve_gpu_info().unwrap() {
+            let groups = match counter_groups(&gpu) {
+                Ok(groups) => groups.unwrap_or_default(),
+                Err(e) => {
+                    eprintln!("{}: {e}", gpu.name);
+                    continue;
+                }
+            };
+            for group in &groups {
+                eprintln!("{}: {group:?}", gpu.name);
+                match sample_counters(&gpu, &group.name) {
+                    Ok(values) => eprintln!("{values:?}"),
+                    Err(e) => eprintln!("{e}"),
+                }
+            }
+        }
+    }
+}
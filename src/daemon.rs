@@ -0,0 +1,292 @@
+//! `gpu-infod`, serving the GPU list over a unix socket. Unix only: Windows
+//! named pipes are out of scope, Windows processes can share a sampling loop
+//! through `serve_http` instead.
+
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::query::Sampler;
+use crate::GPU;
+
+/// How long a sample is served before the next request triggers a new one
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long a client waits for a response, enough for a slow enumeration
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+/// Enumeration failed on the daemon side
+const QUERY_FAILED: i64 = -32000;
+
+#[derive(Debug, thiserror::Error)]
+pub enum DaemonError {
+    #[error("failed to reach gpu-infod: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid message from gpu-infod: {0}")]
+    Protocol(String),
+    #[error("gpu-infod failed: {0}")]
+    Remote(String),
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Request {
+    jsonrpc: String,
+    id: u64,
+    method: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Response<T> {
+    jsonrpc: String,
+    id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<RpcError>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+/// `$XDG_RUNTIME_DIR/gpu-infod.sock`, or `/tmp/gpu-infod.sock` without one
+pub fn default_socket_path() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/tmp"))
+        .join("gpu-infod.sock")
+}
+
+/// Serve newline delimited JSON-RPC 2.0 on a unix socket at `path` until an
+/// accept fails. The only method is `gpus`, returning the GPU list sampled
+/// at most once per `interval`. A stale socket left by a crashed daemon is
+/// replaced, a live one fails with [`std::io::ErrorKind::AddrInUse`] and
+/// anything else at `path` with [`std::io::ErrorKind::AlreadyExists`].
+pub fn serve(path: &Path, interval: Duration) -> std::io::Result<()> {
+    if path.exists() {
+        if !std::fs::symlink_metadata(path)?.file_type().is_socket() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("{} exists and is not a socket", path.display()),
+            ));
+        }
+        if UnixStream::connect(path).is_ok() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::AddrInUse,
+                format!("gpu-infod is already listening on {}", path.display()),
+            ));
+        }
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    log::debug!("gpu-infod listening on {}", path.display());
+
+    let sampler = Arc::new(Sampler::new(interval));
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let sampler = Arc::clone(&sampler);
+        std::thread::spawn(move || {
+            if let Err(e) = handle_client(stream, &sampler) {
+                log::debug!("gpu-infod client disconnected: {e}");
+            }
+        });
+    }
+    Ok(())
+}
+
+fn handle_client(stream: UnixStream, sampler: &Sampler) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) if request.method == "gpus" => match sampler.sample() {
+                Ok(gpus) => response(Some(request.id), Some(gpus), None),
+                Err(e) => response(Some(request.id), None, Some((QUERY_FAILED, e))),
+            },
+            Ok(request) => response(
+                Some(request.id),
+                None,
+                Some((
+                    METHOD_NOT_FOUND,
+                    format!("unknown method {}", request.method),
+                )),
+            ),
+            Err(e) => response(None, None, Some((PARSE_ERROR, e.to_string()))),
+        };
+        let mut message = serde_json::to_vec(&response).map_err(std::io::Error::other)?;
+        message.push(b'\n');
+        writer.write_all(&message)?;
+    }
+    Ok(())
+}
+
+fn response(
+    id: Option<u64>,
+    result: Option<Vec<GPU>>,
+    error: Option<(i64, String)>,
+) -> Response<Vec<GPU>> {
+    Response {
+        jsonrpc: "2.0".to_string(),
+        id,
+        result,
+        error: error.map(|(code, message)| RpcError { code, message }),
+    }
+}
+
+/// Connection to a [`serve`]ing daemon, reconnecting once per call when the
+/// daemon restarted in between
+pub struct DaemonClient {
+    path: PathBuf,
+    connection: Option<BufReader<UnixStream>>,
+    next_id: u64,
+}
+
+impl DaemonClient {
+    pub fn connect(path: impl Into<PathBuf>) -> Result<Self, DaemonError> {
+        let mut client = Self {
+            path: path.into(),
+            connection: None,
+            next_id: 1,
+        };
+        client.reconnect()?;
+        Ok(client)
+    }
+
+    /// The daemon's latest [`GpuQuery::all`] result
+    pub fn gpus(&mut self) -> Result<Vec<GPU>, DaemonError> {
+        match self.call("gpus") {
+            Err(DaemonError::Io(e)) => {
+                log::debug!("reconnecting to gpu-infod after: {e}");
+                self.reconnect()?;
+                self.call("gpus")
+            }
+            result => result,
+        }
+    }
+
+    fn reconnect(&mut self) -> Result<(), DaemonError> {
+        self.connection = None;
+        let stream = UnixStream::connect(&self.path)?;
+        stream.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+        self.connection = Some(BufReader::new(stream));
+        Ok(())
+    }
+
+    fn call<T: serde::de::DeserializeOwned>(&mut self, method: &str) -> Result<T, DaemonError> {
+        if self.connection.is_none() {
+            self.reconnect()?;
+        }
+        let connection = self.connection.as_mut().expect("connected above");
+
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut request = serde_json::to_vec(&Request {
+            jsonrpc: "2.0".to_string(),
+            id,
+            method: method.to_string(),
+        })
+        .map_err(|e| DaemonError::Protocol(e.to_string()))?;
+        request.push(b'\n');
+
+        let mut line = String::new();
+        let read = connection
+            .get_mut()
+            .write_all(&request)
+            .and_then(|()| connection.read_line(&mut line));
+        match read {
+            Ok(0) => {
+                self.connection = None;
+                return Err(DaemonError::Io(std::io::ErrorKind::UnexpectedEof.into()));
+            }
+            Ok(_) => {}
+            Err(e) => {
+                self.connection = None;
+                return Err(e.into());
+            }
+        }
+
+        let response = serde_json::from_str::<Response<T>>(&line)
+            .map_err(|e| DaemonError::Protocol(e.to_string()))?;
+        if response.id != Some(id) {
+            return Err(DaemonError::Protocol(format!(
+                "expected response {id}, got {:?}",
+                response.id
+            )));
+        }
+        match (response.result, response.error) {
+            (_, Some(error)) => Err(DaemonError::Remote(error.message)),
+            (Some(result), None) => Ok(result),
+            (None, None) => Err(DaemonError::Protocol("empty response".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_daemon() {
+        let fixture = tempfile::tempdir().unwrap();
+        let path = fixture.path().join("gpu-infod.sock");
+        let server_path = path.clone();
+        std::thread::spawn(move || serve(&server_path, DEFAULT_INTERVAL));
+
+        let mut client = (0..100)
+            .find_map(|_| {
+                std::thread::sleep(Duration::from_millis(10));
+                DaemonClient::connect(&path).ok()
+            })
+            .expect("daemon did not start");
+        match client.gpus() {
+            Ok(gpus) => eprintln!("{gpus:?}"),
+            Err(DaemonError::Remote(e)) => eprintln!("{e}"),
+            Err(e) => panic!("{e}"),
+        }
+
+        // The connection breaking, as when the daemon restarts, costs one
+        // reconnect and not the call
+        client
+            .connection
+            .as_ref()
+            .unwrap()
+            .get_ref()
+            .shutdown(std::net::Shutdown::Both)
+            .unwrap();
+        assert!(matches!(client.gpus(), Ok(_) | Err(DaemonError::Remote(_))));
+
+        // A second daemon on the same socket must not take it over
+        assert_eq!(
+            serve(&path, DEFAULT_INTERVAL).unwrap_err().kind(),
+            std::io::ErrorKind::AddrInUse
+        );
+        // Nor delete a file that is not a socket
+        let file = fixture.path().join("gpu-infod.conf");
+        std::fs::write(&file, "").unwrap();
+        assert_eq!(
+            serve(&file, DEFAULT_INTERVAL).unwrap_err().kind(),
+            std::io::ErrorKind::AlreadyExists
+        );
+        assert!(file.exists());
+
+        let mut stream = UnixStream::connect(&path).unwrap();
+        stream
+            .write_all(b"{\"jsonrpc\":\"2.0\",\"id\":7,\"method\":\"temperature\"}\n")
+            .unwrap();
+        let mut line = String::new();
+        BufReader::new(stream).read_line(&mut line).unwrap();
+        let response = serde_json::from_str::<Response<Vec<GPU>>>(&line).unwrap();
+        assert_eq!(response.id, Some(7));
+        assert_eq!(response.error.map(|e| e.code), Some(METHOD_NOT_FOUND));
+    }
+}
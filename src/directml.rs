@@ -0,0 +1,204 @@
+use std::ffi::c_void;
+
+use crate::dxgi::{luid_to_u64, utf16_to_string, DxgiError};
+use windows::core::{s, w, Interface, GUID, HRESULT};
+use windows::Win32::Foundation::FreeLibrary;
+use windows::Win32::Graphics::Direct3D::{
+    D3D_FEATURE_LEVEL, D3D_FEATURE_LEVEL_11_0, D3D_FEATURE_LEVEL_11_1, D3D_FEATURE_LEVEL_12_0,
+    D3D_FEATURE_LEVEL_12_1, D3D_FEATURE_LEVEL_12_2,
+};
+use windows::Win32::Graphics::Direct3D12::{
+    D3D12CreateDevice, ID3D12Device, D3D12_FEATURE_DATA_FEATURE_LEVELS,
+    D3D12_FEATURE_FEATURE_LEVELS,
+};
+use windows::Win32::Graphics::Dxgi::{
+    CreateDXGIFactory1, IDXGIFactory1, DXGI_ADAPTER_FLAG_SOFTWARE,
+};
+use windows::Win32::System::LibraryLoader::{
+    GetModuleFileNameW, GetModuleHandleW, GetProcAddress, LoadLibraryW,
+};
+use windows::Win32::System::SystemInformation::GetSystemDirectoryW;
+use windows::Win32::AI::MachineLearning::DirectML::{
+    IDMLDevice, DML_CREATE_DEVICE_FLAGS, DML_CREATE_DEVICE_FLAG_NONE,
+    DML_FEATURE_DATA_FEATURE_LEVELS, DML_FEATURE_FEATURE_LEVELS, DML_FEATURE_LEVEL,
+    DML_FEATURE_QUERY_FEATURE_LEVELS,
+};
+
+type DmlCreateDevice = unsafe extern "system" fn(
+    *mut c_void,
+    DML_CREATE_DEVICE_FLAGS,
+    *const GUID,
+    *mut *mut c_void,
+) -> HRESULT;
+
+const D3D12_FEATURE_LEVELS: &[D3D_FEATURE_LEVEL] = &[
+    D3D_FEATURE_LEVEL_11_0,
+    D3D_FEATURE_LEVEL_11_1,
+    D3D_FEATURE_LEVEL_12_0,
+    D3D_FEATURE_LEVEL_12_1,
+    D3D_FEATURE_LEVEL_12_2,
+];
+
+/// 1.0 to 6.4, encoded like D3D feature levels as `major << 12 | minor << 8`
+const DML_FEATURE_LEVELS: &[DML_FEATURE_LEVEL] = &[
+    DML_FEATURE_LEVEL(0x1000),
+    DML_FEATURE_LEVEL(0x2000),
+    DML_FEATURE_LEVEL(0x2100),
+    DML_FEATURE_LEVEL(0x3000),
+    DML_FEATURE_LEVEL(0x3100),
+    DML_FEATURE_LEVEL(0x4000),
+    DML_FEATURE_LEVEL(0x4100),
+    DML_FEATURE_LEVEL(0x5000),
+    DML_FEATURE_LEVEL(0x5100),
+    DML_FEATURE_LEVEL(0x5200),
+    DML_FEATURE_LEVEL(0x6000),
+    DML_FEATURE_LEVEL(0x6100),
+    DML_FEATURE_LEVEL(0x6200),
+    DML_FEATURE_LEVEL(0x6300),
+    DML_FEATURE_LEVEL(0x6400),
+];
+
+/// Direct3D 12 and DirectML support of one adapter
+#[derive(Debug, Clone)]
+pub struct DirectMlSupport {
+    pub name: String,
+    pub luid: u64,
+    /// Highest Direct3D 12 feature level, e.g. `12.1`
+    pub d3d12_feature_level: String,
+    /// Highest DirectML feature level, e.g. `6.4`. `None` when `DirectML.dll`
+    /// cannot be loaded or rejects the adapter.
+    pub directml_feature_level: Option<String>,
+}
+
+/// Probe every hardware adapter able to create a Direct3D 12 device.
+/// `DirectML.dll` is looked up like ONNX Runtime does, so a redistributable
+/// next to the executable takes precedence over the system copy.
+pub fn retrieve_directml_support() -> Result<Vec<DirectMlSupport>, DxgiError> {
+    let factory: IDXGIFactory1 = unsafe { CreateDXGIFactory1() }?;
+
+    let directml = unsafe { LoadLibraryW(w!("DirectML.dll")) }.ok();
+    scopeguard::defer! {
+        if let Some(module) = directml {
+            let _ = unsafe { FreeLibrary(module) };
+        }
+    }
+    let create_dml_device = directml
+        .and_then(|module| unsafe { GetProcAddress(module, s!("DMLCreateDevice")) })
+        .map(|f| unsafe { std::mem::transmute::<_, DmlCreateDevice>(f) });
+
+    let mut support = Vec::new();
+
+    let mut index = 0;
+    while let Ok(adapter) = unsafe { factory.EnumAdapters1(index) } {
+        index += 1;
+        let desc = unsafe { adapter.GetDesc1() }?;
+        if desc.Flags & DXGI_ADAPTER_FLAG_SOFTWARE.0 as u32 != 0 {
+            continue;
+        }
+
+        let mut device: Option<ID3D12Device> = None;
+        if unsafe { D3D12CreateDevice(&adapter, D3D_FEATURE_LEVEL_11_0, &mut device) }.is_err() {
+            continue;
+        }
+        let Some(device) = device else {
+            continue;
+        };
+
+        support.push(DirectMlSupport {
+            name: utf16_to_string(&desc.Description),
+            luid: luid_to_u64(desc.AdapterLuid),
+            d3d12_feature_level: query_d3d12_feature_level(&device),
+            directml_feature_level: create_dml_device
+                .and_then(|create| query_directml_feature_level(create, &device)),
+        });
+    }
+
+    Ok(support)
+}
+
+/// Whether Direct3D 12 runs from an Agility SDK `D3D12Core.dll` shipped with
+/// the application rather than the one in the system directory. Only
+/// meaningful once a Direct3D 12 device has been created in the process.
+pub fn is_agility_sdk_active() -> bool {
+    let Ok(module) = (unsafe { GetModuleHandleW(w!("D3D12Core.dll")) }) else {
+        return false;
+    };
+
+    let mut path = [0u16; 1024];
+    let len = unsafe { GetModuleFileNameW(Some(module), &mut path) } as usize;
+    let mut system = [0u16; 1024];
+    let system_len = unsafe { GetSystemDirectoryW(Some(&mut system)) } as usize;
+    if len == 0 || system_len == 0 {
+        return false;
+    }
+
+    let path = String::from_utf16_lossy(&path[..len]).to_lowercase();
+    let system = String::from_utf16_lossy(&system[..system_len]).to_lowercase();
+    !path.starts_with(&system)
+}
+
+fn query_d3d12_feature_level(device: &ID3D12Device) -> String {
+    let mut data = D3D12_FEATURE_DATA_FEATURE_LEVELS {
+        NumFeatureLevels: D3D12_FEATURE_LEVELS.len() as u32,
+        pFeatureLevelsRequested: D3D12_FEATURE_LEVELS.as_ptr(),
+        MaxSupportedFeatureLevel: D3D_FEATURE_LEVEL_11_0,
+    };
+    let _ = unsafe {
+        device.CheckFeatureSupport(
+            D3D12_FEATURE_FEATURE_LEVELS,
+            &mut data as *mut _ as *mut _,
+            std::mem::size_of_val(&data) as u32,
+        )
+    };
+    format_feature_level(data.MaxSupportedFeatureLevel.0)
+}
+
+fn query_directml_feature_level(create: DmlCreateDevice, device: &ID3D12Device) -> Option<String> {
+    let mut raw = std::ptr::null_mut();
+    let result = unsafe {
+        create(
+            device.as_raw(),
+            DML_CREATE_DEVICE_FLAG_NONE,
+            &IDMLDevice::IID,
+            &mut raw,
+        )
+    };
+    if result.is_err() || raw.is_null() {
+        return None;
+    }
+    let dml = unsafe { IDMLDevice::from_raw(raw) };
+
+    let query = DML_FEATURE_QUERY_FEATURE_LEVELS {
+        RequestedFeatureLevelCount: DML_FEATURE_LEVELS.len() as u32,
+        RequestedFeatureLevels: DML_FEATURE_LEVELS.as_ptr(),
+    };
+    let mut data = DML_FEATURE_DATA_FEATURE_LEVELS::default();
+    unsafe {
+        dml.CheckFeatureSupport(
+            DML_FEATURE_FEATURE_LEVELS,
+            std::mem::size_of_val(&query) as u32,
+            Some(&query as *const _ as *const _),
+            std::mem::size_of_val(&data) as u32,
+            &mut data as *mut _ as *mut _,
+        )
+    }
+    .ok()?;
+    Some(format_feature_level(data.MaxSupportedFeatureLevel.0))
+}
+
+/// D3D and DirectML both encode feature levels as `major << 12 | minor << 8`
+fn format_feature_level(level: i32) -> String {
+    format!("{}.{}", level >> 12, (level >> 8) & 0xF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retrieve_directml_support() {
+        let support = retrieve_directml_support().unwrap();
+        eprintln!("{support:#?}, agility sdk: {}", is_agility_sdk_active());
+        assert_eq!(format_feature_level(D3D_FEATURE_LEVEL_12_1.0), "12.1");
+    }
+}
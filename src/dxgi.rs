@@ -0,0 +1,1088 @@
+use crate::vm::paravirtual_adapter;
+use crate::vulkan::VulkanGpu;
+use crate::{
+    vendor_from_id, Bytes, DisplayOutput, EngineClass, GPUKind, GpuQuery, HybridRole, MemoryBudget,
+    GPU,
+};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use windows::core::{w, Interface, GUID, PCWSTR, PWSTR};
+use windows::Wdk::Graphics::Direct3D::{
+    D3DKMTCloseAdapter, D3DKMTOpenAdapterFromLuid, D3DKMTQueryAdapterInfo, D3DKMTQueryStatistics,
+    D3DKMT_CLOSEADAPTER, D3DKMT_DRIVERVERSION, D3DKMT_NODEMETADATA, D3DKMT_OPENADAPTERFROMLUID,
+    D3DKMT_PHYSICAL_ADAPTER_COUNT, D3DKMT_QUERYADAPTERINFO, D3DKMT_QUERYSTATISTICS,
+    D3DKMT_QUERYSTATISTICS_0, D3DKMT_QUERYSTATISTICS_ADAPTER, D3DKMT_QUERYSTATISTICS_NODE,
+    D3DKMT_QUERYSTATISTICS_QUERY_NODE, D3DKMT_QUERYSTATISTICS_QUERY_SEGMENT,
+    D3DKMT_QUERYSTATISTICS_RESULT, D3DKMT_QUERYSTATISTICS_SEGMENT, D3DKMT_QUERYSTATISTICS_TYPE,
+    D3DKMT_WDDM_2_7_CAPS, DXGK_ENGINE_TYPE, DXGK_ENGINE_TYPE_3D, DXGK_ENGINE_TYPE_COPY,
+    DXGK_ENGINE_TYPE_VIDEO_DECODE, DXGK_ENGINE_TYPE_VIDEO_ENCODE,
+    DXGK_ENGINE_TYPE_VIDEO_PROCESSING, KMTQAITYPE_DRIVERVERSION, KMTQAITYPE_NODEMETADATA,
+    KMTQAITYPE_PHYSICALADAPTERCOUNT, KMTQAITYPE_WDDM_2_7_CAPS, KMTQUERYADAPTERINFOTYPE,
+};
+use windows::Win32::Devices::DeviceAndDriverInstallation::{
+    SetupDiDestroyDeviceInfoList, SetupDiEnumDeviceInfo, SetupDiGetClassDevsW,
+    SetupDiGetDeviceRegistryPropertyW, SetupDiOpenDevRegKey, DICS_FLAG_GLOBAL, DIGCF_PRESENT,
+    DIREG_DRV, GUID_DEVCLASS_DISPLAY, HDEVINFO, SPDRP_DEVICEDESC, SPDRP_HARDWAREID, SPDRP_SERVICE,
+    SP_DEVINFO_DATA,
+};
+use windows::Win32::Devices::Display::{
+    GetDisplayConfigBufferSizes, QueryDisplayConfig, DISPLAYCONFIG_MODE_INFO,
+    DISPLAYCONFIG_PATH_INFO, QDC_ALL_PATHS,
+};
+use windows::Win32::Foundation::{CloseHandle, ERROR_SUCCESS, LUID, WAIT_OBJECT_0};
+use windows::Win32::Graphics::Dxgi::{
+    CreateDXGIFactory1, IDXGIAdapter1, IDXGIAdapter3, IDXGIDevice, IDXGIFactory1, IDXGIFactory4,
+    IDXGIFactory6, DXGI_ADAPTER_DESC1, DXGI_ADAPTER_FLAG_SOFTWARE, DXGI_ERROR_DEVICE_HUNG,
+    DXGI_ERROR_DEVICE_REMOVED, DXGI_ERROR_DEVICE_RESET, DXGI_GPU_PREFERENCE,
+    DXGI_GPU_PREFERENCE_HIGH_PERFORMANCE, DXGI_GPU_PREFERENCE_MINIMUM_POWER,
+    DXGI_MEMORY_SEGMENT_GROUP_LOCAL, DXGI_QUERY_VIDEO_MEMORY_INFO,
+};
+use windows::Win32::System::Registry::{
+    RegCloseKey, RegEnumValueW, RegGetValueW, RegOpenKeyExW, RegQueryValueExW, HKEY,
+    HKEY_CURRENT_USER, HKEY_LOCAL_MACHINE, KEY_READ, KEY_WOW64_32KEY, KEY_WOW64_64KEY,
+    RRF_RT_REG_SZ,
+};
+use windows::Win32::System::Threading::{CreateEventW, WaitForSingleObject};
+
+#[derive(Debug, thiserror::Error)]
+pub enum DxgiError {
+    #[error("Failed to perform DXGI operation: {0}")]
+    OperationFailed(String),
+    /// The adapter was removed or reset, e.g. by a TDR or an eGPU unplug
+    #[error("DXGI adapter removed: {0}")]
+    DeviceRemoved(String),
+}
+
+impl DxgiError {
+    pub fn is_device_removed(&self) -> bool {
+        matches!(self, DxgiError::DeviceRemoved(_))
+    }
+}
+
+impl From<windows::core::Error> for DxgiError {
+    fn from(e: windows::core::Error) -> Self {
+        match e.code() {
+            DXGI_ERROR_DEVICE_REMOVED | DXGI_ERROR_DEVICE_HUNG | DXGI_ERROR_DEVICE_RESET => {
+                DxgiError::DeviceRemoved(e.to_string())
+            }
+            _ => DxgiError::OperationFailed(e.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DxgiAdapter {
+    pub name: String,
+    pub vendor: String,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    pub luid: u64,
+    pub dedicated_video_memory: Bytes,
+    pub shared_system_memory: Bytes,
+    pub is_software: bool,
+    pub driver_version: Option<String>,
+    pub driver_model: Option<String>,
+    pub hybrid_role: Option<HybridRole>,
+    pub outputs: Vec<DisplayOutput>,
+    /// Video present sources, i.e. displays the adapter can drive at once
+    pub max_displays: Option<u32>,
+    /// Name of the driver service, e.g. `nvlddmkm`
+    pub kernel_driver: Option<String>,
+    /// Hardware-accelerated GPU scheduling, `None` when unsupported
+    pub hardware_scheduling: Option<bool>,
+    /// Physical adapters behind this one in linked adapter mode, the node
+    /// count of its D3D12 devices
+    pub linked_node_count: Option<u32>,
+}
+
+impl From<DxgiAdapter> for GPU {
+    fn from(adapter: DxgiAdapter) -> Self {
+        let kind = if adapter.is_software {
+            GPUKind::Software
+        } else if paravirtual_adapter(adapter.vendor_id, adapter.device_id).is_some() {
+            GPUKind::Virtual
+        } else if adapter.vendor == "Qualcomm" {
+            // Adreno only ships inside Snapdragon SoCs
+            GPUKind::Integrated
+        } else {
+            GPUKind::Unknown
+        };
+        let carve_out = (kind == GPUKind::Integrated).then_some(adapter.dedicated_video_memory);
+        Self {
+            has_display_output: Some(!adapter.outputs.is_empty()),
+            passthrough: crate::vm::passthrough(&kind),
+            kind,
+            name: adapter.name,
+            vendor: adapter.vendor,
+            driver_version: adapter
+                .driver_version
+                .unwrap_or_else(|| "Unknown".to_string()),
+            vram: adapter.dedicated_video_memory,
+            shared_memory: Some(adapter.shared_system_memory),
+            carve_out,
+            luid: Some(adapter.luid),
+            active_displays: Some(active_displays(&adapter.outputs)),
+            max_displays: adapter.max_displays,
+            displays: adapter.outputs,
+            hybrid_role: adapter.hybrid_role,
+            driver_model: adapter.driver_model,
+            kernel_driver: adapter.kernel_driver,
+            hardware_scheduling: adapter.hardware_scheduling,
+            linked_node_count: adapter.linked_node_count,
+            #[cfg(feature = "extra")]
+            extra: crate::extra([
+                ("dxgi.vendor_id", adapter.vendor_id.into()),
+                ("dxgi.device_id", adapter.device_id.into()),
+                (
+                    "dxgi.shared_system_memory",
+                    adapter.shared_system_memory.0.into(),
+                ),
+            ]),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", err))]
+pub fn retrieve_gpu_info_via_dxgi() -> Result<Vec<DxgiAdapter>, DxgiError> {
+    retrieve_adapters(&GpuQuery::all())
+}
+
+/// Skips outputs and driver services unless the query asks for them
+pub(crate) fn retrieve_adapters(query: &GpuQuery) -> Result<Vec<DxgiAdapter>, DxgiError> {
+    let factory: IDXGIFactory1 = unsafe { CreateDXGIFactory1() }?;
+
+    let high_performance = preferred_adapter_luid(&factory, DXGI_GPU_PREFERENCE_HIGH_PERFORMANCE);
+    let minimum_power = preferred_adapter_luid(&factory, DXGI_GPU_PREFERENCE_MINIMUM_POWER);
+    // Only a hybrid system orders its adapters differently per preference
+    let is_hybrid = high_performance.is_some() && high_performance != minimum_power;
+
+    let services = if query.capabilities {
+        display_driver_services()
+    } else {
+        vec![]
+    };
+    let sources = if query.displays {
+        display_source_counts()
+    } else {
+        None
+    };
+    let mut adapters = Vec::new();
+
+    // EnumAdapters1 fails with DXGI_ERROR_NOT_FOUND past the last adapter
+    let mut index = 0;
+    while let Ok(adapter) = unsafe { factory.EnumAdapters1(index) } {
+        index += 1;
+        let desc = unsafe { adapter.GetDesc1() }?;
+        let luid = luid_to_u64(desc.AdapterLuid);
+
+        let hybrid_role = if !is_hybrid {
+            None
+        } else if Some(luid) == high_performance {
+            Some(HybridRole::HighPerformance)
+        } else if Some(luid) == minimum_power {
+            Some(HybridRole::PowerSaving)
+        } else {
+            None
+        };
+
+        trace_debug!(
+            name = %utf16_to_string(&desc.Description),
+            vendor_id = desc.VendorId,
+            device_id = desc.DeviceId,
+            luid,
+            "found dxgi adapter"
+        );
+        adapters.push(DxgiAdapter {
+            driver_model: query_driver_model(desc.AdapterLuid),
+            hardware_scheduling: query_hardware_scheduling(desc.AdapterLuid),
+            linked_node_count: query_linked_node_count(desc.AdapterLuid),
+            hybrid_role,
+            outputs: if query.displays {
+                enumerate_outputs(&adapter)
+            } else {
+                vec![]
+            },
+            max_displays: sources.as_ref().map(|sources| {
+                sources
+                    .iter()
+                    .find(|(source_luid, _)| *source_luid == luid)
+                    .map_or(0, |&(_, count)| count)
+            }),
+            kernel_driver: services
+                .iter()
+                .find(|(vendor_id, device_id, _)| {
+                    *vendor_id == desc.VendorId && *device_id == desc.DeviceId
+                })
+                .map(|(_, _, service)| service.clone()),
+            ..describe_adapter(&adapter, &desc)
+        });
+    }
+
+    Ok(adapters)
+}
+
+/// The fields of an adapter its description and driver version provide
+fn describe_adapter(adapter: &IDXGIAdapter1, desc: &DXGI_ADAPTER_DESC1) -> DxgiAdapter {
+    DxgiAdapter {
+        name: utf16_to_string(&desc.Description),
+        vendor: vendor_from_id(desc.VendorId).to_string(),
+        vendor_id: desc.VendorId,
+        device_id: desc.DeviceId,
+        luid: luid_to_u64(desc.AdapterLuid),
+        dedicated_video_memory: Bytes(desc.DedicatedVideoMemory as u64),
+        shared_system_memory: Bytes(desc.SharedSystemMemory as u64),
+        is_software: desc.Flags & DXGI_ADAPTER_FLAG_SOFTWARE.0 as u32 != 0,
+        driver_version: query_driver_version(adapter),
+        driver_model: None,
+        hybrid_role: None,
+        outputs: vec![],
+        max_displays: None,
+        kernel_driver: None,
+        hardware_scheduling: None,
+        linked_node_count: None,
+    }
+}
+
+/// The adapter DXGI enumerates first, which drives the primary display
+pub(crate) fn primary_adapter() -> Result<Option<DxgiAdapter>, DxgiError> {
+    let factory: IDXGIFactory1 = unsafe { CreateDXGIFactory1() }?;
+    let Ok(adapter) = (unsafe { factory.EnumAdapters1(0) }) else {
+        return Ok(None);
+    };
+    let desc = unsafe { adapter.GetDesc1() }?;
+    Ok(Some(describe_adapter(&adapter, &desc)))
+}
+
+/// Name of [`primary_adapter`], without querying the driver
+pub(crate) fn primary_adapter_name() -> Option<String> {
+    let factory: IDXGIFactory1 = unsafe { CreateDXGIFactory1() }.ok()?;
+    let adapter = unsafe { factory.EnumAdapters1(0) }.ok()?;
+    let desc = unsafe { adapter.GetDesc1() }.ok()?;
+    Some(utf16_to_string(&desc.Description))
+}
+
+/// Join Vulkan devices with DXGI adapters by LUID, so each physical adapter
+/// ends up as exactly one [`GPU`] carrying the data of both APIs.
+pub(crate) fn merge_with_vulkan(vk_gpus: Vec<VulkanGpu>, adapters: Vec<DxgiAdapter>) -> Vec<GPU> {
+    let mut adapters = adapters;
+    let mut gpus = Vec::new();
+
+    for vk_gpu in vk_gpus {
+        let adapter = vk_gpu
+            .luid
+            .and_then(|luid| adapters.iter().position(|a| a.luid == luid))
+            .map(|index| adapters.remove(index));
+
+        let mut gpu: GPU = vk_gpu.into();
+        if let Some(adapter) = adapter {
+            // The Windows driver version is what users see in Device Manager
+            if let Some(driver_version) = adapter.driver_version {
+                gpu.driver_version = driver_version;
+            }
+            gpu.luid = Some(adapter.luid);
+            // DXGI knows the carve-out the firmware reserved for integrated GPUs
+            if gpu.kind == GPUKind::Integrated {
+                gpu.vram = adapter.dedicated_video_memory;
+                gpu.carve_out = Some(gpu.vram);
+            }
+            gpu.shared_memory = Some(adapter.shared_system_memory);
+            gpu.has_display_output = Some(!adapter.outputs.is_empty());
+            gpu.active_displays = Some(active_displays(&adapter.outputs));
+            gpu.max_displays = adapter.max_displays;
+            gpu.displays = adapter.outputs;
+            gpu.hybrid_role = adapter.hybrid_role;
+            gpu.driver_model = adapter.driver_model;
+            gpu.kernel_driver = adapter.kernel_driver;
+            gpu.hardware_scheduling = adapter.hardware_scheduling;
+            gpu.linked_node_count = adapter.linked_node_count.or(gpu.linked_node_count);
+            #[cfg(feature = "extra")]
+            gpu.extra.insert(
+                "dxgi.shared_system_memory".to_string(),
+                adapter.shared_system_memory.0.into(),
+            );
+        }
+        gpus.push(gpu);
+    }
+
+    // Adapters without a Vulkan driver are still real hardware
+    gpus.extend(
+        adapters
+            .into_iter()
+            .filter(|adapter| !adapter.is_software)
+            .map(GPU::from),
+    );
+
+    gpus
+}
+
+/// LUID of the adapter driving the monitor that shows most of the window.
+/// On hybrid systems this is the compositing GPU, which may differ from the
+/// GPU the application renders on.
+#[cfg(feature = "raw-window-handle")]
+pub(crate) fn adapter_luid_for_window(hwnd: isize) -> Result<Option<u64>, DxgiError> {
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::Graphics::Gdi::{MonitorFromWindow, MONITOR_DEFAULTTONULL};
+
+    let monitor = unsafe { MonitorFromWindow(HWND(hwnd as _), MONITOR_DEFAULTTONULL) };
+    if monitor.is_invalid() {
+        return Ok(None);
+    }
+
+    let factory: IDXGIFactory1 = unsafe { CreateDXGIFactory1() }?;
+    let mut index = 0;
+    while let Ok(adapter) = unsafe { factory.EnumAdapters1(index) } {
+        index += 1;
+        let mut output_index = 0;
+        while let Ok(output) = unsafe { adapter.EnumOutputs(output_index) } {
+            output_index += 1;
+            if unsafe { output.GetDesc() }.is_ok_and(|desc| desc.Monitor == monitor) {
+                return Ok(Some(luid_to_u64(
+                    unsafe { adapter.GetDesc1() }?.AdapterLuid,
+                )));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Per-process budget of local (dedicated) memory on the adapter, from
+/// `IDXGIAdapter3::QueryVideoMemoryInfo`. `None` before Windows 10.
+pub fn memory_budget_via_dxgi(luid: u64) -> Result<Option<MemoryBudget>, DxgiError> {
+    let Some(adapter) = adapter_by_luid::<IDXGIAdapter3>(luid)? else {
+        return Ok(None);
+    };
+    let mut info = DXGI_QUERY_VIDEO_MEMORY_INFO::default();
+    unsafe { adapter.QueryVideoMemoryInfo(0, DXGI_MEMORY_SEGMENT_GROUP_LOCAL, &mut info) }?;
+    Ok(Some(MemoryBudget {
+        budget: Bytes(info.Budget),
+        usage: Bytes(info.CurrentUsage),
+    }))
+}
+
+/// Block until the OS changes the memory budget of the adapter, or the timeout
+/// passes. Returns whether the budget changed; run it on a dedicated thread.
+pub fn wait_for_memory_budget_change(luid: u64, timeout: Duration) -> Result<bool, DxgiError> {
+    let Some(adapter) = adapter_by_luid::<IDXGIAdapter3>(luid)? else {
+        return Ok(false);
+    };
+    let event = unsafe { CreateEventW(None, false, false, None) }?;
+    scopeguard::defer! {
+        let _ = unsafe { CloseHandle(event) };
+    }
+    let cookie = unsafe { adapter.RegisterVideoMemoryBudgetChangeNotificationEvent(event) }?;
+    scopeguard::defer! {
+        unsafe { adapter.UnregisterVideoMemoryBudgetChangeNotification(cookie) };
+    }
+
+    let millis = u32::try_from(timeout.as_millis()).unwrap_or(u32::MAX);
+    Ok(unsafe { WaitForSingleObject(event, millis) } == WAIT_OBJECT_0)
+}
+
+/// One engine (node) of an adapter, e.g. `3D`, `Copy` or `Video Decode`
+#[derive(Debug, Clone, PartialEq)]
+pub struct EngineUtilization {
+    pub name: String,
+    pub class: EngineClass,
+    /// Share of the interval the engine was busy, from 0.0 to 1.0
+    pub busy: f32,
+}
+
+/// Engine load and memory residency of an adapter, from the kernel graphics
+/// statistics Task Manager reads, so it works for every vendor without
+/// NVML or ADL installed
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdapterUtilization {
+    pub engines: Vec<EngineUtilization>,
+    /// Memory resident in the adapter's own memory segments
+    pub dedicated_resident: Bytes,
+    /// System memory resident in aperture segments on behalf of the adapter
+    pub shared_resident: Bytes,
+}
+
+impl AdapterUtilization {
+    /// Load of the busiest engine, the headline "GPU" figure in Task Manager
+    pub fn busiest(&self) -> f32 {
+        self.engines
+            .iter()
+            .map(|engine| engine.busy)
+            .fold(0.0, f32::max)
+    }
+}
+
+/// Sample `D3DKMTQueryStatistics` twice, `interval` apart, and report how busy
+/// each engine was in between along with the memory resident at the end
+pub fn utilization_via_d3dkmt(
+    luid: u64,
+    interval: Duration,
+) -> Result<AdapterUtilization, DxgiError> {
+    let luid = LUID {
+        LowPart: luid as u32,
+        HighPart: (luid >> 32) as i32,
+    };
+    let adapter = unsafe {
+        query_statistics(luid, D3DKMT_QUERYSTATISTICS_ADAPTER, Default::default())?
+            .AdapterInformation
+    };
+    let metadata = engine_metadata(luid, adapter.NodeCount);
+
+    let before = node_running_times(luid, adapter.NodeCount)?;
+    let start = Instant::now();
+    std::thread::sleep(interval);
+    let after = node_running_times(luid, adapter.NodeCount)?;
+    // Running times are counted in 100 ns units
+    let elapsed = start.elapsed().as_nanos() as f64 / 100.0;
+
+    let engines = metadata
+        .into_iter()
+        .zip(before.iter().zip(&after))
+        .map(|((name, class), (before, after))| EngineUtilization {
+            name,
+            class,
+            busy: ((after - before) as f64 / elapsed).clamp(0.0, 1.0) as f32,
+        })
+        .collect();
+
+    let (mut dedicated_resident, mut shared_resident) = (Bytes(0), Bytes(0));
+    for segment in 0..adapter.NbSegments {
+        let query = D3DKMT_QUERYSTATISTICS_0 {
+            QuerySegment: D3DKMT_QUERYSTATISTICS_QUERY_SEGMENT { SegmentId: segment },
+        };
+        let info = unsafe {
+            query_statistics(luid, D3DKMT_QUERYSTATISTICS_SEGMENT, query)?.SegmentInformation
+        };
+        if info.Aperture != 0 {
+            shared_resident = shared_resident + Bytes(info.BytesResident);
+        } else {
+            dedicated_resident = dedicated_resident + Bytes(info.BytesResident);
+        }
+    }
+
+    Ok(AdapterUtilization {
+        engines,
+        dedicated_resident,
+        shared_resident,
+    })
+}
+
+fn query_statistics(
+    luid: LUID,
+    kind: D3DKMT_QUERYSTATISTICS_TYPE,
+    query: D3DKMT_QUERYSTATISTICS_0,
+) -> Result<D3DKMT_QUERYSTATISTICS_RESULT, DxgiError> {
+    let mut statistics = D3DKMT_QUERYSTATISTICS {
+        Type: kind,
+        AdapterLuid: luid,
+        Anonymous: query,
+        ..Default::default()
+    };
+    // Declared `*const`, but the kernel writes the result into the struct
+    unsafe { D3DKMTQueryStatistics(std::ptr::addr_of_mut!(statistics)) }.ok()?;
+    Ok(statistics.QueryResult)
+}
+
+/// Total time each node has spent running work since boot
+fn node_running_times(luid: LUID, node_count: u32) -> Result<Vec<i64>, DxgiError> {
+    (0..node_count)
+        .map(|node| {
+            let query = D3DKMT_QUERYSTATISTICS_0 {
+                QueryNode: D3DKMT_QUERYSTATISTICS_QUERY_NODE { NodeId: node },
+            };
+            let info = unsafe {
+                query_statistics(luid, D3DKMT_QUERYSTATISTICS_NODE, query)?.NodeInformation
+            };
+            Ok(info.GlobalInformation.RunningTime)
+        })
+        .collect()
+}
+
+/// Driver provided engine names, `Engine N` where the driver gives none,
+/// and the class of each engine
+fn engine_metadata(luid: LUID, node_count: u32) -> Vec<(String, EngineClass)> {
+    let metadata = with_kmt_adapter(luid, |adapter| {
+        Some(
+            (0..node_count)
+                .map(|node| {
+                    let metadata = D3DKMT_NODEMETADATA {
+                        NodeOrdinalAndAdapterIndex: node,
+                        ..Default::default()
+                    };
+                    query_adapter_info(adapter, KMTQAITYPE_NODEMETADATA, metadata)
+                })
+                .collect::<Vec<_>>(),
+        )
+    })
+    .unwrap_or_default();
+
+    (0..node_count as usize)
+        .map(|node| {
+            let metadata = metadata.get(node).copied().flatten();
+            // Copied out, as the struct is packed
+            let name = metadata
+                .map(|metadata| {
+                    let name = metadata.NodeData.FriendlyName;
+                    utf16_to_string(&name)
+                })
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| format!("Engine {node}"));
+            let engine_type = metadata
+                .map(|metadata| metadata.NodeData.EngineType)
+                .unwrap_or_default();
+            let class = engine_class(engine_type, &name);
+            (name, class)
+        })
+        .collect()
+}
+
+/// NVIDIA and AMD drivers give their compute engines no type of their own
+fn engine_class(engine_type: DXGK_ENGINE_TYPE, name: &str) -> EngineClass {
+    match engine_type {
+        DXGK_ENGINE_TYPE_3D => EngineClass::Graphics,
+        DXGK_ENGINE_TYPE_COPY => EngineClass::Copy,
+        DXGK_ENGINE_TYPE_VIDEO_DECODE => EngineClass::VideoDecode,
+        DXGK_ENGINE_TYPE_VIDEO_ENCODE => EngineClass::VideoEncode,
+        DXGK_ENGINE_TYPE_VIDEO_PROCESSING => EngineClass::VideoProcessing,
+        _ if name.to_ascii_lowercase().starts_with("compute") => EngineClass::Compute,
+        _ => EngineClass::Other,
+    }
+}
+
+/// Whether an adapter with this LUID is still present, `None` when DXGI
+/// cannot be asked. The Rust targets for Windows require Windows 10, so the
+/// lookup by LUID is always available.
+pub(crate) fn adapter_present(luid: u64) -> Option<bool> {
+    adapter_by_luid::<IDXGIAdapter1>(luid)
+        .ok()
+        .map(|adapter| adapter.is_some())
+}
+
+/// Outputs of the adapter with the given LUID, `None` once the adapter is gone
+pub(crate) fn outputs_for_luid(luid: u64) -> Result<Option<Vec<DisplayOutput>>, DxgiError> {
+    Ok(adapter_by_luid::<IDXGIAdapter1>(luid)?.map(|adapter| enumerate_outputs(&adapter)))
+}
+
+fn adapter_by_luid<T: Interface>(luid: u64) -> Result<Option<T>, DxgiError> {
+    // IDXGIFactory4 and IDXGIAdapter3 require Windows 10
+    let factory: IDXGIFactory1 = unsafe { CreateDXGIFactory1() }?;
+    let Ok(factory4) = factory.cast::<IDXGIFactory4>() else {
+        return Ok(None);
+    };
+    let luid = LUID {
+        LowPart: luid as u32,
+        HighPart: (luid >> 32) as i32,
+    };
+    Ok(unsafe { factory4.EnumAdapterByLuid::<T>(luid) }.ok())
+}
+
+fn preferred_adapter_luid(factory: &IDXGIFactory1, preference: DXGI_GPU_PREFERENCE) -> Option<u64> {
+    // IDXGIFactory6 requires Windows 10 1803
+    let factory6: IDXGIFactory6 = factory.cast().ok()?;
+    let adapter: IDXGIAdapter1 =
+        unsafe { factory6.EnumAdapterByGpuPreference(0, preference) }.ok()?;
+    let desc = unsafe { adapter.GetDesc1() }.ok()?;
+    Some(luid_to_u64(desc.AdapterLuid))
+}
+
+fn enumerate_outputs(adapter: &IDXGIAdapter1) -> Vec<DisplayOutput> {
+    let mut outputs = Vec::new();
+
+    let mut index = 0;
+    while let Ok(output) = unsafe { adapter.EnumOutputs(index) } {
+        index += 1;
+        let Ok(desc) = (unsafe { output.GetDesc() }) else {
+            continue;
+        };
+        let rect = desc.DesktopCoordinates;
+        outputs.push(DisplayOutput {
+            name: utf16_to_string(&desc.DeviceName),
+            attached: desc.AttachedToDesktop.as_bool(),
+            x: rect.left,
+            y: rect.top,
+            width: (rect.right - rect.left).unsigned_abs(),
+            height: (rect.bottom - rect.top).unsigned_abs(),
+        });
+    }
+
+    outputs
+}
+
+pub(crate) fn active_displays(outputs: &[DisplayOutput]) -> u32 {
+    outputs.iter().filter(|output| output.attached).count() as u32
+}
+
+/// Number of video present sources per adapter LUID. Every possible path
+/// starts at one of them, whether or not a monitor is connected.
+fn display_source_counts() -> Option<Vec<(u64, u32)>> {
+    let (mut path_count, mut mode_count) = (0, 0);
+    let result =
+        unsafe { GetDisplayConfigBufferSizes(QDC_ALL_PATHS, &mut path_count, &mut mode_count) };
+    if result != ERROR_SUCCESS {
+        return None;
+    }
+    let mut paths = vec![DISPLAYCONFIG_PATH_INFO::default(); path_count as usize];
+    let mut modes = vec![DISPLAYCONFIG_MODE_INFO::default(); mode_count as usize];
+    let result = unsafe {
+        QueryDisplayConfig(
+            QDC_ALL_PATHS,
+            &mut path_count,
+            paths.as_mut_ptr(),
+            &mut mode_count,
+            modes.as_mut_ptr(),
+            None,
+        )
+    };
+    if result != ERROR_SUCCESS {
+        return None;
+    }
+    paths.truncate(path_count as usize);
+
+    let mut sources = paths
+        .iter()
+        .map(|path| (luid_to_u64(path.sourceInfo.adapterId), path.sourceInfo.id))
+        .collect::<Vec<_>>();
+    sources.sort_unstable();
+    sources.dedup();
+    let mut counts: Vec<(u64, u32)> = Vec::new();
+    for (luid, _) in sources {
+        match counts.last_mut() {
+            Some((last, count)) if *last == luid => *count += 1,
+            _ => counts.push((luid, 1)),
+        }
+    }
+    Some(counts)
+}
+
+/// Driver service names of the display class devices, keyed by PCI vendor and device id
+fn display_driver_services() -> Vec<(u32, u32, String)> {
+    class_devices(&GUID_DEVCLASS_DISPLAY)
+        .into_iter()
+        .filter_map(|device| {
+            let (vendor_id, device_id) = device.pci_ids()?;
+            Some((vendor_id, device_id, device.service?))
+        })
+        .collect()
+}
+
+/// A present device of a SetupAPI device class
+pub(crate) struct ClassDevice {
+    /// The first hardware id, e.g. `PCI\VEN_10DE&DEV_2684&SUBSYS_...`
+    pub hardware_id: String,
+    pub description: Option<String>,
+    pub service: Option<String>,
+    /// Vulkan driver manifests from `VulkanDriverName` and, for 32-bit
+    /// processes, `VulkanDriverNameWow` in the driver key
+    pub vulkan_drivers: Vec<String>,
+}
+
+impl ClassDevice {
+    /// Vendor and device id, `None` for non-PCI ids like `ACPI\VEN_QCOM&DEV_0D0A`
+    pub fn pci_ids(&self) -> Option<(u32, u32)> {
+        let id_after = |prefix: &str| {
+            let start = self.hardware_id.find(prefix)? + prefix.len();
+            u32::from_str_radix(self.hardware_id.get(start..start + 4)?, 16).ok()
+        };
+        Some((id_after("VEN_")?, id_after("DEV_")?))
+    }
+}
+
+pub(crate) fn class_devices(class: &GUID) -> Vec<ClassDevice> {
+    let Ok(devices) = (unsafe { SetupDiGetClassDevsW(Some(class), None, None, DIGCF_PRESENT) })
+    else {
+        return vec![];
+    };
+
+    scopeguard::defer! {
+        let _ = unsafe { SetupDiDestroyDeviceInfoList(devices) };
+    }
+
+    let mut class_devices = Vec::new();
+
+    let mut index = 0;
+    loop {
+        let mut info = SP_DEVINFO_DATA {
+            cbSize: std::mem::size_of::<SP_DEVINFO_DATA>() as u32,
+            ..Default::default()
+        };
+        if unsafe { SetupDiEnumDeviceInfo(devices, index, &mut info) }.is_err() {
+            break;
+        }
+        index += 1;
+
+        let property = |property| {
+            let mut buffer = [0u8; 1024];
+            unsafe {
+                SetupDiGetDeviceRegistryPropertyW(
+                    devices,
+                    &info,
+                    property,
+                    None,
+                    Some(&mut buffer),
+                    None,
+                )
+            }
+            .ok()?;
+            let wide = buffer
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect::<Vec<_>>();
+            Some(utf16_to_string(&wide))
+        };
+
+        let Some(hardware_id) = property(SPDRP_HARDWAREID) else {
+            continue;
+        };
+        class_devices.push(ClassDevice {
+            hardware_id,
+            description: property(SPDRP_DEVICEDESC),
+            service: property(SPDRP_SERVICE),
+            vulkan_drivers: driver_key_strings(
+                devices,
+                &info,
+                &[w!("VulkanDriverName"), w!("VulkanDriverNameWow")],
+            ),
+        });
+    }
+
+    class_devices
+}
+
+/// The strings of `REG_SZ` and `REG_MULTI_SZ` values in a device's driver key
+fn driver_key_strings(devices: HDEVINFO, info: &SP_DEVINFO_DATA, names: &[PCWSTR]) -> Vec<String> {
+    let Ok(key) = (unsafe {
+        SetupDiOpenDevRegKey(devices, info, DICS_FLAG_GLOBAL.0, 0, DIREG_DRV, KEY_READ.0)
+    }) else {
+        return vec![];
+    };
+    scopeguard::defer! {
+        let _ = unsafe { RegCloseKey(key) };
+    }
+
+    let mut strings = Vec::new();
+    for &name in names {
+        let mut buffer = [0u16; 2048];
+        let mut size = std::mem::size_of_val(&buffer) as u32;
+        let result = unsafe {
+            RegQueryValueExW(
+                key,
+                name,
+                None,
+                None,
+                Some(buffer.as_mut_ptr().cast()),
+                Some(&mut size),
+            )
+        };
+        if result.is_err() {
+            continue;
+        }
+        strings.extend(
+            buffer[..size as usize / 2]
+                .split(|&c| c == 0)
+                .filter(|s| !s.is_empty())
+                .map(String::from_utf16_lossy),
+        );
+    }
+    strings
+}
+
+/// Vulkan driver manifests of present display adapters, then the enabled ones
+/// under `HKLM\SOFTWARE\Khronos\Vulkan\Drivers` in the 64 and 32-bit views
+pub(crate) fn vulkan_driver_manifests() -> Vec<PathBuf> {
+    let mut manifests = class_devices(&GUID_DEVCLASS_DISPLAY)
+        .into_iter()
+        .flat_map(|device| device.vulkan_drivers)
+        .map(PathBuf::from)
+        .collect::<Vec<_>>();
+
+    for view in [KEY_WOW64_64KEY, KEY_WOW64_32KEY] {
+        let mut key = HKEY::default();
+        let result = unsafe {
+            RegOpenKeyExW(
+                HKEY_LOCAL_MACHINE,
+                w!("SOFTWARE\\Khronos\\Vulkan\\Drivers"),
+                None,
+                KEY_READ | view,
+                &mut key,
+            )
+        };
+        if result.is_err() {
+            continue;
+        }
+        scopeguard::defer! {
+            let _ = unsafe { RegCloseKey(key) };
+        }
+
+        // Value names are manifest paths, a non-zero DWORD disables the driver
+        let mut index = 0;
+        loop {
+            let mut name = [0u16; 1024];
+            let mut name_len = name.len() as u32;
+            let mut disabled = 0u32;
+            let mut data_len = std::mem::size_of_val(&disabled) as u32;
+            let result = unsafe {
+                RegEnumValueW(
+                    key,
+                    index,
+                    Some(PWSTR(name.as_mut_ptr())),
+                    &mut name_len,
+                    None,
+                    None,
+                    Some((&mut disabled as *mut u32).cast()),
+                    Some(&mut data_len),
+                )
+            };
+            if result.is_err() {
+                break;
+            }
+            index += 1;
+            if disabled == 0 {
+                manifests.push(PathBuf::from(String::from_utf16_lossy(
+                    &name[..name_len as usize],
+                )));
+            }
+        }
+    }
+
+    manifests
+}
+
+/// A `REG_SZ` value under `HKEY_LOCAL_MACHINE`
+fn machine_registry_string(subkey: PCWSTR, name: PCWSTR) -> Option<String> {
+    registry_string(HKEY_LOCAL_MACHINE, subkey, name)
+}
+
+fn registry_string(root: HKEY, subkey: PCWSTR, name: PCWSTR) -> Option<String> {
+    let mut buffer = [0u16; 256];
+    let mut size = std::mem::size_of_val(&buffer) as u32;
+    let result = unsafe {
+        RegGetValueW(
+            root,
+            subkey,
+            name,
+            RRF_RT_REG_SZ,
+            None,
+            Some(buffer.as_mut_ptr().cast()),
+            Some(&mut size),
+        )
+    };
+    result.is_ok().then(|| {
+        let len = buffer.iter().position(|&c| c == 0).unwrap_or(buffer.len());
+        String::from_utf16_lossy(&buffer[..len])
+    })
+}
+
+/// SMBIOS system manufacturer and product name, and whether the Azure guest
+/// agent is installed, which tells Azure VMs apart from local Hyper-V ones
+pub(crate) fn read_bios_strings() -> (Option<String>, Option<String>, bool) {
+    let bios = w!("HARDWARE\\DESCRIPTION\\System\\BIOS");
+
+    let mut key = HKEY::default();
+    let has_azure_agent = unsafe {
+        RegOpenKeyExW(
+            HKEY_LOCAL_MACHINE,
+            w!("SOFTWARE\\Microsoft\\Windows Azure"),
+            None,
+            KEY_READ,
+            &mut key,
+        )
+    }
+    .is_ok();
+    if has_azure_agent {
+        let _ = unsafe { RegCloseKey(key) };
+    }
+
+    (
+        machine_registry_string(bios, w!("SystemManufacturer")),
+        machine_registry_string(bios, w!("SystemProductName")),
+        has_azure_agent,
+    )
+}
+
+/// Whether this process runs under Wine or Proton, whose `ntdll.dll` exports
+/// `wine_get_version`
+pub(crate) fn is_wine() -> bool {
+    use windows::Win32::System::LibraryLoader::{GetModuleHandleW, GetProcAddress};
+
+    let Ok(ntdll) = (unsafe { GetModuleHandleW(w!("ntdll.dll")) }) else {
+        return false;
+    };
+    unsafe { GetProcAddress(ntdll, windows::core::s!("wine_get_version")) }.is_some()
+}
+
+/// The load order Wine's configuration sets for `dll`, e.g. `native,builtin`
+pub(crate) fn wine_dll_override(dll: &str) -> Option<String> {
+    let name = windows::core::HSTRING::from(dll);
+    registry_string(
+        HKEY_CURRENT_USER,
+        w!("Software\\Wine\\DllOverrides"),
+        PCWSTR(name.as_ptr()),
+    )
+}
+
+/// SMBIOS BIOS vendor, version and release date
+pub(crate) fn read_bios_version() -> (Option<String>, Option<String>, Option<String>) {
+    let bios = w!("HARDWARE\\DESCRIPTION\\System\\BIOS");
+    (
+        machine_registry_string(bios, w!("BIOSVendor")),
+        machine_registry_string(bios, w!("BIOSVersion")),
+        machine_registry_string(bios, w!("BIOSReleaseDate")),
+    )
+}
+
+/// Installed physical memory in bytes
+pub(crate) fn read_total_ram() -> Option<u64> {
+    use windows::Win32::System::SystemInformation::{GlobalMemoryStatusEx, MEMORYSTATUSEX};
+
+    let mut status = MEMORYSTATUSEX {
+        dwLength: std::mem::size_of::<MEMORYSTATUSEX>() as u32,
+        ..Default::default()
+    };
+    unsafe { GlobalMemoryStatusEx(&mut status) }.ok()?;
+    Some(status.ullTotalPhys)
+}
+
+/// e.g. `AMD Ryzen Z1 Extreme w/ Radeon 780M Graphics`
+pub(crate) fn read_cpu_brand() -> Option<String> {
+    machine_registry_string(
+        w!("HARDWARE\\DESCRIPTION\\System\\CentralProcessor\\0"),
+        w!("ProcessorNameString"),
+    )
+    .map(|brand| brand.trim().to_string())
+}
+
+/// e.g. `Windows 10 Pro 23H2 (build 22631)`. Windows 11 still calls itself
+/// Windows 10 in `ProductName`.
+pub(crate) fn read_windows_version() -> Option<String> {
+    let current_version = w!("SOFTWARE\\Microsoft\\Windows NT\\CurrentVersion");
+    let product = machine_registry_string(current_version, w!("ProductName"))?;
+    let display_version = machine_registry_string(current_version, w!("DisplayVersion"));
+    let build = machine_registry_string(current_version, w!("CurrentBuild"));
+    Some(
+        [Some(product), display_version]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(" ")
+            + &build
+                .map(|build| format!(" (build {build})"))
+                .unwrap_or_default(),
+    )
+}
+
+/// The user-mode driver version, e.g. `32.0.15.6094`
+fn query_driver_version(adapter: &IDXGIAdapter1) -> Option<String> {
+    let version = unsafe { adapter.CheckInterfaceSupport(&IDXGIDevice::IID) }.ok()?;
+    Some(format!(
+        "{}.{}.{}.{}",
+        (version >> 48) & 0xFFFF,
+        (version >> 32) & 0xFFFF,
+        (version >> 16) & 0xFFFF,
+        version & 0xFFFF
+    ))
+}
+
+fn query_driver_model(luid: LUID) -> Option<String> {
+    let version = with_kmt_adapter(luid, |adapter| {
+        query_adapter_info(
+            adapter,
+            KMTQAITYPE_DRIVERVERSION,
+            D3DKMT_DRIVERVERSION::default(),
+        )
+    })?;
+
+    // e.g. KMT_DRIVERVERSION_WDDM_3_1 is 3100
+    Some(format!(
+        "WDDM {}.{}",
+        version.0 / 1000,
+        version.0 % 1000 / 100
+    ))
+}
+
+/// Whether hardware-accelerated GPU scheduling is on, as toggled in
+/// Settings > Display > Graphics. `None` before WDDM 2.7 or when the GPU and
+/// driver do not support it.
+fn query_hardware_scheduling(luid: LUID) -> Option<bool> {
+    let caps = with_kmt_adapter(luid, |adapter| {
+        query_adapter_info(
+            adapter,
+            KMTQAITYPE_WDDM_2_7_CAPS,
+            D3DKMT_WDDM_2_7_CAPS::default(),
+        )
+    })?;
+    // HwSchSupported is bit 0, HwSchEnabled bit 1
+    let caps = unsafe { caps.Anonymous.Value };
+    (caps & 1 != 0).then_some(caps & 2 != 0)
+}
+
+/// Physical adapters linked into this one, e.g. by SLI or CrossFire. D3D12
+/// exposes each as a node of the device, `GetNodeCount` returns the same.
+fn query_linked_node_count(luid: LUID) -> Option<u32> {
+    let count = with_kmt_adapter(luid, |adapter| {
+        query_adapter_info(
+            adapter,
+            KMTQAITYPE_PHYSICALADAPTERCOUNT,
+            D3DKMT_PHYSICAL_ADAPTER_COUNT::default(),
+        )
+    })?;
+    (count.Count > 0).then_some(count.Count)
+}
+
+/// Run `f` with a kernel-mode handle to the adapter
+fn with_kmt_adapter<R>(luid: LUID, f: impl FnOnce(u32) -> Option<R>) -> Option<R> {
+    let mut open = D3DKMT_OPENADAPTERFROMLUID {
+        AdapterLuid: luid,
+        hAdapter: 0,
+    };
+    if unsafe { D3DKMTOpenAdapterFromLuid(&mut open) }.is_err() {
+        return None;
+    }
+
+    scopeguard::defer! {
+        let close = D3DKMT_CLOSEADAPTER { hAdapter: open.hAdapter };
+        let _ = unsafe { D3DKMTCloseAdapter(&close) };
+    }
+
+    f(open.hAdapter)
+}
+
+fn query_adapter_info<T>(adapter: u32, kind: KMTQUERYADAPTERINFOTYPE, mut value: T) -> Option<T> {
+    let mut query = D3DKMT_QUERYADAPTERINFO {
+        hAdapter: adapter,
+        Type: kind,
+        pPrivateDriverData: &mut value as *mut _ as *mut _,
+        PrivateDriverDataSize: std::mem::size_of::<T>() as u32,
+    };
+    if unsafe { D3DKMTQueryAdapterInfo(&mut query) }.is_err() {
+        return None;
+    }
+    Some(value)
+}
+
+pub(crate) fn luid_to_u64(luid: LUID) -> u64 {
+    ((luid.HighPart as u32 as u64) << 32) | luid.LowPart as u64
+}
+
+pub(crate) fn utf16_to_string(raw: &[u16]) -> String {
+    let len = raw.iter().position(|&c| c == 0).unwrap_or(raw.len());
+    String::from_utf16_lossy(&raw[..len])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_retrieve_gpu_info_via_dxgi() {
+        let result = retrieve_gpu_info_via_dxgi();
+        eprintln!("{:#?}", result);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn test_utilization_via_d3dkmt() {
+        for adapter in retrieve_gpu_info_via_dxgi().unwrap() {
+            let result = utilization_via_d3dkmt(adapter.luid, Duration::from_millis(100));
+            eprintln!("{}: {:#?}", adapter.name, result);
+        }
+    }
+}
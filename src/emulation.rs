@@ -0,0 +1,79 @@
+use std::sync::OnceLock;
+
+/// A translation layer running this x86 process on an Arm CPU. GPU APIs
+/// still work but load x86 builds of their drivers: OpenCL is missing under
+/// Rosetta 2 and Vulkan drivers without an x86 build, e.g. older Qualcomm
+/// ones on Windows, are not found. Native builds see the real hardware.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[non_exhaustive]
+pub enum Emulation {
+    /// An x86_64 build on Apple silicon
+    Rosetta2,
+    /// An x86 or x64 build on Windows on Arm
+    WindowsOnArm,
+}
+
+impl Emulation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Emulation::Rosetta2 => "Rosetta 2",
+            Emulation::WindowsOnArm => "Windows on Arm x86 emulation",
+        }
+    }
+}
+
+/// The emulation this process runs under, `None` when it runs natively
+pub fn process_emulation() -> Option<Emulation> {
+    static EMULATION: OnceLock<Option<Emulation>> = OnceLock::new();
+    *EMULATION.get_or_init(detect_emulation)
+}
+
+#[cfg(all(target_os = "macos", target_arch = "x86_64"))]
+fn detect_emulation() -> Option<Emulation> {
+    // 1 when translated, missing before macOS 11
+    let translated = crate::system::sysctl::<std::ffi::c_int>(c"sysctl.proc_translated");
+    (translated == Some(1)).then_some(Emulation::Rosetta2)
+}
+
+#[cfg(all(windows, not(target_arch = "aarch64")))]
+fn detect_emulation() -> Option<Emulation> {
+    use windows::Win32::System::SystemInformation::{IMAGE_FILE_MACHINE, IMAGE_FILE_MACHINE_ARM64};
+    use windows::Win32::System::Threading::{GetCurrentProcess, IsWow64Process2};
+
+    // x64 processes are not WOW64 processes, only the native machine tells
+    let mut process_machine = IMAGE_FILE_MACHINE::default();
+    let mut native_machine = IMAGE_FILE_MACHINE::default();
+    unsafe {
+        IsWow64Process2(
+            GetCurrentProcess(),
+            &mut process_machine,
+            Some(&mut native_machine),
+        )
+    }
+    .ok()?;
+    (native_machine == IMAGE_FILE_MACHINE_ARM64).then_some(Emulation::WindowsOnArm)
+}
+
+#[cfg(not(any(
+    all(target_os = "macos", target_arch = "x86_64"),
+    all(windows, not(target_arch = "aarch64"))
+)))]
+fn detect_emulation() -> Option<Emulation> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_process_emulation() {
+        let emulation = process_emulation();
+        eprintln!("{emulation:?}");
+        if cfg!(target_arch = "aarch64") {
+            assert_eq!(emulation, None);
+        }
+    }
+}
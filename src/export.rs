@@ -0,0 +1,357 @@
+use crate::{Bytes, TemperatureLocation, GPU};
+
+/// Column order of [`to_csv`]. New columns are only ever appended.
+pub const CSV_COLUMNS: &[&str] = &[
+    "kind",
+    "name",
+    "vendor",
+    "driver_version",
+    "vram_mb",
+    "clock_speed",
+    "temperature",
+    "compute_queue_count",
+    "async_compute",
+    "luid",
+    "display_count",
+    "hybrid_role",
+    "driver_model",
+    "pci_address",
+    "kernel_driver",
+    "has_display_output",
+    "uuid",
+    "partition_mode",
+    "partition_count",
+    "memory_partition_mode",
+    "tdp_watts",
+    "min_power_limit",
+    "max_power_limit",
+    "shared_memory_mb",
+    "recommended_working_set_mb",
+    "carve_out_mb",
+    "tile_count",
+    "passthrough",
+    "cloud_instance",
+    "hardware_ray_tracing",
+    "mesh_shading",
+    "hardware_scheduling",
+    "linked_node_count",
+    "instance_index",
+    "is_overclocked",
+    "overclock_settings",
+    "product_line",
+    "product_segment",
+    "translation_layer",
+    "hotspot_temperature",
+    "memory_temperature",
+    "vrm_temperature",
+    "max_displays",
+    "active_displays",
+    "registry_id",
+];
+
+/// One header row followed by one row per GPU, unknown values are left empty.
+pub fn to_csv(gpus: &[GPU]) -> String {
+    let mut out = CSV_COLUMNS.join(",");
+    out.push('\n');
+
+    for gpu in gpus {
+        let row = flat_fields(gpu)
+            .iter()
+            .map(|field| escape_csv(field))
+            .collect::<Vec<_>>();
+        out.push_str(&row.join(","));
+        out.push('\n');
+    }
+
+    out
+}
+
+impl GPU {
+    /// Flat `(key, value)` pairs for structured logging, keyed like the
+    /// [`to_csv`] columns. Unknown values are omitted.
+    pub fn log_fields(&self) -> Vec<(&'static str, String)> {
+        CSV_COLUMNS
+            .iter()
+            .copied()
+            .zip(flat_fields(self))
+            .filter(|(_, value)| !value.is_empty())
+            .collect()
+    }
+}
+
+/// Values in [`CSV_COLUMNS`] order, unknown values are empty
+fn flat_fields(gpu: &GPU) -> Vec<String> {
+    vec![
+        gpu.kind.as_str().to_string(),
+        gpu.name.clone(),
+        gpu.vendor.clone(),
+        gpu.driver_version.clone(),
+        gpu.vram.as_mb().to_string(),
+        optional(gpu.clock_speed.map(|clock| clock.0)),
+        optional(gpu.temperature().map(|temperature| temperature.0)),
+        optional(gpu.compute_queue_count),
+        optional(gpu.async_compute),
+        gpu.luid
+            .map(|luid| format!("{luid:#x}"))
+            .unwrap_or_default(),
+        gpu.displays.len().to_string(),
+        gpu.hybrid_role
+            .map(|role| format!("{role:?}"))
+            .unwrap_or_default(),
+        gpu.driver_model.clone().unwrap_or_default(),
+        optional(gpu.pci_address),
+        gpu.kernel_driver.clone().unwrap_or_default(),
+        optional(gpu.has_display_output),
+        gpu.uuid.clone().unwrap_or_default(),
+        gpu.partition_mode.clone().unwrap_or_default(),
+        gpu.partitions.len().to_string(),
+        gpu.memory_partition_mode.clone().unwrap_or_default(),
+        optional(gpu.tdp_watts.map(|watts| watts.0)),
+        optional(gpu.min_power_limit.map(|watts| watts.0)),
+        optional(gpu.max_power_limit.map(|watts| watts.0)),
+        optional(gpu.shared_memory.map(Bytes::as_mb)),
+        optional(gpu.recommended_working_set.map(Bytes::as_mb)),
+        optional(gpu.carve_out.map(Bytes::as_mb)),
+        optional(gpu.tile_count),
+        optional(gpu.passthrough),
+        gpu.cloud_instance.clone().unwrap_or_default(),
+        optional(gpu.hardware_ray_tracing),
+        optional(gpu.mesh_shading),
+        optional(gpu.hardware_scheduling),
+        optional(gpu.linked_node_count),
+        optional(gpu.instance_index),
+        optional(gpu.is_overclocked),
+        gpu.overclock_settings.len().to_string(),
+        gpu.product_line.clone().unwrap_or_default(),
+        gpu.product_segment
+            .map(|segment| format!("{segment:?}"))
+            .unwrap_or_default(),
+        gpu.translation_layer.clone().unwrap_or_default(),
+        optional(gpu.temperature_at(TemperatureLocation::Hotspot)),
+        optional(gpu.temperature_at(TemperatureLocation::Memory)),
+        optional(gpu.temperature_at(TemperatureLocation::Vrm)),
+        optional(gpu.max_displays),
+        optional(gpu.active_displays),
+        gpu.registry_id
+            .map(|registry_id| format!("{registry_id:#x}"))
+            .unwrap_or_default(),
+    ]
+}
+
+/// Version of the [`to_capability_json`] document, only bumped when a field
+/// is removed or retyped
+#[cfg(feature = "schema")]
+pub const CAPABILITY_MATRIX_VERSION: u32 = 1;
+
+/// Every capability and limit of every GPU, as written by
+/// [`to_capability_json`] and described by [`capability_matrix_schema`]
+#[cfg(feature = "schema")]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
+pub struct CapabilityMatrix {
+    /// [`CAPABILITY_MATRIX_VERSION`] of the writer
+    pub version: u32,
+    /// Version of gpu-info that wrote the document
+    pub crate_version: String,
+    pub gpus: Vec<GPU>,
+}
+
+/// A [`CapabilityMatrix`] as pretty printed JSON, for users to upload to
+/// web-based diagnostics pages
+#[cfg(feature = "schema")]
+pub fn to_capability_json(gpus: &[GPU]) -> String {
+    let matrix = CapabilityMatrix {
+        version: CAPABILITY_MATRIX_VERSION,
+        crate_version: env!("CARGO_PKG_VERSION").to_string(),
+        gpus: gpus.to_vec(),
+    };
+    serde_json::to_string_pretty(&matrix).expect("GPU serializes to JSON")
+}
+
+/// JSON Schema of [`to_capability_json`] documents. The copy published in
+/// `schema/capability-matrix.schema.json` is generated with the `extra`
+/// feature enabled.
+#[cfg(feature = "schema")]
+pub fn capability_matrix_schema() -> String {
+    let schema = schemars::schema_for!(CapabilityMatrix);
+    serde_json::to_string_pretty(&schema).expect("schema serializes to JSON")
+}
+
+/// A plist shaped like `system_profiler -xml SPDisplaysDataType`, so it can be
+/// diffed against the output of the system tool.
+#[cfg(target_os = "macos")]
+pub fn to_plist(gpus: &[crate::MetalGpu]) -> String {
+    use crate::GPULocation;
+    use std::fmt::Write;
+
+    let mut out = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" ",
+        "\"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n",
+        "<plist version=\"1.0\">\n<array>\n\t<dict>\n",
+        "\t\t<key>_dataType</key>\n\t\t<string>SPDisplaysDataType</string>\n",
+        "\t\t<key>_items</key>\n\t\t<array>\n",
+    ));
+
+    for gpu in gpus {
+        let bus = match gpu.location {
+            GPULocation::BuiltIn => "spdisplays_builtin",
+            GPULocation::Slot => "spdisplays_pcie_device",
+            GPULocation::External => "spdisplays_thunderbolt",
+            GPULocation::Unspecified | GPULocation::Other(_) => "spdisplays_unknown",
+        };
+        let vram_key = if gpu.has_unified_memory {
+            "spdisplays_vram_shared"
+        } else {
+            "spdisplays_vram"
+        };
+
+        out.push_str("\t\t\t<dict>\n");
+        for (key, value) in [
+            ("_name", gpu.name.as_str()),
+            ("sppci_bus", bus),
+            ("sppci_device_type", "spdisplays_gpu"),
+            ("sppci_model", gpu.name.as_str()),
+            ("spdisplays_vendor", gpu.vendor.as_str()),
+            (vram_key, &format!("{} MB", gpu.vram)),
+        ] {
+            let _ = writeln!(
+                out,
+                "\t\t\t\t<key>{}</key>\n\t\t\t\t<string>{}</string>",
+                escape_xml(key),
+                escape_xml(value)
+            );
+        }
+        out.push_str("\t\t\t</dict>\n");
+    }
+
+    out.push_str("\t\t</array>\n\t</dict>\n</array>\n</plist>\n");
+    out
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn escape_xml(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log_fields() {
+        let gpu = GPU {
+            name: "NVIDIA GeForce RTX 4090".to_string(),
+            vram: Bytes::from_mb(24564),
+            ..Default::default()
+        };
+        assert_eq!(flat_fields(&gpu).len(), CSV_COLUMNS.len());
+
+        let fields = gpu.log_fields();
+        assert!(fields.contains(&("vram_mb", "24564".to_string())));
+        assert!(!fields.iter().any(|(key, _)| *key == "temperature"));
+    }
+
+    /// Consumers read columns by position, so released columns keep theirs
+    #[test]
+    fn test_csv_columns_are_append_only() {
+        let released = [
+            "kind",
+            "name",
+            "vendor",
+            "driver_version",
+            "vram_mb",
+            "clock_speed",
+            "temperature",
+            "compute_queue_count",
+            "async_compute",
+            "luid",
+            "display_count",
+            "hybrid_role",
+            "driver_model",
+            "pci_address",
+            "kernel_driver",
+            "has_display_output",
+            "uuid",
+            "partition_mode",
+            "partition_count",
+            "memory_partition_mode",
+            "tdp_watts",
+            "min_power_limit",
+            "max_power_limit",
+            "shared_memory_mb",
+            "recommended_working_set_mb",
+            "carve_out_mb",
+            "tile_count",
+            "passthrough",
+            "cloud_instance",
+            "hardware_ray_tracing",
+            "mesh_shading",
+            "hardware_scheduling",
+            "linked_node_count",
+            "instance_index",
+            "is_overclocked",
+            "overclock_settings",
+            "product_line",
+            "product_segment",
+            "translation_layer",
+            "hotspot_temperature",
+            "memory_temperature",
+            "vrm_temperature",
+            "max_displays",
+            "active_displays",
+        ];
+        assert_eq!(&CSV_COLUMNS[..released.len()], released);
+    }
+
+    #[test]
+    fn test_escape_csv() {
+        assert_eq!(
+            escape_csv("NVIDIA GeForce RTX 4090"),
+            "NVIDIA GeForce RTX 4090"
+        );
+        assert_eq!(escape_csv("Radeon, Pro"), "\"Radeon, Pro\"");
+        assert_eq!(escape_csv("12\" card"), "\"12\"\" card\"");
+    }
+
+    /// Run with `UPDATE_SCHEMA=1` to regenerate the published schema
+    #[cfg(all(feature = "schema", feature = "extra"))]
+    #[test]
+    fn test_capability_matrix_schema_is_published() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/schema/capability-matrix.schema.json"
+        );
+        let schema = capability_matrix_schema() + "\n";
+        if std::env::var_os("UPDATE_SCHEMA").is_some() {
+            std::fs::write(path, &schema).unwrap();
+        }
+        pretty_assertions::assert_eq!(std::fs::read_to_string(path).unwrap(), schema);
+
+        let json = to_capability_json(&[GPU::builder().name("Test").build()]);
+        let matrix: CapabilityMatrix = serde_json::from_str(&json).unwrap();
+        assert_eq!(matrix.version, CAPABILITY_MATRIX_VERSION);
+        assert_eq!(matrix.gpus[0].name, "Test");
+    }
+}
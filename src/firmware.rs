@@ -0,0 +1,329 @@
+use crate::{GPUKind, GpuQuery, PciAddress};
+
+/// 64-bit BARs the firmware maps above this address need the "Above 4G
+/// decoding" setting
+#[cfg(target_os = "linux")]
+const FOUR_GIB: u64 = 1 << 32;
+
+/// `IORESOURCE_MEM` in the flags column of a sysfs `resource` file
+#[cfg(any(target_os = "linux", test))]
+const IORESOURCE_MEM: u64 = 0x200;
+
+/// Read-only hints at the UEFI / BIOS settings that change how GPUs show
+/// up, for remote troubleshooting. The firmware does not expose its setup
+/// options, so each is inferred from where the OS found the devices; most
+/// are only known on Linux.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct FirmwareInfo {
+    /// From SMBIOS, e.g. `American Megatrends International, LLC.`
+    pub bios_vendor: Option<String>,
+    pub bios_version: Option<String>,
+    pub bios_date: Option<String>,
+    /// Whether the firmware mapped GPU memory above 4 GiB, which requires
+    /// Above 4G decoding and is needed for Resizable BAR. `false` when every
+    /// GPU BAR sits below, as small BARs may also do with the setting on.
+    pub above_4g_decoding: Option<bool>,
+    /// The GPU the firmware initialized to show the boot screen, chosen by
+    /// the primary display setting (iGPU, PEG or PCI). Compare with
+    /// [`GPU::pci_address`](crate::GPU::pci_address) to tell its kind.
+    pub boot_display: Option<PciAddress>,
+    /// GPUs with SR-IOV enabled, as the capability is hidden when the
+    /// firmware turns SR-IOV off
+    pub sriov: Vec<SriovStatus>,
+    /// Whether the CPU's integrated GPU, which Quick Sync needs on Intel, is
+    /// missing or turned off
+    pub integrated_gpu: IntegratedGpuStatus,
+}
+
+/// The CPU model tells whether it has an integrated GPU, the enumerated
+/// GPUs whether it is turned on
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[non_exhaustive]
+pub enum IntegratedGpuStatus {
+    Present,
+    /// The CPU has none, e.g. Intel `F` and `X` models or a Ryzen 5000
+    /// without `G`
+    NotOnCpu,
+    /// The CPU has one, but the OS does not see it: it is disabled in the
+    /// firmware, or set to turn off when a discrete GPU is installed. The
+    /// setting is usually called "Internal Graphics" or "iGPU Multi-Monitor".
+    DisabledInFirmware,
+    /// The CPU model is not recognized, or enumeration failed
+    #[default]
+    Unknown,
+}
+
+impl IntegratedGpuStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IntegratedGpuStatus::Present => "present",
+            IntegratedGpuStatus::NotOnCpu => "not on the CPU",
+            IntegratedGpuStatus::DisabledInFirmware => "disabled in firmware",
+            IntegratedGpuStatus::Unknown => "unknown",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct SriovStatus {
+    pub pci_address: PciAddress,
+    /// Virtual functions the GPU supports
+    pub total_vfs: u32,
+    /// Virtual functions currently created by the driver
+    pub enabled_vfs: u32,
+}
+
+/// Read the [`FirmwareInfo`], without root on Linux
+pub fn firmware_info() -> FirmwareInfo {
+    let (bios_vendor, bios_version, bios_date) = read_bios();
+    let info = FirmwareInfo {
+        bios_vendor,
+        bios_version,
+        bios_date,
+        integrated_gpu: integrated_gpu_status(),
+        ..Default::default()
+    };
+    #[cfg(target_os = "linux")]
+    let info = read_pci_firmware_hints(std::path::Path::new(crate::pci::SYSFS_PCI_DEVICES), info);
+    info
+}
+
+#[cfg(target_os = "linux")]
+fn read_bios() -> (Option<String>, Option<String>, Option<String>) {
+    let read = |name: &str| {
+        std::fs::read_to_string(format!("/sys/class/dmi/id/{name}"))
+            .ok()
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    (read("bios_vendor"), read("bios_version"), read("bios_date"))
+}
+
+#[cfg(windows)]
+fn read_bios() -> (Option<String>, Option<String>, Option<String>) {
+    crate::dxgi::read_bios_version()
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+fn read_bios() -> (Option<String>, Option<String>, Option<String>) {
+    (None, None, None)
+}
+
+fn integrated_gpu_status() -> IntegratedGpuStatus {
+    let Some(on_cpu) = crate::system::cpu_model()
+        .as_deref()
+        .and_then(cpu_has_integrated_gpu)
+    else {
+        return IntegratedGpuStatus::Unknown;
+    };
+    if !on_cpu {
+        return IntegratedGpuStatus::NotOnCpu;
+    }
+    // Intel iGPUs are always at 00:02.0, which also finds them without a
+    // working driver
+    #[cfg(target_os = "linux")]
+    if let Ok(gpus) = crate::pci::read_pci_gpus(std::path::Path::new(crate::pci::SYSFS_PCI_DEVICES))
+    {
+        if gpus
+            .iter()
+            .any(|gpu| gpu.vendor_id == 0x8086 && gpu.address.bus == 0)
+        {
+            return IntegratedGpuStatus::Present;
+        }
+    }
+    match GpuQuery::new().run() {
+        Ok(gpus) if gpus.iter().any(|gpu| gpu.kind == GPUKind::Integrated) => {
+            IntegratedGpuStatus::Present
+        }
+        Ok(_) => IntegratedGpuStatus::DisabledInFirmware,
+        Err(_) => IntegratedGpuStatus::Unknown,
+    }
+}
+
+/// Whether a CPU, by its brand string, has an integrated GPU. `None` for
+/// models without a rule, e.g. Xeons, where it varies within a series.
+fn cpu_has_integrated_gpu(cpu_model: &str) -> Option<bool> {
+    if cpu_model.starts_with("Apple") {
+        return Some(true);
+    }
+    if cpu_model.contains("EPYC") || cpu_model.contains("Threadripper") {
+        return Some(false);
+    }
+    if cpu_model.contains("Xeon") {
+        return None;
+    }
+    // e.g. `i5-12400F`, `265KF` or `5700G`, split into the number and the
+    // suffix, `G7` for an `i7-1065G7`
+    let model = cpu_model
+        .split_whitespace()
+        .map(|token| token.split_once('-').map_or(token, |(_, number)| number))
+        .find(|token| token.chars().take_while(char::is_ascii_digit).count() >= 3)?;
+    let digits = model.chars().take_while(char::is_ascii_digit).count();
+    let (number, suffix) = model.split_at(digits);
+
+    if cpu_model.contains("Intel") {
+        // HEDT models such as the i9-10980XE have none either
+        Some(!suffix.contains('F') && !suffix.starts_with('X'))
+    } else if cpu_model.contains("Ryzen") {
+        Some(if cpu_model.contains("Ryzen AI") || suffix.contains('G') {
+            true
+        } else if suffix.contains('F') {
+            false
+        } else {
+            // Laptop models all have one, desktop ones from the 7000 series on
+            suffix.starts_with(['U', 'H']) || number.starts_with(['7', '9'])
+        })
+    } else if cpu_model.contains("Athlon") {
+        suffix.contains('G').then_some(true)
+    } else {
+        None
+    }
+}
+
+/// Fill the hints sysfs gives for the display controllers under `root`
+#[cfg(target_os = "linux")]
+fn read_pci_firmware_hints(root: &std::path::Path, mut info: FirmwareInfo) -> FirmwareInfo {
+    let Ok(gpus) = crate::pci::read_pci_gpus(root) else {
+        return info;
+    };
+    let mut bar_starts = Vec::new();
+    for gpu in &gpus {
+        let path = root.join(gpu.address.to_string());
+        let read = |name: &str| std::fs::read_to_string(path.join(name)).ok();
+
+        if read("boot_vga").as_deref().map(str::trim) == Some("1") {
+            info.boot_display = Some(gpu.address);
+        }
+        if let Some(resource) = read("resource") {
+            bar_starts.extend(memory_bar_starts(&resource));
+        }
+        let vfs = |name: &str| read(name)?.trim().parse::<u32>().ok();
+        if let Some(total_vfs) = vfs("sriov_totalvfs").filter(|&total| total > 0) {
+            info.sriov.push(SriovStatus {
+                pci_address: gpu.address,
+                total_vfs,
+                enabled_vfs: vfs("sriov_numvfs").unwrap_or(0),
+            });
+        }
+    }
+    if !bar_starts.is_empty() {
+        info.above_4g_decoding = Some(bar_starts.iter().any(|&start| start >= FOUR_GIB));
+    }
+    info
+}
+
+/// Start addresses of the memory BARs in a sysfs `resource` file, one
+/// `start end flags` line per resource with unused ones all zero
+#[cfg(any(target_os = "linux", test))]
+fn memory_bar_starts(resource: &str) -> Vec<u64> {
+    let hex = |value: &str| u64::from_str_radix(value.trim_start_matches("0x"), 16).ok();
+    resource
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace().map(hex);
+            let (start, end, flags) = (columns.next()??, columns.next()??, columns.next()??);
+            (end > start && flags & IORESOURCE_MEM != 0).then_some(start)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_bar_starts() {
+        // An RTX 3080 with Resizable BAR: registers, VRAM, BAR 3, then I/O
+        // ports and the expansion ROM
+        let resource = "\
+0x00000000fb000000 0x00000000fbffffff 0x0000000000040200
+0x0000006000000000 0x00000063ffffffff 0x000000000014220c
+0x0000000000000000 0x0000000000000000 0x0000000000000000
+0x0000006400000000 0x0000006401ffffff 0x000000000014220c
+0x0000000000000000 0x0000000000000000 0x0000000000000000
+0x000000000000f000 0x000000000000f07f 0x0000000000040101
+0x00000000fc000000 0x00000000fc07ffff 0x0000000000046200
+";
+        assert_eq!(
+            memory_bar_starts(resource),
+            vec![0xfb000000, 0x6000000000, 0x6400000000, 0xfc000000]
+        );
+        assert!(memory_bar_starts("").is_empty());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_read_pci_firmware_hints() {
+        let fixture = tempfile::tempdir().unwrap();
+        let root = fixture.path();
+        let write = |address: &str, name: &str, value: &str| {
+            let dir = root.join(address);
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join(name), value).unwrap();
+        };
+        // An Intel iGPU with SR-IOV, and the discrete GPU the firmware boots on
+        write("0000:00:02.0", "class", "0x030000\n");
+        write("0000:00:02.0", "boot_vga", "0\n");
+        write(
+            "0000:00:02.0",
+            "resource",
+            "0x00000000a0000000 0x00000000a0ffffff 0x0000000000140204\n",
+        );
+        write("0000:00:02.0", "sriov_totalvfs", "7\n");
+        write("0000:00:02.0", "sriov_numvfs", "2\n");
+        write("0000:01:00.0", "class", "0x030000\n");
+        write("0000:01:00.0", "boot_vga", "1\n");
+        write(
+            "0000:01:00.0",
+            "resource",
+            "0x0000006000000000 0x00000063ffffffff 0x000000000014220c\n",
+        );
+
+        let info = read_pci_firmware_hints(root, FirmwareInfo::default());
+        fixture.close().unwrap();
+
+        assert_eq!(info.boot_display, "0000:01:00.0".parse().ok());
+        assert_eq!(info.above_4g_decoding, Some(true));
+        assert_eq!(
+            info.sriov,
+            vec![SriovStatus {
+                pci_address: "0000:00:02.0".parse().unwrap(),
+                total_vfs: 7,
+                enabled_vfs: 2,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_cpu_has_integrated_gpu() {
+        for (cpu_model, expected) in [
+            ("12th Gen Intel(R) Core(TM) i5-12400F", Some(false)),
+            ("13th Gen Intel(R) Core(TM) i9-13900K", Some(true)),
+            ("Intel(R) Core(TM) Ultra 7 265KF", Some(false)),
+            ("Intel(R) Core(TM) i7-1065G7 CPU @ 1.30GHz", Some(true)),
+            ("Intel(R) Core(TM) i9-10980XE CPU @ 3.00GHz", Some(false)),
+            ("Intel(R) Xeon(R) Gold 6338 CPU @ 2.00GHz", None),
+            ("AMD Ryzen 7 5700G with Radeon Graphics", Some(true)),
+            ("AMD Ryzen 7 5800X3D 8-Core", Some(false)),
+            ("AMD Ryzen 9 7950X 16-Core", Some(true)),
+            ("AMD Ryzen 5 7500F 6-Core", Some(false)),
+            ("AMD Ryzen 7 7840HS", Some(true)),
+            ("AMD Ryzen AI 9 HX 370", Some(true)),
+            ("AMD EPYC 7763 64-Core", Some(false)),
+            ("Apple M2 Pro", Some(true)),
+        ] {
+            assert_eq!(cpu_has_integrated_gpu(cpu_model), expected, "{cpu_model}");
+        }
+    }
+
+    #[test]
+    fn test_firmware_info() {
+        eprintln!("{:?}", firmware_info());
+    }
+}
@@ -0,0 +1,265 @@
+use std::ffi::{c_char, c_void, CStr};
+use std::path::Path;
+
+use khronos_egl as egl;
+
+use crate::PciAddress;
+
+/// `EGL_EXT_platform_device`
+const EGL_PLATFORM_DEVICE_EXT: egl::Enum = 0x313F;
+/// `EGL_EXT_device_drm`
+const EGL_DRM_DEVICE_FILE_EXT: egl::Int = 0x3233;
+
+const GL_VENDOR: u32 = 0x1F00;
+const GL_RENDERER: u32 = 0x1F01;
+const GL_VERSION: u32 = 0x1F02;
+const GL_SHADING_LANGUAGE_VERSION: u32 = 0x8B8C;
+
+type EglDevice = *mut c_void;
+type QueryDevicesExt =
+    unsafe extern "system" fn(egl::Int, *mut EglDevice, *mut egl::Int) -> egl::Boolean;
+type QueryDeviceStringExt = unsafe extern "system" fn(EglDevice, egl::Int) -> *const c_char;
+type GlGetString = unsafe extern "system" fn(u32) -> *const c_char;
+
+#[derive(Debug, thiserror::Error)]
+pub enum GlError {
+    #[error("OpenGL probing is not supported: {0}")]
+    NotSupported(String),
+    #[error("Failed to perform EGL operation: {0}")]
+    OperationFailed(String),
+}
+
+impl GlError {
+    pub fn is_not_supported(&self) -> bool {
+        matches!(self, GlError::NotSupported(_))
+    }
+}
+
+impl From<egl::Error> for GlError {
+    fn from(e: egl::Error) -> Self {
+        GlError::OperationFailed(e.to_string())
+    }
+}
+
+/// How an OpenGL implementation reaches the hardware
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub enum GlFlavor {
+    /// The GPU vendor's own OpenGL driver
+    Native,
+    /// ANGLE, translating OpenGL ES to Direct3D, Vulkan or Metal
+    Angle,
+    /// Mesa llvmpipe, softpipe or swrast running on the CPU
+    MesaSoftware,
+    AppleSoftware,
+}
+
+impl GlFlavor {
+    /// Classify a `GL_RENDERER` string
+    pub fn from_renderer(renderer: &str) -> Self {
+        if renderer.starts_with("ANGLE") {
+            GlFlavor::Angle
+        } else if renderer.starts_with("llvmpipe")
+            || renderer.starts_with("softpipe")
+            || renderer.contains("Software Rasterizer")
+        {
+            GlFlavor::MesaSoftware
+        } else if renderer.starts_with("Apple Software Renderer") {
+            GlFlavor::AppleSoftware
+        } else {
+            GlFlavor::Native
+        }
+    }
+}
+
+/// The strings an OpenGL context reports for one EGL device
+#[derive(Debug, Clone)]
+pub struct GlRenderer {
+    pub vendor: String,
+    pub renderer: String,
+    pub version: String,
+    pub glsl_version: String,
+    pub gl_flavor: GlFlavor,
+    /// DRM node of the device, e.g. `/dev/dri/card1`. `None` for software devices.
+    pub device_path: Option<String>,
+    pub pci_address: Option<PciAddress>,
+}
+
+/// Create a surfaceless OpenGL context on every EGL device and record its strings.
+/// Requires `EGL_EXT_platform_device`, which Mesa and the NVIDIA driver provide.
+pub fn retrieve_gpu_info_via_gl() -> Result<Vec<GlRenderer>, GlError> {
+    let egl = unsafe { egl::DynamicInstance::<egl::EGL1_5>::load_required() }
+        .map_err(|e| GlError::NotSupported(e.to_string()))?;
+
+    let client_extensions = egl
+        .query_string(None, egl::EXTENSIONS)
+        .map(|extensions| extensions.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if !client_extensions.contains("EGL_EXT_platform_device") {
+        return Err(GlError::NotSupported(
+            "EGL_EXT_platform_device is not available".to_string(),
+        ));
+    }
+
+    let query_devices: QueryDevicesExt = egl
+        .get_proc_address("eglQueryDevicesEXT")
+        .map(|f| unsafe { std::mem::transmute(f) })
+        .ok_or_else(|| GlError::NotSupported("eglQueryDevicesEXT is missing".to_string()))?;
+    let query_device_string: Option<QueryDeviceStringExt> = egl
+        .get_proc_address("eglQueryDeviceStringEXT")
+        .map(|f| unsafe { std::mem::transmute(f) });
+
+    let mut count = 0;
+    if unsafe { query_devices(0, std::ptr::null_mut(), &mut count) } != egl::TRUE {
+        return Err(GlError::OperationFailed(
+            "eglQueryDevicesEXT failed".to_string(),
+        ));
+    }
+    let mut devices = vec![std::ptr::null_mut(); count.max(0) as usize];
+    if unsafe { query_devices(count, devices.as_mut_ptr(), &mut count) } != egl::TRUE {
+        return Err(GlError::OperationFailed(
+            "eglQueryDevicesEXT failed".to_string(),
+        ));
+    }
+    devices.truncate(count.max(0) as usize);
+
+    let mut renderers = Vec::new();
+
+    for device in devices {
+        let device_path = query_device_string.and_then(|query| {
+            let path = unsafe { query(device, EGL_DRM_DEVICE_FILE_EXT) };
+            (!path.is_null()).then(|| {
+                unsafe { CStr::from_ptr(path) }
+                    .to_string_lossy()
+                    .into_owned()
+            })
+        });
+
+        match probe_device(&egl, device) {
+            Ok(mut renderer) => {
+                renderer.pci_address = device_path.as_deref().and_then(drm_pci_address);
+                renderer.device_path = device_path;
+                renderers.push(renderer);
+            }
+            Err(e) => log::debug!("skipping egl device {device_path:?}: {e}"),
+        }
+    }
+
+    Ok(renderers)
+}
+
+fn probe_device(
+    egl: &egl::DynamicInstance<egl::EGL1_5>,
+    device: EglDevice,
+) -> Result<GlRenderer, GlError> {
+    let display =
+        unsafe { egl.get_platform_display(EGL_PLATFORM_DEVICE_EXT, device, &[egl::ATTRIB_NONE]) }?;
+    egl.initialize(display)?;
+    scopeguard::defer! {
+        let _ = egl.terminate(display);
+    }
+
+    let extensions = egl.query_string(Some(display), egl::EXTENSIONS)?;
+    if !extensions
+        .to_string_lossy()
+        .contains("EGL_KHR_surfaceless_context")
+    {
+        return Err(GlError::NotSupported(
+            "EGL_KHR_surfaceless_context is not available".to_string(),
+        ));
+    }
+
+    egl.bind_api(egl::OPENGL_API)?;
+    let config = egl
+        .choose_first_config(
+            display,
+            &[
+                egl::SURFACE_TYPE,
+                egl::PBUFFER_BIT,
+                egl::RENDERABLE_TYPE,
+                egl::OPENGL_BIT,
+                egl::NONE,
+            ],
+        )?
+        .ok_or_else(|| GlError::NotSupported("no OpenGL config".to_string()))?;
+    let context = egl.create_context(display, config, None, &[egl::NONE])?;
+    scopeguard::defer! {
+        let _ = egl.destroy_context(display, context);
+    }
+
+    egl.make_current(display, None, None, Some(context))?;
+    scopeguard::defer! {
+        let _ = egl.make_current(display, None, None, None);
+    }
+
+    let get_string: GlGetString = egl
+        .get_proc_address("glGetString")
+        .map(|f| unsafe { std::mem::transmute(f) })
+        .ok_or_else(|| GlError::NotSupported("glGetString is missing".to_string()))?;
+    let string = |name| {
+        let value = unsafe { get_string(name) };
+        if value.is_null() {
+            String::new()
+        } else {
+            unsafe { CStr::from_ptr(value) }
+                .to_string_lossy()
+                .into_owned()
+        }
+    };
+
+    let renderer = string(GL_RENDERER);
+    Ok(GlRenderer {
+        vendor: string(GL_VENDOR),
+        gl_flavor: GlFlavor::from_renderer(&renderer),
+        renderer,
+        version: string(GL_VERSION),
+        glsl_version: string(GL_SHADING_LANGUAGE_VERSION),
+        device_path: None,
+        pci_address: None,
+    })
+}
+
+/// `/dev/dri/card1` -> `/sys/class/drm/card1/device` -> `../../../0000:01:00.0`
+fn drm_pci_address(device_path: &str) -> Option<PciAddress> {
+    let node = Path::new(device_path).file_name()?;
+    let device = std::fs::read_link(Path::new("/sys/class/drm").join(node).join("device")).ok()?;
+    device.file_name()?.to_str()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gl_flavor() {
+        assert_eq!(
+            GlFlavor::from_renderer(
+                "ANGLE (NVIDIA, NVIDIA GeForce RTX 3080 Direct3D11 vs_5_0 ps_5_0, D3D11)"
+            ),
+            GlFlavor::Angle
+        );
+        assert_eq!(
+            GlFlavor::from_renderer("llvmpipe (LLVM 15.0.6, 256 bits)"),
+            GlFlavor::MesaSoftware
+        );
+        assert_eq!(
+            GlFlavor::from_renderer("Apple Software Renderer"),
+            GlFlavor::AppleSoftware
+        );
+        assert_eq!(
+            GlFlavor::from_renderer("NVIDIA GeForce RTX 4090/PCIe/SSE2"),
+            GlFlavor::Native
+        );
+    }
+
+    #[test]
+    fn test_retrieve_gpu_info_via_gl() {
+        let result = retrieve_gpu_info_via_gl();
+        eprintln!("{:#?}", result);
+        assert!(match result {
+            Ok(_) => true,
+            Err(e) => e.is_not_supported(),
+        });
+    }
+}
@@ -0,0 +1,180 @@
+use std::sync::OnceLock;
+
+use crate::{GPUKind, Watts, GPU};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[non_exhaustive]
+pub enum HandheldModel {
+    SteamDeck,
+    SteamDeckOled,
+    RogAlly,
+    RogAllyX,
+    LegionGo,
+}
+
+impl HandheldModel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HandheldModel::SteamDeck => "Steam Deck",
+            HandheldModel::SteamDeckOled => "Steam Deck OLED",
+            HandheldModel::RogAlly => "ROG Ally",
+            HandheldModel::RogAllyX => "ROG Ally X",
+            HandheldModel::LegionGo => "Legion Go",
+        }
+    }
+}
+
+/// A gaming handheld, recognized from the firmware (DMI) strings
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct Handheld {
+    pub model: HandheldModel,
+    /// The APU, e.g. `AMD Ryzen Z1 Extreme`, as drivers only report generic
+    /// names such as `AMD Custom GPU 0405` or `AMD Radeon Graphics`
+    pub apu: String,
+    /// Range the APU power limit (TDP) can be set within, from the
+    /// manufacturer's settings UI
+    pub min_tdp: Watts,
+    pub max_tdp: Watts,
+    /// ACPI platform profile, e.g. `low-power`, `balanced` or `performance`.
+    /// Linux only, and not exposed on the Steam Deck.
+    pub power_profile: Option<String>,
+}
+
+/// `(sys_vendor, product_name token, model, APU, min TDP, max TDP)`
+const HANDHELDS: &[(&str, &str, HandheldModel, &str, u32, u32)] = &[
+    (
+        "Valve",
+        "Jupiter",
+        HandheldModel::SteamDeck,
+        "AMD Custom APU 0405",
+        3,
+        15,
+    ),
+    (
+        "Valve",
+        "Galileo",
+        HandheldModel::SteamDeckOled,
+        "AMD Custom APU 0932",
+        3,
+        15,
+    ),
+    // e.g. `ROG Ally RC71L_RC71L`
+    (
+        "ASUSTeK COMPUTER INC.",
+        "RC71L",
+        HandheldModel::RogAlly,
+        "AMD Ryzen Z1 Extreme",
+        7,
+        30,
+    ),
+    (
+        "ASUSTeK COMPUTER INC.",
+        "RC72LA",
+        HandheldModel::RogAllyX,
+        "AMD Ryzen Z1 Extreme",
+        7,
+        30,
+    ),
+    (
+        "LENOVO",
+        "83E1",
+        HandheldModel::LegionGo,
+        "AMD Ryzen Z1 Extreme",
+        8,
+        30,
+    ),
+];
+
+/// `None` on anything but a known gaming handheld. The power profile is
+/// re-read on every call.
+pub fn detect_handheld() -> Option<Handheld> {
+    static HANDHELD: OnceLock<Option<Handheld>> = OnceLock::new();
+    let mut handheld = HANDHELD
+        .get_or_init(|| {
+            let (vendor, product, _) = crate::cloud::read_dmi();
+            let mut handheld = from_dmi(&vendor?, &product?)?;
+            // The Ally also ships with the plain Z1
+            if let Some(brand) = crate::system::cpu_model() {
+                handheld.apu = brand;
+            }
+            Some(handheld)
+        })
+        .clone()?;
+    handheld.power_profile = read_power_profile();
+    Some(handheld)
+}
+
+/// Mark the APU of a handheld as integrated and fill in its TDP range where
+/// the driver does not report one
+pub(crate) fn attach_handheld(gpus: &mut [GPU]) {
+    let Some(handheld) = detect_handheld() else {
+        return;
+    };
+    for gpu in gpus {
+        if matches!(gpu.kind, GPUKind::Software | GPUKind::Virtual) {
+            continue;
+        }
+        gpu.kind = GPUKind::Integrated;
+        gpu.min_power_limit = gpu.min_power_limit.or(Some(handheld.min_tdp));
+        gpu.max_power_limit = gpu.max_power_limit.or(Some(handheld.max_tdp));
+        #[cfg(feature = "extra")]
+        {
+            gpu.extra
+                .insert("handheld.model".to_string(), handheld.model.as_str().into());
+            gpu.extra
+                .insert("handheld.apu".to_string(), handheld.apu.clone().into());
+        }
+    }
+}
+
+fn from_dmi(vendor: &str, product: &str) -> Option<Handheld> {
+    let tokens = product
+        .split(|c: char| c.is_whitespace() || c == '_')
+        .collect::<Vec<_>>();
+    HANDHELDS
+        .iter()
+        .find(|(entry_vendor, token, ..)| *entry_vendor == vendor.trim() && tokens.contains(token))
+        .map(|&(_, _, model, apu, min_tdp, max_tdp)| Handheld {
+            model,
+            apu: apu.to_string(),
+            min_tdp: Watts(min_tdp),
+            max_tdp: Watts(max_tdp),
+            power_profile: None,
+        })
+}
+
+#[cfg(target_os = "linux")]
+fn read_power_profile() -> Option<String> {
+    let profile = std::fs::read_to_string("/sys/firmware/acpi/platform_profile").ok()?;
+    Some(profile.trim().to_string()).filter(|profile| !profile.is_empty())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_power_profile() -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_dmi() {
+        let deck = from_dmi("Valve\n", "Jupiter\n").unwrap();
+        assert_eq!(deck.model, HandheldModel::SteamDeck);
+        assert_eq!(deck.max_tdp, Watts(15));
+        assert_eq!(
+            from_dmi("ASUSTeK COMPUTER INC.", "ROG Ally RC71L_RC71L").map(|h| h.model),
+            Some(HandheldModel::RogAlly)
+        );
+        assert_eq!(
+            from_dmi("ASUSTeK COMPUTER INC.", "ROG Ally X RC72LA_RC72LA").map(|h| h.model),
+            Some(HandheldModel::RogAllyX)
+        );
+        assert_eq!(from_dmi("ASUSTeK COMPUTER INC.", "ROG Strix G16"), None);
+    }
+}
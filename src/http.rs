@@ -0,0 +1,296 @@
+#[cfg(feature = "http-client")]
+use std::io::Read;
+use std::io::Write;
+#[cfg(feature = "http-server")]
+use std::io::{BufRead, BufReader};
+#[cfg(feature = "http-server")]
+use std::net::TcpListener;
+use std::net::TcpStream;
+#[cfg(feature = "http-server")]
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(feature = "http-server")]
+use crate::query::Sampler;
+#[cfg(feature = "http-server")]
+use crate::SampleFields;
+#[cfg(feature = "http-client")]
+use crate::{GpuSample, GPU};
+
+/// Requests slower than this are dropped, so idle connections cannot pile up
+#[cfg(feature = "http-server")]
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Serve the GPU list as JSON over HTTP on `listener` until an accept fails,
+/// for dashboards polling a fleet of machines.
+///
+/// `GET /gpus` returns the [`GpuQuery::all`](crate::GpuQuery::all) result,
+/// sampled at most once per `interval` however many clients poll, in the
+/// schema of [`GPU`](crate::GPU). Failed enumeration is a 500 response with
+/// an `{"error": ...}` body.
+///
+/// `GET /gpus/{index}/sample` returns a fresh [`GpuSample`](crate::GpuSample) of the GPU at
+/// `index` in that list, read with [`GpuQuery::refresh`](crate::GpuQuery::refresh)
+/// and the engine and memory backends like a
+/// [`GpuMonitor`](crate::GpuMonitor) sample, or a 404 response for an index
+/// past the end.
+///
+/// There is no authentication or TLS: bind to a private interface or put a
+/// proxy in front.
+#[cfg(feature = "http-server")]
+pub fn serve_http(listener: TcpListener, interval: Duration) -> std::io::Result<()> {
+    log::debug!("serving gpu info on http://{}", listener.local_addr()?);
+    let sampler = Arc::new(Sampler::new(interval));
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let sampler = Arc::clone(&sampler);
+        std::thread::spawn(move || {
+            if let Err(e) = handle_request(stream, &sampler) {
+                log::debug!("failed to answer http request: {e}");
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(feature = "http-server")]
+fn handle_request(stream: TcpStream, sampler: &Sampler) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // Headers are not needed, but must be read before closing the connection
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 0 && !header.trim().is_empty() {
+        header.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let path = target.split('?').next().unwrap_or(target);
+    let sample_index = path
+        .strip_prefix("/gpus/")
+        .and_then(|rest| rest.strip_suffix("/sample"));
+    let (status, body) = match (method, path, sample_index) {
+        ("GET", "/gpus", _) => match sampler.sample() {
+            Ok(gpus) => ("200 OK", serde_json::to_string(&gpus)),
+            Err(e) => ("500 Internal Server Error", error_body(&e)),
+        },
+        ("GET", _, Some(index)) => sample_response(sampler, index),
+        (_, "/gpus", _) | (_, _, Some(_)) => ("405 Method Not Allowed", Ok(String::new())),
+        _ => ("404 Not Found", Ok(String::new())),
+    };
+    let body = body.map_err(std::io::Error::other)?;
+
+    write!(
+        writer,
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    writer.flush()
+}
+
+/// `GET /gpus/{index}/sample`, for the GPU at `index` of the latest list
+#[cfg(feature = "http-server")]
+fn sample_response(
+    sampler: &Sampler,
+    index: &str,
+) -> (&'static str, Result<String, serde_json::Error>) {
+    let gpus = match sampler.sample() {
+        Ok(gpus) => gpus,
+        Err(e) => return ("500 Internal Server Error", error_body(&e)),
+    };
+    let Some(gpu) = index
+        .parse::<usize>()
+        .ok()
+        .and_then(|index| gpus.get(index))
+    else {
+        return ("404 Not Found", Ok(String::new()));
+    };
+    match crate::monitor::take_sample(gpu, SampleFields::all()) {
+        Ok(sample) => ("200 OK", serde_json::to_string(&sample)),
+        Err(e) => ("500 Internal Server Error", error_body(&e.to_string())),
+    }
+}
+
+#[cfg(feature = "http-server")]
+fn error_body(error: &str) -> Result<String, serde_json::Error> {
+    serde_json::to_string(&serde_json::json!({ "error": error }))
+}
+
+/// How long a remote server may take to connect and to answer
+#[cfg(feature = "http-client")]
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[cfg(feature = "http-client")]
+#[derive(Debug, thiserror::Error)]
+pub enum RemoteError {
+    #[error("invalid gpu-info server url {0}, expected http://host:port")]
+    InvalidUrl(String),
+    #[error("failed to reach gpu-info server: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("gpu-info server answered {0}")]
+    Http(String),
+    #[error("invalid response from gpu-info server: {0}")]
+    Protocol(String),
+}
+
+/// A machine running [`serve_http`], whose GPUs can be appended to a local
+/// [`GpuList`](crate::GpuList) to view a fleet as one list
+#[cfg(feature = "http-client")]
+#[derive(Debug, Clone)]
+pub struct RemoteGpuSource {
+    /// `host:port`
+    authority: String,
+}
+
+#[cfg(feature = "http-client")]
+impl RemoteGpuSource {
+    /// Check that the server at `url`, e.g. `http://render-07:7070`, accepts
+    /// connections. Only plain http is supported.
+    pub fn connect(url: &str) -> Result<Self, RemoteError> {
+        let authority = url
+            .strip_prefix("http://")
+            .map(|rest| rest.split('/').next().unwrap_or(rest))
+            .filter(|authority| !authority.is_empty())
+            .ok_or_else(|| RemoteError::InvalidUrl(url.to_string()))?;
+        let authority = if authority
+            .rsplit_once(':')
+            .is_some_and(|(_, port)| !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()))
+        {
+            authority.to_string()
+        } else {
+            format!("{authority}:80")
+        };
+        let source = Self { authority };
+        source.open()?;
+        Ok(source)
+    }
+
+    /// The server's latest GPU list
+    pub fn gpus(&self) -> Result<Vec<GPU>, RemoteError> {
+        self.get("/gpus")
+    }
+
+    /// A fresh sample of the GPU at `index` in [`gpus`](Self::gpus)
+    pub fn sample(&self, index: usize) -> Result<GpuSample, RemoteError> {
+        self.get(&format!("/gpus/{index}/sample"))
+    }
+
+    fn get<T: serde::de::DeserializeOwned>(&self, path: &str) -> Result<T, RemoteError> {
+        let mut stream = self.open()?;
+        write!(
+            stream,
+            "GET {path} HTTP/1.1\r\nHost: {}\r\nAccept: application/json\r\nConnection: close\r\n\r\n",
+            self.authority
+        )?;
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
+        let response = String::from_utf8_lossy(&response);
+
+        let (head, body) = response
+            .split_once("\r\n\r\n")
+            .ok_or_else(|| RemoteError::Protocol("truncated response".to_string()))?;
+        let status = head.lines().next().unwrap_or_default();
+        match status.split_whitespace().nth(1) {
+            Some("200") => {
+                serde_json::from_str(body).map_err(|e| RemoteError::Protocol(e.to_string()))
+            }
+            Some(_) => {
+                // Failed enumeration comes with the server side error
+                let error = serde_json::from_str::<serde_json::Value>(body)
+                    .ok()
+                    .and_then(|body| body["error"].as_str().map(str::to_string));
+                Err(RemoteError::Http(match error {
+                    Some(error) => format!("{status}: {error}"),
+                    None => status.to_string(),
+                }))
+            }
+            None => Err(RemoteError::Protocol(format!(
+                "invalid status line {status}"
+            ))),
+        }
+    }
+
+    fn open(&self) -> Result<TcpStream, RemoteError> {
+        use std::net::ToSocketAddrs;
+
+        let addr = self
+            .authority
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| RemoteError::InvalidUrl(self.authority.clone()))?;
+        let stream = TcpStream::connect_timeout(&addr, RESPONSE_TIMEOUT)?;
+        stream.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+        Ok(stream)
+    }
+}
+
+#[cfg(all(test, feature = "http-server"))]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    fn get(addr: std::net::SocketAddr, request: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn test_serve_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || serve_http(listener, Duration::from_secs(1)));
+
+        let response = get(addr, "GET /gpus HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        eprintln!("{response}");
+        let listed = response.starts_with("HTTP/1.1 200 OK");
+        assert!(listed || response.starts_with("HTTP/1.1 500 Internal Server Error"));
+
+        let response = get(addr, "POST /gpus HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 405"));
+        let response = get(addr, "GET / HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 404"));
+        let response = get(addr, "GET /gpus/first/sample HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 404"));
+        if listed {
+            let response = get(addr, "GET /gpus/4096/sample HTTP/1.1\r\n\r\n");
+            assert!(response.starts_with("HTTP/1.1 404"));
+        }
+
+        // The client reads back what the server wrote
+        #[cfg(feature = "http-client")]
+        {
+            let source = RemoteGpuSource::connect(&format!("http://{addr}/")).unwrap();
+            match source.gpus() {
+                Ok(gpus) => {
+                    assert!(listed);
+                    if !gpus.is_empty() {
+                        let sample = source.sample(0);
+                        assert!(matches!(sample, Ok(_) | Err(RemoteError::Http(_))));
+                    }
+                }
+                Err(e) => assert!(!listed && matches!(e, RemoteError::Http(_)), "{e}"),
+            }
+        }
+    }
+
+    #[cfg(feature = "http-client")]
+    #[test]
+    fn test_remote_url() {
+        assert!(matches!(
+            RemoteGpuSource::connect("https://render-07:7070"),
+            Err(RemoteError::InvalidUrl(_))
+        ));
+        assert!(matches!(
+            RemoteGpuSource::connect("http://"),
+            Err(RemoteError::InvalidUrl(_))
+        ));
+    }
+}
@@ -0,0 +1,151 @@
+//! Display strings for user interfaces. Languages not covered here can key
+//! their own tables on [`GPUKind::as_str`], which is stable.
+
+use crate::units::{format_number, scale, Unit};
+use crate::{Bytes, GPUKind};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    #[default]
+    English,
+    German,
+    French,
+    Spanish,
+    Japanese,
+    SimplifiedChinese,
+}
+
+impl Language {
+    /// Match a BCP 47 tag such as `de-AT` or `zh-Hans-CN` by its primary
+    /// language. Traditional Chinese is not covered.
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        let mut subtags = tag.split(['-', '_']).map(str::to_ascii_lowercase);
+        match subtags.next()?.as_str() {
+            "en" => Some(Language::English),
+            "de" => Some(Language::German),
+            "fr" => Some(Language::French),
+            "es" => Some(Language::Spanish),
+            "ja" => Some(Language::Japanese),
+            "zh" if !subtags
+                .any(|subtag| matches!(subtag.as_str(), "hant" | "tw" | "hk" | "mo")) =>
+            {
+                Some(Language::SimplifiedChinese)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl GPUKind {
+    pub fn label(&self, language: Language) -> &str {
+        use Language::*;
+
+        #[allow(deprecated)]
+        match (self, language) {
+            (GPUKind::Other(name), _) => name,
+            (GPUKind::CPU, _) => "CPU",
+
+            (GPUKind::Integrated, English) => "Integrated",
+            (GPUKind::Integrated, German) => "Integriert",
+            (GPUKind::Integrated, French) => "Intégré",
+            (GPUKind::Integrated, Spanish) => "Integrada",
+            (GPUKind::Integrated, Japanese) => "内蔵",
+            (GPUKind::Integrated, SimplifiedChinese) => "集成",
+
+            (GPUKind::Discrete, English) => "Discrete",
+            (GPUKind::Discrete, German) => "Dediziert",
+            (GPUKind::Discrete, French) => "Dédié",
+            (GPUKind::Discrete, Spanish) => "Dedicada",
+            (GPUKind::Discrete, Japanese) => "専用",
+            (GPUKind::Discrete, SimplifiedChinese) => "独立",
+
+            (GPUKind::Virtual, English) => "Virtual",
+            (GPUKind::Virtual, German) => "Virtuell",
+            (GPUKind::Virtual, French) => "Virtuel",
+            (GPUKind::Virtual, Spanish) => "Virtual",
+            (GPUKind::Virtual, Japanese) => "仮想",
+            (GPUKind::Virtual, SimplifiedChinese) => "虚拟",
+
+            (GPUKind::Software, English | German | Spanish) => "Software",
+            (GPUKind::Software, French) => "Logiciel",
+            (GPUKind::Software, Japanese) => "ソフトウェア",
+            (GPUKind::Software, SimplifiedChinese) => "软件",
+
+            (GPUKind::Unknown, English) => "Unknown",
+            (GPUKind::Unknown, German) => "Unbekannt",
+            (GPUKind::Unknown, French) => "Inconnu",
+            (GPUKind::Unknown, Spanish) => "Desconocido",
+            (GPUKind::Unknown, Japanese) => "不明",
+            (GPUKind::Unknown, SimplifiedChinese) => "未知",
+        }
+    }
+}
+
+/// Vendor names are brands and stay as they are, only `Unknown` is translated
+pub fn vendor_label(vendor: &str, language: Language) -> &str {
+    match vendor {
+        "Unknown" => GPUKind::Unknown.label(language),
+        vendor => vendor,
+    }
+}
+
+/// [`format_vram`](crate::units::format_vram) with the decimal separator of
+/// `language`, and octets in French, e.g. `12,5 Gio`
+pub fn format_vram_localized(bytes: Bytes, unit: Unit, language: Language) -> String {
+    let (value, unit) = scale(bytes, unit);
+    let (separator, symbol) = match language {
+        Language::French => (
+            ',',
+            match unit {
+                Unit::KiB => "Kio",
+                Unit::MiB => "Mio",
+                Unit::GiB => "Gio",
+                Unit::TiB => "Tio",
+                Unit::KB => "ko",
+                Unit::MB => "Mo",
+                Unit::GB => "Go",
+                Unit::TB => "To",
+                _ => "o",
+            },
+        ),
+        Language::German | Language::Spanish => (',', unit.symbol()),
+        Language::English | Language::Japanese | Language::SimplifiedChinese => {
+            ('.', unit.symbol())
+        }
+    };
+    format!("{} {symbol}", format_number(value, separator))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_labels() {
+        assert_eq!(Language::from_tag("de-AT"), Some(Language::German));
+        assert_eq!(
+            Language::from_tag("zh-Hans-CN"),
+            Some(Language::SimplifiedChinese)
+        );
+        assert_eq!(Language::from_tag("zh-TW"), None);
+        assert_eq!(Language::from_tag("ko"), None);
+
+        assert_eq!(GPUKind::Discrete.label(Language::French), "Dédié");
+        assert_eq!(vendor_label("NVIDIA", Language::Japanese), "NVIDIA");
+        assert_eq!(vendor_label("Unknown", Language::German), "Unbekannt");
+
+        let vram = Bytes::from_mb(12 * 1024 + 512);
+        assert_eq!(
+            format_vram_localized(vram, Unit::Auto, Language::German),
+            "12,5 GiB"
+        );
+        assert_eq!(
+            format_vram_localized(vram, Unit::Auto, Language::French),
+            "12,5 Gio"
+        );
+        assert_eq!(
+            format_vram_localized(vram, Unit::Auto, Language::Japanese),
+            "12.5 GiB"
+        );
+    }
+}
@@ -0,0 +1,365 @@
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use ash::vk;
+
+/// Highest loader-driver interface version this crate understands. Version 6
+/// and later expect loader features a direct probe does not emulate.
+const LOADER_ICD_INTERFACE_VERSION: u32 = 5;
+
+type NegotiateInterfaceVersion = unsafe extern "system" fn(*mut u32) -> vk::Result;
+
+/// A Vulkan driver (ICD) manifest and the library it points to
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct VulkanIcd {
+    pub manifest_path: String,
+    /// `ICD.library_path` as written in the manifest
+    pub library_path: Option<String>,
+    /// `ICD.api_version`, e.g. `1.3.275`
+    pub api_version: Option<String>,
+    /// `false` for manifests left behind by an uninstalled driver. `None` for
+    /// bare library names, which the system library search resolves.
+    pub library_exists: Option<bool>,
+    /// Whether the library is built for 64-bit processes, `None` if it cannot be read
+    pub is_64_bit: Option<bool>,
+}
+
+/// Whether the driver of one manifest works on its own, see [`probe_vulkan_icds`]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct IcdStatus {
+    pub icd: VulkanIcd,
+    /// Names of the devices the driver exposes
+    pub devices: Vec<String>,
+    pub usable: bool,
+    /// Why the driver is not usable, e.g. a library of the wrong bitness
+    pub error: Option<String>,
+}
+
+/// Every driver manifest the Vulkan loader considers, honoring the
+/// `VK_DRIVER_FILES`, `VK_ICD_FILENAMES` and `VK_ADD_DRIVER_FILES` overrides
+pub fn retrieve_vulkan_icds() -> Vec<VulkanIcd> {
+    let split = |value: String| std::env::split_paths(&value).collect::<Vec<_>>();
+    let mut locations =
+        match std::env::var("VK_DRIVER_FILES").or_else(|_| std::env::var("VK_ICD_FILENAMES")) {
+            Ok(value) => split(value),
+            Err(_) => default_locations(),
+        };
+    if let Ok(value) = std::env::var("VK_ADD_DRIVER_FILES") {
+        locations.extend(split(value));
+    }
+
+    let mut manifests = Vec::new();
+    for location in locations {
+        if location.is_dir() {
+            let Ok(entries) = std::fs::read_dir(&location) else {
+                continue;
+            };
+            let mut paths = entries
+                .filter_map(|entry| Some(entry.ok()?.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+                .collect::<Vec<_>>();
+            paths.sort();
+            manifests.extend(paths);
+        } else {
+            manifests.push(location);
+        }
+    }
+    manifests.dedup();
+
+    manifests
+        .iter()
+        .filter_map(|path| read_manifest(path))
+        .collect()
+}
+
+/// Load the driver of every manifest from [`retrieve_vulkan_icds`] directly,
+/// bypassing the loader, and enumerate its devices. Points at the broken driver
+/// when the loader as a whole fails, e.g. a leftover AMD driver breaking the
+/// NVIDIA one. Libraries stay loaded, as some drivers crash when unloaded.
+pub fn probe_vulkan_icds() -> Vec<IcdStatus> {
+    retrieve_vulkan_icds()
+        .into_iter()
+        .map(|icd| {
+            let result = probe_icd(&icd);
+            trace_debug!(manifest = %icd.manifest_path, ok = result.is_ok(), "probed vulkan icd");
+            IcdStatus {
+                icd,
+                usable: result.is_ok(),
+                devices: result.clone().unwrap_or_default(),
+                error: result.err(),
+            }
+        })
+        .collect()
+}
+
+fn probe_icd(icd: &VulkanIcd) -> Result<Vec<String>, String> {
+    let library_path = icd
+        .library_path
+        .as_deref()
+        .ok_or_else(|| "manifest has no library_path".to_string())?;
+    let path = resolve_library(Path::new(&icd.manifest_path), library_path)
+        .unwrap_or_else(|| PathBuf::from(library_path));
+    let library = std::mem::ManuallyDrop::new(
+        unsafe { libloading::Library::new(&path) }
+            .map_err(|e| format!("failed to load {}: {e}", path.display()))?,
+    );
+
+    let get_instance_proc_addr = unsafe {
+        library
+            .get::<vk::PFN_vkGetInstanceProcAddr>(b"vk_icdGetInstanceProcAddr\0")
+            .or_else(|_| library.get(b"vkGetInstanceProcAddr\0"))
+    }
+    .map(|f| *f)
+    .map_err(|e| format!("not a Vulkan driver: {e}"))?;
+    // Drivers without the export predate interface negotiation
+    if let Ok(negotiate) = unsafe {
+        library.get::<NegotiateInterfaceVersion>(b"vk_icdNegotiateLoaderICDInterfaceVersion\0")
+    } {
+        let mut version = LOADER_ICD_INTERFACE_VERSION;
+        let result = unsafe { negotiate(&mut version) };
+        if result != vk::Result::SUCCESS {
+            return Err(format!(
+                "vk_icdNegotiateLoaderICDInterfaceVersion: {result}"
+            ));
+        }
+    }
+
+    let entry = unsafe {
+        ash::Entry::from_static_fn(ash::StaticFn {
+            get_instance_proc_addr,
+        })
+    };
+    let app_info = vk::ApplicationInfo::default().api_version(vk::API_VERSION_1_0);
+    let instance = unsafe {
+        entry.create_instance(
+            &vk::InstanceCreateInfo::default().application_info(&app_info),
+            None,
+        )
+    }
+    .map_err(|e| format!("vkCreateInstance: {e}"))?;
+    scopeguard::defer! {
+        unsafe { instance.destroy_instance(None) };
+    }
+
+    let devices = unsafe { crate::vulkan::enumerate_physical_devices(&instance) }
+        .map_err(|e| format!("vkEnumeratePhysicalDevices: {e}"))?;
+    Ok(devices
+        .into_iter()
+        .map(|device| {
+            let properties = unsafe { instance.get_physical_device_properties(device) };
+            properties
+                .device_name_as_c_str()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        })
+        .collect())
+}
+
+/// The manifest of the driver behind `driver_id`, recognized by its library
+/// name. Prefers a library matching the bitness of this process.
+pub(crate) fn icd_for_driver(driver_id: vk::DriverId, icds: &[VulkanIcd]) -> Option<&VulkanIcd> {
+    let fragments: &[&str] = match driver_id {
+        vk::DriverId::AMD_PROPRIETARY | vk::DriverId::AMD_OPEN_SOURCE => &["amdvlk"],
+        vk::DriverId::MESA_RADV => &["radeon"],
+        vk::DriverId::NVIDIA_PROPRIETARY => &["nvidia", "nvoglv"],
+        vk::DriverId::INTEL_PROPRIETARY_WINDOWS => &["igvk"],
+        vk::DriverId::INTEL_OPEN_SOURCE_MESA => &["intel"],
+        vk::DriverId::QUALCOMM_PROPRIETARY => &["qcvk"],
+        vk::DriverId::GOOGLE_SWIFTSHADER => &["swiftshader"],
+        vk::DriverId::MESA_LLVMPIPE => &["lvp"],
+        vk::DriverId::MESA_TURNIP => &["freedreno"],
+        vk::DriverId::MESA_V3DV => &["broadcom"],
+        vk::DriverId::MESA_PANVK => &["panfrost"],
+        vk::DriverId::MESA_VENUS => &["virtio"],
+        vk::DriverId::MESA_DOZEN => &["dzn"],
+        vk::DriverId::MESA_NVK => &["nouveau"],
+        _ => return None,
+    };
+
+    let mut candidates = icds.iter().filter(|icd| {
+        let Some(library) = &icd.library_path else {
+            return false;
+        };
+        let name = Path::new(library)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        fragments.iter().any(|fragment| name.contains(fragment))
+    });
+    let first = candidates.next()?;
+    let native = cfg!(target_pointer_width = "64");
+    Some(
+        std::iter::once(first)
+            .chain(candidates)
+            .find(|icd| icd.is_64_bit == Some(native))
+            .unwrap_or(first),
+    )
+}
+
+#[cfg(not(windows))]
+fn default_locations() -> Vec<PathBuf> {
+    let home = std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default();
+    let var_or =
+        |name: &str, default: PathBuf| std::env::var_os(name).map(PathBuf::from).unwrap_or(default);
+    let dirs_or = |name: &str, default: &str| {
+        std::env::split_paths(&std::env::var_os(name).unwrap_or_else(|| default.into()))
+            .collect::<Vec<_>>()
+    };
+
+    let mut bases = vec![var_or("XDG_CONFIG_HOME", home.join(".config"))];
+    bases.extend(dirs_or("XDG_CONFIG_DIRS", "/etc/xdg"));
+    bases.push("/etc".into());
+    bases.push(var_or("XDG_DATA_HOME", home.join(".local/share")));
+    bases.extend(dirs_or("XDG_DATA_DIRS", "/usr/local/share:/usr/share"));
+    bases
+        .into_iter()
+        .map(|base| base.join("vulkan/icd.d"))
+        .collect()
+}
+
+#[cfg(windows)]
+fn default_locations() -> Vec<PathBuf> {
+    crate::dxgi::vulkan_driver_manifests()
+}
+
+fn read_manifest(path: &Path) -> Option<VulkanIcd> {
+    let text = std::fs::read_to_string(path).ok()?;
+    let library_path = json_string(&text, "library_path");
+    let library = library_path
+        .as_deref()
+        .and_then(|library| resolve_library(path, library));
+
+    Some(VulkanIcd {
+        manifest_path: path.to_string_lossy().into_owned(),
+        api_version: json_string(&text, "api_version"),
+        library_exists: library.as_ref().map(|library| library.exists()),
+        is_64_bit: library.as_deref().and_then(is_64_bit),
+        library_path,
+    })
+}
+
+/// `None` for bare names, which the system library search resolves
+fn resolve_library(manifest: &Path, library: &str) -> Option<PathBuf> {
+    let library = Path::new(library);
+    if library.is_absolute() {
+        Some(library.to_path_buf())
+    } else if library.components().count() > 1 {
+        // Relative paths are relative to the manifest
+        Some(manifest.parent()?.join(library))
+    } else {
+        None
+    }
+}
+
+/// The string value of the first `key` in a JSON document. Manifests are small
+/// and flat enough that this does not need a JSON parser.
+fn json_string(text: &str, key: &str) -> Option<String> {
+    let start = text.find(&format!("\"{key}\""))? + key.len() + 2;
+    let rest = text[start..].trim_start().strip_prefix(':')?.trim_start();
+    let mut chars = rest.strip_prefix('"')?.chars();
+    let mut value = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(value),
+            '\\' => match chars.next()? {
+                'n' => value.push('\n'),
+                't' => value.push('\t'),
+                c => value.push(c),
+            },
+            c => value.push(c),
+        }
+    }
+}
+
+/// Read the ELF class or PE machine of a shared library
+fn is_64_bit(library: &Path) -> Option<bool> {
+    let mut file = File::open(library).ok()?;
+    let mut header = [0u8; 64];
+    file.read_exact(&mut header).ok()?;
+    match header {
+        [0x7F, b'E', b'L', b'F', class, ..] => match class {
+            1 => Some(false),
+            2 => Some(true),
+            _ => None,
+        },
+        [b'M', b'Z', ..] => {
+            let offset = u32::from_le_bytes(header[0x3C..0x40].try_into().ok()?);
+            file.seek(SeekFrom::Start(offset as u64)).ok()?;
+            let mut signature = [0u8; 6];
+            file.read_exact(&mut signature).ok()?;
+            if signature[..4] != *b"PE\0\0" {
+                return None;
+            }
+            match u16::from_le_bytes([signature[4], signature[5]]) {
+                // AMD64, ARM64
+                0x8664 | 0xAA64 => Some(true),
+                // i386, ARM Thumb-2
+                0x014C | 0x01C4 => Some(false),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_string() {
+        let manifest = r#"{
+            "file_format_version" : "1.0.0",
+            "ICD": {
+                "library_path": "C:\\Windows\\System32\\DriverStore\\nvoglv64.dll",
+                "api_version" : "1.3.277"
+            }
+        }"#;
+        assert_eq!(
+            json_string(manifest, "library_path").as_deref(),
+            Some(r"C:\Windows\System32\DriverStore\nvoglv64.dll")
+        );
+        assert_eq!(
+            json_string(manifest, "api_version").as_deref(),
+            Some("1.3.277")
+        );
+        assert_eq!(json_string(manifest, "missing"), None);
+    }
+
+    #[test]
+    fn test_icd_for_driver() {
+        let icd = |library: &str, is_64_bit| VulkanIcd {
+            manifest_path: String::new(),
+            library_path: Some(library.to_string()),
+            api_version: None,
+            library_exists: None,
+            is_64_bit,
+        };
+        let icds = [
+            icd("/usr/lib/i386-linux-gnu/libvulkan_radeon.so", Some(false)),
+            icd("/usr/lib/x86_64-linux-gnu/libvulkan_radeon.so", Some(true)),
+            icd("libGLX_nvidia.so.0", None),
+        ];
+        assert_eq!(
+            icd_for_driver(vk::DriverId::MESA_RADV, &icds).and_then(|icd| icd.is_64_bit),
+            Some(cfg!(target_pointer_width = "64"))
+        );
+        assert_eq!(
+            icd_for_driver(vk::DriverId::NVIDIA_PROPRIETARY, &icds),
+            Some(&icds[2])
+        );
+        assert_eq!(icd_for_driver(vk::DriverId::MESA_NVK, &icds), None);
+    }
+
+    #[test]
+    fn test_probe_vulkan_icds() {
+        eprintln!("{:#?}", probe_vulkan_icds());
+    }
+}
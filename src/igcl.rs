@@ -0,0 +1,557 @@
+use std::ffi::{c_char, c_void, CStr};
+
+use crate::{Celsius, MegaHertz, OverclockSetting, TemperatureLocation, Watts, GPU};
+
+const IGCL_LIB: &str = "ControlLib.dll";
+
+const CTL_RESULT_SUCCESS: u32 = 0;
+/// `CTL_MAKE_VERSION(1, 1)`
+const CTL_IMPL_VERSION: u32 = (1 << 16) | 1;
+/// Telemetry is read through Level Zero
+const CTL_INIT_FLAG_USE_LEVEL_ZERO: u32 = 1;
+const CTL_MAX_DEVICE_NAME_LEN: usize = 100;
+const CTL_MAX_RESERVED_SIZE: usize = 112;
+const CTL_PSU_COUNT: usize = 5;
+const CTL_FAN_COUNT: usize = 5;
+/// The Intel PCI vendor id, as IGCL also lists adapters it cannot control
+const INTEL_VENDOR_ID: u32 = 0x8086;
+
+// `ctl_data_type_t`
+const CTL_DATA_TYPE_INT32: u32 = 4;
+const CTL_DATA_TYPE_UINT32: u32 = 5;
+const CTL_DATA_TYPE_INT64: u32 = 6;
+const CTL_DATA_TYPE_UINT64: u32 = 7;
+const CTL_DATA_TYPE_FLOAT: u32 = 8;
+const CTL_DATA_TYPE_DOUBLE: u32 = 9;
+
+#[derive(Debug, thiserror::Error)]
+pub enum IgclError {
+    #[error("IGCL is not available: {0}")]
+    NotSupported(String),
+    #[error("Failed to perform IGCL operation: {0}")]
+    OperationFailed(String),
+}
+
+impl IgclError {
+    pub fn is_not_supported(&self) -> bool {
+        matches!(self, IgclError::NotSupported(_))
+    }
+}
+
+/// Telemetry and overclocking settings of an Intel Arc or Xe GPU, as Intel
+/// Arc Control shows them. Unsupported values, e.g. fans on integrated
+/// GPUs, are `None`.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct IgclMetrics {
+    pub clock_speed: Option<MegaHertz>,
+    pub memory_clock: Option<MegaHertz>,
+    pub temperature: Option<Celsius>,
+    pub memory_temperature: Option<Celsius>,
+    pub fan_rpm: Option<u32>,
+    /// Whether the power limit currently holds the clock back
+    pub power_limited: Option<bool>,
+    /// Whether the temperature currently holds the clock back
+    pub thermal_limited: Option<bool>,
+    /// Overclock offset of the GPU clock in MHz, 0 at stock
+    pub frequency_offset: Option<f64>,
+    /// Overclock offset of the GPU voltage in mV, 0 at stock
+    pub voltage_offset: Option<f64>,
+    /// Sustained power limit as currently configured
+    pub power_limit: Option<Watts>,
+}
+
+#[derive(Debug, Clone)]
+pub struct IgclGpu {
+    pub name: String,
+    pub luid: u64,
+    pub metrics: IgclMetrics,
+    /// Default sustained power limit and the range it can be set within
+    pub tdp_watts: Option<Watts>,
+    pub min_power_limit: Option<Watts>,
+    pub max_power_limit: Option<Watts>,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct ApplicationId {
+    data1: u32,
+    data2: u16,
+    data3: u16,
+    data4: [u8; 8],
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct InitArgs {
+    size: u32,
+    version: u8,
+    app_version: u32,
+    flags: u32,
+    supported_version: u32,
+    application_uid: ApplicationId,
+}
+
+#[repr(C)]
+struct AdapterProperties {
+    size: u32,
+    version: u8,
+    /// Points at a `LUID` on Windows
+    device_id: *mut c_void,
+    device_id_size: u32,
+    device_type: u32,
+    supported_subfunction_flags: u32,
+    driver_version: u64,
+    firmware_version: [u64; 3],
+    pci_vendor_id: u32,
+    pci_device_id: u32,
+    rev_id: u32,
+    num_eus_per_sub_slice: u32,
+    num_sub_slices_per_slice: u32,
+    num_slices: u32,
+    name: [c_char; CTL_MAX_DEVICE_NAME_LEN],
+    graphics_adapter_properties: u32,
+    frequency: u32,
+    pci_subsys_id: u16,
+    pci_subsys_vendor_id: u16,
+    adapter_bdf: [u8; 3],
+    reserved: [c_char; CTL_MAX_RESERVED_SIZE],
+}
+
+/// `ctl_oc_telemetry_item_t`
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+struct TelemetryItem {
+    supported: bool,
+    units: u32,
+    data_type: u32,
+    /// `ctl_data_value_t`, a union read according to `data_type`
+    value: u64,
+}
+
+impl TelemetryItem {
+    fn value(&self) -> Option<f64> {
+        if !self.supported {
+            return None;
+        }
+        let bits = self.value;
+        match self.data_type {
+            CTL_DATA_TYPE_INT32 => Some(bits as u32 as i32 as f64),
+            CTL_DATA_TYPE_UINT32 => Some(bits as u32 as f64),
+            CTL_DATA_TYPE_INT64 => Some(bits as i64 as f64),
+            CTL_DATA_TYPE_UINT64 => Some(bits as f64),
+            CTL_DATA_TYPE_FLOAT => Some(f32::from_bits(bits as u32) as f64),
+            CTL_DATA_TYPE_DOUBLE => Some(f64::from_bits(bits)),
+            _ => None,
+        }
+        .filter(|value| value.is_finite() && *value >= 0.0)
+    }
+}
+
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+struct PsuInfo {
+    supported: bool,
+    psu_type: u32,
+    energy_counter: TelemetryItem,
+    voltage: TelemetryItem,
+}
+
+/// `ctl_power_telemetry_t` up to the fans, padded for the fields newer
+/// drivers append
+#[repr(C)]
+#[derive(Default)]
+struct PowerTelemetry {
+    size: u32,
+    version: u8,
+    timestamp: TelemetryItem,
+    gpu_energy_counter: TelemetryItem,
+    gpu_voltage: TelemetryItem,
+    gpu_current_clock_frequency: TelemetryItem,
+    gpu_current_temperature: TelemetryItem,
+    global_activity_counter: TelemetryItem,
+    render_compute_activity_counter: TelemetryItem,
+    media_activity_counter: TelemetryItem,
+    gpu_power_limited: bool,
+    gpu_temperature_limited: bool,
+    gpu_current_limited: bool,
+    gpu_voltage_limited: bool,
+    gpu_utilization_limited: bool,
+    vram_energy_counter: TelemetryItem,
+    vram_voltage: TelemetryItem,
+    vram_current_clock_frequency: TelemetryItem,
+    vram_current_effective_frequency: TelemetryItem,
+    vram_read_bandwidth_counter: TelemetryItem,
+    vram_write_bandwidth_counter: TelemetryItem,
+    vram_current_temperature: TelemetryItem,
+    vram_power_limited: bool,
+    vram_temperature_limited: bool,
+    vram_current_limited: bool,
+    vram_voltage_limited: bool,
+    vram_utilization_limited: bool,
+    total_card_energy_counter: TelemetryItem,
+    psu: [PsuInfo; CTL_PSU_COUNT],
+    fan_speed: [TelemetryItem; CTL_FAN_COUNT],
+    reserved: [[u64; 32]; 4],
+}
+
+/// `ctl_power_properties_t`, limits in milliwatts
+#[repr(C)]
+#[derive(Default)]
+struct PowerProperties {
+    size: u32,
+    version: u8,
+    can_control: bool,
+    default_limit: i32,
+    min_limit: i32,
+    max_limit: i32,
+}
+
+type Handle = *mut c_void;
+type Init = unsafe extern "C" fn(*mut InitArgs, *mut Handle) -> u32;
+type Close = unsafe extern "C" fn(Handle) -> u32;
+type EnumerateDevices = unsafe extern "C" fn(Handle, *mut u32, *mut Handle) -> u32;
+type GetDeviceProperties = unsafe extern "C" fn(Handle, *mut AdapterProperties) -> u32;
+type PowerTelemetryGet = unsafe extern "C" fn(Handle, *mut PowerTelemetry) -> u32;
+type EnumPowerDomains = unsafe extern "C" fn(Handle, *mut u32, *mut Handle) -> u32;
+type PowerGetProperties = unsafe extern "C" fn(Handle, *mut PowerProperties) -> u32;
+type OverclockValueGet = unsafe extern "C" fn(Handle, *mut f64) -> u32;
+
+/// A loaded control library and API handle
+struct Igcl {
+    api: Handle,
+    close: Close,
+    enumerate_devices: EnumerateDevices,
+    get_device_properties: GetDeviceProperties,
+    power_telemetry_get: PowerTelemetryGet,
+    enum_power_domains: EnumPowerDomains,
+    power_get_properties: PowerGetProperties,
+    frequency_offset_get: OverclockValueGet,
+    voltage_offset_get: OverclockValueGet,
+    power_limit_get: OverclockValueGet,
+    // Unloaded after the API handle is closed
+    _lib: libloading::Library,
+}
+
+/// An Intel adapter with the handle to query it through
+struct Device {
+    handle: Handle,
+    name: String,
+    luid: u64,
+}
+
+impl Igcl {
+    fn open() -> Result<Self, IgclError> {
+        let lib = unsafe { libloading::Library::new(IGCL_LIB) }
+            .map_err(|e| IgclError::NotSupported(e.to_string()))?;
+        let init: Init = symbol(&lib, b"ctlInit\0")?;
+        let mut igcl = Self {
+            api: std::ptr::null_mut(),
+            close: symbol(&lib, b"ctlClose\0")?,
+            enumerate_devices: symbol(&lib, b"ctlEnumerateDevices\0")?,
+            get_device_properties: symbol(&lib, b"ctlGetDeviceProperties\0")?,
+            power_telemetry_get: symbol(&lib, b"ctlPowerTelemetryGet\0")?,
+            enum_power_domains: symbol(&lib, b"ctlEnumPowerDomains\0")?,
+            power_get_properties: symbol(&lib, b"ctlPowerGetProperties\0")?,
+            frequency_offset_get: symbol(&lib, b"ctlOverclockGpuFrequencyOffsetGet\0")?,
+            voltage_offset_get: symbol(&lib, b"ctlOverclockGpuVoltageOffsetGet\0")?,
+            power_limit_get: symbol(&lib, b"ctlOverclockPowerLimitGet\0")?,
+            _lib: lib,
+        };
+
+        let mut args = InitArgs {
+            size: std::mem::size_of::<InitArgs>() as u32,
+            app_version: CTL_IMPL_VERSION,
+            flags: CTL_INIT_FLAG_USE_LEVEL_ZERO,
+            ..Default::default()
+        };
+        check(unsafe { init(&mut args, &mut igcl.api) }, "init")?;
+        Ok(igcl)
+    }
+
+    fn devices(&self) -> Result<Vec<Device>, IgclError> {
+        let mut count = 0;
+        check(
+            unsafe { (self.enumerate_devices)(self.api, &mut count, std::ptr::null_mut()) },
+            "enumerate devices",
+        )?;
+        let mut handles = vec![std::ptr::null_mut(); count as usize];
+        check(
+            unsafe { (self.enumerate_devices)(self.api, &mut count, handles.as_mut_ptr()) },
+            "enumerate devices",
+        )?;
+        handles.truncate(count as usize);
+
+        let mut devices = Vec::new();
+        for handle in handles {
+            // `LowPart` then `HighPart`
+            let mut luid = [0u32; 2];
+            let mut properties = AdapterProperties {
+                size: std::mem::size_of::<AdapterProperties>() as u32,
+                device_id: luid.as_mut_ptr().cast(),
+                device_id_size: std::mem::size_of_val(&luid) as u32,
+                ..unsafe { std::mem::zeroed() }
+            };
+            if let Err(e) = check(
+                unsafe { (self.get_device_properties)(handle, &mut properties) },
+                "device properties",
+            ) {
+                log::debug!("skipping igcl device: {e}");
+                continue;
+            }
+            if properties.pci_vendor_id != INTEL_VENDOR_ID {
+                continue;
+            }
+            devices.push(Device {
+                handle,
+                name: unsafe { CStr::from_ptr(properties.name.as_ptr()) }
+                    .to_string_lossy()
+                    .trim()
+                    .to_string(),
+                luid: ((luid[1] as u64) << 32) | luid[0] as u64,
+            });
+        }
+        Ok(devices)
+    }
+
+    fn metrics(&self, device: &Device) -> Result<IgclMetrics, IgclError> {
+        let mut telemetry = PowerTelemetry {
+            size: std::mem::size_of::<PowerTelemetry>() as u32,
+            ..Default::default()
+        };
+        check(
+            unsafe { (self.power_telemetry_get)(device.handle, &mut telemetry) },
+            "power telemetry",
+        )?;
+        // Overclocking is only supported on discrete Arc GPUs
+        let overclock_value = |get: OverclockValueGet| {
+            let mut value = 0.0;
+            let result = unsafe { get(device.handle, &mut value) };
+            (result == CTL_RESULT_SUCCESS).then_some(value)
+        };
+        let mut metrics = metrics_from_telemetry(&telemetry);
+        metrics.frequency_offset = overclock_value(self.frequency_offset_get);
+        metrics.voltage_offset = overclock_value(self.voltage_offset_get);
+        metrics.power_limit = overclock_value(self.power_limit_get)
+            .filter(|&milliwatts| milliwatts > 0.0)
+            .map(|milliwatts| Watts((milliwatts / 1000.0).round() as u32));
+        Ok(metrics)
+    }
+
+    /// The default, minimum and maximum sustained power limit of the first
+    /// power domain, the whole card
+    fn power_limits(&self, device: &Device) -> Option<(Watts, Watts, Watts)> {
+        let mut count = 1;
+        let mut domain = std::ptr::null_mut();
+        let result = unsafe { (self.enum_power_domains)(device.handle, &mut count, &mut domain) };
+        if result != CTL_RESULT_SUCCESS || count == 0 {
+            return None;
+        }
+        let mut properties = PowerProperties {
+            size: std::mem::size_of::<PowerProperties>() as u32,
+            ..Default::default()
+        };
+        if unsafe { (self.power_get_properties)(domain, &mut properties) } != CTL_RESULT_SUCCESS {
+            return None;
+        }
+        let watts = |milliwatts: i32| (milliwatts > 0).then_some(Watts(milliwatts as u32 / 1000));
+        Some((
+            watts(properties.default_limit)?,
+            watts(properties.min_limit)?,
+            watts(properties.max_limit)?,
+        ))
+    }
+}
+
+impl Drop for Igcl {
+    fn drop(&mut self) {
+        if !self.api.is_null() {
+            unsafe { (self.close)(self.api) };
+        }
+    }
+}
+
+fn symbol<T: Copy>(lib: &libloading::Library, name: &[u8]) -> Result<T, IgclError> {
+    unsafe { lib.get::<T>(name) }
+        .map(|symbol| *symbol)
+        .map_err(|e| IgclError::NotSupported(e.to_string()))
+}
+
+fn check(result: u32, call: &str) -> Result<(), IgclError> {
+    match result {
+        CTL_RESULT_SUCCESS => Ok(()),
+        code => Err(IgclError::OperationFailed(format!(
+            "{call} failed with error {code:#x}"
+        ))),
+    }
+}
+
+fn metrics_from_telemetry(telemetry: &PowerTelemetry) -> IgclMetrics {
+    let rounded = |item: &TelemetryItem| item.value().map(|value| value.round() as u32);
+    IgclMetrics {
+        clock_speed: rounded(&telemetry.gpu_current_clock_frequency).map(MegaHertz),
+        memory_clock: rounded(&telemetry.vram_current_clock_frequency).map(MegaHertz),
+        temperature: rounded(&telemetry.gpu_current_temperature).map(Celsius),
+        memory_temperature: rounded(&telemetry.vram_current_temperature).map(Celsius),
+        fan_rpm: rounded(&telemetry.fan_speed[0]),
+        power_limited: telemetry.gpu_power_limited.then_some(true).or(telemetry
+            .gpu_current_clock_frequency
+            .supported
+            .then_some(false)),
+        thermal_limited: telemetry
+            .gpu_temperature_limited
+            .then_some(true)
+            .or(telemetry.gpu_current_temperature.supported.then_some(false)),
+        ..Default::default()
+    }
+}
+
+/// Every Intel GPU the Intel Graphics Control Library sees, with its
+/// current metrics and power limits. The library ships with the Arc and
+/// Xe drivers.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", err))]
+pub fn retrieve_gpu_info_via_igcl() -> Result<Vec<IgclGpu>, IgclError> {
+    let igcl = Igcl::open()?;
+    igcl.devices()?
+        .into_iter()
+        .map(|device| {
+            let limits = igcl.power_limits(&device);
+            Ok(IgclGpu {
+                metrics: igcl.metrics(&device)?,
+                tdp_watts: limits.map(|(default, _, _)| default),
+                min_power_limit: limits.map(|(_, min, _)| min),
+                max_power_limit: limits.map(|(_, _, max)| max),
+                name: device.name,
+                luid: device.luid,
+            })
+        })
+        .collect()
+}
+
+/// The current [`IgclMetrics`] of one Intel GPU, `None` for other GPUs or
+/// when IGCL does not list it
+pub fn igcl_metrics(gpu: &GPU) -> Result<Option<IgclMetrics>, IgclError> {
+    let Some(luid) = gpu.luid.filter(|_| gpu.vendor == "Intel") else {
+        return Ok(None);
+    };
+    let igcl = Igcl::open()?;
+    match igcl.devices()?.iter().find(|device| device.luid == luid) {
+        Some(device) => Ok(Some(igcl.metrics(device)?)),
+        None => Ok(None),
+    }
+}
+
+/// Merge IGCL data into Intel GPUs by LUID
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+pub(crate) fn attach_igcl_info(mut gpus: Vec<GPU>) -> Vec<GPU> {
+    if !gpus.iter().any(|gpu| gpu.vendor == "Intel") {
+        return gpus;
+    }
+    let igcl_gpus = match retrieve_gpu_info_via_igcl() {
+        Ok(igcl_gpus) => igcl_gpus,
+        Err(e) => {
+            log::debug!("skipping igcl: {e}");
+            return gpus;
+        }
+    };
+
+    for gpu in &mut gpus {
+        let Some(igcl_gpu) = igcl_gpus
+            .iter()
+            .find(|igcl_gpu| Some(igcl_gpu.luid) == gpu.luid)
+        else {
+            continue;
+        };
+        apply_metrics(gpu, &igcl_gpu.metrics);
+        gpu.tdp_watts = igcl_gpu.tdp_watts;
+        gpu.min_power_limit = igcl_gpu.min_power_limit;
+        gpu.max_power_limit = igcl_gpu.max_power_limit;
+        crate::overclock::apply_overclock_settings(gpu, overclock_settings(igcl_gpu));
+    }
+    gpus
+}
+
+/// Re-read the temperature and clock of one Intel GPU
+pub(crate) fn refresh_igcl_info(gpu: &mut GPU) {
+    match igcl_metrics(gpu) {
+        Ok(Some(metrics)) => apply_metrics(gpu, &metrics),
+        Ok(None) => {}
+        Err(e) => log::debug!("skipping igcl refresh of {}: {e}", gpu.name),
+    }
+}
+
+/// Overclocking settings are only read on discrete Arc GPUs
+fn overclock_settings(igcl_gpu: &IgclGpu) -> Vec<OverclockSetting> {
+    let metrics = &igcl_gpu.metrics;
+    let offset = |offset: Option<f64>| offset.map(|offset| offset.round() as i32);
+    [
+        offset(metrics.frequency_offset).map(OverclockSetting::GraphicsClockOffset),
+        offset(metrics.voltage_offset).map(OverclockSetting::VoltageOffset),
+        metrics
+            .power_limit
+            .zip(igcl_gpu.tdp_watts)
+            .map(|(value, default)| OverclockSetting::PowerLimit { value, default }),
+    ]
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+fn apply_metrics(gpu: &mut GPU, metrics: &IgclMetrics) {
+    gpu.temperatures = crate::temperature_sensors(
+        [
+            (TemperatureLocation::Edge, metrics.temperature),
+            (TemperatureLocation::Memory, metrics.memory_temperature),
+        ]
+        .map(|(location, celsius)| (location, celsius.map(|celsius| celsius.0 as f32))),
+    );
+    gpu.clock_speed = metrics.clock_speed;
+    #[cfg(feature = "extra")]
+    for (key, value) in [
+        ("igcl.fan_rpm", metrics.fan_rpm),
+        ("igcl.power_limit", metrics.power_limit.map(|watts| watts.0)),
+    ] {
+        if let Some(value) = value {
+            gpu.extra.insert(key.to_string(), value.into());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metrics_from_telemetry() {
+        let item = |data_type: u32, value: u64| TelemetryItem {
+            supported: true,
+            units: 0,
+            data_type,
+            value,
+        };
+        let telemetry = PowerTelemetry {
+            gpu_current_clock_frequency: item(CTL_DATA_TYPE_DOUBLE, 2400.4f64.to_bits()),
+            gpu_current_temperature: item(CTL_DATA_TYPE_FLOAT, 61.6f32.to_bits() as u64),
+            vram_current_clock_frequency: item(CTL_DATA_TYPE_UINT32, 2000),
+            gpu_power_limited: true,
+            ..Default::default()
+        };
+
+        let metrics = metrics_from_telemetry(&telemetry);
+        assert_eq!(metrics.clock_speed, Some(MegaHertz(2400)));
+        assert_eq!(metrics.temperature, Some(Celsius(62)));
+        assert_eq!(metrics.memory_clock, Some(MegaHertz(2000)));
+        assert_eq!(metrics.fan_rpm, None);
+        assert_eq!(metrics.power_limited, Some(true));
+        assert_eq!(metrics.thermal_limited, Some(false));
+    }
+
+    #[test]
+    fn test_retrieve_gpu_info_via_igcl() {
+        match retrieve_gpu_info_via_igcl() {
+            Ok(gpus) => eprintln!("{gpus:#?}"),
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+}
@@ -1,25 +1,170 @@
+/// Emit a `tracing` debug event, compiled out without the `tracing` feature
+macro_rules! trace_debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+
 #[cfg(not(target_os = "macos"))]
 mod vulkan;
 
 #[cfg(target_os = "macos")]
 mod metal;
 
+#[cfg(windows)]
+mod dxgi;
+
+#[cfg(all(feature = "directml", windows))]
+mod directml;
+
+#[cfg(not(target_os = "macos"))]
+mod icd;
+
+#[cfg(target_os = "linux")]
+mod pci;
+
+#[cfg(all(feature = "nvml", not(target_os = "macos")))]
+mod nvml;
+
+#[cfg(all(feature = "adl", windows))]
+mod adl;
+
+#[cfg(all(feature = "igcl", windows))]
+mod igcl;
+
+#[cfg(all(feature = "opengl", target_os = "linux"))]
+mod gl;
+#[cfg(any(feature = "http-server", feature = "http-client"))]
+mod http;
+
+pub mod accelerators;
+#[cfg(feature = "bench")]
+mod bench;
+mod builder;
+mod cloud;
+#[cfg(target_os = "linux")]
+mod compositor;
+#[cfg(feature = "control")]
+mod control;
+#[cfg(feature = "counters")]
+pub mod counters;
+#[cfg(all(feature = "daemon", unix))]
+mod daemon;
+mod emulation;
+pub mod export;
+mod firmware;
+mod handheld;
+#[cfg(feature = "i18n")]
+pub mod i18n;
+mod list;
+mod monitor;
+mod names;
+mod overclock;
+mod preference;
+#[cfg(target_os = "linux")]
+mod prime;
+mod query;
+#[cfg(feature = "report")]
+mod report;
+mod requirements;
+#[cfg(feature = "compute-runtimes")]
+mod runtime;
+mod segment;
+#[cfg(target_os = "macos")]
+mod smc;
+mod system;
+mod thunderbolt;
+mod translation;
+pub mod units;
+pub mod v1;
+
+mod vm;
+mod watch;
+#[cfg(feature = "raw-window-handle")]
+mod window;
+#[cfg(windows)]
+mod wine;
+
+#[cfg(feature = "system-profiler")]
+pub mod system_profiler;
+
+/// Parsers of external data, exposed for the targets in `fuzz/`
+#[cfg(fuzzing)]
+#[doc(hidden)]
+pub mod fuzzing {
+    #[cfg(target_os = "linux")]
+    pub fn lookup_device_name(pci_ids: &str, vendor_id: u32, device_id: u32) -> Option<String> {
+        crate::pci::lookup_device_name(pci_ids, vendor_id, device_id)
+    }
+}
+
+#[cfg(all(feature = "adl", windows))]
+pub use adl::*;
+#[cfg(feature = "bench")]
+pub use bench::{benchmark, Benchmark};
+pub use builder::GpuBuilder;
+pub use cloud::{detect_cloud_instance, CloudInstance, CloudProvider};
+#[cfg(target_os = "linux")]
+pub use compositor::compositor_gpu;
+#[cfg(feature = "control")]
+pub use control::{
+    ControlAction, ControlBackend, ControlCapabilities, ControlError, FanCurvePoint, GpuControl,
+};
+#[cfg(all(feature = "daemon", unix))]
+pub use daemon::{default_socket_path, serve, DaemonClient, DaemonError, DEFAULT_INTERVAL};
+#[cfg(all(feature = "directml", windows))]
+pub use directml::*;
+#[cfg(windows)]
+pub use dxgi::*;
+pub use emulation::{process_emulation, Emulation};
+pub use firmware::{firmware_info, FirmwareInfo, IntegratedGpuStatus, SriovStatus};
+#[cfg(all(feature = "opengl", target_os = "linux"))]
+pub use gl::*;
+pub use handheld::{detect_handheld, Handheld, HandheldModel};
+#[cfg(feature = "http-server")]
+pub use http::serve_http;
+#[cfg(feature = "http-client")]
+pub use http::{RemoteError, RemoteGpuSource};
+#[cfg(not(target_os = "macos"))]
+pub use icd::{probe_vulkan_icds, retrieve_vulkan_icds, IcdStatus, VulkanIcd};
+#[cfg(all(feature = "igcl", windows))]
+pub use igcl::*;
+pub use list::{compare_performance, GpuList};
 #[cfg(target_os = "macos")]
 pub use metal::*;
+pub use monitor::{
+    Aggregate, Alert, GpuMonitor, GpuMonitorBuilder, GpuSample, SampleFields, Subscription,
+};
+#[cfg(all(feature = "nvml", not(target_os = "macos")))]
+pub use nvml::*;
+pub use overclock::OverclockSetting;
+#[cfg(target_os = "linux")]
+pub use pci::*;
+pub use preference::GpuPreference;
+#[cfg(target_os = "linux")]
+pub use prime::*;
+pub use query::GpuQuery;
+#[cfg(feature = "report")]
+pub use report::{generate_report, Report};
+pub use requirements::{Feature, RequirementReport, Requirements};
+#[cfg(feature = "compute-runtimes")]
+pub use runtime::*;
+pub use segment::ProductSegment;
+pub use system::{system_context, SystemContext};
+pub use thunderbolt::{external_link, ExternalLink, ThunderboltGeneration};
+pub use units::{Bytes, Celsius, MegaHertz, Watts};
+pub use vm::in_virtual_machine;
 #[cfg(not(target_os = "macos"))]
 pub use vulkan::*;
-
-// OpenGL related
-// use glutin::{
-//     context::{NotCurrentContext, PossiblyCurrentContext},
-//     display::GetGlDisplay,
-//     prelude::*,
-//     surface::{Surface, WindowSurface},
-// };
-// use raw_window_handle::HasRawWindowHandle;
-// use std::num::ParseIntError;
+pub use watch::{wait_for_gpus, wait_for_gpus_with, DriverWatcher, GpuEvent};
+#[cfg(feature = "raw-window-handle")]
+pub use window::*;
+#[cfg(windows)]
+pub use wine::{wine_environment, WineAdapter, WineEnvironment};
 
 #[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
 pub enum Error {
     #[cfg(not(target_os = "macos"))]
     #[error("failed to query vulkan api: {0}")]
@@ -28,155 +173,984 @@ pub enum Error {
     #[cfg(target_os = "macos")]
     #[error("failed to query metal api: {0}")]
     Metal(#[from] metal::MetalError),
+
+    #[cfg(windows)]
+    #[error("failed to query dxgi api: {0}")]
+    Dxgi(#[from] dxgi::DxgiError),
+
+    #[cfg(target_os = "linux")]
+    #[error("failed to query pci devices: {0}")]
+    Pci(#[from] pci::PciError),
+
+    /// The GPU was unplugged, or its driver reset or was unloaded, see
+    /// [`Error::is_device_removed`]
+    #[error("{0} was removed")]
+    DeviceRemoved(String),
+
+    /// [`wait_for_gpus`] timed out, `diagnostics` says what was missing
+    #[error("{found} of {min_count} GPUs ready: {}", diagnostics.join("; "))]
+    GpusNotReady {
+        min_count: usize,
+        found: usize,
+        diagnostics: Vec<String>,
+    },
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-#[cfg_attr(feature = "specta", derive(specta::Type))]
+impl Error {
+    /// Whether a GPU went away, e.g. an eGPU was unplugged or the driver
+    /// reset after a hang (TDR). GPUs enumerated before may be stale, so
+    /// enumerate again instead of retrying with them.
+    pub fn is_device_removed(&self) -> bool {
+        match self {
+            Error::DeviceRemoved(_) => true,
+            #[cfg(not(target_os = "macos"))]
+            Error::Vulkan(e) => e.is_device_lost(),
+            #[cfg(windows)]
+            Error::Dxgi(e) => e.is_device_removed(),
+            _ => false,
+        }
+    }
+}
+
+/// Serialized as the variant name. Names from newer versions of this crate
+/// deserialize to [`GPUKind::Other`] and serialize back unchanged.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "String", into = "String")
+)]
+#[cfg_attr(feature = "specta", derive(specta::Type), specta(type = String))]
+#[cfg_attr(
+    feature = "schema",
+    derive(schemars::JsonSchema),
+    schemars(with = "String")
+)]
+#[non_exhaustive]
 pub enum GPUKind {
     Integrated,
     Discrete,
     Virtual,
+    /// No longer reported, software rasterizers are [`GPUKind::Software`]
+    #[deprecated(
+        since = "0.3.0",
+        note = "software rasterizers are reported as `GPUKind::Software`"
+    )]
     CPU,
+    #[default]
     Unknown,
+    /// A software rasterizer such as llvmpipe, SwiftShader or WARP
+    Software,
+    Other(String),
 }
 
-#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-#[cfg_attr(feature = "specta", derive(specta::Type))]
+impl GPUKind {
+    pub fn as_str(&self) -> &str {
+        #[allow(deprecated)]
+        match self {
+            GPUKind::Integrated => "Integrated",
+            GPUKind::Discrete => "Discrete",
+            GPUKind::Virtual => "Virtual",
+            GPUKind::CPU => "CPU",
+            GPUKind::Unknown => "Unknown",
+            GPUKind::Software => "Software",
+            GPUKind::Other(name) => name,
+        }
+    }
+}
+
+/// Case-insensitive, unknown names become [`GPUKind::Other`]
+impl std::str::FromStr for GPUKind {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        #[allow(deprecated)]
+        Ok([
+            GPUKind::Integrated,
+            GPUKind::Discrete,
+            GPUKind::Virtual,
+            GPUKind::CPU,
+            GPUKind::Unknown,
+            GPUKind::Software,
+        ]
+        .into_iter()
+        .find(|kind| kind.as_str().eq_ignore_ascii_case(s))
+        .unwrap_or_else(|| GPUKind::Other(s.to_string())))
+    }
+}
+
+impl From<String> for GPUKind {
+    fn from(s: String) -> Self {
+        let Ok(kind) = s.parse();
+        kind
+    }
+}
+
+impl From<GPUKind> for String {
+    fn from(kind: GPUKind) -> Self {
+        kind.as_str().to_string()
+    }
+}
+
+/// Serialized like [`GPUKind`]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(from = "String", into = "String")
+)]
+#[cfg_attr(feature = "specta", derive(specta::Type), specta(type = String))]
+#[non_exhaustive]
 pub enum GPULocation {
     BuiltIn,
     Slot,
     External,
     #[default]
     Unspecified,
+    Other(String),
+}
+
+impl GPULocation {
+    pub fn as_str(&self) -> &str {
+        match self {
+            GPULocation::BuiltIn => "BuiltIn",
+            GPULocation::Slot => "Slot",
+            GPULocation::External => "External",
+            GPULocation::Unspecified => "Unspecified",
+            GPULocation::Other(name) => name,
+        }
+    }
+}
+
+/// Case-insensitive, unknown names become [`GPULocation::Other`]
+impl std::str::FromStr for GPULocation {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok([
+            GPULocation::BuiltIn,
+            GPULocation::Slot,
+            GPULocation::External,
+            GPULocation::Unspecified,
+        ]
+        .into_iter()
+        .find(|location| location.as_str().eq_ignore_ascii_case(s))
+        .unwrap_or_else(|| GPULocation::Other(s.to_string())))
+    }
+}
+
+impl From<String> for GPULocation {
+    fn from(s: String) -> Self {
+        let Ok(location) = s.parse();
+        location
+    }
+}
+
+impl From<GPULocation> for String {
+    fn from(location: GPULocation) -> Self {
+        location.as_str().to_string()
+    }
+}
+
+/// Role of a GPU in a hybrid-graphics (e.g. laptop iGPU + dGPU) system
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub enum HybridRole {
+    HighPerformance,
+    PowerSaving,
+}
+
+/// A monitor output connected to a GPU
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct DisplayOutput {
+    pub name: String,
+    /// Whether the output is part of the desktop
+    pub attached: bool,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// PCI location of a device, displayed as `0000:41:00.0`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PciAddress {
+    pub domain: u32,
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+}
+
+impl std::fmt::Display for PciAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:04x}:{:02x}:{:02x}.{:x}",
+            self.domain, self.bus, self.device, self.function
+        )
+    }
+}
+
+impl std::str::FromStr for PciAddress {
+    type Err = std::num::ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // The domain is optional, as in `lspci` output
+        let (domain, rest) = match s.matches(':').count() {
+            2 => s.split_once(':').unwrap(),
+            _ => ("0", s),
+        };
+        let (bus, rest) = rest.split_once(':').unwrap_or((rest, ""));
+        let (device, function) = rest.split_once('.').unwrap_or((rest, ""));
+        Ok(Self {
+            domain: u32::from_str_radix(domain, 16)?,
+            bus: u8::from_str_radix(bus, 16)?,
+            device: u8::from_str_radix(device, 16)?,
+            function: u8::from_str_radix(function, 16)?,
+        })
+    }
+}
+
+/// GPU memory the OS grants the calling process, which shrinks when other
+/// applications such as the compositor need VRAM. Serialized as
+/// `budget_bytes` and `usage_bytes`, as `budget` and `usage` held MB before
+/// 0.3.0.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct MemoryBudget {
+    #[cfg_attr(feature = "serde", serde(rename = "budget_bytes"))]
+    pub budget: Bytes,
+    /// Memory currently allocated by the process
+    #[cfg_attr(feature = "serde", serde(rename = "usage_bytes"))]
+    pub usage: Bytes,
+}
+
+/// The kind of work a GPU engine runs. Backends that cannot tell 3D and
+/// compute work apart report both as [`EngineClass::Graphics`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[non_exhaustive]
+pub enum EngineClass {
+    Graphics,
+    Compute,
+    Copy,
+    VideoDecode,
+    VideoEncode,
+    /// Scaling and color conversion of video frames
+    VideoProcessing,
+    Other,
+}
+
+impl EngineClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EngineClass::Graphics => "Graphics",
+            EngineClass::Compute => "Compute",
+            EngineClass::Copy => "Copy",
+            EngineClass::VideoDecode => "VideoDecode",
+            EngineClass::VideoEncode => "VideoEncode",
+            EngineClass::VideoProcessing => "VideoProcessing",
+            EngineClass::Other => "Other",
+        }
+    }
+}
+
+/// How busy the engines of one class were, see [`engine_utilization`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct EngineClassUtilization {
+    pub class: EngineClass,
+    /// Share of the time the busiest engine of the class was busy, from 0.0 to 1.0
+    pub busy: f32,
+}
+
+/// One entry per class, from the busiest engine of each
+pub(crate) fn utilization_by_class(
+    engines: impl IntoIterator<Item = (EngineClass, f32)>,
+) -> Vec<EngineClassUtilization> {
+    let mut classes = std::collections::BTreeMap::new();
+    for (class, busy) in engines {
+        let entry = classes.entry(class).or_insert(0.0f32);
+        *entry = entry.max(busy);
+    }
+    classes
+        .into_iter()
+        .map(|(class, busy)| EngineClassUtilization { class, busy })
+        .collect()
+}
+
+/// Outcome of copying a test pattern to GPU memory and back
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct AllocationProbe {
+    pub bytes: u64,
+    /// Allocation and both copies, serialized as `{ "secs": .., "nanos": .. }`
+    pub latency: std::time::Duration,
+    /// Whether the data read back matches the pattern written
+    pub verified: bool,
+}
+
+/// Whether a GPU still completes work, see [`check_device_health`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[non_exhaustive]
+pub enum Health {
+    Ok,
+    /// Completed, but took more than a quarter of the timeout
+    Slow,
+    /// The device was lost or did not complete within the timeout
+    Lost,
+}
+
+impl Health {
+    pub(crate) fn from_latency(latency: std::time::Duration, timeout: std::time::Duration) -> Self {
+        if latency > timeout / 4 {
+            Health::Slow
+        } else {
+            Health::Ok
+        }
+    }
+}
+
+/// A logical slice of a physical GPU, e.g. an NVIDIA MIG instance
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct GpuPartition {
+    pub id: String,
+    pub memory: Bytes,
+    /// Streaming multiprocessors or compute units assigned to the partition
+    pub compute_units: Option<u32>,
+}
+
+/// Where on the board a [`TemperatureSensor`] measures
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub enum TemperatureLocation {
+    /// The edge of the die, what tools have long shown as the GPU temperature
+    Edge,
+    /// The hottest point of the die, also called junction temperature
+    Hotspot,
+    /// The memory junction, which GDDR6X and HBM boards throttle on first
+    Memory,
+    /// The voltage regulators of the core
+    Vrm,
+}
+
+impl TemperatureLocation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TemperatureLocation::Edge => "Edge",
+            TemperatureLocation::Hotspot => "Hotspot",
+            TemperatureLocation::Memory => "Memory",
+            TemperatureLocation::Vrm => "Vrm",
+        }
+    }
+}
+
+/// One temperature reading of a GPU
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct TemperatureSensor {
+    pub location: TemperatureLocation,
+    pub celsius: f32,
 }
 
-#[derive(Debug, Clone)]
+/// The sensors that have a reading, in the order given
+#[cfg_attr(
+    not(any(
+        target_os = "macos",
+        all(feature = "nvml", not(target_os = "macos")),
+        all(feature = "adl", windows),
+        all(feature = "igcl", windows)
+    )),
+    allow(dead_code)
+)]
+pub(crate) fn temperature_sensors(
+    readings: impl IntoIterator<Item = (TemperatureLocation, Option<f32>)>,
+) -> Vec<TemperatureSensor> {
+    readings
+        .into_iter()
+        .filter_map(|(location, celsius)| {
+            Some(TemperatureSensor {
+                location,
+                celsius: celsius?,
+            })
+        })
+        .collect()
+}
+
+/// One GPU as seen by every backend that found it.
+///
+/// New fields land in minor versions, so the struct is `#[non_exhaustive]`:
+/// read fields directly or through the getters, and construct one with
+/// [`GPU::builder`] or by mutating [`GPU::default`]. Removing or retyping a
+/// field is a breaking change and only happens in major versions.
+#[derive(Debug, Default, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+// A daemon or `serve_http` server on an older version omits newer fields
+#[cfg_attr(feature = "serde", serde(default))]
 #[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
 pub struct GPU {
     pub kind: GPUKind,
     pub name: String,
     pub vendor: String,
     pub driver_version: String,
-    /// 0 is means unknown or not available
-    pub vram: u64,
+    /// 0 is means unknown or not available. Serialized as `vram_bytes`, as
+    /// `vram` held MB before 0.3.0.
+    #[cfg_attr(feature = "serde", serde(rename = "vram_bytes"))]
+    pub vram: Bytes,
     // pub max_resolution: Resolution,
     // pub current_resolution: Resolution,
-    pub clock_speed: Option<u32>,
-    pub temperature: Option<u32>,
+    /// Current core clock
+    pub clock_speed: Option<MegaHertz>,
+    /// Every sensor the backends read, [`GPU::temperature`] gives the edge
+    /// one
+    pub temperatures: Vec<TemperatureSensor>,
+    /// Number of hardware queues able to run compute work, `None` if the API does not expose it
+    pub compute_queue_count: Option<u32>,
+    /// Whether compute can overlap with graphics work on a dedicated queue
+    pub async_compute: Option<bool>,
+    /// Windows adapter LUID, used to match the GPU across graphics APIs
+    pub luid: Option<u64>,
+    /// IORegistry entry ID of the Metal device on macOS, which tells apart
+    /// identical GPUs such as the two cards of a Mac Pro
+    pub registry_id: Option<u64>,
+    pub displays: Vec<DisplayOutput>,
+    /// Displays the GPU can drive at once: CRTCs on Linux, video present
+    /// sources on Windows
+    pub max_displays: Option<u32>,
+    /// Displays currently lit by the GPU, as part of the desktop or not
+    pub active_displays: Option<u32>,
+    pub hybrid_role: Option<HybridRole>,
+    /// Kernel driver model, e.g. `WDDM 3.1`
+    pub driver_model: Option<String>,
+    pub pci_address: Option<PciAddress>,
+    /// Kernel driver claiming the device: the sysfs driver name on Linux
+    /// (e.g. `amdgpu`), the driver service on Windows, the kext bundle id on macOS
+    pub kernel_driver: Option<String>,
+    /// Whether the GPU can drive a display at all, `false` for compute-only cards.
+    /// On Windows only outputs with a monitor attached are visible.
+    pub has_display_output: Option<bool>,
+    /// Device UUID as `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`
+    pub uuid: Option<String>,
+    /// Set when the GPU is split into logical partitions, e.g. `MIG`, or the
+    /// AMD compute partition mode (`DPX`, `CPX`, ...)
+    pub partition_mode: Option<String>,
+    pub partitions: Vec<GpuPartition>,
+    /// AMD memory partition mode, e.g. `NPS1` or `NPS4`
+    pub memory_partition_mode: Option<String>,
+    /// Default board power limit
+    pub tdp_watts: Option<Watts>,
+    /// Range the power limit can be configured within
+    pub min_power_limit: Option<Watts>,
+    pub max_power_limit: Option<Watts>,
+    /// System memory the GPU can address. Integrated GPUs report their
+    /// memory here and `vram` only holds a dedicated carve-out, if any.
+    /// Serialized as `shared_memory_bytes`, see `vram`.
+    #[cfg_attr(feature = "serde", serde(rename = "shared_memory_bytes"))]
+    pub shared_memory: Option<Bytes>,
+    /// Memory the OS recommends the GPU keeps resident at most, Metal's
+    /// `recommendedMaxWorkingSetSize`. On unified memory Macs this is the
+    /// share of [`GPU::shared_memory`] (all of RAM) usable without paging.
+    pub recommended_working_set: Option<Bytes>,
+    /// Memory the firmware reserves for an integrated GPU at boot, the UMA
+    /// frame buffer size set in the BIOS. Games that read it as VRAM see
+    /// e.g. 512 MB, while the GPU can also map [`GPU::shared_memory`] (the
+    /// GTT on Linux). `None` for discrete GPUs.
+    pub carve_out: Option<Bytes>,
+    /// Dies or tiles making up this GPU, e.g. 2 on an M2 Ultra or a multi-tile
+    /// Intel Xe part. The tiles are not reported as separate GPUs.
+    pub tile_count: Option<u32>,
+    /// Only set for GPUs seen from inside a virtual machine: `true` for
+    /// hardware passed through to the VM, `false` for an emulated or
+    /// paravirtual adapter such as virtio-gpu or VMware SVGA
+    pub passthrough: Option<bool>,
+    /// Cloud instance type this GPU came with, e.g. `g5.xlarge` on EC2, or
+    /// the instance family inferred from the GPU model where the type is not
+    /// exposed to the guest
+    pub cloud_instance: Option<String>,
+    /// Ray tracing in hardware rather than emulated in compute shaders:
+    /// Apple9 (M3) and later on Metal, the ray tracing extensions on Vulkan
+    pub hardware_ray_tracing: Option<bool>,
+    /// Mesh and task (object) shaders
+    pub mesh_shading: Option<bool>,
+    /// Whether hardware-accelerated GPU scheduling (HAGS) is enabled on
+    /// Windows, `None` elsewhere and on GPUs or drivers without support
+    pub hardware_scheduling: Option<bool>,
+    /// GPUs linked into one logical device, this one included: the Vulkan
+    /// device group, the D3D12 node count or the Metal peer group. Above 1
+    /// the explicit multi-GPU APIs can split work across them, e.g. over
+    /// NVLink or Infinity Fabric Link.
+    pub linked_node_count: Option<u32>,
+    /// 1, 2, ... among GPUs with the same name, e.g. two identical cards,
+    /// `None` for a GPU whose name is unique. Numbered by PCI address, so it
+    /// is stable as long as the cards stay in their slots. See
+    /// [`GPU::display_name`].
+    pub instance_index: Option<u32>,
+    /// Whether any clock, voltage or power limit is raised above stock, as
+    /// crash reports from overclocked systems are often not worth chasing.
+    /// Read from NVML, IGCL and amdgpu, `None` elsewhere and without
+    /// [`GpuQuery::with_telemetry`].
+    pub is_overclocked: Option<bool>,
+    /// Settings changed from their default, including lowered limits and
+    /// manual fan control, which do not make the GPU overclocked
+    pub overclock_settings: Vec<OverclockSetting>,
+    /// Product line, e.g. `GeForce`, `Quadro`, `Radeon Pro` or `Instinct`.
+    /// From NVML's brand with [`GpuQuery::with_telemetry`], inferred from
+    /// the name otherwise.
+    pub product_line: Option<String>,
+    pub product_segment: Option<ProductSegment>,
+    /// Layer implementing the graphics APIs on top of another one, where
+    /// performance differs from native: `MoltenVK` (Vulkan over Metal),
+    /// `Venus` (Vulkan forwarded out of a VM), `Dozen` (Vulkan over D3D12),
+    /// and `DXVK` or `WineD3D` for Direct3D under Wine or Proton
+    pub translation_layer: Option<String>,
+    /// Backend specific values without a field of their own, keyed like
+    /// `nvml.brand` or `metal.gpu_family`
+    #[cfg(feature = "extra")]
+    pub extra: std::collections::BTreeMap<String, serde_json::Value>,
+}
+
+#[cfg(feature = "extra")]
+pub(crate) fn extra<const N: usize>(
+    entries: [(&str, serde_json::Value); N],
+) -> std::collections::BTreeMap<String, serde_json::Value> {
+    entries
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value))
+        .collect()
 }
 
+#[cfg(not(target_os = "macos"))]
+pub(crate) fn vendor_from_id(vendor_id: u32) -> &'static str {
+    match vendor_id {
+        0x8086 => "Intel",
+        0x10DE => "NVIDIA",
+        0x1002 => "AMD",
+        // DXGI on Windows on Arm reports the ACPI id `QCOM`, Vulkan the PCI id
+        0x5143 | 0x4D4F_4351 => "Qualcomm",
+        0x13B5 => "ARM",
+        0x1414 => "Microsoft",
+        0x1ED5 => "Moore Threads",
+        0x0014 => "Loongson",
+        0x1D17 => "Zhaoxin",
+        // Zhaoxin's former GPU division, Arise cards
+        0x6766 => "GlenFly",
+        _ => "Unknown",
+    }
+}
+
+/// Every GPU with all the data this crate can collect, see [`GpuQuery`] to
+/// fetch less. Software renderers are left out.
 pub fn retrieve_gpu_info() -> Result<Vec<GPU>, Error> {
+    GpuQuery::all().run()
+}
+
+/// The GPU of the primary display, found without enumerating the others: the
+/// Metal system default device on macOS, the first DXGI adapter on Windows
+/// and the boot VGA device on Linux. For splash and status screens, as only
+/// the identity is filled in, and on Linux the name comes from `pci.ids`
+/// rather than the driver. `None` without a GPU.
+pub fn primary_gpu() -> Result<Option<GPU>, Error> {
+    #[cfg(target_os = "macos")]
+    return Ok(metal::primary_gpu_via_metal()?.map(GPU::from));
+
+    #[cfg(windows)]
+    return Ok(dxgi::primary_adapter()?.map(GPU::from));
+
+    #[cfg(target_os = "linux")]
+    return Ok(pci::primary_pci_gpu()?.map(GPU::from));
+
+    #[cfg(not(any(target_os = "macos", windows, target_os = "linux")))]
+    Ok(retrieve_gpu_info()?.into_iter().next())
+}
+
+/// Just the name of [`primary_gpu`], skipping the driver queries the
+/// identity needs on macOS and Windows
+pub fn primary_gpu_name() -> Option<String> {
+    #[cfg(target_os = "macos")]
+    return metal::primary_gpu_name_via_metal();
+
+    #[cfg(windows)]
+    return dxgi::primary_adapter_name();
+
+    #[cfg(not(any(target_os = "macos", windows)))]
+    primary_gpu().ok().flatten().map(|gpu| gpu.name)
+}
+
+/// [`primary_gpu`] unless `query` excludes it, in which case the first GPU
+/// [`GpuQuery::run`] returns takes its place, with the fields of `query`
+pub fn primary_gpu_with(query: &GpuQuery) -> Result<Option<GPU>, Error> {
+    match primary_gpu()? {
+        Some(gpu) if query.is_excluded(&gpu) => Ok(query.run()?.into_iter().next()),
+        gpu => Ok(gpu),
+    }
+}
+
+/// The name of [`primary_gpu_with`], as fast as [`primary_gpu_name`] unless
+/// `query` excludes devices, which needs the identity of the primary GPU
+pub fn primary_gpu_name_with(query: &GpuQuery) -> Option<String> {
+    if !query.has_exclusions() {
+        return primary_gpu_name();
+    }
+    primary_gpu_with(query).ok().flatten().map(|gpu| gpu.name)
+}
+
+/// Re-query displays and telemetry of a GPU returned by [`retrieve_gpu_info`]
+/// without enumerating the other GPUs
+pub fn refresh_gpu(gpu: &GPU) -> Result<GPU, Error> {
+    GpuQuery::all().refresh(gpu)
+}
+
+/// VRAM allocated by the calling process alone, for in-app memory
+/// displays. Read from the DRM fdinfo of the process on Linux, NVML where the
+/// driver reports nothing there, and otherwise the usage half of
+/// [`memory_budget`], which DXGI, Metal and `VK_EXT_memory_budget` track per
+/// process.
+pub fn self_vram_usage(gpu: &GPU) -> Result<Option<Bytes>, Error> {
+    #[cfg(target_os = "linux")]
+    if let Some(bytes) = gpu.pci_address.and_then(pci::self_vram_usage_via_fdinfo) {
+        return Ok(Some(Bytes(bytes)));
+    }
+
+    #[cfg(all(feature = "nvml", not(target_os = "macos")))]
+    if let Some(bytes) = nvml::self_vram_usage_via_nvml(gpu) {
+        return Ok(Some(Bytes(bytes)));
+    }
+
+    Ok(memory_budget(gpu)?.map(|budget| budget.usage))
+}
+
+/// How busy each class of engine of a GPU returned by [`retrieve_gpu_info`]
+/// is, e.g. to tell a saturated video encoder apart from idle 3D engines.
+/// Empty when the platform cannot report it.
+///
+/// Windows samples the kernel graphics statistics `interval` apart, as Task
+/// Manager does, and Linux the DRM fdinfo of every process this one may
+/// read, which misses other users' processes without root. NVML on Linux
+/// and Metal report their own running average and ignore `interval`; both
+/// count 3D and compute work as [`EngineClass::Graphics`], and Metal has
+/// nothing on the media engines.
+#[cfg_attr(not(any(windows, target_os = "linux")), allow(unused_variables))]
+pub fn engine_utilization(
+    gpu: &GPU,
+    interval: std::time::Duration,
+) -> Result<Vec<EngineClassUtilization>, Error> {
+    #[cfg(windows)]
+    if let Some(luid) = gpu.luid {
+        let utilization = utilization_via_d3dkmt(luid, interval)?;
+        return Ok(utilization_by_class(
+            utilization
+                .engines
+                .into_iter()
+                .map(|engine| (engine.class, engine.busy)),
+        ));
+    }
+
+    #[cfg(all(feature = "nvml", target_os = "linux"))]
+    if let Some(classes) = nvml::engine_utilization_via_nvml(gpu) {
+        return Ok(classes);
+    }
+
+    #[cfg(target_os = "linux")]
+    if let Some(address) = gpu.pci_address {
+        return Ok(pci::engine_utilization_via_fdinfo(address, interval));
+    }
+
     #[cfg(target_os = "macos")]
-    let gpus = retrieve_gpu_info_via_metal()?
+    return Ok(gpu
+        .registry_id
+        .map(engine_utilization_via_metal)
+        .unwrap_or_default());
+
+    #[cfg(not(target_os = "macos"))]
+    Ok(vec![])
+}
+
+/// How much memory to plan GPU allocations such as model weights around:
+/// the OS recommended working set on unified memory Macs, as Apple advises
+/// over a fixed fraction of RAM, otherwise the dedicated VRAM, or the shared
+/// memory of integrated GPUs without any. `None` when neither is known.
+///
+/// Static, unlike [`memory_budget`], which also subtracts what other
+/// applications hold right now.
+pub fn suggested_gpu_memory_budget(gpu: &GPU) -> Option<Bytes> {
+    if let Some(working_set) = gpu.recommended_working_set {
+        return Some(working_set);
+    }
+    Some(gpu.vram.max(gpu.shared_memory.unwrap_or_default())).filter(|&bytes| bytes != Bytes(0))
+}
+
+/// The current memory budget of the calling process on a GPU returned by
+/// [`retrieve_gpu_info`]. `None` when the platform cannot report it.
+pub fn memory_budget(gpu: &GPU) -> Result<Option<MemoryBudget>, Error> {
+    #[cfg(windows)]
+    if let Some(luid) = gpu.luid {
+        return Ok(memory_budget_via_dxgi(luid)?);
+    }
+
+    #[cfg(target_os = "macos")]
+    return Ok(
+        find_metal_gpu(gpu)?.and_then(|metal_gpu| memory_budget_via_metal(metal_gpu.registry_id))
+    );
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let Some(uuid) = gpu.uuid.as_deref().and_then(parse_uuid) else {
+            return Ok(None);
+        };
+        Ok(memory_budget_via_vk(&uuid)?)
+    }
+}
+
+/// The Metal device of a GPU returned by [`retrieve_gpu_info`], matched by
+/// registry ID as identical GPUs share a name
+#[cfg(target_os = "macos")]
+pub(crate) fn find_metal_gpu(gpu: &GPU) -> Result<Option<MetalGpu>, Error> {
+    let Some(registry_id) = gpu.registry_id else {
+        return Ok(None);
+    };
+    Ok(retrieve_gpu_info_via_metal()?
         .into_iter()
-        .map(|g| g.into())
-        .collect::<Vec<GPU>>();
+        .find(|metal_gpu| metal_gpu.registry_id == registry_id))
+}
+
+/// Allocate `bytes` of device memory on a GPU returned by [`retrieve_gpu_info`],
+/// copy a pattern into it and back, and time the round trip. Catches GPUs that
+/// enumerate fine but fail on first use. `None` when the GPU cannot be matched.
+pub fn probe_allocation(gpu: &GPU, bytes: u64) -> Result<Option<AllocationProbe>, Error> {
+    #[cfg(target_os = "macos")]
+    return match find_metal_gpu(gpu)? {
+        Some(metal_gpu) => Ok(Some(probe_allocation_via_metal(
+            metal_gpu.registry_id,
+            bytes,
+        )?)),
+        None => Ok(None),
+    };
 
     #[cfg(not(target_os = "macos"))]
-    let gpus = retrieve_gpu_info_via_vk()?;
-
-    Ok(gpus)
-}
-
-// pub fn retrieve_gpu_info_via_gl() -> Result<Vec<GPU>, Error> {
-//     // Create a headless context
-//     let event_loop = winit::event_loop::EventLoop::new();
-//     let window_builder = winit::window::WindowAttributes::default()
-//         .with_visible(false)
-//         .with_inner_size(winit::dpi::LogicalSize::new(1, 1));
-
-//     let template = glutin::config::ConfigTemplateBuilder::new()
-//         .with_transparency(true)
-//         .with_float_pixels(true)
-//         .build();
-//     let config = glutin::display::
-
-//     let context = unsafe {
-//         gl_config
-//             .display()
-//             .create_context(&gl_config, &window.raw_window_handle())
-//             .map_err(|_| Error::OpenGLContextCreationFailed)?
-//     };
-
-//     let surface = Surface::new(&gl_config.display(), window.inner_size().into(), &window);
-
-//     let context = context
-//         .make_current(&surface)
-//         .map_err(|_| Error::OpenGLContextCreationFailed)?;
-
-//     // Load OpenGL functions
-//     gl::load_with(|s| context.get_proc_address(s));
-
-//     // Query GPU information
-//     let vendor = unsafe {
-//         let data = gl::GetString(gl::VENDOR);
-//         std::ffi::CStr::from_ptr(data as *const i8)
-//             .to_string_lossy()
-//             .into_owned()
-//     };
-
-//     let renderer = unsafe {
-//         let data = gl::GetString(gl::RENDERER);
-//         std::ffi::CStr::from_ptr(data as *const i8)
-//             .to_string_lossy()
-//             .into_owned()
-//     };
-
-//     let version = unsafe {
-//         let data = gl::GetString(gl::VERSION);
-//         std::ffi::CStr::from_ptr(data as *const i8)
-//             .to_string_lossy()
-//             .into_owned()
-//     };
-
-//     // Try to determine GPU kind (this is a rough estimate)
-//     let kind = if renderer.to_lowercase().contains("intel") {
-//         GPUKind::Integrated
-//     } else if renderer.to_lowercase().contains("nvidia") || renderer.to_lowercase().contains("amd")
-//     {
-//         GPUKind::Discrete
-//     } else {
-//         GPUKind::Unknown
-//     };
-
-//     // Try to get VRAM info (this is not standardized and may not work on all GPUs)
-//     let vram = unsafe {
-//         let mut vram_size = 0;
-//         gl::GetIntegerv(
-//             gl::GPU_MEMORY_INFO_TOTAL_AVAILABLE_MEMORY_NVX,
-//             &mut vram_size,
-//         );
-//         if vram_size > 0 {
-//             (vram_size as u64) * 1024 // Convert KB to bytes
-//         } else {
-//             0
-//         }
-//     };
-
-//     let gpu = GPU {
-//         kind,
-//         name: renderer,
-//         vendor,
-//         driver_version: version,
-//         vram,
-//         clock_speed: None,
-//         temperature: None,
-//     };
-
-//     Ok(vec![gpu])
-// }
+    {
+        let Some(uuid) = gpu.uuid.as_deref().and_then(parse_uuid) else {
+            return Ok(None);
+        };
+        Ok(probe_allocation_via_vk(&uuid, bytes)?)
+    }
+}
+
+/// Whether a GPU returned by [`retrieve_gpu_info`] can present to the
+/// windowing system of the session, i.e. back a swapchain. Compute-only cards
+/// and some render offload devices cannot, which otherwise only shows once
+/// swapchain creation fails. `None` without a windowing system to connect to
+/// or when the GPU cannot be matched.
+pub fn presentation_support(gpu: &GPU) -> Result<Option<bool>, Error> {
+    // Any Metal device can render into a CAMetalLayer, the window server
+    // composites it on the GPU driving the display
+    #[cfg(target_os = "macos")]
+    return Ok(find_metal_gpu(gpu)?.map(|_| true));
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let Some(uuid) = gpu.uuid.as_deref().and_then(parse_uuid) else {
+            return Ok(None);
+        };
+        Ok(presentation_support_via_vk(&uuid)?)
+    }
+}
+
+/// Submit an empty command buffer to a GPU returned by [`retrieve_gpu_info`]
+/// and wait up to `timeout` for it to complete, so orchestration layers can
+/// notice hung or removed devices. `None` when the GPU cannot be matched.
+pub fn check_device_health(
+    gpu: &GPU,
+    timeout: std::time::Duration,
+) -> Result<Option<Health>, Error> {
+    #[cfg(target_os = "macos")]
+    return match find_metal_gpu(gpu)? {
+        Some(metal_gpu) => Ok(Some(check_device_health_via_metal(
+            metal_gpu.registry_id,
+            timeout,
+        )?)),
+        None => Ok(None),
+    };
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        let Some(uuid) = gpu.uuid.as_deref().and_then(parse_uuid) else {
+            return Ok(None);
+        };
+        Ok(check_device_health_via_vk(&uuid, timeout)?)
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn parse_uuid(uuid: &str) -> Option<[u8; 16]> {
+    let hex = uuid.replace('-', "");
+    if hex.len() != 32 {
+        return None;
+    }
+    let mut bytes = [0; 16];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(bytes)
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_pci_address() {
+        let address: PciAddress = "0000:41:00.0".parse().unwrap();
+        assert_eq!(address.bus, 0x41);
+        assert_eq!(address.to_string(), "0000:41:00.0");
+        assert_eq!(
+            "0a:00.1".parse::<PciAddress>().unwrap().to_string(),
+            "0000:0a:00.1"
+        );
+        assert!("not-an-address".parse::<PciAddress>().is_err());
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn test_vendor_from_id() {
+        assert_eq!(vendor_from_id(0x10DE), "NVIDIA");
+        assert_eq!(vendor_from_id(0x4D4F_4351), "Qualcomm");
+        assert_eq!(vendor_from_id(0x5143), "Qualcomm");
+        assert_eq!(vendor_from_id(0x1ED5), "Moore Threads");
+        assert_eq!(vendor_from_id(0xFFFF), "Unknown");
+    }
+
+    #[test]
+    fn test_kind_from_str() {
+        assert_eq!("discrete".parse(), Ok(GPUKind::Discrete));
+        assert_eq!(GPUKind::Software.as_str(), "Software");
+        let future: GPUKind = "Neural".parse().unwrap();
+        assert_eq!(future, GPUKind::Other("Neural".to_string()));
+        assert_eq!(String::from(future), "Neural");
+        assert_eq!("builtin".parse(), Ok(GPULocation::BuiltIn));
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    #[test]
+    fn test_parse_uuid() {
+        let uuid = parse_uuid("a0b1c2d3-0000-1111-2222-333344445555").unwrap();
+        assert_eq!(uuid[0], 0xa0);
+        assert_eq!(uuid[15], 0x55);
+        assert_eq!(parse_uuid("a0b1c2d3"), None);
+    }
+
+    #[test]
+    fn test_suggested_gpu_memory_budget() {
+        let gib = |gib: u64| Bytes::from_mb(gib * 1024);
+        let mac = GPU::builder()
+            .shared_memory(gib(32))
+            .recommended_working_set(gib(21))
+            .build();
+        assert_eq!(suggested_gpu_memory_budget(&mac), Some(gib(21)));
+        let igpu = GPU::builder().vram_mb(512).shared_memory(gib(16)).build();
+        assert_eq!(suggested_gpu_memory_budget(&igpu), Some(gib(16)));
+        assert_eq!(
+            suggested_gpu_memory_budget(&GPU::builder().vram_gib(24).build()),
+            Some(gib(24))
+        );
+        assert_eq!(suggested_gpu_memory_budget(&GPU::default()), None);
+    }
+
     #[test]
     fn test_retrieve_gpu_info() {
+        // Empty on machines without a GPU or driver
         let gpus = retrieve_gpu_info().unwrap();
         eprintln!("GPUs: {gpus:#?}");
-        assert!(!gpus.is_empty());
+        assert!(gpus.iter().all(|gpu| gpu.kind != GPUKind::Software));
+
+        let with_software = GpuQuery::new().with_software_renderers().run().unwrap();
+        assert!(with_software.len() >= GpuQuery::new().run().unwrap().len());
+    }
+
+    #[test]
+    fn test_primary_gpu() {
+        let start = std::time::Instant::now();
+        let name = primary_gpu_name();
+        eprintln!("{name:?} in {:?}", start.elapsed());
+        eprintln!("{:#?}", primary_gpu());
+
+        let query = GpuQuery::new();
+        assert_eq!(primary_gpu_name_with(&query), name);
+        if let Ok(Some(gpu)) = primary_gpu() {
+            let query = query.exclude(gpu.pci_address.map_or_else(
+                || format!("{:#x}", gpu.registry_id.unwrap_or_default()),
+                |address| address.to_string(),
+            ));
+            let other = primary_gpu_with(&query).unwrap();
+            assert!(other.is_none_or(|other| !query.is_excluded(&other)));
+        }
+    }
+
+    #[test]
+    fn test_health_from_latency() {
+        let timeout = std::time::Duration::from_secs(1);
+        assert_eq!(
+            Health::from_latency(std::time::Duration::from_millis(5), timeout),
+            Health::Ok
+        );
+        assert_eq!(
+            Health::from_latency(std::time::Duration::from_millis(400), timeout),
+            Health::Slow
+        );
+    }
+
+    #[test]
+    fn test_presentation_support() {
+        // `None` on headless machines without a windowing system
+        for gpu in retrieve_gpu_info().unwrap() {
+            match presentation_support(&gpu) {
+                Ok(support) => eprintln!("{}: {support:?}", gpu.name),
+                Err(e) => eprintln!("{}: {e}", gpu.name),
+            }
+        }
+    }
+
+    #[test]
+    fn test_engine_utilization() {
+        for gpu in retrieve_gpu_info().unwrap() {
+            match engine_utilization(&gpu, std::time::Duration::from_millis(50)) {
+                Ok(classes) => eprintln!("{}: {classes:?}", gpu.name),
+                Err(e) => eprintln!("{}: {e}", gpu.name),
+            }
+        }
+    }
+
+    #[test]
+    fn test_probe_allocation() {
+        for gpu in retrieve_gpu_info().unwrap() {
+            match probe_allocation(&gpu, 1 << 20) {
+                Ok(probe) => eprintln!("{}: {probe:?}", gpu.name),
+                Err(e) => eprintln!("{}: {e}", gpu.name),
+            }
+        }
     }
 }
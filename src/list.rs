@@ -0,0 +1,117 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::ops::{Deref, DerefMut};
+
+use crate::{Bytes, GPUKind, GPU};
+
+/// The GPUs of a system with the aggregations reports commonly need
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct GpuList(pub Vec<GPU>);
+
+impl GpuList {
+    /// Dedicated VRAM of all GPUs
+    pub fn total_vram(&self) -> Bytes {
+        self.iter().map(|gpu| gpu.vram).sum()
+    }
+
+    pub fn max_vram_gpu(&self) -> Option<&GPU> {
+        self.iter().max_by_key(|gpu| gpu.vram)
+    }
+
+    /// GPUs keyed by vendor name, in enumeration order
+    pub fn group_by_vendor(&self) -> BTreeMap<&str, Vec<&GPU>> {
+        let mut groups = BTreeMap::<&str, Vec<&GPU>>::new();
+        for gpu in self.iter() {
+            groups.entry(gpu.vendor.as_str()).or_default().push(gpu);
+        }
+        groups
+    }
+
+    /// Fastest first, see [`compare_performance`]
+    pub fn sort_by_performance(&mut self) {
+        self.0.sort_by(|a, b| compare_performance(b, a));
+    }
+}
+
+/// Orders GPUs by performance tier (discrete, integrated, virtual, unknown,
+/// software), then by VRAM. `Greater` means `a` is expected to be faster.
+pub fn compare_performance(a: &GPU, b: &GPU) -> Ordering {
+    performance_tier(&a.kind)
+        .cmp(&performance_tier(&b.kind))
+        .then(a.vram.cmp(&b.vram))
+}
+
+fn performance_tier(kind: &GPUKind) -> u8 {
+    #[allow(deprecated)]
+    match kind {
+        GPUKind::Discrete => 4,
+        GPUKind::Integrated => 3,
+        GPUKind::Virtual => 2,
+        GPUKind::Unknown | GPUKind::Other(_) => 1,
+        GPUKind::CPU | GPUKind::Software => 0,
+    }
+}
+
+impl Deref for GpuList {
+    type Target = Vec<GPU>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for GpuList {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl From<Vec<GPU>> for GpuList {
+    fn from(gpus: Vec<GPU>) -> Self {
+        Self(gpus)
+    }
+}
+
+impl IntoIterator for GpuList {
+    type Item = GPU;
+    type IntoIter = std::vec::IntoIter<GPU>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gpu_list() {
+        let gpu = |kind, vendor: &str, vram| GPU {
+            kind,
+            vendor: vendor.to_string(),
+            vram: Bytes::from_mb(vram),
+            ..Default::default()
+        };
+        let mut gpus = GpuList::from(vec![
+            gpu(GPUKind::Software, "Unknown", 0),
+            gpu(GPUKind::Integrated, "Intel", 128),
+            gpu(GPUKind::Discrete, "NVIDIA", 8192),
+            gpu(GPUKind::Discrete, "NVIDIA", 24576),
+        ]);
+
+        assert_eq!(gpus.total_vram(), Bytes::from_mb(32896));
+        assert_eq!(gpus.max_vram_gpu().map(|gpu| gpu.vram.as_mb()), Some(24576));
+        assert_eq!(gpus.group_by_vendor()["NVIDIA"].len(), 2);
+
+        gpus.sort_by_performance();
+        let order = gpus.iter().map(|gpu| gpu.vram.as_mb()).collect::<Vec<_>>();
+        assert_eq!(order, [24576, 8192, 128, 0]);
+    }
+}
@@ -1,11 +1,41 @@
-use crate::{GPUKind, GPULocation};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "bench")]
+use crate::bench::{self, Benchmark};
+#[cfg(feature = "counters")]
+use crate::counters::{Counter, CounterGroup, CounterUnit, CounterValue};
+use crate::{
+    AllocationProbe, Bytes, EngineClass, EngineClassUtilization, GPUKind, GPULocation, Health,
+    MemoryBudget, TemperatureLocation,
+};
 use objc2::{rc::Retained, runtime::ProtocolObject};
-use objc2_metal::{MTLCopyAllDevices, MTLDevice, MTLDeviceLocation, MTLSize};
+use objc2_core_foundation::{
+    CFDictionary, CFMutableDictionary, CFNumber, CFRetained, CFString, CFType,
+};
+use objc2_core_graphics::{
+    CGDirectDisplayCopyCurrentMetalDevice, CGDirectDisplayID, CGError, CGGetActiveDisplayList,
+};
+#[cfg(feature = "bench")]
+use objc2_foundation::{ns_string, NSError, NSString};
+use objc2_metal::{
+    MTLBlitCommandEncoder, MTLBuffer, MTLCommandBuffer, MTLCommandBufferStatus, MTLCommandEncoder,
+    MTLCommandQueue, MTLCopyAllDevices, MTLCreateSystemDefaultDevice, MTLDevice, MTLDeviceLocation,
+    MTLGPUFamily, MTLResourceOptions, MTLSize,
+};
+#[cfg(feature = "counters")]
+use objc2_metal::{
+    MTLBlitPassDescriptor, MTLCounter, MTLCounterSampleBuffer, MTLCounterSampleBufferDescriptor,
+    MTLCounterSamplingPoint, MTLCounterSet, MTLStorageMode,
+};
+#[cfg(feature = "bench")]
+use objc2_metal::{MTLComputeCommandEncoder, MTLLibrary};
 
 #[derive(Debug, thiserror::Error)]
 pub enum MetalError {
     #[error("Metal is not supported on this platform")]
     NotSupported,
+    #[error("Failed to perform Metal operation: {0}")]
+    OperationFailed(String),
 }
 
 impl MetalError {
@@ -20,7 +50,7 @@ pub struct MetalGpu {
     pub name: String,
     pub vendor: String,
     // pub driver_version: String,
-    pub vram: u64, // MB
+    pub vram: Bytes,
     pub is_removable: bool,
     pub is_headless: bool,
     pub registry_id: u64,
@@ -28,18 +58,80 @@ pub struct MetalGpu {
     pub has_unified_memory: bool,
     pub max_threads_per_threadgroup: MaxThreadsPerThreadgroup,
     pub recommended_max_working_set: u64, // bytes
+    /// Bundle id of the kext driving the device
+    pub kernel_driver: Option<String>,
+    /// Raw `MTLGPUFamily` of the newest Apple family the device supports,
+    /// e.g. 1009 for `Apple9`, or 2002 (`Mac2`) for other GPUs
+    pub gpu_family: Option<isize>,
+    /// Ray tracing units, Apple9 (M3) and later. Older GPUs run the Metal ray
+    /// tracing API in compute shaders.
+    pub hardware_ray_tracing: bool,
+    /// Dynamic caching of on-chip memory between shaders, Apple9 and later
+    pub dynamic_caching: bool,
+    /// Mesh and object shaders, part of the Metal 3 family
+    pub mesh_shading: bool,
+    /// MetalFX spatial and temporal upscaling, which requires a Metal 3 GPU
+    pub metalfx_upscaling: bool,
+    /// Active displays the device currently drives
+    pub active_displays: u32,
+    /// Celsius, from the SMC for the built-in discrete GPU of an Intel Mac
+    pub temperature: Option<u32>,
+    /// GPUs in the peer group of the device, linked by Infinity Fabric Link
+    /// in a Mac Pro. 1 outside of a group.
+    pub peer_count: u32,
 }
 
 impl From<MetalGpu> for super::GPU {
     fn from(gpu: MetalGpu) -> Self {
+        // Ultra chips fuse two Max dies into one Metal device
+        let tile_count =
+            (gpu.vendor == "Apple").then_some(if gpu.name.contains("Ultra") { 2 } else { 1 });
         Self {
+            passthrough: crate::vm::passthrough(&gpu.kind),
             kind: gpu.kind,
             name: gpu.name,
             vendor: gpu.vendor,
             driver_version: "Unknown".to_string(),
-            vram: gpu.vram,
+            // Unified memory is system RAM, there is no dedicated VRAM
+            vram: if gpu.has_unified_memory {
+                Bytes(0)
+            } else {
+                gpu.vram
+            },
+            // All of RAM, of which the GPU should keep the working set resident
+            shared_memory: gpu
+                .has_unified_memory
+                .then(|| crate::system::total_ram().unwrap_or(gpu.vram)),
+            recommended_working_set: Some(Bytes(gpu.recommended_max_working_set)),
+            tile_count,
             clock_speed: None,
-            temperature: None,
+            temperatures: crate::temperature_sensors([(
+                TemperatureLocation::Edge,
+                gpu.temperature.map(|celsius| celsius as f32),
+            )]),
+            // Metal does not expose hardware queues, so whether compute
+            // overlaps render work is unknown
+            async_compute: None,
+            kernel_driver: gpu.kernel_driver,
+            registry_id: Some(gpu.registry_id),
+            has_display_output: Some(!gpu.is_headless),
+            active_displays: Some(gpu.active_displays),
+            hardware_ray_tracing: Some(gpu.hardware_ray_tracing),
+            mesh_shading: Some(gpu.mesh_shading),
+            linked_node_count: Some(gpu.peer_count),
+            #[cfg(feature = "extra")]
+            extra: crate::extra([
+                ("metal.dynamic_caching", gpu.dynamic_caching.into()),
+                ("metal.metalfx_upscaling", gpu.metalfx_upscaling.into()),
+                ("metal.location", gpu.location.as_str().into()),
+                ("metal.is_removable", gpu.is_removable.into()),
+                ("metal.gpu_family", gpu.gpu_family.into()),
+                (
+                    "metal.recommended_max_working_set",
+                    gpu.recommended_max_working_set.into(),
+                ),
+            ]),
+            ..Default::default()
         }
     }
 }
@@ -73,23 +165,39 @@ impl From<MTLDeviceLocation> for GPULocation {
     }
 }
 
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", err))]
 pub fn retrieve_gpu_info_via_metal() -> Result<Vec<MetalGpu>, MetalError> {
     let devices = MTLCopyAllDevices();
 
+    // Headless VMs have no Metal device at all
     if devices.is_empty() {
-        return Err(MetalError::NotSupported);
+        log::info!("no metal devices found");
+        return Ok(vec![]);
     }
 
     let mut gpus = Vec::new();
 
     for device in devices {
         let gpu = extract_gpu_info(&device)?;
+        trace_debug!(name = %gpu.name, registry_id = gpu.registry_id, "found metal device");
         gpus.push(gpu);
     }
 
+    // The SMC has one set of GPU sensors, which can't be told apart on the
+    // Mac Pro models with two built-in GPUs
+    let mut built_in_discrete = gpus.iter_mut().filter(|gpu| has_smc_temperature(gpu));
+    if let (Some(gpu), None) = (built_in_discrete.next(), built_in_discrete.next()) {
+        gpu.temperature = crate::smc::gpu_temperature().map(|celsius| celsius.0);
+    }
+
     Ok(gpus)
 }
 
+/// Whether the SMC of an Intel Mac has sensors for this GPU
+fn has_smc_temperature(gpu: &MetalGpu) -> bool {
+    gpu.kind == GPUKind::Discrete && gpu.location == GPULocation::BuiltIn && gpu.vendor != "Apple"
+}
+
 fn extract_gpu_info(device: &ProtocolObject<dyn MTLDevice>) -> Result<MetalGpu, MetalError> {
     let name = device.name().to_string();
     let is_removable = device.isRemovable();
@@ -98,7 +206,10 @@ fn extract_gpu_info(device: &ProtocolObject<dyn MTLDevice>) -> Result<MetalGpu,
     let registry_id = device.registryID();
     let location: GPULocation = device.location().into();
     let has_unified_memory = device.hasUnifiedMemory();
-    let kind = if is_low_power {
+    // macOS guests on Apple silicon see an "Apple Paravirtual device"
+    let kind = if name.contains("Paravirtual") {
+        GPUKind::Virtual
+    } else if is_low_power {
         GPUKind::Integrated
     } else if is_removable {
         GPUKind::Discrete
@@ -112,6 +223,28 @@ fn extract_gpu_info(device: &ProtocolObject<dyn MTLDevice>) -> Result<MetalGpu,
         device.maxThreadsPerThreadgroup().into();
     let recommended_max_working_set = device.recommendedMaxWorkingSetSize();
     let vram = calculate_vram(has_unified_memory, recommended_max_working_set, registry_id);
+    let kernel_driver = get_kext_bundle_id(registry_id);
+    let gpu_family = (MTLGPUFamily::Apple1.0..=MTLGPUFamily::Apple10.0)
+        .rev()
+        .map(MTLGPUFamily)
+        .chain([MTLGPUFamily::Mac2])
+        .find(|&family| device.supportsFamily(family))
+        .map(|family| family.0);
+    let apple9 = device.supportsFamily(MTLGPUFamily::Apple9);
+    let metal3 = device.supportsFamily(MTLGPUFamily::Metal3);
+    let active_displays = active_display_ids()
+        .into_iter()
+        .filter(|&display_id| {
+            CGDirectDisplayCopyCurrentMetalDevice(display_id)
+                .is_some_and(|display_device| display_device.registryID() == registry_id)
+        })
+        .count() as u32;
+    // A group id of 0 means the device has no peers
+    let peer_count = if device.peerGroupID() == 0 {
+        1
+    } else {
+        device.peerCount()
+    };
     // let driver_version = get_metal_version();
 
     Ok(MetalGpu {
@@ -127,37 +260,507 @@ fn extract_gpu_info(device: &ProtocolObject<dyn MTLDevice>) -> Result<MetalGpu,
         has_unified_memory,
         max_threads_per_threadgroup,
         recommended_max_working_set,
+        kernel_driver,
+        gpu_family,
+        hardware_ray_tracing: apple9,
+        dynamic_caching: apple9,
+        mesh_shading: metal3,
+        metalfx_upscaling: metal3,
+        active_displays,
+        temperature: None,
+        peer_count,
     })
 }
 
+/// Displays that are drawable, i.e. not asleep, mirrored or disabled
+fn active_display_ids() -> Vec<CGDirectDisplayID> {
+    let mut count = 0;
+    if unsafe { CGGetActiveDisplayList(0, std::ptr::null_mut(), &mut count) } != CGError::Success {
+        return vec![];
+    }
+    let mut ids = vec![0; count as usize];
+    if unsafe { CGGetActiveDisplayList(count, ids.as_mut_ptr(), &mut count) } != CGError::Success {
+        return vec![];
+    }
+    ids.truncate(count as usize);
+    ids
+}
+
+/// The system default device from `MTLCreateSystemDefaultDevice`. On Macs
+/// with automatic graphics switching this powers up the discrete GPU.
+pub(crate) fn primary_gpu_via_metal() -> Result<Option<MetalGpu>, MetalError> {
+    MTLCreateSystemDefaultDevice()
+        .map(|device| extract_gpu_info(&device))
+        .transpose()
+}
+
+pub(crate) fn primary_gpu_name_via_metal() -> Option<String> {
+    Some(MTLCreateSystemDefaultDevice()?.name().to_string())
+}
+
+/// The GPU currently driving a display, e.g. the one from `CGMainDisplayID()`.
+/// `None` if the display is offline or its GPU is not in the Metal device list.
+pub fn gpu_for_display(display_id: CGDirectDisplayID) -> Result<Option<MetalGpu>, MetalError> {
+    let Some(device) = CGDirectDisplayCopyCurrentMetalDevice(display_id) else {
+        return Ok(None);
+    };
+    let registry_id = device.registryID();
+    Ok(retrieve_gpu_info_via_metal()?
+        .into_iter()
+        .find(|gpu| gpu.registry_id == registry_id))
+}
+
+/// Per-process budget of the device with the given registry id: the
+/// recommended working set and what the process has allocated on the device
+pub fn memory_budget_via_metal(registry_id: u64) -> Option<MemoryBudget> {
+    let device = MTLCopyAllDevices()
+        .into_iter()
+        .find(|device| device.registryID() == registry_id)?;
+    Some(MemoryBudget {
+        budget: Bytes(device.recommendedMaxWorkingSetSize()),
+        usage: Bytes(device.currentAllocatedSize() as u64),
+    })
+}
+
+/// Load of the GPU cores from the `PerformanceStatistics` the driver keeps
+/// in the IOKit registry, as shown by Activity Monitor. AMD drivers on Intel
+/// Macs name it differently.
+pub fn engine_utilization_via_metal(registry_id: u64) -> Vec<EngineClassUtilization> {
+    let Some(properties) = get_iokit_properties(registry_id) else {
+        return vec![];
+    };
+    let properties = unsafe { properties.cast_unchecked::<CFString, CFType>() };
+    let Some(statistics) = properties
+        .get(&CFString::from_str("PerformanceStatistics"))
+        .and_then(|value| value.downcast::<CFDictionary>().ok())
+    else {
+        return vec![];
+    };
+    let statistics = unsafe { statistics.cast_unchecked::<CFString, CFType>() };
+    let percent = ["Device Utilization %", "GPU Activity(%)"]
+        .iter()
+        .find_map(|key| {
+            statistics
+                .get(&CFString::from_str(key))?
+                .downcast::<CFNumber>()
+                .ok()?
+                .as_i64()
+        });
+    crate::utilization_by_class(
+        percent.map(|percent| (EngineClass::Graphics, percent.clamp(0, 100) as f32 / 100.0)),
+    )
+}
+
+/// Blit a pattern through a private (GPU only) buffer of the device with the
+/// given registry id and read it back
+pub fn probe_allocation_via_metal(
+    registry_id: u64,
+    bytes: u64,
+) -> Result<AllocationProbe, MetalError> {
+    let device = MTLCopyAllDevices()
+        .into_iter()
+        .find(|device| device.registryID() == registry_id)
+        .ok_or(MetalError::NotSupported)?;
+    let failed = |call: &str| MetalError::OperationFailed(format!("{call} failed"));
+
+    let size = bytes.max(4) as usize;
+    let start = Instant::now();
+    let buffer = |options| {
+        device
+            .newBufferWithLength_options(size, options)
+            .ok_or_else(|| failed("newBufferWithLength"))
+    };
+    let upload = buffer(MTLResourceOptions::StorageModeShared)?;
+    let private = buffer(MTLResourceOptions::StorageModePrivate)?;
+    let readback = buffer(MTLResourceOptions::StorageModeShared)?;
+
+    let pattern = |index: usize| (index as u8).wrapping_mul(31) ^ 0xA5;
+    let data =
+        unsafe { std::slice::from_raw_parts_mut(upload.contents().as_ptr() as *mut u8, size) };
+    for (index, byte) in data.iter_mut().enumerate() {
+        *byte = pattern(index);
+    }
+
+    let queue = device
+        .newCommandQueue()
+        .ok_or_else(|| failed("newCommandQueue"))?;
+    run_commands(&queue, |commands| {
+        let blit = commands
+            .blitCommandEncoder()
+            .ok_or_else(|| failed("blitCommandEncoder"))?;
+        unsafe {
+            blit.copyFromBuffer_sourceOffset_toBuffer_destinationOffset_size(
+                &upload, 0, &private, 0, size,
+            );
+            blit.copyFromBuffer_sourceOffset_toBuffer_destinationOffset_size(
+                &private, 0, &readback, 0, size,
+            );
+        }
+        blit.endEncoding();
+        Ok(())
+    })?;
+
+    let data =
+        unsafe { std::slice::from_raw_parts(readback.contents().as_ptr() as *const u8, size) };
+    Ok(AllocationProbe {
+        bytes: size as u64,
+        latency: start.elapsed(),
+        verified: data
+            .iter()
+            .enumerate()
+            .all(|(index, &byte)| byte == pattern(index)),
+    })
+}
+
+/// Counter sets of the device with the given registry id
+#[cfg(feature = "counters")]
+pub(crate) fn counter_groups_via_metal(registry_id: u64) -> Vec<CounterGroup> {
+    let Some(device) = MTLCopyAllDevices()
+        .into_iter()
+        .find(|device| device.registryID() == registry_id)
+    else {
+        return vec![];
+    };
+    let Some(counter_sets) = device.counterSets() else {
+        return vec![];
+    };
+    counter_sets
+        .iter()
+        .map(|counter_set| {
+            let name = counter_set.name().to_string();
+            let unit = counter_unit(&name);
+            CounterGroup {
+                counters: counter_set
+                    .counters()
+                    .iter()
+                    .map(|counter| Counter {
+                        name: counter.name().to_string(),
+                        description: None,
+                        unit,
+                    })
+                    .collect(),
+                name,
+            }
+        })
+        .collect()
+}
+
+/// Sample the counter set `group` at the start and end of a blit pass
+/// copying between private buffers of the device with the given registry id.
+/// `None` if the counter set is missing.
+#[cfg(feature = "counters")]
+pub(crate) fn sample_counters_via_metal(
+    registry_id: u64,
+    group: &str,
+) -> Result<Option<Vec<CounterValue>>, MetalError> {
+    let device = MTLCopyAllDevices()
+        .into_iter()
+        .find(|device| device.registryID() == registry_id)
+        .ok_or(MetalError::NotSupported)?;
+    let Some(counter_set) = device
+        .counterSets()
+        .and_then(|sets| sets.iter().find(|set| set.name().to_string() == group))
+    else {
+        return Ok(None);
+    };
+    if !device.supportsCounterSampling(MTLCounterSamplingPoint::AtBlitBoundary) {
+        return Err(MetalError::OperationFailed(
+            "counter sampling at blit boundaries is not supported".to_string(),
+        ));
+    }
+    let failed = |call: &str| MetalError::OperationFailed(format!("{call} failed"));
+
+    let descriptor = MTLCounterSampleBufferDescriptor::new();
+    descriptor.setCounterSet(Some(&counter_set));
+    descriptor.setStorageMode(MTLStorageMode::Shared);
+    unsafe { descriptor.setSampleCount(2) };
+    let sample_buffer = device
+        .newCounterSampleBufferWithDescriptor_error(&descriptor)
+        .map_err(|e| MetalError::OperationFailed(e.localizedDescription().to_string()))?;
+
+    let size = crate::counters::SAMPLE_COPY_BYTES as usize;
+    let buffer = || {
+        device
+            .newBufferWithLength_options(size, MTLResourceOptions::StorageModePrivate)
+            .ok_or_else(|| failed("newBufferWithLength"))
+    };
+    let (source, destination) = (buffer()?, buffer()?);
+    let pass = MTLBlitPassDescriptor::new();
+    let attachment = unsafe { pass.sampleBufferAttachments().objectAtIndexedSubscript(0) };
+    attachment.setSampleBuffer(Some(&sample_buffer));
+    unsafe {
+        attachment.setStartOfEncoderSampleIndex(0);
+        attachment.setEndOfEncoderSampleIndex(1);
+    }
+
+    let queue = device
+        .newCommandQueue()
+        .ok_or_else(|| failed("newCommandQueue"))?;
+    run_commands(&queue, |commands| {
+        let blit = commands
+            .blitCommandEncoderWithDescriptor(&pass)
+            .ok_or_else(|| failed("blitCommandEncoderWithDescriptor"))?;
+        unsafe {
+            blit.copyFromBuffer_sourceOffset_toBuffer_destinationOffset_size(
+                &source,
+                0,
+                &destination,
+                0,
+                size,
+            );
+        }
+        blit.endEncoding();
+        Ok(())
+    })?;
+
+    // Every counter result is a struct of one u64 per counter of the set
+    let resolved = unsafe { sample_buffer.resolveCounterRange((0..2).into()) }
+        .ok_or_else(|| failed("resolveCounterRange"))?
+        .to_vec();
+    let samples: Vec<u64> = resolved
+        .chunks_exact(8)
+        .map(|bytes| u64::from_ne_bytes(bytes.try_into().expect("chunks of 8 bytes")))
+        .collect();
+    let counters = counter_set.counters();
+    let (start, end) = samples.split_at(samples.len() / 2);
+    let unit = counter_unit(group);
+    Ok(Some(
+        counters
+            .iter()
+            .zip(start.iter().zip(end))
+            .map(|(counter, (start, end))| CounterValue {
+                name: counter.name().to_string(),
+                unit,
+                value: end.wrapping_sub(*start) as f64,
+            })
+            .collect(),
+    ))
+}
+
+/// The timestamp counter set counts GPU ticks, the others events
+#[cfg(feature = "counters")]
+fn counter_unit(counter_set: &str) -> CounterUnit {
+    if counter_set == "timestamp" {
+        CounterUnit::Ticks
+    } else {
+        CounterUnit::Generic
+    }
+}
+
+/// Commit an empty command buffer to the device with the given registry id and
+/// wait up to `timeout` for it
+pub fn check_device_health_via_metal(
+    registry_id: u64,
+    timeout: Duration,
+) -> Result<Health, MetalError> {
+    let device = MTLCopyAllDevices()
+        .into_iter()
+        .find(|device| device.registryID() == registry_id)
+        .ok_or(MetalError::NotSupported)?;
+    let failed = |call: &str| MetalError::OperationFailed(format!("{call} failed"));
+    let queue = device
+        .newCommandQueue()
+        .ok_or_else(|| failed("newCommandQueue"))?;
+    let commands = queue
+        .commandBuffer()
+        .ok_or_else(|| failed("commandBuffer"))?;
+
+    // waitUntilCompleted has no timeout, so poll the status instead
+    let start = Instant::now();
+    commands.commit();
+    loop {
+        match commands.status() {
+            MTLCommandBufferStatus::Completed => {
+                return Ok(Health::from_latency(start.elapsed(), timeout))
+            }
+            MTLCommandBufferStatus::Error => return Ok(Health::Lost),
+            _ if start.elapsed() > timeout => return Ok(Health::Lost),
+            _ => std::thread::sleep(Duration::from_millis(1)),
+        }
+    }
+}
+
+/// Copy bandwidth and FMA throughput of the device with the given registry id
+#[cfg(feature = "bench")]
+pub(crate) fn benchmark_via_metal(registry_id: u64) -> Result<Benchmark, MetalError> {
+    let device = MTLCopyAllDevices()
+        .into_iter()
+        .find(|device| device.registryID() == registry_id)
+        .ok_or(MetalError::NotSupported)?;
+    let failed = |call: &str| MetalError::OperationFailed(format!("{call} failed"));
+    let queue = device
+        .newCommandQueue()
+        .ok_or_else(|| failed("newCommandQueue"))?;
+    let buffer = |length| {
+        device
+            .newBufferWithLength_options(length, MTLResourceOptions::StorageModePrivate)
+            .ok_or_else(|| failed("newBufferWithLength"))
+    };
+
+    let size = bench::COPY_BYTES as usize;
+    let source = buffer(size)?;
+    let destination = buffer(size)?;
+    let (copies, copy_time) = bench::measure(|count| {
+        run_commands(&queue, |commands| {
+            let blit = commands
+                .blitCommandEncoder()
+                .ok_or_else(|| failed("blitCommandEncoder"))?;
+            for _ in 0..count {
+                unsafe {
+                    blit.copyFromBuffer_sourceOffset_toBuffer_destinationOffset_size(
+                        &source,
+                        0,
+                        &destination,
+                        0,
+                        size,
+                    )
+                };
+            }
+            blit.endEncoding();
+            Ok(())
+        })
+    })?;
+
+    let compile_failed =
+        |e: Retained<NSError>| MetalError::OperationFailed(e.localizedDescription().to_string());
+    let source = NSString::from_str(&format!(
+        "kernel void fma(device float *data [[buffer(0)]], uint id [[thread_position_in_grid]]) {{
+            float value = data[id];
+            for (uint i = 0; i < {}; i++) {{
+                value = value * 0.999f + 0.001f;
+            }}
+            data[id] = value;
+        }}",
+        bench::FMA_ITERATIONS
+    ));
+    let library = device
+        .newLibraryWithSource_options_error(&source, None)
+        .map_err(compile_failed)?;
+    let function = library
+        .newFunctionWithName(ns_string!("fma"))
+        .ok_or_else(|| failed("newFunctionWithName"))?;
+    let pipeline = device
+        .newComputePipelineStateWithFunction_error(&function)
+        .map_err(compile_failed)?;
+
+    let data = buffer(bench::FMA_ELEMENTS as usize * 4)?;
+    let group_size = bench::FMA_GROUP_SIZE as usize;
+    let groups = MTLSize {
+        width: bench::FMA_ELEMENTS as usize / group_size,
+        height: 1,
+        depth: 1,
+    };
+    let group = MTLSize {
+        width: group_size,
+        height: 1,
+        depth: 1,
+    };
+    // Dispatches in one encoder run in order, each continuing from the last
+    let (dispatches, compute_time) = bench::measure(|count| {
+        run_commands(&queue, |commands| {
+            let encoder = commands
+                .computeCommandEncoder()
+                .ok_or_else(|| failed("computeCommandEncoder"))?;
+            encoder.setComputePipelineState(&pipeline);
+            unsafe { encoder.setBuffer_offset_atIndex(Some(&data), 0, 0) };
+            for _ in 0..count {
+                encoder.dispatchThreadgroups_threadsPerThreadgroup(groups, group);
+            }
+            encoder.endEncoding();
+            Ok(())
+        })
+    })?;
+
+    Ok(Benchmark::new(copies, copy_time, dispatches, compute_time))
+}
+
+/// Encode one command buffer, commit it and wait for it to finish. Returns the
+/// time from commit to completion.
+fn run_commands(
+    queue: &ProtocolObject<dyn MTLCommandQueue>,
+    encode: impl FnOnce(&ProtocolObject<dyn MTLCommandBuffer>) -> Result<(), MetalError>,
+) -> Result<Duration, MetalError> {
+    let commands = queue
+        .commandBuffer()
+        .ok_or_else(|| MetalError::OperationFailed("commandBuffer failed".to_string()))?;
+    encode(&commands)?;
+    let start = Instant::now();
+    commands.commit();
+    commands.waitUntilCompleted();
+    if commands.status() != MTLCommandBufferStatus::Completed {
+        return Err(MetalError::OperationFailed(format!(
+            "command buffer ended with status {:?}",
+            commands.status()
+        )));
+    }
+    Ok(start.elapsed())
+}
+
+/// Display of the screen the view's window is on. Must be called on the main thread.
+#[cfg(feature = "raw-window-handle")]
+pub(crate) fn display_id_for_view(
+    ns_view: std::ptr::NonNull<std::ffi::c_void>,
+) -> Option<CGDirectDisplayID> {
+    use objc2_app_kit::NSView;
+    use objc2_foundation::{ns_string, NSNumber};
+
+    let view: &NSView = unsafe { ns_view.cast().as_ref() };
+    let screen = view.window()?.screen()?;
+    Some(
+        screen
+            .deviceDescription()
+            .objectForKey(ns_string!("NSScreenNumber"))?
+            .downcast::<NSNumber>()
+            .ok()?
+            .unsignedIntValue(),
+    )
+}
+
 fn calculate_vram(
     has_unified_memory: bool,
     recommended_max_working_set: u64,
     registry_id: u64,
-) -> u64 {
+) -> Bytes {
     if has_unified_memory {
-        // To MB
-        recommended_max_working_set / (1024 * 1024)
+        Bytes(recommended_max_working_set)
     } else {
-        get_vram_via_iokit(registry_id).unwrap_or(recommended_max_working_set / (1024 * 1024))
+        get_vram_via_iokit(registry_id).unwrap_or(Bytes(recommended_max_working_set))
     }
 }
 
-/// Use iokit to get VRAM size for external gpu
+/// Properties of the IOKit registry entry (the IOAccelerator service) backing a Metal device
 #[allow(deprecated)]
-fn get_vram_via_iokit(registry_id: u64) -> Option<u64> {
-    use objc2_core_foundation::{CFAllocator, CFDictionary, CFNumber, CFString, CFType};
-    use objc2_io_kit::{
-        kIOMasterPortDefault, IOObjectRelease, IORegistryEntryCreateCFProperties,
-        IORegistryEntryIDMatching, IOServiceGetMatchingService,
-    };
+fn get_iokit_properties(registry_id: u64) -> Option<Retained<CFMutableDictionary>> {
+    use objc2_io_kit::IORegistryEntryIDMatching;
 
     let matching = unsafe { IORegistryEntryIDMatching(registry_id) }?;
-
     let matching_cast = matching
         .downcast::<CFDictionary>()
         .expect("Failed to downcast to CFDictionary");
-    let entry = unsafe { IOServiceGetMatchingService(kIOMasterPortDefault, Some(matching_cast)) };
+    get_matching_service_properties(matching_cast)
+}
+
+/// Properties of the first IOKit service of a class, e.g. `H11ANEIn`
+pub(crate) fn get_iokit_service_properties(
+    class: &std::ffi::CStr,
+) -> Option<Retained<CFMutableDictionary>> {
+    use objc2_io_kit::IOServiceMatching;
+
+    let matching = unsafe { IOServiceMatching(class.as_ptr()) }?;
+    get_matching_service_properties(matching.downcast::<CFDictionary>().ok()?)
+}
+
+#[allow(deprecated)]
+fn get_matching_service_properties(
+    matching: CFRetained<CFDictionary>,
+) -> Option<Retained<CFMutableDictionary>> {
+    use objc2_core_foundation::CFAllocator;
+    use objc2_io_kit::{
+        kIOMasterPortDefault, IOObjectRelease, IORegistryEntryCreateCFProperties,
+        IOServiceGetMatchingService,
+    };
+
+    let entry = unsafe { IOServiceGetMatchingService(kIOMasterPortDefault, Some(matching)) };
     if entry == 0 {
         return None;
     }
@@ -177,31 +780,125 @@ fn get_vram_via_iokit(registry_id: u64) -> Option<u64> {
         )
     };
 
-    let vram = if result == 0 && !properties.is_null() {
-        let dict = unsafe { Retained::from_raw(properties) }?;
-        let dict_cast = unsafe { dict.cast_unchecked::<CFString, CFType>() };
-
-        let keys = ["VRAM,totalMB", "VRAM", "VRAM,total"];
-        let mut vram_value = None;
-
-        for key in &keys {
-            let cf_key = CFString::new(key);
-            if let Some(value) = dict_cast.get(&cf_key) {
-                if let Ok(num) = value.downcast::<CFNumber>() {
-                    if let Some(mb) = num.as_i64() {
-                        vram_value = Some(mb as u64);
-                        break;
-                    }
+    if result == 0 && !properties.is_null() {
+        unsafe { Retained::from_raw(properties) }
+    } else {
+        None
+    }
+}
+
+/// Use iokit to get VRAM size for external gpu
+fn get_vram_via_iokit(registry_id: u64) -> Option<Bytes> {
+    let dict = get_iokit_properties(registry_id)?;
+    let dict_cast = unsafe { dict.cast_unchecked::<CFString, CFType>() };
+
+    let keys = ["VRAM,totalMB", "VRAM", "VRAM,total"];
+
+    for key in &keys {
+        let cf_key = CFString::from_str(key);
+        if let Some(value) = dict_cast.get(&cf_key) {
+            if let Ok(num) = value.downcast::<CFNumber>() {
+                if let Some(mb) = num.as_i64() {
+                    return Some(Bytes::from_mb(mb as u64));
                 }
             }
         }
+    }
 
-        vram_value
-    } else {
-        None
+    None
+}
+
+/// Bundle id of the kext driving the device, e.g. `com.apple.AGXG13X`
+/// A process with an open connection to a GPU, see [`gpu_clients`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct GpuClient {
+    pub pid: u32,
+    /// Truncated by the kernel, e.g. `WindowServer`
+    pub process_name: String,
+}
+
+/// Other processes with an open connection (IOKit user client) to the GPU
+/// with this registry id. A hint for capture and streaming tools that another
+/// app, e.g. a full-screen game, competes for the GPU: a connection says
+/// nothing about how busy its client keeps the GPU, and `WindowServer`
+/// always holds one.
+#[allow(deprecated)]
+pub fn gpu_clients(registry_id: u64) -> Result<Vec<GpuClient>, MetalError> {
+    use objc2_io_kit::{
+        kIOMasterPortDefault, kIOServicePlane, IOIteratorNext, IOObjectRelease,
+        IORegistryEntryCreateCFProperty, IORegistryEntryGetChildIterator,
+        IORegistryEntryIDMatching, IOServiceGetMatchingService,
     };
 
-    vram
+    let matching = unsafe { IORegistryEntryIDMatching(registry_id) }
+        .and_then(|matching| matching.downcast::<CFDictionary>().ok())
+        .ok_or_else(|| MetalError::OperationFailed("IORegistryEntryIDMatching".to_string()))?;
+    let accelerator = unsafe { IOServiceGetMatchingService(kIOMasterPortDefault, Some(matching)) };
+    if accelerator == 0 {
+        return Err(MetalError::OperationFailed(format!(
+            "no IOKit service with registry id {registry_id:#x}"
+        )));
+    }
+    scopeguard::defer! {
+        IOObjectRelease(accelerator);
+    }
+
+    let mut plane = [0 as std::ffi::c_char; 128];
+    for (dst, &src) in plane.iter_mut().zip(kIOServicePlane.to_bytes()) {
+        *dst = src as std::ffi::c_char;
+    }
+    let mut iterator = 0;
+    let result = unsafe { IORegistryEntryGetChildIterator(accelerator, &mut plane, &mut iterator) };
+    if result != 0 {
+        return Err(MetalError::OperationFailed(format!(
+            "IORegistryEntryGetChildIterator: {result:#x}"
+        )));
+    }
+    scopeguard::defer! {
+        IOObjectRelease(iterator);
+    }
+
+    let key = CFString::from_str("IOUserClientCreator");
+    let mut clients = Vec::new();
+    loop {
+        let child = IOIteratorNext(iterator);
+        if child == 0 {
+            break;
+        }
+        let creator = unsafe { IORegistryEntryCreateCFProperty(child, Some(&key), None, 0) };
+        IOObjectRelease(child);
+        let Some(client) = creator
+            .and_then(|creator| creator.downcast::<CFString>().ok())
+            .and_then(|creator| parse_user_client_creator(&creator.to_string()))
+        else {
+            continue;
+        };
+        // Every process opening a Metal device holds several user clients
+        if client.pid != std::process::id() && !clients.contains(&client) {
+            clients.push(client);
+        }
+    }
+    Ok(clients)
+}
+
+/// Parse an `IOUserClientCreator` value such as `pid 412, WindowServer`
+fn parse_user_client_creator(creator: &str) -> Option<GpuClient> {
+    let (pid, process_name) = creator.strip_prefix("pid ")?.split_once(", ")?;
+    Some(GpuClient {
+        pid: pid.parse().ok()?,
+        process_name: process_name.to_string(),
+    })
+}
+
+fn get_kext_bundle_id(registry_id: u64) -> Option<String> {
+    let dict = get_iokit_properties(registry_id)?;
+    let dict_cast = unsafe { dict.cast_unchecked::<CFString, CFType>() };
+
+    let value = dict_cast.get(&CFString::from_str("CFBundleIdentifier"))?;
+    let bundle_id = value.downcast::<CFString>().ok()?;
+    Some(bundle_id.to_string())
 }
 
 fn detect_vendor(name: &str) -> String {
@@ -266,4 +963,16 @@ mod tests {
             retrieve_gpu_info_via_metal()
         );
     }
+
+    #[test]
+    fn test_parse_user_client_creator() {
+        assert_eq!(
+            parse_user_client_creator("pid 412, WindowServer"),
+            Some(GpuClient {
+                pid: 412,
+                process_name: "WindowServer".to_string(),
+            })
+        );
+        assert_eq!(parse_user_client_creator("WindowServer"), None);
+    }
 }
@@ -0,0 +1,607 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, SystemTime};
+
+use crate::{
+    EngineClass, Error, GpuEvent, GpuPreference, GpuQuery, MegaHertz, TemperatureLocation, GPU,
+};
+
+/// Samples kept per GPU unless [`GpuMonitorBuilder::history`] says otherwise
+const DEFAULT_HISTORY: usize = 60;
+
+/// How long the engines are watched for the utilization of one sample
+const UTILIZATION_WINDOW: Duration = Duration::from_millis(100);
+
+/// Events queued for [`GpuMonitor::events`] until they are received, later
+/// ones are dropped
+const EVENT_CAPACITY: usize = 64;
+
+/// The values of one GPU that change while it runs, as read by a
+/// [`GpuMonitor`]. `None` where the platform cannot report a value.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GpuSample {
+    pub taken_at: SystemTime,
+    /// Edge temperature in °C
+    pub temperature: Option<f32>,
+    pub clock_speed: Option<MegaHertz>,
+    /// Share of the time the graphics engines were busy, from 0.0 to 1.0
+    pub utilization: Option<f32>,
+    /// Share of the memory budget of the process in use, from 0.0 to 1.0
+    pub vram_usage: Option<f32>,
+}
+
+/// The values a [`GpuMonitor`] reads, so it skips the backend calls of the
+/// others, e.g. NVML queries or the utilization window, on servers with many
+/// GPUs. Values left out are `None` in every [`GpuSample`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SampleFields {
+    telemetry: bool,
+    utilization: bool,
+    vram_usage: bool,
+}
+
+impl Default for SampleFields {
+    fn default() -> Self {
+        Self::all()
+    }
+}
+
+impl SampleFields {
+    /// Nothing, to add fields to with the `with_` methods
+    pub fn none() -> Self {
+        Self {
+            telemetry: false,
+            utilization: false,
+            vram_usage: false,
+        }
+    }
+
+    pub fn all() -> Self {
+        Self::none()
+            .with_telemetry()
+            .with_utilization()
+            .with_vram_usage()
+    }
+
+    /// Temperature and clock, read like [`GpuQuery::with_telemetry`]
+    pub fn with_telemetry(mut self) -> Self {
+        self.telemetry = true;
+        self
+    }
+
+    /// Graphics utilization, which watches the engines for 100 ms per GPU
+    pub fn with_utilization(mut self) -> Self {
+        self.utilization = true;
+        self
+    }
+
+    /// VRAM usage, from the memory budget of the process
+    pub fn with_vram_usage(mut self) -> Self {
+        self.vram_usage = true;
+        self
+    }
+}
+
+/// Lowest, highest and mean value over a run of samples, e.g. to scale a
+/// sparkline
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aggregate {
+    pub min: f32,
+    pub max: f32,
+    pub avg: f32,
+}
+
+impl Aggregate {
+    /// Over the samples that have the value, `None` if none has it:
+    /// `Aggregate::of(&monitor.history(&gpu), |sample| sample.temperature)`
+    pub fn of(samples: &[GpuSample], value: impl Fn(&GpuSample) -> Option<f32>) -> Option<Self> {
+        let mut values = samples.iter().filter_map(value);
+        let first = values.next()?;
+        let (min, max, sum, count) =
+            values.fold((first, first, first, 1), |(min, max, sum, count), value| {
+                (min.min(value), max.max(value), sum + value, count + 1)
+            });
+        Some(Self {
+            min,
+            max,
+            avg: sum / count as f32,
+        })
+    }
+}
+
+/// A threshold a [`GpuMonitor`] checks every sample against, reported as
+/// [`GpuEvent::AlertRaised`] when a value goes above it and
+/// [`GpuEvent::AlertCleared`] once it falls back below it by a margin, so a
+/// value hovering at the threshold does not raise the alert on every other
+/// sample
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Alert {
+    /// Edge temperature in °C, cleared 5 °C below
+    TemperatureAbove(u32),
+    /// [`GpuSample::vram_usage`], from 0.0 to 1.0, cleared 0.05 below
+    VramUsageAbove(f32),
+    /// [`GpuSample::utilization`], from 0.0 to 1.0, cleared 0.05 below
+    UtilizationAbove(f32),
+}
+
+impl Alert {
+    fn value(&self, sample: &GpuSample) -> Option<f32> {
+        match self {
+            Alert::TemperatureAbove(_) => sample.temperature,
+            Alert::VramUsageAbove(_) => sample.vram_usage,
+            Alert::UtilizationAbove(_) => sample.utilization,
+        }
+    }
+
+    fn threshold(&self) -> f32 {
+        match *self {
+            Alert::TemperatureAbove(celsius) => celsius as f32,
+            Alert::VramUsageAbove(share) | Alert::UtilizationAbove(share) => share,
+        }
+    }
+
+    fn hysteresis(&self) -> f32 {
+        match self {
+            Alert::TemperatureAbove(_) => 5.0,
+            Alert::VramUsageAbove(_) | Alert::UtilizationAbove(_) => 0.05,
+        }
+    }
+}
+
+/// Configures a [`GpuMonitor`] before it starts sampling
+#[derive(Debug, Clone)]
+pub struct GpuMonitorBuilder {
+    interval: Duration,
+    query: GpuQuery,
+    fields: SampleFields,
+    history: usize,
+    alerts: Vec<Alert>,
+}
+
+impl GpuMonitorBuilder {
+    /// Enumerate the GPUs to sample with `query`, e.g. to leave out the GPUs
+    /// it excludes
+    pub fn query(mut self, query: GpuQuery) -> Self {
+        self.query = query;
+        self
+    }
+
+    /// Read only `fields`, all of them by default
+    pub fn fields(mut self, fields: SampleFields) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    /// Samples kept per GPU for [`GpuMonitor::history`], 60 by default
+    pub fn history(mut self, len: usize) -> Self {
+        self.history = len;
+        self
+    }
+
+    /// Check every sample of every GPU against `alert`
+    pub fn alert(mut self, alert: Alert) -> Self {
+        self.alerts.push(alert);
+        self
+    }
+
+    /// Enumerates before returning, so its errors surface here. Samples that
+    /// fail later are logged and skipped.
+    pub fn spawn(self) -> Result<GpuMonitor, Error> {
+        let gpus = self.query.run()?;
+        let state = Arc::new(Mutex::new(State {
+            histories: vec![VecDeque::new(); gpus.len()],
+            next_id: 0,
+            sample_callbacks: vec![],
+            event_callbacks: vec![],
+        }));
+        let (sender, events) = mpsc::sync_channel(EVENT_CAPACITY);
+        let (stop, stopped) = mpsc::channel::<()>();
+
+        let thread_gpus = gpus.clone();
+        let thread_state = Arc::clone(&state);
+        // Per GPU, whether each alert is raised
+        let mut raised = vec![vec![false; self.alerts.len()]; gpus.len()];
+        std::thread::spawn(move || loop {
+            for (index, gpu) in thread_gpus.iter().enumerate() {
+                let sample = match take_sample(gpu, self.fields) {
+                    Ok(sample) => sample,
+                    Err(e) => {
+                        log::debug!("skipping sample of {}: {e}", gpu.name);
+                        continue;
+                    }
+                };
+                let events = check_alerts(&self.alerts, &mut raised[index], gpu, &sample);
+                // Called without the lock held, so callbacks can use the monitor
+                let (sample_callbacks, event_callbacks) = {
+                    let mut state = thread_state.lock().unwrap_or_else(|e| e.into_inner());
+                    push_sample(&mut state.histories[index], self.history, sample.clone());
+                    (
+                        state
+                            .sample_callbacks
+                            .iter()
+                            .map(|(_, f)| Arc::clone(f))
+                            .collect::<Vec<_>>(),
+                        state
+                            .event_callbacks
+                            .iter()
+                            .map(|(_, f)| Arc::clone(f))
+                            .collect::<Vec<_>>(),
+                    )
+                };
+                for callback in &sample_callbacks {
+                    callback(gpu, &sample);
+                }
+                for event in events {
+                    for callback in &event_callbacks {
+                        callback(&event);
+                    }
+                    if !send_event(&sender, event) {
+                        return;
+                    }
+                }
+            }
+            if !matches!(
+                stopped.recv_timeout(self.interval),
+                Err(RecvTimeoutError::Timeout)
+            ) {
+                return;
+            }
+        });
+
+        Ok(GpuMonitor {
+            gpus,
+            state,
+            events,
+            _stop: stop,
+        })
+    }
+}
+
+/// Samples the temperature, clock, utilization and VRAM usage of each GPU
+/// on a background thread and keeps the last samples, so UI widgets such as
+/// sparklines do not have to buffer them. The GPUs are enumerated once, when
+/// the monitor starts; a [`DriverWatcher`](crate::DriverWatcher) notices
+/// when they change. The thread stops when the monitor is dropped.
+pub struct GpuMonitor {
+    gpus: Vec<GPU>,
+    state: Arc<Mutex<State>>,
+    events: Receiver<GpuEvent>,
+    // Dropping the sender wakes the thread up to exit
+    _stop: Sender<()>,
+}
+
+type SampleCallback = Arc<dyn Fn(&GPU, &GpuSample) + Send + Sync>;
+type EventCallback = Arc<dyn Fn(&GpuEvent) + Send + Sync>;
+
+struct State {
+    /// Per GPU, in [`GpuMonitor::gpus`] order, oldest first
+    histories: Vec<VecDeque<GpuSample>>,
+    /// Of the next [`Subscription`]
+    next_id: u64,
+    sample_callbacks: Vec<(u64, SampleCallback)>,
+    event_callbacks: Vec<(u64, EventCallback)>,
+}
+
+/// A callback registered on a [`GpuMonitor`], which is removed when this is
+/// dropped
+#[must_use = "the callback is removed when the subscription is dropped"]
+pub struct Subscription {
+    id: u64,
+    state: Weak<Mutex<State>>,
+}
+
+impl Subscription {
+    /// Remove the callback, the same as dropping the subscription
+    pub fn unsubscribe(self) {}
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let Some(state) = self.state.upgrade() else {
+            return;
+        };
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        state.sample_callbacks.retain(|(id, _)| *id != self.id);
+        state.event_callbacks.retain(|(id, _)| *id != self.id);
+    }
+}
+
+impl GpuMonitor {
+    /// Sample every `interval`, starting right away
+    pub fn builder(interval: Duration) -> GpuMonitorBuilder {
+        GpuMonitorBuilder {
+            interval,
+            query: GpuQuery::new(),
+            fields: SampleFields::all(),
+            history: DEFAULT_HISTORY,
+            alerts: vec![],
+        }
+    }
+
+    pub fn spawn(interval: Duration) -> Result<Self, Error> {
+        Self::builder(interval).spawn()
+    }
+
+    /// The sampled GPUs, as enumerated when the monitor started
+    pub fn gpus(&self) -> &[GPU] {
+        &self.gpus
+    }
+
+    /// The last samples of `gpu`, oldest first. GPUs are matched like a
+    /// [`GpuPreference`]; empty for a GPU the monitor does not sample.
+    pub fn history(&self, gpu: &GPU) -> Vec<GpuSample> {
+        let Some(index) = self.index_of(gpu) else {
+            return vec![];
+        };
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.histories[index].iter().cloned().collect()
+    }
+
+    /// Alerts raised and cleared, in the order they were noticed, e.g.
+    /// `for event in monitor.events().try_iter()`. Only the first 64 events
+    /// not received yet are kept.
+    pub fn events(&self) -> &Receiver<GpuEvent> {
+        &self.events
+    }
+
+    /// Call `callback` with each sample of each GPU, on the sampling thread,
+    /// for consumers that do not want to poll, e.g. GUI frameworks with their
+    /// own executor. A slow callback delays the next sample.
+    pub fn on_sample(
+        &self,
+        callback: impl Fn(&GPU, &GpuSample) + Send + Sync + 'static,
+    ) -> Subscription {
+        self.subscribe(|state, id| state.sample_callbacks.push((id, Arc::new(callback))))
+    }
+
+    /// Call `callback` with each event, on the sampling thread. Events are
+    /// still sent to [`events`](Self::events) as well.
+    pub fn on_event(&self, callback: impl Fn(&GpuEvent) + Send + Sync + 'static) -> Subscription {
+        self.subscribe(|state, id| state.event_callbacks.push((id, Arc::new(callback))))
+    }
+
+    fn subscribe(&self, register: impl FnOnce(&mut State, u64)) -> Subscription {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let id = state.next_id;
+        state.next_id += 1;
+        register(&mut state, id);
+        Subscription {
+            id,
+            state: Arc::downgrade(&self.state),
+        }
+    }
+
+    fn index_of(&self, gpu: &GPU) -> Option<usize> {
+        let matched = GpuPreference::new(gpu).resolve(&self.gpus)?;
+        self.gpus.iter().position(|gpu| std::ptr::eq(gpu, matched))
+    }
+}
+
+pub(crate) fn take_sample(gpu: &GPU, fields: SampleFields) -> Result<GpuSample, Error> {
+    // Fails once the GPU is gone, instead of sampling its last values
+    let refreshed = if fields.telemetry {
+        Some(GpuQuery::new().with_telemetry().refresh(gpu)?)
+    } else {
+        None
+    };
+    let utilization = fields
+        .utilization
+        .then(|| crate::engine_utilization(gpu, UTILIZATION_WINDOW).ok())
+        .flatten()
+        .and_then(|classes| {
+            classes
+                .into_iter()
+                .find(|class| class.class == EngineClass::Graphics)
+        })
+        .map(|class| class.busy);
+    let vram_usage = fields
+        .vram_usage
+        .then(|| crate::memory_budget(gpu).ok().flatten())
+        .flatten()
+        .filter(|budget| budget.budget.0 > 0)
+        .map(|budget| budget.usage.0 as f32 / budget.budget.0 as f32);
+    Ok(GpuSample {
+        taken_at: SystemTime::now(),
+        temperature: refreshed
+            .as_ref()
+            .and_then(|gpu| gpu.temperature_at(TemperatureLocation::Edge)),
+        clock_speed: refreshed.and_then(|gpu| gpu.clock_speed),
+        utilization,
+        vram_usage,
+    })
+}
+
+/// Events for the alerts `sample` raises or clears. `raised` holds whether
+/// each alert is raised and is updated. Alerts on values the sample lacks
+/// keep their state.
+fn check_alerts(
+    alerts: &[Alert],
+    raised: &mut [bool],
+    gpu: &GPU,
+    sample: &GpuSample,
+) -> Vec<GpuEvent> {
+    let mut events = vec![];
+    for (alert, raised) in alerts.iter().zip(raised) {
+        let Some(value) = alert.value(sample) else {
+            continue;
+        };
+        if !*raised && value > alert.threshold() {
+            *raised = true;
+            events.push(GpuEvent::AlertRaised {
+                gpu: gpu.clone(),
+                alert: *alert,
+                value,
+            });
+        } else if *raised && value < alert.threshold() - alert.hysteresis() {
+            *raised = false;
+            events.push(GpuEvent::AlertCleared {
+                gpu: gpu.clone(),
+                alert: *alert,
+                value,
+            });
+        }
+    }
+    events
+}
+
+/// Queue `event` for [`GpuMonitor::events`], dropping it when the queue is
+/// full so a monitor nobody receives events from does not grow without
+/// bound. `false` once the monitor is gone.
+fn send_event(sender: &SyncSender<GpuEvent>, event: GpuEvent) -> bool {
+    match sender.try_send(event) {
+        Ok(()) => true,
+        Err(TrySendError::Full(event)) => {
+            log::debug!("dropping monitor event, the queue is full: {event:?}");
+            true
+        }
+        Err(TrySendError::Disconnected(_)) => false,
+    }
+}
+
+/// Append to a ring buffer of at most `len` samples
+fn push_sample(history: &mut VecDeque<GpuSample>, len: usize, sample: GpuSample) {
+    if len == 0 {
+        return;
+    }
+    while history.len() >= len {
+        history.pop_front();
+    }
+    history.push_back(sample);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(temperature: Option<f32>) -> GpuSample {
+        GpuSample {
+            taken_at: SystemTime::now(),
+            temperature,
+            clock_speed: None,
+            utilization: None,
+            vram_usage: None,
+        }
+    }
+
+    #[test]
+    fn test_push_sample() {
+        let mut history = VecDeque::new();
+        for temperature in [50.0, 60.0, 70.0] {
+            push_sample(&mut history, 2, sample(Some(temperature)));
+        }
+        let temperatures: Vec<_> = history.iter().map(|s| s.temperature).collect();
+        assert_eq!(temperatures, [Some(60.0), Some(70.0)]);
+
+        push_sample(&mut history, 0, sample(Some(80.0)));
+        assert_eq!(history.len(), 2);
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let samples = [sample(Some(50.0)), sample(None), sample(Some(70.0))];
+        assert_eq!(
+            Aggregate::of(&samples, |sample| sample.temperature),
+            Some(Aggregate {
+                min: 50.0,
+                max: 70.0,
+                avg: 60.0,
+            })
+        );
+        assert_eq!(Aggregate::of(&samples, |sample| sample.utilization), None);
+        assert_eq!(Aggregate::of(&[], |sample| sample.temperature), None);
+    }
+
+    #[test]
+    fn test_check_alerts() {
+        let gpu = GPU::builder().name("Radeon RX 7600").build();
+        let alerts = [Alert::TemperatureAbove(90), Alert::VramUsageAbove(0.95)];
+        let mut raised = [false; 2];
+        let mut check =
+            |temperature| check_alerts(&alerts, &mut raised, &gpu, &sample(Some(temperature)));
+
+        assert!(check(85.0).is_empty());
+        assert!(matches!(
+            &check(91.0)[..],
+            [GpuEvent::AlertRaised { alert: Alert::TemperatureAbove(90), value, .. }] if *value == 91.0
+        ));
+        // Within the hysteresis, so neither cleared nor raised again
+        assert!(check(89.0).is_empty());
+        assert!(check(92.0).is_empty());
+        assert!(matches!(
+            &check(84.0)[..],
+            [GpuEvent::AlertCleared {
+                alert: Alert::TemperatureAbove(90),
+                ..
+            }]
+        ));
+        // The samples have no VRAM usage to check
+        assert_eq!(raised, [false, false]);
+    }
+
+    #[test]
+    fn test_send_event() {
+        let (sender, events) = mpsc::sync_channel(1);
+        let event = || GpuEvent::AlertRaised {
+            gpu: GPU::default(),
+            alert: Alert::TemperatureAbove(90),
+            value: 91.0,
+        };
+        assert!(send_event(&sender, event()));
+        // Dropped, the queue holds one event
+        assert!(send_event(&sender, event()));
+        assert_eq!(events.try_iter().count(), 1);
+        drop(events);
+        assert!(!send_event(&sender, event()));
+    }
+
+    #[test]
+    fn test_gpu_monitor() {
+        let monitor = GpuMonitor::builder(Duration::from_millis(10))
+            .history(4)
+            .alert(Alert::TemperatureAbove(90))
+            .spawn()
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        for gpu in monitor.gpus() {
+            assert!(monitor.history(gpu).len() <= 4);
+        }
+        assert!(monitor
+            .history(&GPU::builder().name("Absent").build())
+            .is_empty());
+    }
+
+    #[test]
+    fn test_take_sample_fields() {
+        let gpu = GPU::builder().name("Radeon RX 7600").build();
+        let sample = take_sample(&gpu, SampleFields::none()).unwrap();
+        assert_eq!(
+            (
+                sample.temperature,
+                sample.clock_speed,
+                sample.utilization,
+                sample.vram_usage
+            ),
+            (None, None, None, None)
+        );
+    }
+
+    #[test]
+    fn test_subscription() {
+        let monitor = GpuMonitor::spawn(Duration::from_secs(60)).unwrap();
+        let first = monitor.on_sample(|_, _| {});
+        let second = monitor.on_event(|_| {});
+        let callbacks = |monitor: &GpuMonitor| {
+            let state = monitor.state.lock().unwrap();
+            (state.sample_callbacks.len(), state.event_callbacks.len())
+        };
+        assert_eq!(callbacks(&monitor), (1, 1));
+        first.unsubscribe();
+        assert_eq!(callbacks(&monitor), (0, 1));
+        drop(second);
+        assert_eq!(callbacks(&monitor), (0, 0));
+    }
+}
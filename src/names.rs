@@ -0,0 +1,192 @@
+use crate::GPU;
+
+/// Wrappers Mesa and older Gallium drivers put before the renderer name
+const RENDERER_PREFIXES: &[&str] = &["Mesa DRI ", "Mesa ", "DRI "];
+
+/// Bus and instruction set tags NVIDIA's OpenGL driver appends, and the
+/// suffix of macOS OpenGL renderers
+const RENDERER_SUFFIXES: &[&str] = &["/PCIe/SSE2", "/PCI/SSE2", "/SSE2", " OpenGL Engine"];
+
+impl GPU {
+    /// The marketing name without driver noise, e.g. "AMD Radeon RX 7900
+    /// XTX" for "AMD Radeon RX 7900 XTX (RADV NAVI31)" from Vulkan on Linux
+    /// or "Intel Iris Xe Graphics" for "Intel(R) Iris(R) Xe Graphics".
+    /// Drops trademark signs, parenthesized codenames and driver versions,
+    /// "Series", a repeated vendor and Mesa wrappers. Deterministic, so
+    /// suited for grouping GPUs reported by different APIs and drivers.
+    pub fn normalized_name(&self) -> String {
+        normalize_name(&self.name)
+    }
+
+    /// The name with the [`GPU::instance_index`] and PCI address of GPUs
+    /// sharing it, e.g. "NVIDIA GeForce RTX 4090 #2 @ 0000:41:00.0", so
+    /// pickers do not list identical entries. Just the name otherwise.
+    pub fn display_name(&self) -> String {
+        match (self.instance_index, self.pci_address) {
+            (Some(index), Some(address)) => format!("{} #{index} @ {address}", self.name),
+            (Some(index), None) => format!("{} #{index}", self.name),
+            (None, _) => self.name.clone(),
+        }
+    }
+}
+
+/// Number the GPUs sharing a normalized name by PCI address, then LUID and
+/// enumeration order for GPUs without one
+pub(crate) fn assign_instance_indices(gpus: &mut [GPU]) {
+    let names: Vec<String> = gpus.iter().map(GPU::normalized_name).collect();
+    for index in 0..gpus.len() {
+        let mut same_name: Vec<usize> = (0..gpus.len())
+            .filter(|&other| names[other] == names[index])
+            .collect();
+        gpus[index].instance_index = if same_name.len() < 2 {
+            None
+        } else {
+            same_name.sort_by_key(|&other| (gpus[other].pci_address, gpus[other].luid, other));
+            same_name
+                .iter()
+                .position(|&other| other == index)
+                .map(|position| position as u32 + 1)
+        };
+    }
+}
+
+pub(crate) fn normalize_name(name: &str) -> String {
+    let mut name = name.trim();
+    if let Some(rest) = RENDERER_PREFIXES
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+    {
+        name = rest;
+    }
+    // e.g. "Gallium 0.4 on AMD HAWAII"
+    if let Some((_, rest)) = name
+        .strip_prefix("Gallium ")
+        .and_then(|rest| rest.split_once(" on "))
+    {
+        name = rest;
+    }
+    if let Some(rest) = RENDERER_SUFFIXES
+        .iter()
+        .find_map(|suffix| name.strip_suffix(suffix))
+    {
+        name = rest;
+    }
+
+    // Parentheses hold trademarks, codenames and driver versions. "Intel(R)
+    // Iris(R)" keeps its words apart, an unclosed one is truncated noise.
+    let mut cleaned = String::with_capacity(name.len());
+    let mut depth = 0;
+    for c in name.chars() {
+        match c {
+            '(' => {
+                depth += 1;
+                cleaned.push(' ');
+            }
+            ')' if depth > 0 => depth -= 1,
+            '™' | '®' => cleaned.push(' '),
+            _ if depth == 0 => cleaned.push(c),
+            _ => {}
+        }
+    }
+
+    let mut words: Vec<&str> = cleaned
+        .split_whitespace()
+        .filter(|word| !word.eq_ignore_ascii_case("series"))
+        .collect();
+    // e.g. "AMD AMD Radeon" from drivers that prefix the vendor themselves
+    while words.len() > 1 && words[0].eq_ignore_ascii_case(words[1]) {
+        words.remove(0);
+    }
+    if words.is_empty() {
+        return name.to_string();
+    }
+    words.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PciAddress;
+
+    /// Renderer and adapter names as reported by users' systems
+    const CORPUS: &[(&str, &str)] = &[
+        ("NVIDIA GeForce RTX 4090", "NVIDIA GeForce RTX 4090"),
+        (
+            "NVIDIA GeForce GTX 1080/PCIe/SSE2",
+            "NVIDIA GeForce GTX 1080",
+        ),
+        ("NVIDIA NVIDIA GeForce RTX 3060", "NVIDIA GeForce RTX 3060"),
+        (
+            "AMD Radeon RX 7900 XTX (RADV NAVI31)",
+            "AMD Radeon RX 7900 XTX",
+        ),
+        (
+            "AMD Radeon RX 6700 XT (radeonsi, navi22, LLVM 15.0.7, DRM 3.54, 6.5.0-14-generic)",
+            "AMD Radeon RX 6700 XT",
+        ),
+        ("AMD Radeon(TM) Graphics", "AMD Radeon Graphics"),
+        ("AMD AMD Radeon RX 580 Series", "AMD Radeon RX 580"),
+        ("Radeon RX 580 Series", "Radeon RX 580"),
+        (
+            "Gallium 0.4 on AMD HAWAII (DRM 2.50.0, LLVM 3.9.1)",
+            "AMD HAWAII",
+        ),
+        ("Intel(R) Iris(R) Xe Graphics", "Intel Iris Xe Graphics"),
+        ("Intel(R) Arc(TM) A770 Graphics", "Intel Arc A770 Graphics"),
+        (
+            "Mesa Intel(R) UHD Graphics 620 (KBL GT2)",
+            "Intel UHD Graphics 620",
+        ),
+        (
+            "Mesa DRI Intel(R) HD Graphics 4000 (IVB GT2)",
+            "Intel HD Graphics 4000",
+        ),
+        ("Intel® UHD Graphics 770", "Intel UHD Graphics 770"),
+        ("AMD Radeon Pro 5500M OpenGL Engine", "AMD Radeon Pro 5500M"),
+        ("llvmpipe (LLVM 15.0.6, 256 bits)", "llvmpipe"),
+        ("  Apple M2 Max  ", "Apple M2 Max"),
+        (
+            "Microsoft Basic Render Driver",
+            "Microsoft Basic Render Driver",
+        ),
+        ("(RADV NAVI21)", "(RADV NAVI21)"),
+    ];
+
+    #[test]
+    fn test_normalize_name() {
+        for (raw, normalized) in CORPUS {
+            assert_eq!(normalize_name(raw), *normalized, "for {raw:?}");
+        }
+        let gpu = GPU::builder().name("Intel(R) UHD Graphics 630").build();
+        assert_eq!(gpu.normalized_name(), "Intel UHD Graphics 630");
+    }
+
+    #[test]
+    fn test_assign_instance_indices() {
+        let gpu = |name: &str, address: &str| {
+            GPU::builder()
+                .name(name)
+                .pci_address(address.parse::<PciAddress>().unwrap())
+                .build()
+        };
+        // Enumerated out of slot order, one reported with trademark signs
+        let mut gpus = vec![
+            gpu("NVIDIA GeForce RTX 4090", "0000:41:00.0"),
+            gpu("Intel(R) UHD Graphics 770", "0000:00:02.0"),
+            gpu("NVIDIA(R) GeForce RTX 4090", "0000:01:00.0"),
+        ];
+        assign_instance_indices(&mut gpus);
+
+        assert_eq!(gpus[0].instance_index, Some(2));
+        assert!(gpus[1].instance_index.is_none());
+        assert_eq!(gpus[2].instance_index, Some(1));
+        assert_eq!(
+            gpus[0].display_name(),
+            "NVIDIA GeForce RTX 4090 #2 @ 0000:41:00.0"
+        );
+        assert_eq!(gpus[1].display_name(), "Intel(R) UHD Graphics 770");
+
+        gpus[0].pci_address = None;
+        assert_eq!(gpus[0].display_name(), "NVIDIA GeForce RTX 4090 #2");
+    }
+}
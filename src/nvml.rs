@@ -0,0 +1,514 @@
+use nvml_wrapper::enum_wrappers::device::{Brand, Clock, TemperatureSensor};
+use nvml_wrapper::enums::device::{FanControlPolicy, SampleValue};
+use nvml_wrapper::structs::device::FieldId;
+use nvml_wrapper::{Device, Nvml};
+use nvml_wrapper_sys::bindings::{
+    field_id::NVML_FI_DEV_MEMORY_TEMP, nvmlDevice_t, nvmlReturn_enum_NVML_SUCCESS, NvmlLib,
+};
+
+#[cfg(feature = "control")]
+use crate::control::{
+    check_range, not_supported, ControlAction, ControlBackend, ControlCapabilities, ControlError,
+};
+use crate::{
+    Bytes, GpuPartition, MegaHertz, OverclockSetting, PciAddress, ProductSegment,
+    TemperatureLocation, Watts, GPU,
+};
+
+#[cfg(windows)]
+const NVML_LIB: &str = "nvml.dll";
+#[cfg(not(windows))]
+const NVML_LIB: &str = "libnvidia-ml.so.1";
+
+#[derive(Debug, thiserror::Error)]
+pub enum NvmlError {
+    #[error("NVML is not available: {0}")]
+    NotSupported(String),
+    #[error("Failed to perform NVML operation: {0}")]
+    OperationFailed(#[from] nvml_wrapper::error::NvmlError),
+}
+
+impl NvmlError {
+    pub fn is_not_supported(&self) -> bool {
+        matches!(self, NvmlError::NotSupported(_))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NvmlGpu {
+    pub name: String,
+    pub uuid: String,
+    pub pci_address: Option<PciAddress>,
+    pub memory_total: Bytes,
+    pub mig_enabled: bool,
+    pub mig_instances: Vec<GpuPartition>,
+    /// Power limits in watts, `None` on GPUs without power management
+    pub tdp_watts: Option<u32>,
+    pub min_power_limit: Option<u32>,
+    pub max_power_limit: Option<u32>,
+    /// NVML's brand, e.g. `GeForceRTX` or `Tesla`
+    pub brand: Option<String>,
+    /// Product line the brand belongs to, e.g. `GeForce` for `GeForceRTX`
+    pub product_line: Option<(&'static str, ProductSegment)>,
+    pub temperature: Option<u32>, // °C
+    /// Memory junction in °C, only reported for HBM and GDDR6X boards
+    pub memory_temperature: Option<u32>,
+    /// Current graphics clock in MHz
+    pub clock_speed: Option<u32>,
+    /// Every clock, power and fan setting NVML could read, changed or not
+    pub overclock_settings: Vec<OverclockSetting>,
+}
+
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", err))]
+pub fn retrieve_gpu_info_via_nvml() -> Result<Vec<NvmlGpu>, NvmlError> {
+    let nvml = Nvml::init().map_err(|e| NvmlError::NotSupported(e.to_string()))?;
+    // nvml-wrapper does not wrap the MIG handle functions
+    let lib =
+        unsafe { NvmlLib::new(NVML_LIB) }.map_err(|e| NvmlError::NotSupported(e.to_string()))?;
+    trace_debug!(library = NVML_LIB, "nvml loaded");
+
+    let mut gpus = Vec::new();
+
+    for index in 0..nvml.device_count()? {
+        let device = nvml.device_by_index(index)?;
+        // NVML reports an 8 digit domain, e.g. `00000000:41:00.0`
+        let pci_address = device
+            .pci_info()
+            .ok()
+            .and_then(|info| info.bus_id.parse().ok());
+        let mig_enabled = device.mig_mode().is_ok_and(|mode| mode.current == 1);
+        // NVML reports power in milliwatts
+        let constraints = device.power_management_limit_constraints().ok();
+        let brand = device.brand().ok();
+        trace_debug!(index, mig_enabled, "found nvml device");
+
+        gpus.push(NvmlGpu {
+            name: device.name()?,
+            uuid: device.uuid()?,
+            pci_address,
+            memory_total: Bytes(device.memory_info()?.total),
+            mig_enabled,
+            mig_instances: if mig_enabled {
+                retrieve_mig_instances(&nvml, &lib, &device)
+            } else {
+                vec![]
+            },
+            tdp_watts: device
+                .power_management_limit_default()
+                .ok()
+                .map(|limit| limit / 1000),
+            min_power_limit: constraints.as_ref().map(|c| c.min_limit / 1000),
+            max_power_limit: constraints.as_ref().map(|c| c.max_limit / 1000),
+            brand: brand.map(|brand| format!("{brand:?}")),
+            product_line: brand.and_then(product_line),
+            temperature: device.temperature(TemperatureSensor::Gpu).ok(),
+            memory_temperature: memory_temperature(&device),
+            clock_speed: device.clock_info(Clock::Graphics).ok(),
+            overclock_settings: read_overclock_settings(&device),
+        });
+    }
+
+    Ok(gpus)
+}
+
+/// nvml-wrapper has no getter for the memory temperature, it is a field value
+fn memory_temperature(device: &Device) -> Option<u32> {
+    let sample = device
+        .field_values_for(&[FieldId(NVML_FI_DEV_MEMORY_TEMP)])
+        .ok()?
+        .pop()?
+        .ok()?;
+    match sample.value.ok()? {
+        SampleValue::U32(celsius) => Some(celsius),
+        SampleValue::U64(celsius) => u32::try_from(celsius).ok(),
+        SampleValue::I64(celsius) => u32::try_from(celsius).ok(),
+        SampleValue::F64(celsius) => Some(celsius.round() as u32),
+    }
+    // 0 on boards without a memory sensor
+    .filter(|&celsius| celsius > 0)
+}
+
+fn temperature_sensors(
+    temperature: Option<u32>,
+    memory_temperature: Option<u32>,
+) -> Vec<crate::TemperatureSensor> {
+    crate::temperature_sensors(
+        [
+            (TemperatureLocation::Edge, temperature),
+            (TemperatureLocation::Memory, memory_temperature),
+        ]
+        .map(|(location, celsius)| (location, celsius.map(|celsius| celsius as f32))),
+    )
+}
+
+/// Virtual GPU brands are the licensed vGPU editions
+fn product_line(brand: Brand) -> Option<(&'static str, ProductSegment)> {
+    Some(match brand {
+        Brand::GeForce | Brand::GeForceRTX => ("GeForce", ProductSegment::Consumer),
+        Brand::Titan | Brand::TitanRTX => ("Titan", ProductSegment::Consumer),
+        Brand::Quadro | Brand::QuadroRTX => ("Quadro", ProductSegment::Workstation),
+        Brand::NvidiaRTX => ("RTX", ProductSegment::Workstation),
+        Brand::NVS => ("NVS", ProductSegment::Workstation),
+        Brand::Tesla => ("Tesla", ProductSegment::Datacenter),
+        Brand::Nvidia => ("NVIDIA", ProductSegment::Datacenter),
+        Brand::VWS => ("NVIDIA vWS", ProductSegment::Workstation),
+        Brand::GRID
+        | Brand::VApps
+        | Brand::VPC
+        | Brand::VCS
+        | Brand::CloudGaming
+        | Brand::VGaming => ("GRID", ProductSegment::Datacenter),
+        Brand::Unknown => return None,
+    })
+}
+
+/// Application clocks are set on datacenter GPUs, clock offsets on GeForce
+/// ones, each unsupported on the other
+fn read_overclock_settings(device: &Device) -> Vec<OverclockSetting> {
+    let application_clock = |clock: Clock| {
+        let value = device.applications_clock(clock).ok()?;
+        let default = device.default_applications_clock(clock).ok()?;
+        Some((MegaHertz(value), MegaHertz(default)))
+    };
+    let power_limit = device
+        .power_management_limit()
+        .ok()
+        .zip(device.power_management_limit_default().ok());
+    let fans = (0..device.num_fans().unwrap_or(0))
+        .filter(|&fan| matches!(device.fan_control_policy(fan), Ok(FanControlPolicy::Manual)))
+        .map(OverclockSetting::ManualFan);
+
+    [
+        application_clock(Clock::Graphics)
+            .map(|(value, default)| OverclockSetting::GraphicsClock { value, default }),
+        application_clock(Clock::Memory)
+            .map(|(value, default)| OverclockSetting::MemoryClock { value, default }),
+        device
+            .gpc_clock_vf_offset()
+            .ok()
+            .map(OverclockSetting::GraphicsClockOffset),
+        device
+            .mem_clock_vf_offset()
+            .ok()
+            .map(OverclockSetting::MemoryClockOffset),
+        // Milliwatts
+        power_limit.map(|(value, default)| OverclockSetting::PowerLimit {
+            value: Watts(value / 1000),
+            default: Watts(default / 1000),
+        }),
+    ]
+    .into_iter()
+    .flatten()
+    .chain(fans)
+    .collect()
+}
+
+fn retrieve_mig_instances(nvml: &Nvml, lib: &NvmlLib, device: &Device) -> Vec<GpuPartition> {
+    // Drivers before R450 do not export the MIG functions at all
+    let (Ok(max_count), Ok(by_index)) = (
+        lib.nvmlDeviceGetMaxMigDeviceCount.as_ref(),
+        lib.nvmlDeviceGetMigDeviceHandleByIndex.as_ref(),
+    ) else {
+        return vec![];
+    };
+
+    let parent = unsafe { device.handle() };
+    let mut count = 0;
+    if unsafe { max_count(parent, &mut count) } != nvmlReturn_enum_NVML_SUCCESS {
+        return vec![];
+    }
+
+    let mut instances = Vec::new();
+
+    for index in 0..count {
+        let mut handle: nvmlDevice_t = std::ptr::null_mut();
+        // Unpopulated slots return NVML_ERROR_NOT_FOUND
+        if unsafe { by_index(parent, index, &mut handle) } != nvmlReturn_enum_NVML_SUCCESS {
+            continue;
+        }
+        let instance = unsafe { Device::new(handle, nvml) };
+        let Ok(uuid) = instance.uuid() else {
+            continue;
+        };
+        instances.push(GpuPartition {
+            id: uuid,
+            memory: instance
+                .memory_info()
+                .map(|info| Bytes(info.total))
+                .unwrap_or_default(),
+            compute_units: instance
+                .attributes()
+                .ok()
+                .map(|attributes| attributes.multiprocessor_count),
+        });
+    }
+
+    instances
+}
+
+/// Merge NVML data into GPUs found by the graphics APIs, matched by PCI address or UUID
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+pub(crate) fn attach_nvml_info(mut gpus: Vec<GPU>) -> Vec<GPU> {
+    let nvml_gpus = match retrieve_gpu_info_via_nvml() {
+        Ok(nvml_gpus) => nvml_gpus,
+        Err(e) => {
+            log::debug!("skipping nvml: {e}");
+            return gpus;
+        }
+    };
+
+    for gpu in &mut gpus {
+        let Some(nvml_gpu) = nvml_gpus.iter().find(|nvml_gpu| {
+            (gpu.pci_address.is_some() && gpu.pci_address == nvml_gpu.pci_address)
+                || gpu
+                    .uuid
+                    .as_ref()
+                    .is_some_and(|uuid| nvml_gpu.uuid.trim_start_matches("GPU-") == uuid)
+        }) else {
+            continue;
+        };
+
+        #[cfg(feature = "extra")]
+        if let Some(brand) = &nvml_gpu.brand {
+            gpu.extra
+                .insert("nvml.brand".to_string(), brand.clone().into());
+        }
+        gpu.temperatures = temperature_sensors(nvml_gpu.temperature, nvml_gpu.memory_temperature);
+        gpu.clock_speed = nvml_gpu.clock_speed.map(MegaHertz);
+        gpu.tdp_watts = nvml_gpu.tdp_watts.map(Watts);
+        gpu.min_power_limit = nvml_gpu.min_power_limit.map(Watts);
+        gpu.max_power_limit = nvml_gpu.max_power_limit.map(Watts);
+        if let Some((line, segment)) = nvml_gpu.product_line {
+            gpu.product_line = Some(line.to_string());
+            gpu.product_segment = Some(segment);
+        }
+        crate::overclock::apply_overclock_settings(gpu, nvml_gpu.overclock_settings.clone());
+
+        if nvml_gpu.mig_enabled {
+            gpu.partition_mode = Some("MIG".to_string());
+            gpu.partitions = nvml_gpu.mig_instances.clone();
+        }
+    }
+
+    gpus
+}
+
+/// VRAM the calling process uses on one NVIDIA GPU, in bytes
+pub(crate) fn self_vram_usage_via_nvml(gpu: &GPU) -> Option<u64> {
+    use nvml_wrapper::enums::device::UsedGpuMemory;
+
+    if gpu.vendor != "NVIDIA" {
+        return None;
+    }
+    let nvml = Nvml::init().ok()?;
+    let device = find_device(&nvml, gpu)?.ok()?;
+
+    // A process using both graphics and compute is listed twice with the
+    // same allocations
+    let pid = std::process::id();
+    [
+        device.running_compute_processes(),
+        device.running_graphics_processes(),
+    ]
+    .into_iter()
+    .flatten()
+    .flatten()
+    .find(|process| process.pid == pid)
+    .and_then(|process| match process.used_gpu_memory {
+        UsedGpuMemory::Used(bytes) => Some(bytes),
+        UsedGpuMemory::Unavailable => None,
+    })
+}
+
+/// Load of the shader cores and the video engines of one NVIDIA GPU, as
+/// averaged by the driver over its last sample period. Windows reads the
+/// same from the kernel graphics statistics for every vendor.
+#[cfg(target_os = "linux")]
+pub(crate) fn engine_utilization_via_nvml(gpu: &GPU) -> Option<Vec<crate::EngineClassUtilization>> {
+    use crate::EngineClass;
+
+    if gpu.vendor != "NVIDIA" {
+        return None;
+    }
+    let nvml = Nvml::init().ok()?;
+    let device = find_device(&nvml, gpu)?.ok()?;
+
+    let percent = |percent: u32| percent.min(100) as f32 / 100.0;
+    Some(crate::utilization_by_class(
+        [
+            (
+                EngineClass::Graphics,
+                device.utilization_rates().map(|u| u.gpu),
+            ),
+            (
+                EngineClass::VideoEncode,
+                device.encoder_utilization().map(|u| u.utilization),
+            ),
+            (
+                EngineClass::VideoDecode,
+                device.decoder_utilization().map(|u| u.utilization),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(class, utilization)| Some((class, percent(utilization.ok()?)))),
+    ))
+}
+
+/// Re-read the temperature and clock of one NVIDIA GPU
+pub(crate) fn refresh_nvml_info(gpu: &mut GPU) {
+    if gpu.vendor != "NVIDIA" {
+        return;
+    }
+    let Ok(nvml) = Nvml::init() else {
+        return;
+    };
+    let Some(device) = find_device(&nvml, gpu) else {
+        return;
+    };
+    match device {
+        Ok(device) => {
+            gpu.temperatures = temperature_sensors(
+                device.temperature(TemperatureSensor::Gpu).ok(),
+                memory_temperature(&device),
+            );
+            gpu.clock_speed = device.clock_info(Clock::Graphics).ok().map(MegaHertz);
+        }
+        Err(e) => log::debug!("skipping nvml refresh of {}: {e}", gpu.name),
+    }
+}
+
+/// The NVML device of a GPU, by UUID or PCI address
+fn find_device<'n>(
+    nvml: &'n Nvml,
+    gpu: &GPU,
+) -> Option<Result<Device<'n>, nvml_wrapper::error::NvmlError>> {
+    Some(match (&gpu.uuid, gpu.pci_address) {
+        (Some(uuid), _) => nvml.device_by_uuid(format!("GPU-{uuid}")),
+        (None, Some(address)) => nvml.device_by_pci_bus_id(address.to_string()),
+        (None, None) => return None,
+    })
+}
+
+#[cfg(feature = "control")]
+pub(crate) fn nvml_control_capabilities(gpu: &GPU) -> Result<ControlCapabilities, ControlError> {
+    let nvml = init_control()?;
+    Ok(control_capabilities(&control_device(&nvml, gpu)?))
+}
+
+/// Set the power limit, or restore the default one for `None`
+#[cfg(feature = "control")]
+pub(crate) fn nvml_set_power_limit(
+    gpu: &GPU,
+    limit: Option<Watts>,
+    dry_run: bool,
+) -> Result<ControlAction, ControlError> {
+    let nvml = init_control()?;
+    let mut device = control_device(&nvml, gpu)?;
+    // Compared in milliwatts, as the range need not be whole watts
+    let Ok(constraints) = device.power_management_limit_constraints() else {
+        return Err(not_supported("power limit"));
+    };
+    let milliwatts = match limit {
+        Some(limit) => {
+            let milliwatts = limit.0.saturating_mul(1000);
+            if !(constraints.min_limit..=constraints.max_limit).contains(&milliwatts) {
+                return Err(ControlError::OutOfRange {
+                    value: limit.0,
+                    min: constraints.min_limit.div_ceil(1000),
+                    max: constraints.max_limit / 1000,
+                });
+            }
+            milliwatts
+        }
+        None => device
+            .power_management_limit_default()
+            .map_err(|_| not_supported("default power limit"))?,
+    };
+    if !dry_run {
+        device
+            .set_power_management_limit(milliwatts)
+            .map_err(|e| control_error("set the power limit", e))?;
+    }
+    Ok(ControlAction {
+        backend: ControlBackend::Nvml,
+        description: format!("power limit = {milliwatts} mW"),
+        applied: !dry_run,
+    })
+}
+
+/// Run every fan at `percent`, or hand them back to the driver for `None`
+#[cfg(feature = "control")]
+pub(crate) fn nvml_set_fan_speed(
+    gpu: &GPU,
+    percent: Option<u32>,
+    dry_run: bool,
+) -> Result<ControlAction, ControlError> {
+    let nvml = init_control()?;
+    let mut device = control_device(&nvml, gpu)?;
+    let Some(fans) = device.num_fans().ok().filter(|&fans| fans > 0) else {
+        return Err(not_supported("fan control"));
+    };
+    if let (Some(percent), Ok((min, max))) = (percent, device.min_max_fan_speed()) {
+        check_range(percent, min, max)?;
+    }
+    if !dry_run {
+        for fan in 0..fans {
+            match percent {
+                Some(percent) => device.set_fan_speed(fan, percent),
+                None => device.set_default_fan_speed(fan),
+            }
+            .map_err(|e| control_error("set the fan speed", e))?;
+        }
+    }
+    Ok(ControlAction {
+        backend: ControlBackend::Nvml,
+        description: match percent {
+            Some(percent) => format!("speed of {fans} fans = {percent}%"),
+            None => format!("speed of {fans} fans = automatic"),
+        },
+        applied: !dry_run,
+    })
+}
+
+#[cfg(feature = "control")]
+fn init_control() -> Result<Nvml, ControlError> {
+    Nvml::init().map_err(|e| ControlError::NotSupported(format!("NVML ({e})")))
+}
+
+#[cfg(feature = "control")]
+fn control_device<'n>(nvml: &'n Nvml, gpu: &GPU) -> Result<Device<'n>, ControlError> {
+    find_device(nvml, gpu)
+        .ok_or_else(|| not_supported("NVML without a UUID or PCI address"))?
+        .map_err(|e| control_error("find the NVML device", e))
+}
+
+/// NVML only tells whether the process has the rights by trying
+#[cfg(feature = "control")]
+fn control_capabilities(device: &Device) -> ControlCapabilities {
+    // Milliwatts
+    let constraints = device.power_management_limit_constraints().ok();
+    ControlCapabilities {
+        backend: Some(ControlBackend::Nvml),
+        // The whole watts within the range
+        power_limit_range: constraints
+            .map(|c| (Watts(c.min_limit.div_ceil(1000)), Watts(c.max_limit / 1000))),
+        default_power_limit: device
+            .power_management_limit_default()
+            .ok()
+            .map(|limit| Watts(limit / 1000)),
+        fan_speed: device.num_fans().is_ok_and(|fans| fans > 0),
+        fan_curve: false,
+        privileged: None,
+    }
+}
+
+#[cfg(feature = "control")]
+fn control_error(action: &str, e: nvml_wrapper::error::NvmlError) -> ControlError {
+    use nvml_wrapper::error::NvmlError;
+
+    match e {
+        NvmlError::NoPermission => ControlError::PermissionDenied(action.to_string()),
+        NvmlError::NotSupported => ControlError::NotSupported(action.to_string()),
+        e => ControlError::OperationFailed(format!("{action}: {e}")),
+    }
+}
@@ -0,0 +1,138 @@
+use crate::{MegaHertz, Watts, GPU};
+
+/// A clock, voltage, power or fan setting changed from its default, see
+/// [`GPU::overclock_settings`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub enum OverclockSetting {
+    /// NVML application clock of the graphics engine
+    GraphicsClock {
+        value: MegaHertz,
+        default: MegaHertz,
+    },
+    /// NVML application clock of the memory
+    MemoryClock {
+        value: MegaHertz,
+        default: MegaHertz,
+    },
+    /// Offset added to the graphics clock curve, in MHz
+    GraphicsClockOffset(i32),
+    /// Offset added to the memory clock, in MHz
+    MemoryClockOffset(i32),
+    /// Offset added to the GPU voltage, in mV
+    VoltageOffset(i32),
+    PowerLimit {
+        value: Watts,
+        default: Watts,
+    },
+    /// A fan, by index, following a manual speed or custom curve instead of
+    /// the driver's
+    ManualFan(u32),
+}
+
+impl OverclockSetting {
+    /// Whether the setting runs the GPU faster or hotter than stock. Lower
+    /// limits and negative offsets, i.e. undervolting, do not count.
+    pub fn is_overclock(&self) -> bool {
+        match *self {
+            OverclockSetting::GraphicsClock { value, default }
+            | OverclockSetting::MemoryClock { value, default } => value > default,
+            OverclockSetting::GraphicsClockOffset(offset)
+            | OverclockSetting::MemoryClockOffset(offset)
+            | OverclockSetting::VoltageOffset(offset) => offset > 0,
+            OverclockSetting::PowerLimit { value, default } => value > default,
+            OverclockSetting::ManualFan(_) => false,
+        }
+    }
+
+    #[cfg_attr(
+        not(any(
+            target_os = "linux",
+            all(feature = "nvml", not(target_os = "macos")),
+            all(feature = "igcl", windows)
+        )),
+        allow(dead_code)
+    )]
+    fn is_default(&self) -> bool {
+        match *self {
+            OverclockSetting::GraphicsClock { value, default }
+            | OverclockSetting::MemoryClock { value, default } => value == default,
+            OverclockSetting::GraphicsClockOffset(offset)
+            | OverclockSetting::MemoryClockOffset(offset)
+            | OverclockSetting::VoltageOffset(offset) => offset == 0,
+            OverclockSetting::PowerLimit { value, default } => value == default,
+            OverclockSetting::ManualFan(_) => false,
+        }
+    }
+}
+
+/// Set [`GPU::overclock_settings`] and [`GPU::is_overclocked`] from every
+/// setting a backend could read, changed or not. Leaves both untouched when
+/// none could be read.
+#[cfg_attr(
+    not(any(
+        target_os = "linux",
+        all(feature = "nvml", not(target_os = "macos")),
+        all(feature = "igcl", windows)
+    )),
+    allow(dead_code)
+)]
+pub(crate) fn apply_overclock_settings(gpu: &mut GPU, settings: Vec<OverclockSetting>) {
+    if settings.is_empty() {
+        return;
+    }
+    gpu.overclock_settings = settings
+        .into_iter()
+        .filter(|setting| !setting.is_default())
+        .collect();
+    gpu.is_overclocked = Some(
+        gpu.overclock_settings
+            .iter()
+            .any(OverclockSetting::is_overclock),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_overclock_settings() {
+        let mut gpu = GPU::default();
+        apply_overclock_settings(&mut gpu, vec![]);
+        assert_eq!(gpu.is_overclocked, None);
+
+        apply_overclock_settings(
+            &mut gpu,
+            vec![
+                OverclockSetting::GraphicsClockOffset(0),
+                OverclockSetting::PowerLimit {
+                    value: Watts(250),
+                    default: Watts(320),
+                },
+                OverclockSetting::ManualFan(0),
+            ],
+        );
+        assert_eq!(gpu.is_overclocked, Some(false));
+        assert_eq!(gpu.overclock_settings.len(), 2);
+
+        apply_overclock_settings(
+            &mut gpu,
+            vec![
+                OverclockSetting::GraphicsClockOffset(150),
+                OverclockSetting::VoltageOffset(-50),
+            ],
+        );
+        assert_eq!(gpu.is_overclocked, Some(true));
+        assert_eq!(
+            gpu.overclock_settings,
+            vec![
+                OverclockSetting::GraphicsClockOffset(150),
+                OverclockSetting::VoltageOffset(-50),
+            ]
+        );
+    }
+}
@@ -0,0 +1,917 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::vm::paravirtual_adapter;
+use crate::vulkan::{retrieve_vulkan_gpus, VulkanGpu};
+use crate::{
+    vendor_from_id, Bytes, EngineClass, EngineClassUtilization, GPUKind, GpuQuery, MegaHertz,
+    OverclockSetting, PciAddress, TemperatureLocation, TemperatureSensor, Watts, GPU,
+};
+
+pub(crate) const SYSFS_PCI_DEVICES: &str = "/sys/bus/pci/devices";
+const PCI_IDS_PATHS: &[&str] = &[
+    "/usr/share/hwdata/pci.ids",
+    "/usr/share/misc/pci.ids",
+    "/usr/share/pci.ids",
+];
+
+/// PCI base class of display controllers (VGA, XGA, 3D and other)
+const PCI_CLASS_DISPLAY: u32 = 0x03;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PciError {
+    #[error("sysfs PCI enumeration is not available")]
+    NotSupported,
+    #[error("Failed to read sysfs: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl PciError {
+    pub fn is_not_supported(&self) -> bool {
+        matches!(self, PciError::NotSupported)
+    }
+}
+
+/// A display controller found on the PCI bus, whether or not a driver claims it
+#[derive(Debug, Clone)]
+pub struct PciGpu {
+    pub address: PciAddress,
+    pub name: String,
+    pub vendor: String,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    pub subsystem_vendor_id: u32,
+    pub subsystem_device_id: u32,
+    pub class: u32,
+    /// Kernel driver bound to the device, `None` when no driver is loaded
+    pub driver: Option<String>,
+}
+
+impl From<PciGpu> for GPU {
+    fn from(gpu: PciGpu) -> Self {
+        let kind = if paravirtual_adapter(gpu.vendor_id, gpu.device_id).is_some() {
+            GPUKind::Virtual
+        } else {
+            GPUKind::Unknown
+        };
+        Self {
+            passthrough: crate::vm::passthrough(&kind),
+            kind,
+            name: gpu.name,
+            vendor: gpu.vendor,
+            driver_version: "Unknown".to_string(),
+            vram: Bytes(0),
+            pci_address: Some(gpu.address),
+            kernel_driver: gpu.driver,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", err))]
+pub fn retrieve_gpu_info_via_pci() -> Result<Vec<PciGpu>, PciError> {
+    let root = Path::new(SYSFS_PCI_DEVICES);
+    if !root.is_dir() {
+        return Err(PciError::NotSupported);
+    }
+
+    let mut gpus = read_pci_gpus(root)?;
+    trace_debug!(count = gpus.len(), "read sysfs display controllers");
+
+    if let Some(pci_ids) = read_pci_ids() {
+        for gpu in &mut gpus {
+            if let Some(name) = lookup_device_name(&pci_ids, gpu.vendor_id, gpu.device_id) {
+                gpu.name = name;
+            }
+        }
+    }
+
+    Ok(gpus)
+}
+
+/// The boot VGA device, which the firmware initialized and Mesa renders on by
+/// default, or the first display controller. `None` without one.
+pub(crate) fn primary_pci_gpu() -> Result<Option<PciGpu>, PciError> {
+    let root = Path::new(SYSFS_PCI_DEVICES);
+    if !root.is_dir() {
+        return Err(PciError::NotSupported);
+    }
+    let gpus = read_pci_gpus(root)?;
+    let boot_vga = gpus.iter().position(|gpu| {
+        read_string(&root.join(gpu.address.to_string()).join("boot_vga")).as_deref() == Some("1")
+    });
+    let Some(mut gpu) = gpus.into_iter().nth(boot_vga.unwrap_or(0)) else {
+        return Ok(None);
+    };
+    if let Some(name) = read_pci_ids()
+        .and_then(|pci_ids| lookup_device_name(&pci_ids, gpu.vendor_id, gpu.device_id))
+    {
+        gpu.name = name;
+    }
+    Ok(Some(gpu))
+}
+
+/// GPUs present on the PCI bus that no Vulkan driver exposes, typically
+/// because no driver is loaded for them.
+pub fn retrieve_unexposed_gpus() -> Result<Vec<GPU>, crate::Error> {
+    let pci_gpus = retrieve_gpu_info_via_pci()?;
+    let vk_gpus = match retrieve_vulkan_gpus() {
+        Ok(gpus) => gpus,
+        Err(e) if e.is_not_supported() => vec![],
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(unexposed(pci_gpus, &vk_gpus)
+        .into_iter()
+        .map(GPU::from)
+        .collect())
+}
+
+/// Fill in the fields only sysfs knows about for GPUs with a PCI address
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip_all))]
+pub(crate) fn attach_sysfs_info(mut gpus: Vec<GPU>, query: &GpuQuery) -> Vec<GPU> {
+    for gpu in &mut gpus {
+        read_sysfs_info(gpu, query);
+    }
+    gpus
+}
+
+/// Fill in the fields `query` selects for one GPU with a PCI address
+pub(crate) fn read_sysfs_info(gpu: &mut GPU, query: &GpuQuery) {
+    let Some(address) = gpu.pci_address else {
+        return;
+    };
+    let path = Path::new(SYSFS_PCI_DEVICES).join(address.to_string());
+    if query.displays {
+        gpu.has_display_output = read_drm_connectors(&path).map(|c| !c.is_empty());
+        gpu.active_displays = read_active_displays(&path);
+        gpu.max_displays = read_crtc_count(&path);
+    }
+
+    if query.capabilities {
+        gpu.kernel_driver = read_driver(&path);
+        // Exposed by amdgpu on MI200/MI300, `SPX` means a single partition
+        let compute_partition = read_string(&path.join("current_compute_partition"));
+        if compute_partition
+            .as_deref()
+            .is_some_and(|mode| mode != "SPX")
+        {
+            gpu.partition_mode = compute_partition;
+        }
+        gpu.memory_partition_mode = read_string(&path.join("current_memory_partition"));
+        gpu.tile_count = read_tile_count(&path);
+        if gpu.kind == GPUKind::Integrated {
+            let (carve_out, gtt) = read_amdgpu_memory(&path);
+            gpu.carve_out = carve_out.or(gpu.carve_out);
+            gpu.shared_memory = gtt.or(gpu.shared_memory);
+        }
+    }
+
+    if let Some(hwmon) = find_hwmon(&path).filter(|_| query.telemetry) {
+        let watts = |name: &str| read_watts(&hwmon.join(name));
+        gpu.tdp_watts = watts("power1_cap_default").map(Watts);
+        gpu.min_power_limit = watts("power1_cap_min").map(Watts);
+        gpu.max_power_limit = watts("power1_cap_max").map(Watts);
+        // Millidegrees and Hz
+        let read_scaled = |name: &str, scale: u64| {
+            let value: u64 = read_string(&hwmon.join(name))?.parse().ok()?;
+            u32::try_from(value / scale).ok()
+        };
+        gpu.temperatures = read_temperatures(&hwmon);
+        gpu.clock_speed = read_scaled("freq1_input", 1_000_000).map(MegaHertz);
+        crate::overclock::apply_overclock_settings(gpu, read_overclock_settings(&hwmon));
+    }
+}
+
+fn unexposed(mut pci_gpus: Vec<PciGpu>, vk_gpus: &[VulkanGpu]) -> Vec<PciGpu> {
+    for vk_gpu in vk_gpus {
+        // Without VK_EXT_pci_bus_info, fall back to the first device with matching ids
+        let index = match vk_gpu.pci_address {
+            Some(address) => pci_gpus.iter().position(|gpu| gpu.address == address),
+            None => pci_gpus.iter().position(|gpu| {
+                gpu.vendor_id == vk_gpu.vendor_id && gpu.device_id == vk_gpu.device_id
+            }),
+        };
+        if let Some(index) = index {
+            pci_gpus.remove(index);
+        }
+    }
+    pci_gpus
+}
+
+pub(crate) fn read_pci_gpus(root: &Path) -> Result<Vec<PciGpu>, PciError> {
+    let mut gpus = Vec::new();
+
+    for entry in fs::read_dir(root)? {
+        let path = entry?.path();
+        let Some(class) = read_hex(&path.join("class")) else {
+            continue;
+        };
+        if class >> 16 != PCI_CLASS_DISPLAY {
+            continue;
+        }
+        let Some(address) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.parse().ok())
+        else {
+            continue;
+        };
+
+        let vendor_id = read_hex(&path.join("vendor")).unwrap_or_default();
+        let device_id = read_hex(&path.join("device")).unwrap_or_default();
+        let driver = read_driver(&path);
+
+        gpus.push(PciGpu {
+            address,
+            name: format!("PCI device {vendor_id:04x}:{device_id:04x}"),
+            vendor: vendor_from_id(vendor_id).to_string(),
+            vendor_id,
+            device_id,
+            subsystem_vendor_id: read_hex(&path.join("subsystem_vendor")).unwrap_or_default(),
+            subsystem_device_id: read_hex(&path.join("subsystem_device")).unwrap_or_default(),
+            class,
+            driver,
+        });
+    }
+
+    gpus.sort_by_key(|gpu| gpu.address);
+    Ok(gpus)
+}
+
+/// The `driver` symlink points at `/sys/bus/pci/drivers/<name>`
+pub(crate) fn read_driver(device: &Path) -> Option<String> {
+    let driver = fs::read_link(device.join("driver")).ok()?;
+    Some(driver.file_name()?.to_string_lossy().into_owned())
+}
+
+/// DRM connectors of a device as `(name, connected)`, e.g. `("card0-DP-1", true)`.
+/// `None` when the device has no DRM node.
+fn read_drm_connectors(device: &Path) -> Option<Vec<(String, bool)>> {
+    let mut connectors = read_connector_attribute(device, "status")?
+        .into_iter()
+        .map(|(name, status)| (name, status == "connected"))
+        .collect::<Vec<_>>();
+    connectors.sort();
+    Some(connectors)
+}
+
+/// Connectors currently scanning out, whether or not they are part of the
+/// desktop
+fn read_active_displays(device: &Path) -> Option<u32> {
+    let connectors = read_connector_attribute(device, "enabled")?;
+    Some(
+        connectors
+            .iter()
+            .filter(|(_, enabled)| enabled == "enabled")
+            .count() as u32,
+    )
+}
+
+/// `(connector, value)` of an attribute of every connector of the device
+fn read_connector_attribute(device: &Path, attribute: &str) -> Option<Vec<(String, String)>> {
+    let mut connectors = Vec::new();
+
+    for card_name in read_card_names(device)? {
+        let Ok(entries) = fs::read_dir(device.join("drm").join(&card_name)) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.starts_with(&format!("{card_name}-")) {
+                continue;
+            }
+            let value = fs::read_to_string(entry.path().join(attribute)).unwrap_or_default();
+            connectors.push((name, value.trim().to_string()));
+        }
+    }
+
+    Some(connectors)
+}
+
+/// `cardN` nodes of a device, `None` when it has no DRM node
+fn read_card_names(device: &Path) -> Option<Vec<String>> {
+    Some(
+        fs::read_dir(device.join("drm"))
+            .ok()?
+            .flatten()
+            .map(|card| card.file_name().to_string_lossy().into_owned())
+            .filter(|name| name.starts_with("card"))
+            .collect(),
+    )
+}
+
+/// `struct drm_mode_card_res` of the DRM uapi
+#[repr(C)]
+#[derive(Default)]
+struct DrmModeCardRes {
+    fb_id_ptr: u64,
+    crtc_id_ptr: u64,
+    connector_id_ptr: u64,
+    encoder_id_ptr: u64,
+    count_fbs: u32,
+    count_crtcs: u32,
+    count_connectors: u32,
+    count_encoders: u32,
+    min_width: u32,
+    max_width: u32,
+    min_height: u32,
+    max_height: u32,
+}
+
+/// `_IOWR('d', 0xA0, struct drm_mode_card_res)`
+const DRM_IOCTL_MODE_GETRESOURCES: std::ffi::c_ulong = 0xC040_64A0;
+
+extern "C" {
+    fn ioctl(fd: std::ffi::c_int, request: std::ffi::c_ulong, ...) -> std::ffi::c_int;
+}
+
+/// CRTCs of the first DRM card of the device, each scanning out one display.
+/// Needs read access to `/dev/dri/cardN`, which logind grants the active seat.
+fn read_crtc_count(device: &Path) -> Option<u32> {
+    use std::os::fd::AsRawFd;
+
+    let card = read_card_names(device)?.into_iter().min()?;
+    let file = fs::File::open(Path::new("/dev/dri").join(card)).ok()?;
+    // With null id pointers the kernel only fills in the counts
+    let mut resources = DrmModeCardRes::default();
+    let result = unsafe {
+        ioctl(
+            file.as_raw_fd(),
+            DRM_IOCTL_MODE_GETRESOURCES,
+            &mut resources as *mut DrmModeCardRes,
+        )
+    };
+    // Render-only devices without modesetting fail with EOPNOTSUPP
+    (result == 0).then_some(resources.count_crtcs)
+}
+
+/// xe lists tiles as `tileN` next to the device, i915 as `gt/gtN` under the DRM card
+fn read_tile_count(device: &Path) -> Option<u32> {
+    let count_prefixed = |dir: &Path, prefix: &str| {
+        fs::read_dir(dir).ok().map(|entries| {
+            entries
+                .flatten()
+                .filter(|entry| {
+                    entry
+                        .file_name()
+                        .to_str()
+                        .and_then(|name| name.strip_prefix(prefix))
+                        .is_some_and(|index| index.parse::<u32>().is_ok())
+                })
+                .count() as u32
+        })
+    };
+
+    if let Some(count) = count_prefixed(device, "tile").filter(|&count| count > 0) {
+        return Some(count);
+    }
+    fs::read_dir(device.join("drm"))
+        .ok()?
+        .flatten()
+        .filter(|card| {
+            card.file_name()
+                .to_str()
+                .is_some_and(|name| name.starts_with("card"))
+        })
+        .find_map(|card| count_prefixed(&card.path().join("gt"), "gt"))
+        .filter(|&count| count > 0)
+}
+
+/// The `hwmon/hwmonN` directory amdgpu registers for the device
+pub(crate) fn find_hwmon(device: &Path) -> Option<PathBuf> {
+    fs::read_dir(device.join("hwmon"))
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| path.join("name").is_file())
+}
+
+/// `tempN_input` in millidegrees, placed by `tempN_label`, e.g. amdgpu's
+/// `edge`, `junction` and `mem` or xe's `vram`. An unlabeled `temp1`, as
+/// nouveau has, is the die.
+fn read_temperatures(hwmon: &Path) -> Vec<TemperatureSensor> {
+    (1..=8)
+        .filter_map(|index| {
+            let millidegrees: i64 = read_string(&hwmon.join(format!("temp{index}_input")))?
+                .parse()
+                .ok()?;
+            let label = read_string(&hwmon.join(format!("temp{index}_label")));
+            let location = match label.as_deref() {
+                None if index == 1 => TemperatureLocation::Edge,
+                Some("edge") => TemperatureLocation::Edge,
+                Some("junction" | "hotspot") => TemperatureLocation::Hotspot,
+                Some("mem" | "vram") => TemperatureLocation::Memory,
+                Some(label) if label.starts_with("vr") => TemperatureLocation::Vrm,
+                _ => return None,
+            };
+            Some(TemperatureSensor {
+                location,
+                celsius: millidegrees as f32 / 1000.0,
+            })
+        })
+        .collect()
+}
+
+/// The power cap and fan mode amdgpu exposes through hwmon, the clocks in
+/// `pp_od_clk_voltage` have no default to compare against
+fn read_overclock_settings(hwmon: &Path) -> Vec<OverclockSetting> {
+    let watts = |name: &str| read_watts(&hwmon.join(name)).map(Watts);
+    let power_limit = watts("power1_cap")
+        .zip(watts("power1_cap_default"))
+        .map(|(value, default)| OverclockSetting::PowerLimit { value, default });
+    // 1 is manual, 2 the driver's automatic control
+    let manual_fan = read_string(&hwmon.join("pwm1_enable"))
+        .filter(|mode| mode == "1")
+        .map(|_| OverclockSetting::ManualFan(0));
+    power_limit.into_iter().chain(manual_fan).collect()
+}
+
+/// hwmon power attributes are in microwatts
+pub(crate) fn read_microwatts(path: &Path) -> Option<u64> {
+    read_string(path)?.parse().ok()
+}
+
+/// A hwmon power attribute rounded down to whole watts
+pub(crate) fn read_watts(path: &Path) -> Option<u32> {
+    u32::try_from(read_microwatts(path)? / 1_000_000)
+        .ok()
+        .filter(|&watts| watts > 0)
+}
+
+/// VRAM held by the DRM clients of this process on the device at `address`,
+/// in bytes. `None` when the driver does not report per-client memory in
+/// fdinfo, e.g. the NVIDIA proprietary one.
+pub(crate) fn self_vram_usage_via_fdinfo(address: PciAddress) -> Option<u64> {
+    read_fdinfo_vram(Path::new("/proc/self/fdinfo"), &address.to_string())
+}
+
+fn read_fdinfo_vram(fdinfo: &Path, pdev: &str) -> Option<u64> {
+    // Duplicated file descriptors share one client
+    let mut clients = BTreeMap::new();
+    for entry in fs::read_dir(fdinfo).ok()?.flatten() {
+        let Ok(info) = fs::read_to_string(entry.path()) else {
+            continue;
+        };
+        if fdinfo_field(&info, "drm-pdev") != Some(pdev) {
+            continue;
+        }
+        let Some(client_id) = fdinfo_field(&info, "drm-client-id") else {
+            continue;
+        };
+        if let Some(bytes) = parse_fdinfo_vram(&info) {
+            clients.insert(client_id.to_string(), bytes);
+        }
+    }
+    (!clients.is_empty()).then(|| clients.values().sum())
+}
+
+fn fdinfo_field<'a>(info: &'a str, name: &str) -> Option<&'a str> {
+    info.lines()
+        .find_map(|line| Some(line.strip_prefix(name)?.strip_prefix(':')?.trim()))
+}
+
+/// Busy nanoseconds and capacity of each engine, by engine name, per DRM client id
+type EngineTimes = BTreeMap<String, BTreeMap<String, (u64, u64)>>;
+
+/// How busy each class of engine of the device at `address` was over
+/// `interval`, from the DRM fdinfo of every process this one may read
+pub(crate) fn engine_utilization_via_fdinfo(
+    address: PciAddress,
+    interval: Duration,
+) -> Vec<EngineClassUtilization> {
+    let pdev = address.to_string();
+    let before = read_fdinfo_engine_times(Path::new("/proc"), &pdev);
+    let start = Instant::now();
+    std::thread::sleep(interval);
+    let after = read_fdinfo_engine_times(Path::new("/proc"), &pdev);
+    fdinfo_utilization(&before, &after, start.elapsed())
+}
+
+fn read_fdinfo_engine_times(proc: &Path, pdev: &str) -> EngineTimes {
+    let mut clients = EngineTimes::new();
+    let Ok(processes) = fs::read_dir(proc) else {
+        return clients;
+    };
+    for process in processes.flatten() {
+        // Denied for other users' processes without root
+        let Ok(fds) = fs::read_dir(process.path().join("fdinfo")) else {
+            continue;
+        };
+        for fd in fds.flatten() {
+            let Ok(info) = fs::read_to_string(fd.path()) else {
+                continue;
+            };
+            if fdinfo_field(&info, "drm-pdev") != Some(pdev) {
+                continue;
+            }
+            // Shared and duplicated file descriptors share one client
+            if let Some(client_id) = fdinfo_field(&info, "drm-client-id") {
+                clients
+                    .entry(client_id.to_string())
+                    .or_insert_with(|| parse_fdinfo_engines(&info));
+            }
+        }
+    }
+    clients
+}
+
+/// `drm-engine-<name>: <time> ns` and `drm-engine-capacity-<name>: <count>`
+/// lines of one DRM client
+fn parse_fdinfo_engines(info: &str) -> BTreeMap<String, (u64, u64)> {
+    let mut engines = BTreeMap::new();
+    for (key, value) in info.lines().filter_map(|line| line.split_once(':')) {
+        let value = value.trim();
+        if let Some(engine) = key.strip_prefix("drm-engine-capacity-") {
+            if let Ok(capacity) = value.parse() {
+                engines.entry(engine.to_string()).or_insert((0, 1)).1 = capacity;
+            }
+        } else if let Some(engine) = key.strip_prefix("drm-engine-") {
+            if let Some(Ok(busy)) = value.strip_suffix("ns").map(|ns| ns.trim().parse()) {
+                engines.entry(engine.to_string()).or_insert((0, 1)).0 = busy;
+            }
+        }
+    }
+    engines
+}
+
+/// Only clients present in both samples count, as the busy time of a client
+/// is counted from its creation
+fn fdinfo_utilization(
+    before: &EngineTimes,
+    after: &EngineTimes,
+    elapsed: Duration,
+) -> Vec<EngineClassUtilization> {
+    let mut engines = BTreeMap::new();
+    for (client_id, engines_after) in after {
+        let Some(engines_before) = before.get(client_id) else {
+            continue;
+        };
+        for (engine, &(busy, capacity)) in engines_after {
+            let previous = engines_before.get(engine).map_or(0, |&(busy, _)| busy);
+            let entry = engines.entry(engine.as_str()).or_insert((0, capacity));
+            entry.0 += busy.saturating_sub(previous);
+            entry.1 = entry.1.max(capacity);
+        }
+    }
+    let elapsed = elapsed.as_nanos() as f64;
+    crate::utilization_by_class(engines.into_iter().map(|(engine, (busy, capacity))| {
+        let busy = busy as f64 / (elapsed * capacity.max(1) as f64);
+        (fdinfo_engine_class(engine), busy.clamp(0.0, 1.0) as f32)
+    }))
+}
+
+/// Engine names of amdgpu, i915, msm, panfrost and v3d. i915 runs decode and
+/// encode on the same `video` engines.
+fn fdinfo_engine_class(engine: &str) -> EngineClass {
+    match engine {
+        "gfx" | "render" | "gpu" | "fragment" | "vertex-tiler" | "bin" => EngineClass::Graphics,
+        "compute" | "csd" => EngineClass::Compute,
+        "dma" | "copy" => EngineClass::Copy,
+        "dec" | "video" | "jpeg" => EngineClass::VideoDecode,
+        "enc" | "enc_1" => EngineClass::VideoEncode,
+        "video-enhance" | "vpe" => EngineClass::VideoProcessing,
+        _ => EngineClass::Other,
+    }
+}
+
+/// Resident device memory of one DRM client: the `drm-resident-vram*` or
+/// `drm-resident-local*` regions, or the older amdgpu `drm-memory-vram`
+fn parse_fdinfo_vram(info: &str) -> Option<u64> {
+    let sizes = |matches: &dyn Fn(&str) -> bool| {
+        info.lines()
+            .filter_map(|line| line.split_once(':'))
+            .filter(|(key, _)| matches(key))
+            .filter_map(|(_, value)| parse_fdinfo_size(value))
+            .reduce(|a, b| a + b)
+    };
+    sizes(&|key| key.starts_with("drm-resident-vram") || key.starts_with("drm-resident-local"))
+        .or_else(|| sizes(&|key| key == "drm-memory-vram"))
+}
+
+/// Sizes are written like `1024 KiB`, without a unit for bytes
+fn parse_fdinfo_size(value: &str) -> Option<u64> {
+    let mut parts = value.split_whitespace();
+    let number: u64 = parts.next()?.parse().ok()?;
+    let scale = match parts.next() {
+        None => 1,
+        Some("KiB") => 1 << 10,
+        Some("MiB") => 1 << 20,
+        Some("GiB") => 1 << 30,
+        Some(_) => return None,
+    };
+    Some(number * scale)
+}
+
+/// `(carve-out, GTT)` of an amdgpu APU, whose VRAM is the carve-out
+fn read_amdgpu_memory(device: &Path) -> (Option<Bytes>, Option<Bytes>) {
+    let bytes = |name: &str| {
+        let bytes: u64 = read_string(&device.join(name))?.parse().ok()?;
+        Some(Bytes(bytes)).filter(|&bytes| bytes != Bytes(0))
+    };
+    (bytes("mem_info_vram_total"), bytes("mem_info_gtt_total"))
+}
+
+fn read_string(path: &Path) -> Option<String> {
+    let value = fs::read_to_string(path).ok()?;
+    Some(value.trim().to_string()).filter(|value| !value.is_empty())
+}
+
+/// sysfs attributes hold values like `0x10de`
+pub(crate) fn read_hex(path: &Path) -> Option<u32> {
+    let value = fs::read_to_string(path).ok()?;
+    u32::from_str_radix(value.trim().trim_start_matches("0x"), 16).ok()
+}
+
+/// The `pci.ids` database used by `lspci`, if installed
+pub(crate) fn read_pci_ids() -> Option<String> {
+    PCI_IDS_PATHS
+        .iter()
+        .find_map(|p| fs::read_to_string(p).ok())
+}
+
+/// Look up a device name in the `pci.ids` database
+pub(crate) fn lookup_device_name(pci_ids: &str, vendor_id: u32, device_id: u32) -> Option<String> {
+    let vendor = format!("{vendor_id:04x}");
+    let device = format!("{device_id:04x}");
+    let mut in_vendor = false;
+
+    for line in pci_ids.lines() {
+        if line.starts_with('#') || line.is_empty() {
+            continue;
+        }
+        if !line.starts_with('\t') {
+            // A new vendor section, or the device classes at the end of the file
+            if in_vendor {
+                return None;
+            }
+            in_vendor = line.starts_with(&vendor);
+        } else if in_vendor && !line.starts_with("\t\t") {
+            if let Some(name) = line[1..].strip_prefix(&device) {
+                return Some(name.trim().to_string());
+            }
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_fdinfo_vram() {
+        let fixture = tempfile::tempdir().unwrap();
+        let root = fixture.path();
+        let amdgpu = "drm-driver:\tamdgpu\ndrm-pdev:\t0000:03:00.0\ndrm-client-id:\t7\n\
+                      drm-memory-vram:\t2048 KiB\ndrm-memory-gtt:\t512 KiB\n";
+        let xe = "drm-driver:\txe\ndrm-pdev:\t0000:00:02.0\ndrm-client-id:\t9\n\
+                  drm-total-vram0:\t8 MiB\ndrm-resident-vram0:\t4 MiB\n\
+                  drm-resident-vram1:\t1 MiB\n";
+        fs::write(root.join("3"), amdgpu).unwrap();
+        // A duplicated descriptor of the same client
+        fs::write(root.join("4"), amdgpu).unwrap();
+        fs::write(root.join("5"), xe).unwrap();
+        fs::write(root.join("6"), "pos:\t0\nflags:\t02\n").unwrap();
+
+        assert_eq!(read_fdinfo_vram(root, "0000:03:00.0"), Some(2048 * 1024));
+        assert_eq!(read_fdinfo_vram(root, "0000:00:02.0"), Some(5 << 20));
+        assert_eq!(read_fdinfo_vram(root, "0000:41:00.0"), None);
+        fixture.close().unwrap();
+    }
+
+    #[test]
+    fn test_fdinfo_utilization() {
+        let fixture = tempfile::tempdir().unwrap();
+        // Owned, as the last check reads the directory once removed
+        let root = fixture.path().to_path_buf();
+        let write = |pid: &str, fd: &str, client_id: u32, gfx: u64, enc: u64| {
+            let dir = root.join(pid).join("fdinfo");
+            fs::create_dir_all(&dir).unwrap();
+            let info = format!(
+                "drm-driver:\tamdgpu\ndrm-pdev:\t0000:03:00.0\ndrm-client-id:\t{client_id}\n\
+                 drm-engine-gfx:\t{gfx} ns\ndrm-engine-enc:\t{enc} ns\n\
+                 drm-engine-capacity-enc:\t2\n"
+            );
+            fs::write(dir.join(fd), info).unwrap();
+        };
+        write("100", "5", 1, 1_000_000, 0);
+        write("200", "7", 2, 0, 0);
+        let before = read_fdinfo_engine_times(&root, "0000:03:00.0");
+        // Client 1 rendered for 10 ms and client 2 kept one of two encoders
+        // busy for 100 ms, a new client 3 does not count
+        write("100", "5", 1, 11_000_000, 0);
+        write("200", "7", 2, 0, 100_000_000);
+        write("200", "8", 2, 0, 100_000_000);
+        write("300", "4", 3, 50_000_000, 0);
+        let after = read_fdinfo_engine_times(&root, "0000:03:00.0");
+        fixture.close().unwrap();
+
+        assert_eq!(before.len(), 2);
+        assert_eq!(before["2"]["enc"], (0, 2));
+        assert_eq!(
+            fdinfo_utilization(&before, &after, Duration::from_millis(100)),
+            [
+                EngineClassUtilization {
+                    class: EngineClass::Graphics,
+                    busy: 0.1
+                },
+                EngineClassUtilization {
+                    class: EngineClass::VideoEncode,
+                    busy: 0.5
+                },
+            ]
+        );
+        assert!(read_fdinfo_engine_times(&root, "0000:03:00.0").is_empty());
+    }
+
+    #[test]
+    fn test_read_pci_gpus() {
+        let fixture = tempfile::tempdir().unwrap();
+        let root = fixture.path();
+        let write = |device: &str, class: &str, vendor: &str, id: &str| {
+            let dir = root.join(device);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("class"), class).unwrap();
+            fs::write(dir.join("vendor"), vendor).unwrap();
+            fs::write(dir.join("device"), id).unwrap();
+        };
+        write("0000:01:00.0", "0x030000\n", "0x10de\n", "0x2684\n");
+        write("0000:00:1f.3", "0x040300\n", "0x8086\n", "0x7a50\n");
+        std::os::unix::fs::symlink(
+            "/sys/bus/pci/drivers/nvidia",
+            root.join("0000:01:00.0/driver"),
+        )
+        .unwrap();
+
+        let gpus = read_pci_gpus(root);
+        fixture.close().unwrap();
+
+        // The audio controller is not a GPU
+        let gpus = gpus.unwrap();
+        assert_eq!(gpus.len(), 1);
+        assert_eq!(gpus[0].address.to_string(), "0000:01:00.0");
+        assert_eq!(gpus[0].vendor, "NVIDIA");
+        assert_eq!(gpus[0].device_id, 0x2684);
+        assert_eq!(gpus[0].driver.as_deref(), Some("nvidia"));
+    }
+
+    #[test]
+    fn test_lookup_device_name() {
+        let pci_ids = "# comment\n10de  NVIDIA Corporation\n\t2684  AD102 [GeForce RTX 4090]\n\t\t10de 167c  RTX 4090\n1002  Advanced Micro Devices, Inc. [AMD/ATI]\n";
+        assert_eq!(
+            lookup_device_name(pci_ids, 0x10de, 0x2684).as_deref(),
+            Some("AD102 [GeForce RTX 4090]")
+        );
+        assert_eq!(lookup_device_name(pci_ids, 0x1002, 0x2684), None);
+    }
+
+    #[test]
+    fn test_read_drm_connectors() {
+        let fixture = tempfile::tempdir().unwrap();
+        let root = fixture.path();
+        let device = root.join("0000:01:00.0");
+        let no_drm = root.join("0000:00:1f.3");
+        fs::create_dir_all(&no_drm).unwrap();
+        for (connector, status, enabled) in [
+            ("card1-DP-1", "connected", "enabled"),
+            ("card1-HDMI-A-1", "disconnected", "disabled"),
+        ] {
+            let connector = device.join("drm/card1").join(connector);
+            fs::create_dir_all(&connector).unwrap();
+            fs::write(connector.join("status"), format!("{status}\n")).unwrap();
+            fs::write(connector.join("enabled"), format!("{enabled}\n")).unwrap();
+        }
+
+        let connectors = read_drm_connectors(&device);
+        let active_displays = read_active_displays(&device);
+        let no_connectors = read_drm_connectors(&no_drm);
+        fixture.close().unwrap();
+
+        assert_eq!(
+            connectors,
+            Some(vec![
+                ("card1-DP-1".to_string(), true),
+                ("card1-HDMI-A-1".to_string(), false)
+            ])
+        );
+        assert_eq!(active_displays, Some(1));
+        assert_eq!(no_connectors, None);
+    }
+
+    #[test]
+    fn test_read_memory_partition() {
+        let fixture = tempfile::tempdir().unwrap();
+        let root = fixture.path();
+        fs::write(root.join("current_memory_partition"), "NPS4\n").unwrap();
+
+        let memory_partition = read_string(&root.join("current_memory_partition"));
+        let no_partition = read_string(&root.join("current_compute_partition"));
+        fixture.close().unwrap();
+
+        assert_eq!(memory_partition.as_deref(), Some("NPS4"));
+        assert_eq!(no_partition, None);
+    }
+
+    #[test]
+    fn test_read_amdgpu_memory() {
+        let fixture = tempfile::tempdir().unwrap();
+        let root = fixture.path();
+        fs::write(root.join("mem_info_vram_total"), "536870912\n").unwrap();
+
+        let amdgpu_memory = read_amdgpu_memory(root);
+        fixture.close().unwrap();
+
+        assert_eq!(amdgpu_memory, (Some(Bytes::from_mb(512)), None));
+    }
+
+    #[test]
+    fn test_read_tile_count() {
+        let fixture = tempfile::tempdir().unwrap();
+        let root = fixture.path();
+        let device = root.join("0000:03:00.0");
+        let no_tiles = root.join("0000:00:02.0");
+        fs::create_dir_all(&no_tiles).unwrap();
+        for gt in ["gt0", "gt1"] {
+            fs::create_dir_all(device.join("drm/card1/gt").join(gt)).unwrap();
+        }
+
+        let tile_count = read_tile_count(&device);
+        let no_tile_count = read_tile_count(&no_tiles);
+        fixture.close().unwrap();
+
+        assert_eq!(tile_count, Some(2));
+        assert_eq!(no_tile_count, None);
+    }
+
+    #[test]
+    fn test_read_power_limits() {
+        let fixture = tempfile::tempdir().unwrap();
+        let root = fixture.path();
+        let hwmon = root.join("hwmon/hwmon3");
+        fs::create_dir_all(&hwmon).unwrap();
+        fs::write(hwmon.join("name"), "amdgpu\n").unwrap();
+        fs::write(hwmon.join("power1_cap_max"), "750000000\n").unwrap();
+        fs::write(hwmon.join("power1_cap"), "400000000\n").unwrap();
+        fs::write(hwmon.join("power1_cap_default"), "355000000\n").unwrap();
+        fs::write(hwmon.join("pwm1_enable"), "2\n").unwrap();
+
+        let max_power =
+            find_hwmon(root).and_then(|hwmon| read_watts(&hwmon.join("power1_cap_max")));
+        let overclock_settings = read_overclock_settings(&hwmon);
+        fixture.close().unwrap();
+
+        assert_eq!(max_power, Some(750));
+        // The fan runs under automatic control, so only the raised power
+        // limit counts
+        assert_eq!(
+            overclock_settings,
+            vec![OverclockSetting::PowerLimit {
+                value: Watts(400),
+                default: Watts(355),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_read_temperatures() {
+        let fixture = tempfile::tempdir().unwrap();
+        let hwmon = fixture.path();
+        for (index, label, millidegrees) in [
+            (1, "edge", 45000),
+            (2, "junction", 52500),
+            (3, "mem", 70000),
+        ] {
+            fs::write(
+                hwmon.join(format!("temp{index}_label")),
+                format!("{label}\n"),
+            )
+            .unwrap();
+            fs::write(
+                hwmon.join(format!("temp{index}_input")),
+                format!("{millidegrees}\n"),
+            )
+            .unwrap();
+        }
+        fs::write(hwmon.join("temp4_input"), "30000\n").unwrap();
+
+        let temperatures = read_temperatures(hwmon);
+        fixture.close().unwrap();
+
+        // The unlabeled `temp4` is skipped
+        assert_eq!(
+            temperatures
+                .iter()
+                .map(|sensor| (sensor.location, sensor.celsius))
+                .collect::<Vec<_>>(),
+            [
+                (TemperatureLocation::Edge, 45.0),
+                (TemperatureLocation::Hotspot, 52.5),
+                (TemperatureLocation::Memory, 70.0)
+            ]
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::list::compare_performance;
+use crate::{PciAddress, GPU};
+
+/// A GPU chosen by the user, e.g. in a settings dropdown, kept as the
+/// identifiers that survive reboots, driver updates and changes in
+/// enumeration order. The index in the GPU list and the Windows LUID do not.
+///
+/// Stored as `key=value` lines, through [`GpuPreference::save`] or in the
+/// application's own settings via `to_string()` and `parse()`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct GpuPreference {
+    pub vendor: String,
+    pub name: String,
+    pub uuid: Option<String>,
+    pub pci_address: Option<PciAddress>,
+}
+
+impl GpuPreference {
+    pub fn new(gpu: &GPU) -> Self {
+        Self {
+            vendor: gpu.vendor.clone(),
+            name: gpu.name.clone(),
+            uuid: gpu.uuid.clone(),
+            pci_address: gpu.pci_address,
+        }
+    }
+
+    /// Write the preference for `gpu` to `path`, creating its directory
+    pub fn save(gpu: &GPU, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, Self::new(gpu).to_string())
+    }
+
+    /// The preference saved at `path`, `None` if there is none yet
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Option<Self>> {
+        match fs::read_to_string(path) {
+            Ok(contents) => contents
+                .parse()
+                .map(Some)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The preferred GPU among `gpus`, matched by UUID, then by PCI address
+    /// for a GPU of the same vendor, then by vendor and name. Driver updates
+    /// can rename a GPU and moving a card changes its address, but rarely
+    /// both at once. `None` once the GPU is gone.
+    pub fn resolve<'a>(&self, gpus: &'a [GPU]) -> Option<&'a GPU> {
+        let same_vendor = |gpu: &&GPU| gpu.vendor.eq_ignore_ascii_case(&self.vendor);
+        let by_uuid = || {
+            let uuid = self.uuid.as_deref()?;
+            gpus.iter().find(|gpu| {
+                gpu.uuid
+                    .as_deref()
+                    .is_some_and(|id| id.eq_ignore_ascii_case(uuid))
+            })
+        };
+        let by_address = || {
+            let address = self.pci_address?;
+            gpus.iter()
+                .filter(same_vendor)
+                .find(|gpu| gpu.pci_address == Some(address))
+        };
+        let by_name = || {
+            gpus.iter()
+                .filter(same_vendor)
+                .find(|gpu| gpu.name.trim().eq_ignore_ascii_case(self.name.trim()))
+        };
+        by_uuid().or_else(by_address).or_else(by_name)
+    }
+
+    /// [`GpuPreference::resolve`], falling back to the fastest GPU by
+    /// [`compare_performance`] when the preferred one is gone
+    pub fn resolve_or_fastest<'a>(&self, gpus: &'a [GPU]) -> Option<&'a GPU> {
+        self.resolve(gpus)
+            .or_else(|| gpus.iter().max_by(|a, b| compare_performance(a, b)))
+    }
+}
+
+impl std::fmt::Display for GpuPreference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "vendor={}", self.vendor)?;
+        writeln!(f, "name={}", self.name)?;
+        if let Some(uuid) = &self.uuid {
+            writeln!(f, "uuid={uuid}")?;
+        }
+        if let Some(address) = self.pci_address {
+            writeln!(f, "pci_address={address}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for GpuPreference {
+    type Err = String;
+
+    /// Unknown keys are skipped, so newer versions can add identifiers
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut preference = GpuPreference::default();
+        for line in s.lines().filter(|line| !line.trim().is_empty()) {
+            let (key, value) = line
+                .split_once('=')
+                .ok_or_else(|| format!("expected `key=value`, found `{line}`"))?;
+            match key.trim() {
+                "vendor" => preference.vendor = value.to_string(),
+                "name" => preference.name = value.to_string(),
+                "uuid" => preference.uuid = Some(value.to_string()),
+                "pci_address" => {
+                    preference.pci_address = Some(
+                        value
+                            .parse()
+                            .map_err(|e| format!("invalid PCI address `{value}`: {e}"))?,
+                    )
+                }
+                _ => {}
+            }
+        }
+        if preference.name.is_empty() {
+            return Err("missing GPU name".to_string());
+        }
+        Ok(preference)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GPUKind;
+
+    #[test]
+    fn test_resolve() {
+        let igpu = GPU::builder()
+            .kind(GPUKind::Integrated)
+            .vendor("Intel")
+            .name("Intel(R) UHD Graphics 770")
+            .pci_address("0000:00:02.0".parse::<PciAddress>().unwrap())
+            .build();
+        let dgpu = GPU::builder()
+            .kind(GPUKind::Discrete)
+            .vendor("NVIDIA")
+            .name("NVIDIA GeForce RTX 4070")
+            .uuid("a0b1c2d3-0000-1111-2222-333344445555")
+            .pci_address("0000:01:00.0".parse::<PciAddress>().unwrap())
+            .vram_gib(12)
+            .build();
+        let preference = GpuPreference::new(&dgpu);
+        let fixture = tempfile::tempdir().unwrap();
+        let path = fixture.path().join("gpu");
+        GpuPreference::save(&dgpu, &path).unwrap();
+        let loaded = GpuPreference::load(&path).unwrap();
+        fixture.close().unwrap();
+        assert_eq!(loaded.as_ref(), Some(&preference));
+        assert_eq!(GpuPreference::load(&path).unwrap(), None);
+
+        // Re-ordered, and renamed and re-slotted by a driver update and a move
+        let mut renamed = dgpu.clone();
+        renamed.name = "GeForce RTX 4070".to_string();
+        renamed.pci_address = "0000:02:00.0".parse().ok();
+        let gpus = [igpu.clone(), renamed];
+        assert_eq!(
+            preference.resolve(&gpus).map(|gpu| &gpu.name),
+            Some(&gpus[1].name)
+        );
+
+        let mut without_uuid = preference.clone();
+        without_uuid.uuid = None;
+        without_uuid.name = "NVIDIA GeForce RTX 3060".to_string();
+        assert!(without_uuid.resolve(&gpus).is_none());
+        let mut moved = dgpu.clone();
+        moved.uuid = None;
+        assert_eq!(
+            without_uuid.resolve(&[moved]).map(|gpu| gpu.pci_address),
+            Some(dgpu.pci_address)
+        );
+
+        let gone = [igpu];
+        assert!(preference.resolve(&gone).is_none());
+        assert_eq!(
+            preference.resolve_or_fastest(&gone).map(|gpu| &gpu.vendor),
+            Some(&"Intel".to_string())
+        );
+        assert!("name".parse::<GpuPreference>().is_err());
+    }
+}
@@ -0,0 +1,261 @@
+use std::path::Path;
+
+use crate::compositor::{card_pci_address, is_boot_vga, read_cards, SYSFS_DRM};
+use crate::{Error, GPUKind, PciAddress, GPU};
+
+/// Variables the NVIDIA proprietary driver reads to render offloaded
+/// OpenGL and Vulkan work on the NVIDIA GPU
+const NVIDIA_OFFLOAD_ENVIRONMENT: &[(&str, &str)] = &[
+    ("__NV_PRIME_RENDER_OFFLOAD", "1"),
+    ("__GLX_VENDOR_LIBRARY_NAME", "nvidia"),
+    ("__VK_LAYER_NV_optimus", "NVIDIA_only"),
+];
+
+/// The GPU Mesa renders on when a process runs with `DRI_PRIME=1`: the first
+/// card other than the boot VGA device, or the boot VGA device itself on
+/// single GPU systems
+pub fn dri_prime_gpu() -> Result<Option<GPU>, Error> {
+    let Some(address) = dri_prime_pci_address(Path::new(SYSFS_DRM)) else {
+        return Ok(None);
+    };
+    Ok(crate::retrieve_gpu_info()?
+        .into_iter()
+        .find(|gpu| gpu.pci_address == Some(address)))
+}
+
+fn dri_prime_pci_address(drm: &Path) -> Option<PciAddress> {
+    let cards = read_cards(drm);
+    cards
+        .iter()
+        .filter(|card| !is_boot_vga(drm, card))
+        .chain(cards.iter().filter(|card| is_boot_vga(drm, card)))
+        .find_map(|card| card_pci_address(drm, card))
+}
+
+/// Environment variables that make a child process render on `gpu`, for
+/// [`std::process::Command::envs`]. Taken from switcheroo-control when the
+/// `switcheroo` feature is enabled and the daemon runs, as GNOME does, and
+/// otherwise `DRI_PRIME` for Mesa drivers or the PRIME render offload
+/// variables for the NVIDIA proprietary driver. Empty for GPUs without a PCI
+/// address.
+pub fn launch_environment(gpu: &GPU) -> Vec<(String, String)> {
+    let Some(address) = gpu.pci_address else {
+        return vec![];
+    };
+
+    #[cfg(feature = "switcheroo")]
+    match switcheroo_gpus() {
+        Ok(gpus) => {
+            if let Some(switcheroo_gpu) = gpus
+                .into_iter()
+                .find(|switcheroo_gpu| switcheroo_gpu.pci_address() == Some(address))
+            {
+                return switcheroo_gpu.environment;
+            }
+        }
+        Err(e) => log::debug!("skipping switcheroo-control: {e}"),
+    }
+
+    offload_environment(gpu, address)
+}
+
+fn offload_environment(gpu: &GPU, address: PciAddress) -> Vec<(String, String)> {
+    let nvidia_driver = match gpu.kernel_driver.as_deref() {
+        Some(driver) => driver == "nvidia",
+        None => gpu.vendor == "NVIDIA",
+    };
+    if nvidia_driver {
+        return NVIDIA_OFFLOAD_ENVIRONMENT
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+    }
+    vec![("DRI_PRIME".to_string(), dri_prime_tag(address))]
+}
+
+/// [`launch_environment`] of the first discrete GPU, `None` without one
+pub fn discrete_launch_environment() -> Result<Option<Vec<(String, String)>>, Error> {
+    Ok(crate::retrieve_gpu_info()?
+        .iter()
+        .find(|gpu| gpu.kind == GPUKind::Discrete)
+        .map(launch_environment))
+}
+
+/// Mesa's device tag, e.g. `pci-0000_03_00_0`
+fn dri_prime_tag(address: PciAddress) -> String {
+    format!(
+        "pci-{:04x}_{:02x}_{:02x}_{:x}",
+        address.domain, address.bus, address.device, address.function
+    )
+}
+
+#[cfg(feature = "switcheroo")]
+#[derive(Debug, thiserror::Error)]
+pub enum SwitcherooError {
+    #[error("Failed to run busctl: {0}")]
+    Command(#[from] std::io::Error),
+    #[error("Failed to query switcheroo-control: {0}")]
+    Parse(String),
+}
+
+/// A GPU as listed by the switcheroo-control daemon
+#[cfg(feature = "switcheroo")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SwitcherooGpu {
+    pub name: String,
+    /// Variables to launch a process on this GPU with
+    pub environment: Vec<(String, String)>,
+    /// Whether applications render on this GPU unless told otherwise
+    pub default: bool,
+    pub discrete: bool,
+}
+
+#[cfg(feature = "switcheroo")]
+impl SwitcherooGpu {
+    /// Parsed from the `DRI_PRIME` tag in [`SwitcherooGpu::environment`]
+    pub fn pci_address(&self) -> Option<PciAddress> {
+        let (_, tag) = self
+            .environment
+            .iter()
+            .find(|(name, _)| name == "DRI_PRIME")?;
+        let mut parts = tag.strip_prefix("pci-")?.split('_');
+        let (domain, bus, device, function) =
+            (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+        format!("{domain}:{bus}:{device}.{function}").parse().ok()
+    }
+}
+
+/// The `GPUs` property of `net.hadess.SwitcherooControl`, read with `busctl`
+/// from the system bus
+#[cfg(feature = "switcheroo")]
+pub fn switcheroo_gpus() -> Result<Vec<SwitcherooGpu>, SwitcherooError> {
+    let output = std::process::Command::new("busctl")
+        .args([
+            "--system",
+            "--json=short",
+            "get-property",
+            "net.hadess.SwitcherooControl",
+            "/net/hadess/SwitcherooControl",
+            "net.hadess.SwitcherooControl",
+            "GPUs",
+        ])
+        .output()?;
+    if !output.status.success() {
+        return Err(SwitcherooError::Parse(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    parse_switcheroo_gpus(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// busctl writes each `a{sv}` entry as `{"Key": {"type": "s", "data": ...}}`
+#[cfg(feature = "switcheroo")]
+fn parse_switcheroo_gpus(json: &str) -> Result<Vec<SwitcherooGpu>, SwitcherooError> {
+    use serde_json::Value;
+
+    let root: Value =
+        serde_json::from_str(json).map_err(|e| SwitcherooError::Parse(e.to_string()))?;
+    let entries = root["data"]
+        .as_array()
+        .ok_or_else(|| SwitcherooError::Parse("missing GPUs array".to_string()))?;
+
+    Ok(entries
+        .iter()
+        .map(|entry| {
+            let data = |key: &str| &entry[key]["data"];
+            // A flat list of alternating names and values
+            let environment = data("Environment")
+                .as_array()
+                .map(|values| {
+                    values
+                        .chunks_exact(2)
+                        .filter_map(|pair| {
+                            Some((pair[0].as_str()?.to_string(), pair[1].as_str()?.to_string()))
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            SwitcherooGpu {
+                name: data("Name").as_str().unwrap_or_default().to_string(),
+                environment,
+                default: data("Default").as_bool().unwrap_or_default(),
+                discrete: data("Discrete").as_bool().unwrap_or_default(),
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_dri_prime_pci_address() {
+        let fixture = tempfile::tempdir().unwrap();
+        let root = fixture.path();
+        let drm = root.join("drm");
+        for (card, address, boot_vga) in [
+            ("card0", "0000:00:02.0", "1\n"),
+            ("card1", "0000:03:00.0", "0\n"),
+        ] {
+            let device = root.join("pci").join(address);
+            fs::create_dir_all(&device).unwrap();
+            fs::write(device.join("boot_vga"), boot_vga).unwrap();
+            fs::create_dir_all(drm.join(card)).unwrap();
+            std::os::unix::fs::symlink(&device, drm.join(card).join("device")).unwrap();
+        }
+        let address = dri_prime_pci_address(&drm);
+        fixture.close().unwrap();
+
+        assert_eq!(address, "0000:03:00.0".parse().ok());
+        assert_eq!(dri_prime_tag(address.unwrap()), "pci-0000_03_00_0");
+    }
+
+    #[test]
+    fn test_offload_environment() {
+        let address = "0000:01:00.0".parse::<PciAddress>().unwrap();
+        let gpu = |vendor: &str, driver: &str| {
+            GPU::builder()
+                .vendor(vendor)
+                .kernel_driver(driver)
+                .pci_address(address)
+                .build()
+        };
+        let dri_prime = vec![("DRI_PRIME".to_string(), "pci-0000_01_00_0".to_string())];
+        assert_eq!(
+            offload_environment(&gpu("AMD", "amdgpu"), address),
+            dri_prime
+        );
+        assert_eq!(
+            offload_environment(&gpu("NVIDIA", "nouveau"), address),
+            dri_prime
+        );
+        assert_eq!(
+            offload_environment(&gpu("NVIDIA", "nvidia"), address).len(),
+            3
+        );
+        assert!(launch_environment(&GPU::builder().vendor("Apple").build()).is_empty());
+    }
+
+    #[cfg(feature = "switcheroo")]
+    #[test]
+    fn test_parse_switcheroo_gpus() {
+        let json = r#"{"type":"aa{sv}","data":[
+            {"Name":{"type":"s","data":"Intel® UHD Graphics 630"},
+             "Environment":{"type":"as","data":["DRI_PRIME","pci-0000_00_02_0"]},
+             "Default":{"type":"b","data":true},"Discrete":{"type":"b","data":false}},
+            {"Name":{"type":"s","data":"NVIDIA GeForce GTX 1650"},
+             "Environment":{"type":"as","data":["__GLX_VENDOR_LIBRARY_NAME","nvidia",
+                 "__NV_PRIME_RENDER_OFFLOAD","1","__VK_LAYER_NV_optimus","NVIDIA_only"]},
+             "Default":{"type":"b","data":false},"Discrete":{"type":"b","data":true}}]}"#;
+        let gpus = parse_switcheroo_gpus(json).unwrap();
+        assert_eq!(gpus.len(), 2);
+        assert!(gpus[0].default);
+        assert_eq!(gpus[0].pci_address(), "0000:00:02.0".parse().ok());
+        assert!(gpus[1].discrete);
+        assert_eq!(gpus[1].environment.len(), 3);
+        assert_eq!(gpus[1].pci_address(), None);
+        assert!(parse_switcheroo_gpus("{}").is_err());
+    }
+}
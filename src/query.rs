@@ -0,0 +1,351 @@
+#[cfg(target_os = "macos")]
+use crate::retrieve_gpu_info_via_metal;
+#[cfg(not(target_os = "macos"))]
+use crate::retrieve_vulkan_gpus;
+use crate::{Error, GPUKind, PciAddress, GPU};
+#[cfg(any(feature = "daemon", feature = "http-server"))]
+use std::sync::Mutex;
+#[cfg(any(feature = "daemon", feature = "http-server"))]
+use std::time::{Duration, Instant};
+
+/// Selects the groups of data fetched on top of the identity of each GPU
+/// (kind, name, vendor, driver version and memory), so callers only pay for
+/// what they use. Metal reports everything in one pass and ignores the selection.
+///
+/// ```no_run
+/// let gpus = gpu_info::GpuQuery::new().with_displays().run()?;
+/// # Ok::<(), gpu_info::Error>(())
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct GpuQuery {
+    pub(crate) telemetry: bool,
+    pub(crate) displays: bool,
+    pub(crate) capabilities: bool,
+    pub(crate) software_renderers: bool,
+    excluded: Vec<String>,
+}
+
+impl GpuQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every group, as returned by [`retrieve_gpu_info`](crate::retrieve_gpu_info)
+    pub fn all() -> Self {
+        Self::new()
+            .with_telemetry()
+            .with_displays()
+            .with_capabilities()
+    }
+
+    /// Temperature, clock, power limits and MIG partitions, from NVML and hwmon,
+    /// the temperature and clock of AMD GPUs on Windows from ADL and of Intel
+    /// GPUs from IGCL, and the temperature of the discrete GPU of Intel Macs
+    /// from the SMC
+    pub fn with_telemetry(mut self) -> Self {
+        self.telemetry = true;
+        self
+    }
+
+    /// Connected displays and whether the GPU can drive one at all
+    pub fn with_displays(mut self) -> Self {
+        self.displays = true;
+        self
+    }
+
+    /// Kernel driver, tiles, compute / memory partition modes and the cloud
+    /// instance type
+    pub fn with_capabilities(mut self) -> Self {
+        self.capabilities = true;
+        self
+    }
+
+    /// Keep [`GPUKind::Software`] devices such as llvmpipe or SwiftShader,
+    /// which are left out so GPU pickers only list hardware. For diagnostics,
+    /// where a software renderer explains why rendering is slow.
+    pub fn with_software_renderers(mut self) -> Self {
+        self.software_renderers = true;
+        self
+    }
+
+    /// Leave out a device by UUID, with or without NVML's `GPU-` prefix, by
+    /// PCI address, or on macOS by Metal registry id, in hex with `0x` as in
+    /// the CSV export or in decimal, e.g. the GPU reserved for the desktop.
+    /// Excluded GPUs are dropped by [`run`](Self::run) before the telemetry
+    /// backends are asked about them, and skipped by
+    /// [`wait_for_gpus_with`](crate::wait_for_gpus_with),
+    /// [`primary_gpu_with`](crate::primary_gpu_with) and
+    /// [`primary_gpu_name_with`](crate::primary_gpu_name_with). The variants
+    /// that do not take a query, such as [`primary_gpu`](crate::primary_gpu),
+    /// still see them.
+    pub fn exclude(mut self, device: impl Into<String>) -> Self {
+        self.excluded.push(device.into());
+        self
+    }
+
+    pub(crate) fn has_exclusions(&self) -> bool {
+        !self.excluded.is_empty()
+    }
+
+    pub(crate) fn is_excluded(&self, gpu: &GPU) -> bool {
+        self.excluded.iter().any(|device| {
+            let device = device.trim();
+            if let Ok(address) = device.parse::<PciAddress>() {
+                return gpu.pci_address == Some(address);
+            }
+            if parse_registry_id(device).is_some_and(|id| gpu.registry_id == Some(id)) {
+                return true;
+            }
+            gpu.uuid
+                .as_deref()
+                .is_some_and(|uuid| uuid.eq_ignore_ascii_case(device.trim_start_matches("GPU-")))
+        })
+    }
+
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "info", err))]
+    pub fn run(&self) -> Result<Vec<GPU>, Error> {
+        #[cfg(target_os = "macos")]
+        let gpus = retrieve_gpu_info_via_metal()?
+            .into_iter()
+            .map(|g| g.into())
+            .collect::<Vec<GPU>>();
+
+        // Containers and VMs without a GPU usually lack a Vulkan driver too
+        #[cfg(all(not(target_os = "macos"), not(windows)))]
+        let gpus = match retrieve_vulkan_gpus() {
+            Ok(vk_gpus) => vk_gpus.into_iter().map(|g| g.into()).collect::<Vec<GPU>>(),
+            Err(e) if e.is_not_supported() => {
+                log::info!("no vulkan driver, reporting no GPUs: {e}");
+                vec![]
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        #[cfg(windows)]
+        let gpus = {
+            use crate::dxgi;
+
+            let mut gpus = match (retrieve_vulkan_gpus(), dxgi::retrieve_adapters(self)) {
+                (Ok(vk_gpus), Ok(adapters)) => dxgi::merge_with_vulkan(vk_gpus, adapters),
+                (Ok(vk_gpus), Err(e)) => {
+                    log::warn!("dxgi unavailable, using vulkan only: {e}");
+                    vk_gpus.into_iter().map(|g| g.into()).collect()
+                }
+                (Err(e), Ok(adapters)) => {
+                    // A broken ICD or an outdated driver, DXGI still lists the adapters
+                    if !e.is_not_supported() {
+                        log::warn!("vulkan unavailable, using dxgi only: {e}");
+                    }
+                    dxgi::merge_with_vulkan(vec![], adapters)
+                }
+                (Err(e), Err(_)) => return Err(e.into()),
+            };
+            // Without outputs every adapter would look headless
+            if !self.displays {
+                for gpu in &mut gpus {
+                    gpu.has_display_output = None;
+                    gpu.active_displays = None;
+                }
+            }
+            gpus
+        };
+
+        #[cfg(target_os = "linux")]
+        let gpus = crate::pci::attach_sysfs_info(gpus, self);
+
+        let gpus: Vec<GPU> = gpus
+            .into_iter()
+            .filter(|gpu| !self.is_excluded(gpu))
+            .collect();
+
+        #[cfg(all(feature = "nvml", not(target_os = "macos")))]
+        let gpus = if self.telemetry {
+            crate::nvml::attach_nvml_info(gpus)
+        } else {
+            gpus
+        };
+
+        #[cfg(all(feature = "adl", windows))]
+        let gpus = if self.telemetry {
+            crate::adl::attach_adl_info(gpus)
+        } else {
+            gpus
+        };
+
+        #[cfg(all(feature = "igcl", windows))]
+        let gpus = if self.telemetry {
+            crate::igcl::attach_igcl_info(gpus)
+        } else {
+            gpus
+        };
+
+        let mut gpus = gpus;
+        if !self.software_renderers {
+            gpus.retain(|gpu| gpu.kind != GPUKind::Software);
+        }
+        crate::names::assign_instance_indices(&mut gpus);
+        crate::segment::attach_product_lines(&mut gpus);
+        crate::translation::attach_translation_layers(&mut gpus);
+        if self.capabilities {
+            crate::cloud::attach_cloud_instance(&mut gpus);
+            crate::handheld::attach_handheld(&mut gpus);
+        }
+
+        trace_debug!(count = gpus.len(), "enumeration finished");
+        Ok(gpus)
+    }
+
+    /// Re-query the selected fields of one GPU that change at runtime, i.e.
+    /// displays and telemetry, without enumerating the other GPUs. Fails with
+    /// [`Error::DeviceRemoved`] once the GPU is gone, instead of returning
+    /// its last values.
+    pub fn refresh(&self, gpu: &GPU) -> Result<GPU, Error> {
+        if !is_present(gpu) {
+            return Err(Error::DeviceRemoved(gpu.name.clone()));
+        }
+        let mut gpu = gpu.clone();
+
+        #[cfg(windows)]
+        if let (true, Some(luid)) = (self.displays, gpu.luid) {
+            let displays = crate::dxgi::outputs_for_luid(luid)?.unwrap_or_default();
+            gpu.has_display_output = Some(!displays.is_empty());
+            gpu.active_displays = Some(crate::dxgi::active_displays(&displays));
+            gpu.displays = displays;
+        }
+
+        #[cfg(target_os = "linux")]
+        crate::pci::read_sysfs_info(
+            &mut gpu,
+            &GpuQuery {
+                capabilities: false,
+                ..self.clone()
+            },
+        );
+
+        #[cfg(all(feature = "nvml", not(target_os = "macos")))]
+        if self.telemetry {
+            crate::nvml::refresh_nvml_info(&mut gpu);
+        }
+
+        #[cfg(all(feature = "adl", windows))]
+        if self.telemetry {
+            crate::adl::refresh_adl_info(&mut gpu);
+        }
+
+        #[cfg(all(feature = "igcl", windows))]
+        if self.telemetry {
+            crate::igcl::refresh_igcl_info(&mut gpu);
+        }
+
+        // Only set for the one GPU the SMC has sensors for
+        #[cfg(target_os = "macos")]
+        if self.telemetry && gpu.temperature().is_some() {
+            gpu.temperatures = crate::temperature_sensors([(
+                crate::TemperatureLocation::Edge,
+                crate::smc::gpu_temperature().map(|celsius| celsius.0 as f32),
+            )]);
+        }
+
+        Ok(gpu)
+    }
+}
+
+/// A Metal registry id, `0x`-prefixed hex or decimal
+fn parse_registry_id(device: &str) -> Option<u64> {
+    match device
+        .strip_prefix("0x")
+        .or_else(|| device.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => device.parse().ok(),
+    }
+}
+
+/// `false` once the GPU is known to be gone: its PCI device left sysfs or
+/// DXGI has no adapter with its LUID. A TDR keeps the LUID, the reset shows
+/// as `DXGI_ERROR_DEVICE_REMOVED` from the calls instead.
+#[cfg_attr(not(any(target_os = "linux", windows)), allow(unused_variables))]
+fn is_present(gpu: &GPU) -> bool {
+    #[cfg(target_os = "linux")]
+    if let Some(address) = gpu.pci_address {
+        let devices = std::path::Path::new(crate::pci::SYSFS_PCI_DEVICES);
+        // Sandboxes may hide sysfs altogether
+        return !devices.is_dir() || devices.join(address.to_string()).exists();
+    }
+    #[cfg(windows)]
+    if let Some(luid) = gpu.luid {
+        return crate::dxgi::adapter_present(luid).unwrap_or(true);
+    }
+    true
+}
+
+/// When a sample was taken and what it returned
+#[cfg(any(feature = "daemon", feature = "http-server"))]
+type Sample = (Instant, Result<Vec<GPU>, String>);
+
+/// The last [`GpuQuery::all`] result, shared by every client of a server so
+/// that enumeration runs at most once per interval however many ask
+#[cfg(any(feature = "daemon", feature = "http-server"))]
+pub(crate) struct Sampler {
+    interval: Duration,
+    last: Mutex<Option<Sample>>,
+}
+
+#[cfg(any(feature = "daemon", feature = "http-server"))]
+impl Sampler {
+    pub(crate) fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last: Mutex::new(None),
+        }
+    }
+
+    pub(crate) fn sample(&self) -> Result<Vec<GPU>, String> {
+        // Held while enumerating, so concurrent requests wait for one sample
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        match &*last {
+            Some((taken, result)) if taken.elapsed() < self.interval => result.clone(),
+            _ => {
+                let result = match GpuQuery::all().run() {
+                    // Enumerating again sees the GPUs left after the removal
+                    Err(e) if e.is_device_removed() => GpuQuery::all().run(),
+                    result => result,
+                }
+                .map_err(|e| e.to_string());
+                *last = Some((Instant::now(), result.clone()));
+                result
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_excluded() {
+        let gpu = GPU::builder()
+            .uuid("6f1c2a3b-0000-4000-8000-000000000001")
+            .pci_address("0000:01:00.0".parse::<PciAddress>().unwrap())
+            .build();
+        let excludes = |device: &str| GpuQuery::new().exclude(device).is_excluded(&gpu);
+        assert!(excludes("GPU-6F1C2A3B-0000-4000-8000-000000000001"));
+        assert!(excludes("01:00.0"));
+        assert!(!excludes("0000:02:00.0"));
+        assert!(!excludes("6f1c2a3b-0000-4000-8000-000000000002"));
+        assert!(!GpuQuery::new().is_excluded(&gpu));
+
+        let metal_gpu = GPU::builder().registry_id(0x1000_0042a_u64).build();
+        assert!(GpuQuery::new()
+            .exclude("0x10000042A")
+            .is_excluded(&metal_gpu));
+        assert!(GpuQuery::new()
+            .exclude("4294968362")
+            .is_excluded(&metal_gpu));
+        assert!(!GpuQuery::new()
+            .exclude("0x10000042b")
+            .is_excluded(&metal_gpu));
+        assert!(!excludes("0x10000042a"));
+    }
+}
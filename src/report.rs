@@ -0,0 +1,277 @@
+use std::collections::BTreeMap;
+
+use serde_json::Value;
+
+use crate::{CloudInstance, Emulation, FirmwareInfo, SystemContext, GPU};
+
+/// Environment variables steering GPU selection, drivers and loaders. Other
+/// variables are left out of reports.
+const ENVIRONMENT_PREFIXES: &[&str] = &[
+    "VK_",
+    "MESA_",
+    "__NV_",
+    "__GLX_",
+    "CUDA_",
+    "HIP_",
+    "ROCR_",
+    "HSA_",
+    "GPU_DEVICE_ORDINAL",
+    "DRI_PRIME",
+    "LIBGL_",
+    "METAL_",
+    "MTL_",
+    "LD_LIBRARY_PATH",
+    "DYLD_LIBRARY_PATH",
+    "DISPLAY",
+    "WAYLAND_DISPLAY",
+    "XDG_SESSION_TYPE",
+];
+
+/// Keys whose values identify a machine, replaced in the rendered report
+const REDACTED_KEYS: &[&str] = &["uuid", "luid", "serial"];
+
+/// Everything needed to diagnose a GPU detection issue, see [`generate_report`]
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Report {
+    pub crate_version: String,
+    /// `std::env::consts::OS` and `ARCH`, e.g. `linux x86_64`
+    pub platform: String,
+    /// Distribution or product name and version, e.g. `Ubuntu 24.04.1 LTS`
+    pub os_version: Option<String>,
+    /// Kernel release on Linux
+    pub kernel: Option<String>,
+    pub in_virtual_machine: bool,
+    /// Set for x86 builds running on Arm, which see the x86 builds of the
+    /// GPU drivers and runtimes
+    pub emulation: Option<Emulation>,
+    pub cloud_instance: Option<CloudInstance>,
+    /// BIOS version and the firmware settings that affect GPUs
+    pub firmware: FirmwareInfo,
+    pub gpus: Vec<GPU>,
+    /// Why [`retrieve_gpu_info`](crate::retrieve_gpu_info) failed, in which
+    /// case `gpus` is empty
+    pub enumeration_error: Option<String>,
+    /// Each Vulkan driver probed on its own, with its manifest and library
+    #[cfg(not(target_os = "macos"))]
+    pub vulkan_icds: Vec<crate::IcdStatus>,
+    #[cfg(feature = "compute-runtimes")]
+    pub compute_runtimes: Vec<crate::RuntimeStatus>,
+    /// Variables matching [`ENVIRONMENT_PREFIXES`]
+    pub environment: BTreeMap<String, String>,
+    /// Memory and CPU of the host, only set by [`Report::with_system_context`]
+    pub system: Option<SystemContext>,
+}
+
+/// Collect a [`Report`] to attach to a bug report in one file, instead of
+/// the output of a dozen commands. Takes as long as a full enumeration plus
+/// loading every Vulkan driver.
+pub fn generate_report() -> Report {
+    // A software renderer in place of the GPU is often the bug being reported
+    let (gpus, enumeration_error) = match crate::GpuQuery::all().with_software_renderers().run() {
+        Ok(gpus) => (gpus, None),
+        Err(e) => (vec![], Some(e.to_string())),
+    };
+    Report {
+        crate_version: env!("CARGO_PKG_VERSION").to_string(),
+        platform: format!("{} {}", std::env::consts::OS, std::env::consts::ARCH),
+        os_version: crate::system::os_version(),
+        kernel: std::fs::read_to_string("/proc/sys/kernel/osrelease")
+            .ok()
+            .map(|release| release.trim().to_string()),
+        in_virtual_machine: crate::in_virtual_machine(),
+        emulation: crate::process_emulation(),
+        cloud_instance: crate::detect_cloud_instance(),
+        firmware: crate::firmware_info(),
+        gpus,
+        enumeration_error,
+        #[cfg(not(target_os = "macos"))]
+        vulkan_icds: crate::probe_vulkan_icds(),
+        #[cfg(feature = "compute-runtimes")]
+        compute_runtimes: crate::compute_runtime_status(),
+        environment: std::env::vars()
+            .filter(|(name, _)| {
+                ENVIRONMENT_PREFIXES
+                    .iter()
+                    .any(|prefix| name.starts_with(prefix))
+            })
+            .collect(),
+        system: None,
+    }
+}
+
+impl Report {
+    /// Include the host's total memory and CPU model, left out by default
+    pub fn with_system_context(mut self) -> Self {
+        self.system = Some(crate::system_context());
+        self
+    }
+
+    /// Pretty printed JSON, with device ids, the user name and the home
+    /// directory redacted. Keys are sorted, so reports diff cleanly.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(&self.redacted()).expect("report is serializable")
+    }
+
+    /// A summary for an issue description, followed by [`Report::to_json`]
+    /// in a collapsed block
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!(
+            "## gpu-info {} report\n\n- Platform: {}\n- OS: {}\n",
+            self.crate_version,
+            self.platform,
+            self.os_version.as_deref().unwrap_or("unknown"),
+        );
+        if let Some(kernel) = &self.kernel {
+            markdown += &format!("- Kernel: {kernel}\n");
+        }
+        if let Some(system) = &self.system {
+            markdown += &format!(
+                "- CPU: {}\n- Memory: {}\n",
+                system.cpu_model.as_deref().unwrap_or("unknown"),
+                system.total_ram
+            );
+        }
+        markdown += &format!("- Virtual machine: {}\n", self.in_virtual_machine);
+        if let Some(emulation) = self.emulation {
+            markdown += &format!("- Emulation: {}\n", emulation.as_str());
+        }
+        if let Some(version) = &self.firmware.bios_version {
+            markdown += &format!(
+                "- BIOS: {} {version} ({})\n",
+                self.firmware.bios_vendor.as_deref().unwrap_or("unknown"),
+                self.firmware.bios_date.as_deref().unwrap_or("unknown date"),
+            );
+        }
+        if let Some(above_4g_decoding) = self.firmware.above_4g_decoding {
+            markdown += &format!("- GPU memory above 4 GiB: {above_4g_decoding}\n");
+        }
+        if let Some(boot_display) = self.firmware.boot_display {
+            markdown += &format!("- Boot display: {boot_display}\n");
+        }
+        if self.firmware.integrated_gpu != crate::IntegratedGpuStatus::Unknown {
+            markdown += &format!(
+                "- Integrated GPU: {}\n",
+                self.firmware.integrated_gpu.as_str()
+            );
+        }
+        for sriov in &self.firmware.sriov {
+            markdown += &format!(
+                "- SR-IOV on {}: {} of {} virtual functions\n",
+                sriov.pci_address, sriov.enabled_vfs, sriov.total_vfs
+            );
+        }
+        if let Some(error) = &self.enumeration_error {
+            markdown += &format!("- Enumeration failed: {}\n", redact_string(error));
+        }
+
+        markdown += "\n| GPU | Kind | Vendor | Driver | VRAM |\n|---|---|---|---|---|\n";
+        for gpu in &self.gpus {
+            markdown += &format!(
+                "| {} | {} | {} | {} | {} |\n",
+                gpu.name,
+                gpu.kind.as_str(),
+                gpu.vendor,
+                gpu.driver_version,
+                gpu.vram
+            );
+        }
+
+        #[cfg(not(target_os = "macos"))]
+        if !self.vulkan_icds.is_empty() {
+            markdown += "\n| Vulkan driver | Usable | Devices |\n|---|---|---|\n";
+            for status in &self.vulkan_icds {
+                markdown += &format!(
+                    "| {} | {} | {} |\n",
+                    redact_string(&status.icd.manifest_path),
+                    status.usable,
+                    match &status.error {
+                        Some(error) => redact_string(error),
+                        None => status.devices.join(", "),
+                    }
+                );
+            }
+        }
+
+        markdown += &format!(
+            "\n<details><summary>Full report</summary>\n\n```json\n{}\n```\n\n</details>\n",
+            self.to_json()
+        );
+        markdown
+    }
+
+    fn redacted(&self) -> Value {
+        let mut value = serde_json::to_value(self).expect("report is serializable");
+        redact(&mut value);
+        value
+    }
+}
+
+fn redact(value: &mut Value) {
+    match value {
+        Value::String(string) => *string = redact_string(string),
+        Value::Array(values) => values.iter_mut().for_each(redact),
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                if REDACTED_KEYS.contains(&key.as_str()) && !value.is_null() {
+                    *value = Value::String("<redacted>".to_string());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Replace the home directory with `~` and the user name with `<user>`
+fn redact_string(string: &str) -> String {
+    let home = std::env::var("HOME").or_else(|_| std::env::var("USERPROFILE"));
+    let user = std::env::var("USER").or_else(|_| std::env::var("USERNAME"));
+    redact_with(string, home.ok().as_deref(), user.ok().as_deref())
+}
+
+fn redact_with(string: &str, home: Option<&str>, user: Option<&str>) -> String {
+    let mut string = string.to_string();
+    if let Some(home) = home.filter(|home| home.len() > 1) {
+        string = string.replace(home, "~");
+    }
+    // Short names such as `gpu` would garble unrelated words
+    if let Some(user) = user.filter(|user| user.len() >= 4) {
+        string = string.replace(user, "<user>");
+    }
+    string
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(
+            redact_with(
+                "/home/alice/.local/share/vulkan/icd.d/x.json (alice)",
+                Some("/home/alice"),
+                Some("alice")
+            ),
+            "~/.local/share/vulkan/icd.d/x.json (<user>)"
+        );
+        assert_eq!(
+            redact_with("NVIDIA GPU", Some("/"), Some("gpu")),
+            "NVIDIA GPU"
+        );
+
+        let mut value = serde_json::json!({
+            "gpus": [{ "name": "A", "uuid": "0000", "luid": null }],
+        });
+        redact(&mut value);
+        assert_eq!(value["gpus"][0]["uuid"], "<redacted>");
+        assert_eq!(value["gpus"][0]["luid"], Value::Null);
+        assert_eq!(value["gpus"][0]["name"], "A");
+    }
+
+    #[test]
+    fn test_generate_report() {
+        eprintln!("{}", generate_report().with_system_context().to_markdown());
+    }
+}
@@ -0,0 +1,191 @@
+use std::cmp::Ordering;
+
+use crate::{Bytes, GPU};
+
+/// Minimum hardware an application needs, checked with [`GPU::meets`].
+/// Unset fields accept any GPU.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct Requirements {
+    pub min_vram: Option<Bytes>,
+    /// Accepted [`GPU::vendor`] names, e.g. `NVIDIA`. Any vendor when empty.
+    pub vendors: Vec<String>,
+    /// Lowest accepted [`GPU::driver_version`], compared number by number.
+    /// Versions are vendor specific, so combine with a single vendor.
+    pub min_driver: Option<String>,
+    pub required_features: Vec<Feature>,
+}
+
+/// A capability [`Requirements`] can ask for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[non_exhaustive]
+pub enum Feature {
+    HardwareRayTracing,
+    MeshShading,
+    AsyncCompute,
+    DisplayOutput,
+}
+
+impl Feature {
+    fn supported_by(self, gpu: &GPU) -> Option<bool> {
+        match self {
+            Feature::HardwareRayTracing => gpu.hardware_ray_tracing,
+            Feature::MeshShading => gpu.mesh_shading,
+            Feature::AsyncCompute => gpu.async_compute,
+            Feature::DisplayOutput => gpu.has_display_output,
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            Feature::HardwareRayTracing => "hardware ray tracing",
+            Feature::MeshShading => "mesh shaders",
+            Feature::AsyncCompute => "async compute",
+            Feature::DisplayOutput => "a display output",
+        }
+    }
+}
+
+/// Outcome of [`GPU::meets`]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct RequirementReport {
+    /// Why the GPU falls short, as sentences for users, e.g. `8 GiB of VRAM,
+    /// 12 GiB required`. Requirements that cannot be checked because this
+    /// GPU does not report the value count as unmet.
+    pub unmet: Vec<String>,
+}
+
+impl RequirementReport {
+    pub fn is_met(&self) -> bool {
+        self.unmet.is_empty()
+    }
+}
+
+impl GPU {
+    /// Check this GPU against `requirements`, listing every unmet one
+    pub fn meets(&self, requirements: &Requirements) -> RequirementReport {
+        let mut unmet = Vec::new();
+
+        if let Some(min_vram) = requirements.min_vram {
+            // Counts shared memory for integrated GPUs
+            match crate::suggested_gpu_memory_budget(self) {
+                None => unmet.push(format!("Unknown amount of VRAM, {min_vram} required")),
+                Some(vram) if vram < min_vram => {
+                    unmet.push(format!("{vram} of VRAM, {min_vram} required"))
+                }
+                Some(_) => {}
+            }
+        }
+
+        if !requirements.vendors.is_empty()
+            && !requirements
+                .vendors
+                .iter()
+                .any(|vendor| vendor.eq_ignore_ascii_case(&self.vendor))
+        {
+            unmet.push(format!(
+                "{} GPU, {} required",
+                self.vendor,
+                requirements.vendors.join(" or ")
+            ));
+        }
+
+        if let Some(min_driver) = &requirements.min_driver {
+            match compare_versions(&self.driver_version, min_driver) {
+                Some(Ordering::Less) => unmet.push(format!(
+                    "Driver {}, {min_driver} or newer required",
+                    self.driver_version
+                )),
+                Some(_) => {}
+                None => unmet.push(format!(
+                    "Driver version {} cannot be compared to the required {min_driver}",
+                    self.driver_version
+                )),
+            }
+        }
+
+        for &feature in &requirements.required_features {
+            match feature.supported_by(self) {
+                Some(true) => {}
+                Some(false) => unmet.push(format!("No support for {}", feature.description())),
+                None => unmet.push(format!("Unknown support for {}", feature.description())),
+            }
+        }
+
+        RequirementReport { unmet }
+    }
+}
+
+/// Compare dotted versions number by number, missing trailing numbers as 0.
+/// `None` if either contains anything but numbers and dots.
+fn compare_versions(version: &str, other: &str) -> Option<Ordering> {
+    let parse = |version: &str| {
+        version
+            .trim()
+            .split('.')
+            .map(|part| part.parse::<u64>().ok())
+            .collect::<Option<Vec<_>>>()
+    };
+    let (mut version, mut other) = (parse(version)?, parse(other)?);
+    let len = version.len().max(other.len());
+    version.resize(len, 0);
+    other.resize(len, 0);
+    Some(version.cmp(&other))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::GPUKind;
+
+    #[test]
+    fn test_meets() {
+        let gpu = GPU::builder()
+            .kind(GPUKind::Discrete)
+            .vendor("NVIDIA")
+            .driver_version("551.86")
+            .vram_gib(8)
+            .hardware_ray_tracing(true)
+            .build();
+
+        let requirements = Requirements {
+            min_vram: Some(Bytes::from_mb(6 * 1024)),
+            vendors: vec!["nvidia".to_string(), "AMD".to_string()],
+            min_driver: Some("550".to_string()),
+            required_features: vec![Feature::HardwareRayTracing],
+        };
+        assert!(gpu.meets(&requirements).is_met());
+
+        let requirements = Requirements {
+            min_vram: Some(Bytes::from_mb(12 * 1024)),
+            vendors: vec!["AMD".to_string()],
+            min_driver: Some("552.12".to_string()),
+            required_features: vec![Feature::HardwareRayTracing, Feature::MeshShading],
+        };
+        assert_eq!(
+            gpu.meets(&requirements).unmet,
+            [
+                "8 GiB of VRAM, 12 GiB required",
+                "NVIDIA GPU, AMD required",
+                "Driver 551.86, 552.12 or newer required",
+                "Unknown support for mesh shaders",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compare_versions() {
+        assert_eq!(
+            compare_versions("31.0.101.5333", "31.0.101"),
+            Some(Ordering::Greater)
+        );
+        assert_eq!(compare_versions("550.0", "550"), Some(Ordering::Equal));
+        assert_eq!(compare_versions("24.3.1", "24.10"), Some(Ordering::Less));
+        assert_eq!(compare_versions("Unknown", "1.0"), None);
+    }
+}
@@ -0,0 +1,321 @@
+#[cfg(not(target_os = "macos"))]
+use std::ffi::{c_char, c_int, c_uint, c_void, CStr};
+
+use crate::PciAddress;
+
+/// A compute API able to run work on a GPU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub enum ComputeRuntime {
+    Cuda,
+    Rocm,
+    Metal,
+    DirectMl,
+    VulkanCompute,
+}
+
+/// Whether a runtime could create a context on one device
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct RuntimeStatus {
+    pub runtime: ComputeRuntime,
+    /// `None` when the runtime failed before reaching a device, e.g. because
+    /// its library is not installed
+    pub device_name: Option<String>,
+    pub pci_address: Option<PciAddress>,
+    pub usable: bool,
+    /// Why the runtime or device is not usable
+    pub error: Option<String>,
+}
+
+impl RuntimeStatus {
+    fn unusable(runtime: ComputeRuntime, error: impl ToString) -> Self {
+        Self {
+            runtime,
+            device_name: None,
+            pci_address: None,
+            usable: false,
+            error: Some(error.to_string()),
+        }
+    }
+
+    fn device(
+        runtime: ComputeRuntime,
+        device_name: String,
+        pci_address: Option<PciAddress>,
+        result: Result<(), String>,
+    ) -> Self {
+        Self {
+            runtime,
+            device_name: Some(device_name),
+            pci_address,
+            usable: result.is_ok(),
+            error: result.err(),
+        }
+    }
+}
+
+/// Load every compute runtime the platform can have and create a context on
+/// each of its devices. Having an NVIDIA GPU does not mean CUDA works: the
+/// driver may be missing or too old. Every runtime reports at least one entry.
+pub fn compute_runtime_status() -> Vec<RuntimeStatus> {
+    let mut status = Vec::new();
+
+    #[cfg(not(target_os = "macos"))]
+    {
+        status.extend(probe_driver_api(&CUDA));
+        status.extend(probe_driver_api(&HIP));
+        status.extend(probe_vulkan());
+    }
+    #[cfg(target_os = "macos")]
+    status.extend(probe_metal());
+    #[cfg(all(feature = "directml", windows))]
+    status.extend(probe_directml());
+
+    status
+}
+
+/// CUDA and HIP share the shape of their device query functions
+#[cfg(not(target_os = "macos"))]
+struct DriverApi {
+    runtime: ComputeRuntime,
+    libraries: &'static [&'static str],
+    init: &'static [u8],
+    device_count: &'static [u8],
+    device_get: &'static [u8],
+    device_name: &'static [u8],
+    pci_bus_id: &'static [u8],
+    /// Create a context from the ordinal and device handle
+    create_context: fn(&libloading::Library, c_int, c_int) -> Result<(), String>,
+}
+
+// CUDA uses stdcall on 32-bit Windows only, which it no longer supports
+#[cfg(not(target_os = "macos"))]
+type Init = unsafe extern "C" fn(c_uint) -> c_int;
+#[cfg(not(target_os = "macos"))]
+type DeviceCount = unsafe extern "C" fn(*mut c_int) -> c_int;
+#[cfg(not(target_os = "macos"))]
+type DeviceGet = unsafe extern "C" fn(*mut c_int, c_int) -> c_int;
+#[cfg(not(target_os = "macos"))]
+type DeviceString = unsafe extern "C" fn(*mut c_char, c_int, c_int) -> c_int;
+
+#[cfg(not(target_os = "macos"))]
+const CUDA: DriverApi = DriverApi {
+    runtime: ComputeRuntime::Cuda,
+    libraries: if cfg!(windows) {
+        &["nvcuda.dll"]
+    } else {
+        &["libcuda.so.1"]
+    },
+    init: b"cuInit\0",
+    device_count: b"cuDeviceGetCount\0",
+    device_get: b"cuDeviceGet\0",
+    device_name: b"cuDeviceGetName\0",
+    pci_bus_id: b"cuDeviceGetPCIBusId\0",
+    create_context: |lib, _, device| {
+        let retain: unsafe extern "C" fn(*mut *mut c_void, c_int) -> c_int =
+            symbol(lib, b"cuDevicePrimaryCtxRetain\0")?;
+        let release: unsafe extern "C" fn(c_int) -> c_int =
+            symbol(lib, b"cuDevicePrimaryCtxRelease\0")?;
+        let mut context = std::ptr::null_mut();
+        check(
+            unsafe { retain(&mut context, device) },
+            "cuDevicePrimaryCtxRetain",
+        )?;
+        unsafe { release(device) };
+        Ok(())
+    },
+};
+
+#[cfg(not(target_os = "macos"))]
+const HIP: DriverApi = DriverApi {
+    runtime: ComputeRuntime::Rocm,
+    libraries: if cfg!(windows) {
+        &["amdhip64_6.dll", "amdhip64.dll"]
+    } else {
+        &["libamdhip64.so", "libamdhip64.so.6", "libamdhip64.so.5"]
+    },
+    init: b"hipInit\0",
+    device_count: b"hipGetDeviceCount\0",
+    device_get: b"hipDeviceGet\0",
+    device_name: b"hipDeviceGetName\0",
+    pci_bus_id: b"hipDeviceGetPCIBusId\0",
+    create_context: |lib, ordinal, _| {
+        let set_device: unsafe extern "C" fn(c_int) -> c_int = symbol(lib, b"hipSetDevice\0")?;
+        let free: unsafe extern "C" fn(*mut c_void) -> c_int = symbol(lib, b"hipFree\0")?;
+        check(unsafe { set_device(ordinal) }, "hipSetDevice")?;
+        // The first runtime call creates the context
+        check(unsafe { free(std::ptr::null_mut()) }, "hipFree")
+    },
+};
+
+#[cfg(not(target_os = "macos"))]
+fn probe_driver_api(api: &DriverApi) -> Vec<RuntimeStatus> {
+    match probe_driver_devices(api) {
+        Ok(status) if status.is_empty() => vec![RuntimeStatus::unusable(api.runtime, "no devices")],
+        Ok(status) => status,
+        Err(e) => vec![RuntimeStatus::unusable(api.runtime, e)],
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn probe_driver_devices(api: &DriverApi) -> Result<Vec<RuntimeStatus>, String> {
+    let lib = api
+        .libraries
+        .iter()
+        .find_map(|name| unsafe { libloading::Library::new(name) }.ok())
+        .ok_or_else(|| format!("{} is not installed", api.libraries[0]))?;
+
+    let init: Init = symbol(&lib, api.init)?;
+    let device_count: DeviceCount = symbol(&lib, api.device_count)?;
+    let device_get: DeviceGet = symbol(&lib, api.device_get)?;
+    let device_name: DeviceString = symbol(&lib, api.device_name)?;
+    let pci_bus_id: DeviceString = symbol(&lib, api.pci_bus_id)?;
+
+    check(unsafe { init(0) }, "init")?;
+    let mut count = 0;
+    check(unsafe { device_count(&mut count) }, "device count")?;
+
+    let read_string = |query: DeviceString, device| {
+        let mut buffer = [0 as c_char; 256];
+        let result = unsafe { query(buffer.as_mut_ptr(), buffer.len() as c_int, device) };
+        (result == 0).then(|| {
+            unsafe { CStr::from_ptr(buffer.as_ptr()) }
+                .to_string_lossy()
+                .into_owned()
+        })
+    };
+
+    let mut status = Vec::new();
+
+    for ordinal in 0..count {
+        let mut device = 0;
+        if let Err(e) = check(unsafe { device_get(&mut device, ordinal) }, "device get") {
+            status.push(RuntimeStatus::device(
+                api.runtime,
+                format!("device {ordinal}"),
+                None,
+                Err(e),
+            ));
+            continue;
+        }
+        status.push(RuntimeStatus::device(
+            api.runtime,
+            read_string(device_name, device).unwrap_or_else(|| format!("device {ordinal}")),
+            read_string(pci_bus_id, device).and_then(|id| id.parse().ok()),
+            (api.create_context)(&lib, ordinal, device),
+        ));
+    }
+
+    // Drivers register exit handlers that crash once they are unloaded
+    std::mem::forget(lib);
+    Ok(status)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn symbol<T: Copy>(lib: &libloading::Library, name: &[u8]) -> Result<T, String> {
+    unsafe { lib.get::<T>(name) }
+        .map(|symbol| *symbol)
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(not(target_os = "macos"))]
+fn check(result: c_int, call: &str) -> Result<(), String> {
+    match result {
+        0 => Ok(()),
+        code => Err(format!("{call} failed with error {code}")),
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn probe_vulkan() -> Vec<RuntimeStatus> {
+    match crate::vulkan::probe_compute_devices() {
+        Ok(devices) if devices.is_empty() => {
+            vec![RuntimeStatus::unusable(
+                ComputeRuntime::VulkanCompute,
+                "no devices",
+            )]
+        }
+        Ok(devices) => devices
+            .into_iter()
+            .map(|probe| {
+                RuntimeStatus::device(
+                    ComputeRuntime::VulkanCompute,
+                    probe.name,
+                    probe.pci_address,
+                    probe.result,
+                )
+            })
+            .collect(),
+        Err(e) => vec![RuntimeStatus::unusable(ComputeRuntime::VulkanCompute, e)],
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn probe_metal() -> Vec<RuntimeStatus> {
+    use objc2_metal::{MTLCopyAllDevices, MTLDevice};
+
+    let devices = MTLCopyAllDevices();
+    if devices.is_empty() {
+        return vec![RuntimeStatus::unusable(ComputeRuntime::Metal, "no devices")];
+    }
+    devices
+        .iter()
+        .map(|device| {
+            RuntimeStatus::device(
+                ComputeRuntime::Metal,
+                device.name().to_string(),
+                None,
+                device
+                    .newCommandQueue()
+                    .map(|_| ())
+                    .ok_or_else(|| "newCommandQueue failed".to_string()),
+            )
+        })
+        .collect()
+}
+
+#[cfg(all(feature = "directml", windows))]
+fn probe_directml() -> Vec<RuntimeStatus> {
+    match crate::retrieve_directml_support() {
+        Ok(adapters) if adapters.is_empty() => vec![RuntimeStatus::unusable(
+            ComputeRuntime::DirectMl,
+            "no Direct3D 12 adapters",
+        )],
+        Ok(adapters) => adapters
+            .into_iter()
+            .map(|adapter| {
+                let result = match adapter.directml_feature_level {
+                    Some(_) => Ok(()),
+                    None => Err("DirectML.dll is missing or rejects the adapter".to_string()),
+                };
+                RuntimeStatus::device(ComputeRuntime::DirectMl, adapter.name, None, result)
+            })
+            .collect(),
+        Err(e) => vec![RuntimeStatus::unusable(ComputeRuntime::DirectMl, e)],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compute_runtime_status() {
+        let status = compute_runtime_status();
+        eprintln!("{status:#?}");
+        #[cfg(not(target_os = "macos"))]
+        for runtime in [
+            ComputeRuntime::Cuda,
+            ComputeRuntime::Rocm,
+            ComputeRuntime::VulkanCompute,
+        ] {
+            assert!(status.iter().any(|entry| entry.runtime == runtime));
+        }
+        assert!(status
+            .iter()
+            .all(|entry| entry.usable == entry.error.is_none()));
+    }
+}
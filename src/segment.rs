@@ -0,0 +1,145 @@
+use crate::GPU;
+
+/// Market segment of a product line, telling consumer SKUs from
+/// professional ones built on the same chip, e.g. for certified driver
+/// checks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[non_exhaustive]
+pub enum ProductSegment {
+    /// GeForce, Titan, Radeon, Arc
+    Consumer,
+    /// Quadro, NVIDIA RTX, Radeon Pro, Arc Pro
+    Workstation,
+    /// Tesla and later NVIDIA data center GPUs, Instinct, Intel Data Center
+    /// GPU
+    Datacenter,
+}
+
+impl ProductSegment {
+    pub fn is_professional(&self) -> bool {
+        !matches!(self, ProductSegment::Consumer)
+    }
+}
+
+/// NVIDIA data center GPUs without a brand in their name, e.g. `NVIDIA
+/// H100 80GB HBM3`, by the letter of their architecture
+const NVIDIA_DATACENTER_PREFIXES: &[&str] = &["a", "b", "gb", "gh", "h", "l", "t"];
+
+/// Fill [`GPU::product_line`] and [`GPU::product_segment`] from the name of
+/// GPUs a backend did not report them for
+pub(crate) fn attach_product_lines(gpus: &mut [GPU]) {
+    for gpu in gpus {
+        if gpu.product_line.is_some() {
+            continue;
+        }
+        if let Some((line, segment)) = product_line_from_name(&gpu.normalized_name()) {
+            gpu.product_line = Some(line.to_string());
+            gpu.product_segment = Some(segment);
+        }
+    }
+}
+
+/// The product line in a normalized marketing name. Intel's integrated
+/// GPUs and Apple's have none.
+fn product_line_from_name(name: &str) -> Option<(&'static str, ProductSegment)> {
+    let words: Vec<String> = name
+        .split_whitespace()
+        .map(|word| word.to_ascii_lowercase())
+        .collect();
+    let has = |word: &str| words.iter().any(|w| w == word);
+    let has_pair = |first: &str, second: &str| {
+        words
+            .windows(2)
+            .any(|pair| pair[0] == first && pair[1] == second)
+    };
+
+    Some(if has("quadro") {
+        ("Quadro", ProductSegment::Workstation)
+    } else if has("tesla") {
+        ("Tesla", ProductSegment::Datacenter)
+    } else if has("titan") {
+        ("Titan", ProductSegment::Consumer)
+    } else if has("geforce") {
+        ("GeForce", ProductSegment::Consumer)
+    } else if has("nvs") {
+        ("NVS", ProductSegment::Workstation)
+    } else if has("rtx") {
+        // The professional line dropped Quadro for `RTX A6000`, `RTX 6000
+        // Ada Generation` and `RTX PRO 6000`
+        ("RTX", ProductSegment::Workstation)
+    } else if has("instinct") || words.iter().any(|word| is_model(word, "mi")) {
+        ("Instinct", ProductSegment::Datacenter)
+    } else if has("firepro") {
+        ("FirePro", ProductSegment::Workstation)
+    } else if has_pair("radeon", "pro") {
+        ("Radeon Pro", ProductSegment::Workstation)
+    } else if has("radeon") {
+        ("Radeon", ProductSegment::Consumer)
+    } else if has_pair("arc", "pro") {
+        ("Arc Pro", ProductSegment::Workstation)
+    } else if has("arc") {
+        ("Arc", ProductSegment::Consumer)
+    } else if has_pair("data", "center") {
+        ("Data Center GPU", ProductSegment::Datacenter)
+    } else if let Some(model) = words.get(1).filter(|_| words[0] == "nvidia") {
+        // Turing workstation cards, e.g. `NVIDIA T1000`, next to the `T4`
+        if is_model(model, "t") && model.len() > 3 {
+            ("NVIDIA", ProductSegment::Workstation)
+        } else if NVIDIA_DATACENTER_PREFIXES
+            .iter()
+            .any(|prefix| is_model(model, prefix))
+        {
+            ("NVIDIA", ProductSegment::Datacenter)
+        } else {
+            return None;
+        }
+    } else {
+        return None;
+    })
+}
+
+/// Whether `word` is `prefix` followed by a model number, e.g. `mi300x`
+fn is_model(word: &str, prefix: &str) -> bool {
+    word.strip_prefix(prefix)
+        .is_some_and(|rest| rest.starts_with(|c: char| c.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_product_line_from_name() {
+        use ProductSegment::*;
+        for (name, expected) in [
+            ("NVIDIA GeForce RTX 4090", Some(("GeForce", Consumer))),
+            ("NVIDIA TITAN RTX", Some(("Titan", Consumer))),
+            ("NVIDIA Quadro RTX 5000", Some(("Quadro", Workstation))),
+            ("NVIDIA RTX A6000", Some(("RTX", Workstation))),
+            ("NVIDIA RTX 6000 Ada Generation", Some(("RTX", Workstation))),
+            ("Tesla V100-SXM2-16GB", Some(("Tesla", Datacenter))),
+            ("NVIDIA H100 80GB HBM3", Some(("NVIDIA", Datacenter))),
+            ("NVIDIA A10G", Some(("NVIDIA", Datacenter))),
+            ("NVIDIA T4", Some(("NVIDIA", Datacenter))),
+            ("NVIDIA T1000 8GB", Some(("NVIDIA", Workstation))),
+            ("AMD Radeon RX 7900 XTX", Some(("Radeon", Consumer))),
+            ("AMD Radeon PRO W7900", Some(("Radeon Pro", Workstation))),
+            ("AMD Instinct MI300X", Some(("Instinct", Datacenter))),
+            ("AMD MI250X", Some(("Instinct", Datacenter))),
+            ("Intel Arc A770 Graphics", Some(("Arc", Consumer))),
+            ("Intel Arc Pro A60", Some(("Arc Pro", Workstation))),
+            (
+                "Intel Data Center GPU Max 1550",
+                Some(("Data Center GPU", Datacenter)),
+            ),
+            ("Intel Iris Xe Graphics", None),
+            ("Apple M2 Max", None),
+            ("NVIDIA Jetson", None),
+        ] {
+            assert_eq!(product_line_from_name(name), expected, "{name}");
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::ffi::c_void;
+
+use crate::Celsius;
+
+/// `kSMCHandleYPCEvent`, the user client method taking a [`KeyData`]
+const KERNEL_INDEX_SMC: u32 = 2;
+const SMC_CMD_READ_BYTES: u8 = 5;
+const SMC_CMD_READ_KEYINFO: u8 = 9;
+
+/// Discrete GPU die sensors, AMD's on the 16" MacBook Pro and the generic
+/// one, then the proximity sensor older models only have
+const GPU_TEMPERATURE_KEYS: [&[u8; 4]; 3] = [b"TGDD", b"TG0D", b"TG0P"];
+
+#[link(name = "IOKit", kind = "framework")]
+extern "C" {
+    fn IOServiceOpen(service: u32, owning_task: u32, r#type: u32, connect: *mut u32) -> i32;
+    fn IOServiceClose(connect: u32) -> i32;
+    fn IOConnectCallStructMethod(
+        connection: u32,
+        selector: u32,
+        input: *const c_void,
+        input_size: usize,
+        output: *mut c_void,
+        output_size: *mut usize,
+    ) -> i32;
+}
+
+extern "C" {
+    static mach_task_self_: u32;
+}
+
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+struct KeyDataVersion {
+    major: u8,
+    minor: u8,
+    build: u8,
+    reserved: u8,
+    release: u16,
+}
+
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+struct PowerLimitData {
+    version: u16,
+    length: u16,
+    cpu_limit: u32,
+    gpu_limit: u32,
+    memory_limit: u32,
+}
+
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+struct KeyInfo {
+    data_size: u32,
+    data_type: u32,
+    data_attributes: u8,
+}
+
+/// `SMCKeyData_t`, the request and response of every SMC call
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+struct KeyData {
+    key: u32,
+    version: KeyDataVersion,
+    power_limit: PowerLimitData,
+    key_info: KeyInfo,
+    result: u8,
+    status: u8,
+    data8: u8,
+    data32: u32,
+    bytes: [u8; 32],
+}
+
+/// A connection to the System Management Controller of Intel Macs
+struct Smc(u32);
+
+impl Smc {
+    #[allow(deprecated)]
+    fn open() -> Option<Self> {
+        use objc2_core_foundation::CFDictionary;
+        use objc2_io_kit::{
+            kIOMasterPortDefault, IOObjectRelease, IOServiceGetMatchingService, IOServiceMatching,
+        };
+
+        let matching = unsafe { IOServiceMatching(c"AppleSMC".as_ptr()) }?
+            .downcast::<CFDictionary>()
+            .ok()?;
+        let service = unsafe { IOServiceGetMatchingService(kIOMasterPortDefault, Some(matching)) };
+        if service == 0 {
+            return None;
+        }
+        scopeguard::defer! {
+            IOObjectRelease(service);
+        }
+
+        let mut connection = 0;
+        let result = unsafe { IOServiceOpen(service, mach_task_self_, 0, &mut connection) };
+        (result == 0).then_some(Self(connection))
+    }
+
+    fn call(&self, input: &KeyData) -> Option<KeyData> {
+        let mut output = KeyData::default();
+        let mut output_size = std::mem::size_of::<KeyData>();
+        let result = unsafe {
+            IOConnectCallStructMethod(
+                self.0,
+                KERNEL_INDEX_SMC,
+                (input as *const KeyData).cast(),
+                std::mem::size_of::<KeyData>(),
+                (&mut output as *mut KeyData).cast(),
+                &mut output_size,
+            )
+        };
+        (result == 0 && output.result == 0).then_some(output)
+    }
+
+    /// The type, e.g. `sp78`, and the value of `key`
+    fn read(&self, key: &[u8; 4]) -> Option<([u8; 4], Vec<u8>)> {
+        let key = u32::from_be_bytes(*key);
+        let info = self.call(&KeyData {
+            key,
+            data8: SMC_CMD_READ_KEYINFO,
+            ..Default::default()
+        })?;
+        let value = self.call(&KeyData {
+            key,
+            key_info: info.key_info,
+            data8: SMC_CMD_READ_BYTES,
+            ..Default::default()
+        })?;
+        let size = (info.key_info.data_size as usize).min(value.bytes.len());
+        Some((
+            info.key_info.data_type.to_be_bytes(),
+            value.bytes[..size].to_vec(),
+        ))
+    }
+}
+
+impl Drop for Smc {
+    fn drop(&mut self) {
+        unsafe { IOServiceClose(self.0) };
+    }
+}
+
+/// The temperature of the discrete GPU of an Intel Mac from the SMC, which
+/// has no sensor for the GPUs of Apple silicon or in an eGPU enclosure
+pub(crate) fn gpu_temperature() -> Option<Celsius> {
+    let smc = Smc::open()?;
+    GPU_TEMPERATURE_KEYS.into_iter().find_map(|key| {
+        let (data_type, bytes) = smc.read(key)?;
+        decode_temperature(&data_type, &bytes)
+    })
+}
+
+fn decode_temperature(data_type: &[u8; 4], bytes: &[u8]) -> Option<Celsius> {
+    let celsius = match data_type {
+        // Signed fixed point with 8 fractional bits
+        b"sp78" => i16::from_be_bytes(bytes.get(..2)?.try_into().ok()?) as f64 / 256.0,
+        b"flt " => f32::from_le_bytes(bytes.get(..4)?.try_into().ok()?) as f64,
+        _ => return None,
+    };
+    // Keys of sensors a model lacks read zero
+    (celsius > 0.0 && celsius < 150.0).then(|| Celsius(celsius.round() as u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_temperature() {
+        assert_eq!(std::mem::size_of::<KeyData>(), 80);
+        assert_eq!(
+            decode_temperature(b"sp78", &[0x3c, 0x80]).map(|t| t.0),
+            Some(61)
+        );
+        assert_eq!(
+            decode_temperature(b"flt ", &54.2f32.to_le_bytes()).map(|t| t.0),
+            Some(54)
+        );
+        assert!(decode_temperature(b"sp78", &[0, 0]).is_none());
+        assert!(decode_temperature(b"ui8 ", &[60]).is_none());
+    }
+
+    #[test]
+    fn test_gpu_temperature() {
+        eprintln!("{:?}", gpu_temperature());
+    }
+}
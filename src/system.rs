@@ -0,0 +1,208 @@
+use crate::Bytes;
+
+/// The host around the GPUs, for selection heuristics that weigh VRAM
+/// against system memory, e.g. on unified memory Macs
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct SystemContext {
+    /// Physical memory installed, 0 when unknown
+    pub total_ram: Bytes,
+    /// e.g. `AMD Ryzen 9 7950X 16-Core` or `Apple M2 Pro`
+    pub cpu_model: Option<String>,
+    /// Distribution or product name and version, e.g. `Ubuntu 24.04.1 LTS`
+    pub os_version: Option<String>,
+}
+
+/// Read the [`SystemContext`]. Not part of [`GpuQuery`](crate::GpuQuery)
+/// results, as most callers only need the GPUs.
+pub fn system_context() -> SystemContext {
+    SystemContext {
+        total_ram: total_ram().unwrap_or_default(),
+        cpu_model: cpu_model(),
+        os_version: os_version(),
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn total_ram() -> Option<Bytes> {
+    parse_meminfo_total(&std::fs::read_to_string("/proc/meminfo").ok()?)
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn total_ram() -> Option<Bytes> {
+    let bytes = sysctl::<u64>(c"hw.memsize")?;
+    Some(Bytes(bytes))
+}
+
+#[cfg(windows)]
+pub(crate) fn total_ram() -> Option<Bytes> {
+    crate::dxgi::read_total_ram().map(Bytes)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub(crate) fn total_ram() -> Option<Bytes> {
+    None
+}
+
+/// `MemTotal` is in KiB
+#[cfg(any(target_os = "linux", test))]
+fn parse_meminfo_total(meminfo: &str) -> Option<Bytes> {
+    let line = meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemTotal:"))?;
+    let kib: u64 = line.trim().strip_suffix("kB")?.trim().parse().ok()?;
+    Some(Bytes(kib * 1024))
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn cpu_model() -> Option<String> {
+    parse_cpu_model(&std::fs::read_to_string("/proc/cpuinfo").ok()?)
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn cpu_model() -> Option<String> {
+    let mut buffer = [0u8; 128];
+    let len = sysctl_into(c"machdep.cpu.brand_string", &mut buffer)?;
+    let brand = std::ffi::CStr::from_bytes_until_nul(&buffer[..len]).ok()?;
+    clean_cpu_model(&brand.to_string_lossy())
+}
+
+#[cfg(windows)]
+pub(crate) fn cpu_model() -> Option<String> {
+    clean_cpu_model(&crate::dxgi::read_cpu_brand()?)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub(crate) fn cpu_model() -> Option<String> {
+    None
+}
+
+/// The first `model name` in `/proc/cpuinfo`
+#[cfg(any(target_os = "linux", test))]
+fn parse_cpu_model(cpuinfo: &str) -> Option<String> {
+    clean_cpu_model(cpuinfo.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        (key.trim() == "model name").then_some(value)
+    })?)
+}
+
+/// Without the graphics suffix, e.g. `AMD Ryzen Z1 Extreme` from
+/// `AMD Ryzen Z1 Extreme w/ Radeon 780M Graphics`
+fn clean_cpu_model(name: &str) -> Option<String> {
+    let name = name.trim();
+    let name = name.split(" w/ ").next().unwrap_or(name);
+    let name = name.strip_suffix(" Processor").unwrap_or(name);
+    Some(name.trim().to_string()).filter(|name| !name.is_empty())
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn sysctl<T: Default + Copy>(name: &std::ffi::CStr) -> Option<T> {
+    let mut value = T::default();
+    let mut len = std::mem::size_of::<T>();
+    let result = unsafe {
+        sysctlbyname(
+            name.as_ptr(),
+            (&mut value as *mut T).cast(),
+            &mut len,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    (result == 0 && len == std::mem::size_of::<T>()).then_some(value)
+}
+
+/// Length written, including the trailing nul of strings
+#[cfg(target_os = "macos")]
+fn sysctl_into(name: &std::ffi::CStr, buffer: &mut [u8]) -> Option<usize> {
+    let mut len = buffer.len();
+    let result = unsafe {
+        sysctlbyname(
+            name.as_ptr(),
+            buffer.as_mut_ptr().cast(),
+            &mut len,
+            std::ptr::null_mut(),
+            0,
+        )
+    };
+    (result == 0).then_some(len)
+}
+
+#[cfg(target_os = "macos")]
+extern "C" {
+    fn sysctlbyname(
+        name: *const std::ffi::c_char,
+        oldp: *mut std::ffi::c_void,
+        oldlenp: *mut usize,
+        newp: *mut std::ffi::c_void,
+        newlen: usize,
+    ) -> std::ffi::c_int;
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn os_version() -> Option<String> {
+    let os_release = std::fs::read_to_string("/etc/os-release").ok()?;
+    os_release.lines().find_map(|line| {
+        Some(
+            line.strip_prefix("PRETTY_NAME=")?
+                .trim_matches('"')
+                .to_string(),
+        )
+    })
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn os_version() -> Option<String> {
+    let plist = std::fs::read_to_string("/System/Library/CoreServices/SystemVersion.plist").ok()?;
+    let value = |key: &str| {
+        let rest = &plist[plist.find(&format!("<key>{key}</key>"))?..];
+        let start = rest.find("<string>")? + "<string>".len();
+        let end = rest.find("</string>")?;
+        rest.get(start..end).map(str::to_string)
+    };
+    Some(format!(
+        "{} {} ({})",
+        value("ProductName")?,
+        value("ProductVersion")?,
+        value("ProductBuildVersion")?
+    ))
+}
+
+#[cfg(windows)]
+pub(crate) fn os_version() -> Option<String> {
+    crate::dxgi::read_windows_version()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+pub(crate) fn os_version() -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_context() {
+        eprintln!("{:#?}", system_context());
+    }
+
+    #[test]
+    fn test_parse_proc_files() {
+        let meminfo = "MemTotal:       32594532 kB\nMemFree:         1234 kB\n";
+        assert_eq!(parse_meminfo_total(meminfo), Some(Bytes(32594532 * 1024)));
+        assert_eq!(parse_meminfo_total("MemFree: 1 kB\n"), None);
+
+        let cpuinfo = "processor\t: 0\nvendor_id\t: AuthenticAMD\n\
+                       model name\t: AMD Ryzen Z1 Extreme w/ Radeon 780M Graphics\n";
+        assert_eq!(
+            parse_cpu_model(cpuinfo).as_deref(),
+            Some("AMD Ryzen Z1 Extreme")
+        );
+        assert_eq!(
+            parse_cpu_model("model name\t: AMD Custom APU 0405\n").as_deref(),
+            Some("AMD Custom APU 0405")
+        );
+        assert_eq!(parse_cpu_model("processor\t: 0\n"), None);
+    }
+}
@@ -0,0 +1,233 @@
+use serde_json::Value;
+
+use crate::Bytes;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SystemProfilerError {
+    #[error("Failed to run system_profiler: {0}")]
+    Command(#[from] std::io::Error),
+    #[error("Failed to parse system_profiler output: {0}")]
+    Parse(String),
+}
+
+/// A GPU as reported by `system_profiler SPDisplaysDataType`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemProfilerGpu {
+    pub name: String,
+    pub vendor: String,
+    /// `None` when system_profiler does not list a VRAM size
+    pub vram: Option<Bytes>,
+    pub bus: String,
+    pub cores: Option<u32>,
+}
+
+/// A field on which the Metal backend and system_profiler disagree
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Discrepancy {
+    pub gpu_name: String,
+    pub field: &'static str,
+    pub metal: String,
+    pub system_profiler: String,
+}
+
+#[cfg(target_os = "macos")]
+pub fn retrieve_gpu_info_via_system_profiler() -> Result<Vec<SystemProfilerGpu>, SystemProfilerError>
+{
+    let output = std::process::Command::new("system_profiler")
+        .args(["SPDisplaysDataType", "-json"])
+        .output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(SystemProfilerError::Command(std::io::Error::other(
+            format!("{}: {}", output.status, stderr.trim()),
+        )));
+    }
+    parse_displays_json(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Compare the Metal results against system_profiler, the tool users check
+/// when they report wrong VRAM numbers.
+#[cfg(target_os = "macos")]
+pub fn verify_with_system_profiler(
+    gpus: &[crate::MetalGpu],
+) -> Result<Vec<Discrepancy>, SystemProfilerError> {
+    let reported = retrieve_gpu_info_via_system_profiler()?;
+    let (pairs, unpaired) = pair_by_name(gpus.iter().map(|gpu| gpu.name.as_str()), &reported);
+    let mut discrepancies = Vec::new();
+
+    for (gpu, pair) in gpus.iter().zip(pairs) {
+        let Some(other) = pair.map(|index| &reported[index]) else {
+            discrepancies.push(Discrepancy {
+                gpu_name: gpu.name.clone(),
+                field: "name",
+                metal: gpu.name.clone(),
+                system_profiler: String::new(),
+            });
+            continue;
+        };
+
+        if !other.vendor.is_empty() && !other.vendor.eq_ignore_ascii_case(&gpu.vendor) {
+            discrepancies.push(Discrepancy {
+                gpu_name: gpu.name.clone(),
+                field: "vendor",
+                metal: gpu.vendor.clone(),
+                system_profiler: other.vendor.clone(),
+            });
+        }
+        // Unified memory GPUs have no fixed VRAM size to compare against
+        if let Some(vram) = other.vram.filter(|_| !gpu.has_unified_memory) {
+            if vram != gpu.vram {
+                discrepancies.push(Discrepancy {
+                    gpu_name: gpu.name.clone(),
+                    field: "vram",
+                    metal: gpu.vram.to_string(),
+                    system_profiler: vram.to_string(),
+                });
+            }
+        }
+    }
+
+    for other in unpaired.into_iter().map(|index| &reported[index]) {
+        discrepancies.push(Discrepancy {
+            gpu_name: other.name.clone(),
+            field: "name",
+            metal: String::new(),
+            system_profiler: other.name.clone(),
+        });
+    }
+
+    Ok(discrepancies)
+}
+
+/// Pair each name with its own entry of `reported`, in order, so two GPUs of
+/// the same model are not both compared against the first one. Returns the
+/// index paired with each name and the indices left over.
+#[cfg(any(target_os = "macos", test))]
+fn pair_by_name<'a>(
+    names: impl IntoIterator<Item = &'a str>,
+    reported: &[SystemProfilerGpu],
+) -> (Vec<Option<usize>>, Vec<usize>) {
+    let mut unpaired: Vec<usize> = (0..reported.len()).collect();
+    let pairs = names
+        .into_iter()
+        .map(|name| {
+            let position = unpaired
+                .iter()
+                .position(|&index| reported[index].name == name)?;
+            Some(unpaired.remove(position))
+        })
+        .collect();
+    (pairs, unpaired)
+}
+
+pub fn parse_displays_json(json: &str) -> Result<Vec<SystemProfilerGpu>, SystemProfilerError> {
+    let root: Value =
+        serde_json::from_str(json).map_err(|e| SystemProfilerError::Parse(e.to_string()))?;
+    let items = root
+        .get("SPDisplaysDataType")
+        .and_then(Value::as_array)
+        .ok_or_else(|| SystemProfilerError::Parse("missing SPDisplaysDataType".to_string()))?;
+
+    let string = |item: &Value, key: &str| {
+        item.get(key)
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string()
+    };
+
+    Ok(items
+        .iter()
+        .map(|item| SystemProfilerGpu {
+            name: string(item, "sppci_model"),
+            // e.g. `sppci_vendor_Apple`, or a plain string for third party vendors
+            vendor: string(item, "spdisplays_vendor")
+                .trim_start_matches("sppci_vendor_")
+                .to_string(),
+            vram: item
+                .get("spdisplays_vram")
+                .or_else(|| item.get("spdisplays_vram_shared"))
+                .and_then(Value::as_str)
+                .and_then(parse_vram),
+            bus: string(item, "sppci_bus"),
+            cores: item
+                .get("sppci_cores")
+                .and_then(Value::as_str)
+                .and_then(|cores| cores.parse().ok()),
+        })
+        .collect())
+}
+
+/// Parse sizes like `8 GB` or `1536 MB`
+fn parse_vram(value: &str) -> Option<Bytes> {
+    let (amount, unit) = value.trim().split_once(' ')?;
+    let amount: u64 = amount.parse().ok()?;
+    match unit {
+        "MB" => Some(Bytes::from_mb(amount)),
+        "GB" => Some(Bytes::from_mb(amount * 1024)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_displays_json() {
+        let json = r#"{
+            "SPDisplaysDataType": [
+                {
+                    "_name": "kHW_AMDRadeonPro5500MItem",
+                    "sppci_bus": "spdisplays_pcie_device",
+                    "sppci_model": "AMD Radeon Pro 5500M",
+                    "spdisplays_vendor": "sppci_vendor_amd",
+                    "spdisplays_vram": "8 GB"
+                },
+                {
+                    "_name": "Apple M2 Max",
+                    "sppci_bus": "spdisplays_builtin",
+                    "sppci_cores": "38",
+                    "sppci_model": "Apple M2 Max",
+                    "spdisplays_vendor": "sppci_vendor_Apple"
+                }
+            ]
+        }"#;
+
+        let gpus = parse_displays_json(json).unwrap();
+        assert_eq!(gpus.len(), 2);
+        assert_eq!(gpus[0].name, "AMD Radeon Pro 5500M");
+        assert_eq!(gpus[0].vram, Some(Bytes::from_mb(8192)));
+        assert_eq!(gpus[1].vendor, "Apple");
+        assert_eq!(gpus[1].vram, None);
+        assert_eq!(gpus[1].cores, Some(38));
+    }
+
+    #[test]
+    fn test_pair_by_name() {
+        let gpu = |name: &str| SystemProfilerGpu {
+            name: name.to_string(),
+            vendor: "amd".to_string(),
+            vram: Some(Bytes::from_mb(8192)),
+            bus: "spdisplays_pcie_device".to_string(),
+            cores: None,
+        };
+        // Two eGPUs of the same model, of which Metal sees only one
+        let reported = [
+            gpu("AMD Radeon RX 6800"),
+            gpu("Intel UHD Graphics 630"),
+            gpu("AMD Radeon RX 6800"),
+        ];
+
+        let (pairs, unpaired) = pair_by_name(
+            ["AMD Radeon RX 6800", "Intel UHD Graphics 630", "Apple M2"],
+            &reported,
+        );
+        assert_eq!(pairs, [Some(0), Some(1), None]);
+        assert_eq!(unpaired, [2]);
+
+        let (pairs, unpaired) =
+            pair_by_name(["AMD Radeon RX 6800", "AMD Radeon RX 6800"], &reported);
+        assert_eq!(pairs, [Some(0), Some(2)]);
+        assert_eq!(unpaired, [1]);
+    }
+}
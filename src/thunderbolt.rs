@@ -0,0 +1,272 @@
+use crate::{Error, GPU};
+
+/// Link speed Thunderbolt 3 eGPU enclosures are specified for, of which
+/// about 22 Gb/s carry PCIe
+#[cfg(any(target_os = "linux", test))]
+const FULL_LINK_GBPS: f32 = 40.0;
+
+/// PCIe lanes Thunderbolt 3 and later tunnel to an eGPU
+#[cfg(any(target_os = "linux", test))]
+const FULL_PCIE_WIDTH: u32 = 4;
+
+/// Thunderbolt controller generation, as Linux reports it. Thunderbolt 4
+/// controllers are USB4 ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[non_exhaustive]
+pub enum ThunderboltGeneration {
+    Thunderbolt1,
+    Thunderbolt2,
+    Thunderbolt3,
+    Usb4,
+}
+
+impl ThunderboltGeneration {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ThunderboltGeneration::Thunderbolt1 => "Thunderbolt 1",
+            ThunderboltGeneration::Thunderbolt2 => "Thunderbolt 2",
+            ThunderboltGeneration::Thunderbolt3 => "Thunderbolt 3",
+            ThunderboltGeneration::Usb4 => "USB4",
+        }
+    }
+}
+
+/// How an external GPU is connected, see [`external_link`]
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct ExternalLink {
+    /// Vendor and model of the enclosure's Thunderbolt controller, e.g.
+    /// `Razer Core X`. `None` when several devices are connected.
+    pub enclosure: Option<String>,
+    pub generation: Option<ThunderboltGeneration>,
+    /// Speed of the Thunderbolt link to the enclosure in Gb/s, all lanes of
+    /// one direction
+    pub link_gbps: Option<f32>,
+    /// Narrowest PCIe link between the root port and the GPU. Laptops
+    /// wiring their Thunderbolt controller with two lanes halve the
+    /// bandwidth left to the GPU.
+    pub pcie_link_width: Option<u32>,
+    /// Whether the link is slower than the 40 Gb/s or narrower than the
+    /// four PCIe lanes Thunderbolt 3 enclosures are specified for
+    pub bandwidth_limited: Option<bool>,
+}
+
+/// The Thunderbolt or USB4 link of an external GPU, `None` for GPUs that
+/// are not external. eGPU performance issues are almost always down to the
+/// link, so this is the first thing to check. Only Linux reports the link,
+/// macOS only tells that the GPU is external, and Windows tells nothing.
+pub fn external_link(gpu: &GPU) -> Result<Option<ExternalLink>, Error> {
+    #[cfg(target_os = "linux")]
+    return Ok(gpu.pci_address.and_then(|address| {
+        external_link_via_sysfs(
+            std::path::Path::new(crate::pci::SYSFS_PCI_DEVICES),
+            std::path::Path::new(THUNDERBOLT_DEVICES),
+            address,
+        )
+    }));
+
+    #[cfg(target_os = "macos")]
+    return Ok(crate::find_metal_gpu(gpu)?
+        .filter(|metal_gpu| metal_gpu.location == crate::GPULocation::External)
+        .map(|_| ExternalLink::default()));
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    {
+        let _ = gpu;
+        Ok(None)
+    }
+}
+
+#[cfg(target_os = "linux")]
+const THUNDERBOLT_DEVICES: &str = "/sys/bus/thunderbolt/devices";
+
+#[cfg(target_os = "linux")]
+fn external_link_via_sysfs(
+    pci_root: &std::path::Path,
+    thunderbolt_root: &std::path::Path,
+    address: crate::PciAddress,
+) -> Option<ExternalLink> {
+    let device = pci_root.join(address.to_string());
+    // Set for devices behind an external facing Thunderbolt or USB4 port
+    if read_string(&device.join("removable"))? != "removable" {
+        return None;
+    }
+
+    // The device links into the tree of bridges leading to it
+    let path = std::fs::canonicalize(&device).ok()?;
+    let pcie_link_width = path
+        .ancestors()
+        .take_while(|ancestor| {
+            ancestor
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.parse::<crate::PciAddress>().is_ok())
+        })
+        .filter_map(|ancestor| {
+            read_string(&ancestor.join("current_link_width"))?
+                .parse()
+                .ok()
+        })
+        .filter(|&width: &u32| width > 0)
+        .min();
+
+    let router = single_device_router(thunderbolt_root);
+    let read = |name: &str| read_string(&router.as_ref()?.join(name));
+    let enclosure = match (read("vendor_name"), read("device_name")) {
+        (Some(vendor), Some(device)) => Some(format!("{vendor} {device}")),
+        (vendor, device) => vendor.or(device),
+    };
+    let generation = read("generation").and_then(|generation| match generation.as_str() {
+        "1" => Some(ThunderboltGeneration::Thunderbolt1),
+        "2" => Some(ThunderboltGeneration::Thunderbolt2),
+        "3" => Some(ThunderboltGeneration::Thunderbolt3),
+        "4" => Some(ThunderboltGeneration::Usb4),
+        _ => None,
+    });
+    let link_gbps = read("rx_speed")
+        .as_deref()
+        .and_then(parse_lane_speed)
+        .zip(read("rx_lanes").and_then(|lanes| lanes.parse::<u32>().ok()))
+        .map(|(speed, lanes)| speed * lanes as f32);
+
+    Some(ExternalLink {
+        enclosure,
+        generation,
+        link_gbps,
+        pcie_link_width,
+        bandwidth_limited: bandwidth_limited(link_gbps, pcie_link_width),
+    })
+}
+
+/// The router of the only device connected directly to a host port, as
+/// routers do not tell which PCIe devices they tunnel to
+#[cfg(target_os = "linux")]
+fn single_device_router(thunderbolt_root: &std::path::Path) -> Option<std::path::PathBuf> {
+    let mut routers = std::fs::read_dir(thunderbolt_root)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| {
+            // `<domain>-<route>`, with a byte of route per hop and route 0
+            // being the host. Services are named `0-1.1`.
+            let name = entry.file_name();
+            let Some((_, route)) = name.to_str().and_then(|name| name.split_once('-')) else {
+                return false;
+            };
+            u64::from_str_radix(route, 16).is_ok_and(|route| route > 0 && route < 0x100)
+        })
+        .map(|entry| entry.path());
+    match (routers.next(), routers.next()) {
+        (Some(router), None) => Some(router),
+        _ => None,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn read_string(path: &std::path::Path) -> Option<String> {
+    let value = std::fs::read_to_string(path).ok()?;
+    Some(value.trim().to_string()).filter(|value| !value.is_empty())
+}
+
+/// Parse a lane speed such as `20.0 Gb/s`
+#[cfg(any(target_os = "linux", test))]
+fn parse_lane_speed(speed: &str) -> Option<f32> {
+    speed.strip_suffix("Gb/s")?.trim().parse().ok()
+}
+
+#[cfg(any(target_os = "linux", test))]
+fn bandwidth_limited(link_gbps: Option<f32>, pcie_link_width: Option<u32>) -> Option<bool> {
+    [
+        link_gbps.map(|gbps| gbps < FULL_LINK_GBPS),
+        pcie_link_width.map(|width| width < FULL_PCIE_WIDTH),
+    ]
+    .into_iter()
+    .flatten()
+    .reduce(|a, b| a || b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bandwidth_limited() {
+        assert_eq!(parse_lane_speed("20.0 Gb/s"), Some(20.0));
+        assert_eq!(parse_lane_speed("10 Gb/s"), Some(10.0));
+        assert_eq!(parse_lane_speed("fast"), None);
+
+        assert_eq!(bandwidth_limited(Some(40.0), Some(4)), Some(false));
+        assert_eq!(bandwidth_limited(Some(40.0), Some(2)), Some(true));
+        assert_eq!(bandwidth_limited(Some(20.0), None), Some(true));
+        assert_eq!(bandwidth_limited(None, None), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_external_link_via_sysfs() {
+        let fixture = tempfile::tempdir().unwrap();
+        let root = fixture.path();
+        let write = |dir: &std::path::Path, name: &str, value: &str| {
+            std::fs::create_dir_all(dir).unwrap();
+            std::fs::write(dir.join(name), value).unwrap();
+        };
+        // Root port, the enclosure's controller and its bridge, then the GPU
+        let root_port = root.join("devices/pci0000:00/0000:00:07.0");
+        let upstream = root_port.join("0000:03:00.0");
+        let downstream = upstream.join("0000:04:01.0");
+        let gpu = downstream.join("0000:05:00.0");
+        write(&root_port, "current_link_width", "4\n");
+        write(&upstream, "current_link_width", "2\n");
+        write(&downstream, "current_link_width", "4\n");
+        write(&gpu, "current_link_width", "16\n");
+        write(&gpu, "removable", "removable\n");
+        write(
+            &root.join("devices/pci0000:00/0000:00:02.0"),
+            "removable",
+            "fixed\n",
+        );
+        let pci = root.join("bus/pci/devices");
+        std::fs::create_dir_all(&pci).unwrap();
+        std::os::unix::fs::symlink(&gpu, pci.join("0000:05:00.0")).unwrap();
+        std::os::unix::fs::symlink(
+            root.join("devices/pci0000:00/0000:00:02.0"),
+            pci.join("0000:00:02.0"),
+        )
+        .unwrap();
+
+        let thunderbolt = root.join("bus/thunderbolt/devices");
+        write(&thunderbolt.join("0-0"), "device_name", "Host\n");
+        let enclosure = thunderbolt.join("0-1");
+        write(&enclosure, "vendor_name", "Razer\n");
+        write(&enclosure, "device_name", "Core X\n");
+        write(&enclosure, "generation", "3\n");
+        write(&enclosure, "rx_speed", "20.0 Gb/s\n");
+        write(&enclosure, "rx_lanes", "2\n");
+        write(&thunderbolt.join("0-1.1"), "key", "\n");
+
+        let link = external_link_via_sysfs(&pci, &thunderbolt, "0000:05:00.0".parse().unwrap());
+        let fixed = external_link_via_sysfs(&pci, &thunderbolt, "0000:00:02.0".parse().unwrap());
+        fixture.close().unwrap();
+
+        assert_eq!(
+            link,
+            Some(ExternalLink {
+                enclosure: Some("Razer Core X".to_string()),
+                generation: Some(ThunderboltGeneration::Thunderbolt3),
+                link_gbps: Some(40.0),
+                pcie_link_width: Some(2),
+                bandwidth_limited: Some(true),
+            })
+        );
+        assert_eq!(fixed, None);
+    }
+
+    #[test]
+    fn test_external_link() {
+        for gpu in crate::retrieve_gpu_info().unwrap() {
+            eprintln!("{}: {:?}", gpu.name, external_link(&gpu));
+        }
+    }
+}
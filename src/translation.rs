@@ -0,0 +1,125 @@
+use crate::GPU;
+
+/// Where the Vulkan loader looks for driver manifests on macOS, as MoltenVK
+/// is installed with the Vulkan SDK or Homebrew
+#[cfg(target_os = "macos")]
+const MACOS_ICD_DIRS: &[&str] = &[
+    "/usr/local/share/vulkan/icd.d",
+    "/opt/homebrew/share/vulkan/icd.d",
+    "/usr/local/etc/vulkan/icd.d",
+    "/etc/vulkan/icd.d",
+    "/usr/share/vulkan/icd.d",
+];
+
+/// The layer implementing Vulkan on top of another API for devices of
+/// `driver_id`
+#[cfg(not(target_os = "macos"))]
+pub(crate) fn vulkan_translation_layer(driver_id: ash::vk::DriverId) -> Option<&'static str> {
+    use ash::vk::DriverId;
+
+    match driver_id {
+        // Metal
+        DriverId::MOLTENVK => Some("MoltenVK"),
+        // The host's Vulkan driver, over virtio-gpu
+        DriverId::MESA_VENUS => Some("Venus"),
+        // D3D12, e.g. in WSL
+        DriverId::MESA_DOZEN => Some("Dozen"),
+        _ => None,
+    }
+}
+
+/// Fill [`GPU::translation_layer`] with the layer the graphics APIs go
+/// through on this system, for GPUs whose driver is not one itself
+pub(crate) fn attach_translation_layers(gpus: &mut [GPU]) {
+    let Some(layer) = system_translation_layer() else {
+        return;
+    };
+    for gpu in gpus {
+        gpu.translation_layer
+            .get_or_insert_with(|| layer.to_string());
+    }
+}
+
+/// Direct3D under Wine or Proton: DXVK when it replaces Wine's DXGI, as
+/// Proton and winetricks set up, WineD3D otherwise
+#[cfg(windows)]
+fn system_translation_layer() -> Option<&'static str> {
+    if !crate::dxgi::is_wine() {
+        return None;
+    }
+    Some(if wine_dll_is_native("dxgi") {
+        "DXVK"
+    } else {
+        "WineD3D"
+    })
+}
+
+/// Whether Wine loads a native build of `dll`, e.g. DXVK's `dxgi`, before
+/// its own, from `WINEDLLOVERRIDES` or the prefix's configuration
+#[cfg(windows)]
+pub(crate) fn wine_dll_is_native(dll: &str) -> bool {
+    let overrides = std::env::var("WINEDLLOVERRIDES").unwrap_or_default();
+    override_is_native(&overrides, dll)
+        || crate::dxgi::wine_dll_override(dll).is_some_and(|mode| mode.starts_with('n'))
+}
+
+/// Vulkan is only available through MoltenVK, when installed
+#[cfg(target_os = "macos")]
+fn system_translation_layer() -> Option<&'static str> {
+    let home = std::env::var("HOME")
+        .ok()
+        .map(|home| format!("{home}/.local/share/vulkan/icd.d"));
+    MACOS_ICD_DIRS
+        .iter()
+        .copied()
+        .chain(home.as_deref())
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .any(|entry| entry.file_name().to_string_lossy().contains("MoltenVK"))
+        .then_some("MoltenVK")
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn system_translation_layer() -> Option<&'static str> {
+    None
+}
+
+/// Whether `WINEDLLOVERRIDES`, e.g. `d3d11,dxgi=n,b;d3d12=n`, loads the
+/// native build of `dll` first
+#[cfg(any(windows, test))]
+fn override_is_native(overrides: &str, dll: &str) -> bool {
+    overrides.split(';').any(|entry| {
+        let Some((dlls, modes)) = entry.split_once('=') else {
+            return false;
+        };
+        dlls.split(',')
+            .any(|name| name.trim().eq_ignore_ascii_case(dll))
+            && modes.trim_start().starts_with('n')
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_override_is_native() {
+        let proton = "steam.exe=b;dotnetfx35.exe=b;d3d11=n;d3d10core=n;dxgi=n;d3d9=n;d3d12=n";
+        assert!(override_is_native(proton, "dxgi"));
+        assert!(override_is_native("d3d11,DXGI=n,b", "dxgi"));
+        assert!(!override_is_native("dxgi=b,n", "dxgi"));
+        assert!(!override_is_native("d3d11=n", "dxgi"));
+        assert!(!override_is_native("", "dxgi"));
+    }
+
+    #[test]
+    fn test_attach_translation_layers() {
+        let mut gpus = vec![GPU {
+            translation_layer: Some("Venus".to_string()),
+            ..Default::default()
+        }];
+        attach_translation_layers(&mut gpus);
+        assert_eq!(gpus[0].translation_layer.as_deref(), Some("Venus"));
+    }
+}
@@ -0,0 +1,262 @@
+//! Typed quantities for the memory, clock, temperature and power fields of
+//! [`GPU`](crate::GPU). Each serializes as a plain number in the unit of its
+//! name, e.g. [`Bytes`] as a number of bytes.
+
+use std::fmt;
+
+const MB: u64 = 1024 * 1024;
+
+/// An amount of memory. This crate's "MB" are mebibytes (1024 × 1024 bytes),
+/// as reported by drivers and system tools.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Bytes(pub u64);
+
+impl Bytes {
+    pub const fn from_mb(mb: u64) -> Self {
+        Self(mb * MB)
+    }
+
+    /// Rounded down
+    pub const fn as_mb(self) -> u64 {
+        self.0 / MB
+    }
+
+    pub fn as_gb(self) -> f64 {
+        self.0 as f64 / (MB * 1024) as f64
+    }
+}
+
+impl std::iter::Sum for Bytes {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        Self(iter.map(|bytes| bytes.0).sum())
+    }
+}
+
+impl std::ops::Add for Bytes {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self(self.0 + other.0)
+    }
+}
+
+/// [`format_vram`] with [`Unit::Auto`], e.g. `512 MiB` or `8 GiB`
+impl fmt::Display for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&format_vram(*self, Unit::Auto))
+    }
+}
+
+/// The unit [`format_vram`] scales an amount of memory to. Binary units
+/// (powers of 1024) match what drivers and task managers report, decimal
+/// ones (powers of 1000) what GPU boxes and spec sheets print.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Unit {
+    /// The largest binary unit the amount reaches one of
+    #[default]
+    Auto,
+    /// The largest decimal unit the amount reaches one of
+    AutoDecimal,
+    Bytes,
+    KiB,
+    MiB,
+    GiB,
+    TiB,
+    KB,
+    MB,
+    GB,
+    TB,
+}
+
+impl Unit {
+    const BINARY: [Unit; 5] = [Unit::Bytes, Unit::KiB, Unit::MiB, Unit::GiB, Unit::TiB];
+    const DECIMAL: [Unit; 5] = [Unit::Bytes, Unit::KB, Unit::MB, Unit::GB, Unit::TB];
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Unit::Auto | Unit::AutoDecimal | Unit::Bytes => "B",
+            Unit::KiB => "KiB",
+            Unit::MiB => "MiB",
+            Unit::GiB => "GiB",
+            Unit::TiB => "TiB",
+            Unit::KB => "kB",
+            Unit::MB => "MB",
+            Unit::GB => "GB",
+            Unit::TB => "TB",
+        }
+    }
+
+    fn size(self) -> u64 {
+        match self {
+            Unit::Auto | Unit::AutoDecimal | Unit::Bytes => 1,
+            Unit::KiB => 1 << 10,
+            Unit::MiB => 1 << 20,
+            Unit::GiB => 1 << 30,
+            Unit::TiB => 1 << 40,
+            Unit::KB => 1_000,
+            Unit::MB => 1_000_000,
+            Unit::GB => 1_000_000_000,
+            Unit::TB => 1_000_000_000_000,
+        }
+    }
+
+    /// The fixed unit `self` resolves to for `bytes`
+    fn resolve(self, bytes: Bytes) -> Unit {
+        let candidates: &[Unit] = match self {
+            Unit::Auto => &Unit::BINARY,
+            Unit::AutoDecimal => &Unit::DECIMAL,
+            unit => return unit,
+        };
+        candidates
+            .iter()
+            .copied()
+            .rfind(|unit| bytes.0 >= unit.size())
+            .unwrap_or(Unit::Bytes)
+    }
+}
+
+/// `bytes` in `unit`, with at most one decimal and none for whole numbers,
+/// e.g. `8 GiB` for 8589934592 bytes or `8.6 GB` with [`Unit::AutoDecimal`]
+pub fn format_vram(bytes: Bytes, unit: Unit) -> String {
+    let (value, unit) = scale(bytes, unit);
+    format!("{} {}", format_number(value, '.'), unit.symbol())
+}
+
+pub(crate) fn scale(bytes: Bytes, unit: Unit) -> (f64, Unit) {
+    let unit = unit.resolve(bytes);
+    (bytes.0 as f64 / unit.size() as f64, unit)
+}
+
+/// Rounded to one decimal, which is dropped when it is 0
+pub(crate) fn format_number(value: f64, decimal_separator: char) -> String {
+    let rounded = (value * 10.0).round() / 10.0;
+    if rounded.fract() == 0.0 {
+        format!("{rounded:.0}")
+    } else {
+        format!("{rounded:.1}").replace('.', &decimal_separator.to_string())
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct MegaHertz(pub u32);
+
+impl MegaHertz {
+    pub fn as_ghz(self) -> f64 {
+        self.0 as f64 / 1000.0
+    }
+}
+
+impl fmt::Display for MegaHertz {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} MHz", self.0)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Celsius(pub u32);
+
+impl Celsius {
+    pub fn as_fahrenheit(self) -> f64 {
+        self.0 as f64 * 9.0 / 5.0 + 32.0
+    }
+}
+
+impl fmt::Display for Celsius {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} °C", self.0)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Watts(pub u32);
+
+impl Watts {
+    /// Rounded down, as NVML and hwmon report milliwatts and microwatts
+    pub const fn from_milliwatts(milliwatts: u32) -> Self {
+        Self(milliwatts / 1000)
+    }
+}
+
+impl fmt::Display for Watts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} W", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_units() {
+        assert_eq!(Bytes::from_mb(24576).as_mb(), 24576);
+        assert_eq!(Bytes::from_mb(24576).as_gb(), 24.0);
+        assert_eq!(Bytes::from_mb(512).to_string(), "512 MiB");
+        assert_eq!(Bytes::from_mb(8192).to_string(), "8 GiB");
+        assert_eq!([Bytes(1), Bytes(2)].into_iter().sum::<Bytes>(), Bytes(3));
+        assert_eq!(MegaHertz(1800).as_ghz(), 1.8);
+        assert_eq!(Celsius(100).as_fahrenheit(), 212.0);
+        assert_eq!(Watts::from_milliwatts(250_900), Watts(250));
+    }
+
+    #[test]
+    fn test_format_vram() {
+        let bytes = Bytes(8_589_934_592);
+        assert_eq!(format_vram(bytes, Unit::Auto), "8 GiB");
+        assert_eq!(format_vram(bytes, Unit::AutoDecimal), "8.6 GB");
+        assert_eq!(format_vram(bytes, Unit::MiB), "8192 MiB");
+        assert_eq!(
+            format_vram(Bytes::from_mb(12 * 1024 + 512), Unit::GiB),
+            "12.5 GiB"
+        );
+        assert_eq!(format_vram(Bytes(0), Unit::Auto), "0 B");
+        assert_eq!(format_vram(Bytes(1536), Unit::Auto), "1.5 KiB");
+        assert_eq!(format_vram(Bytes(1_500_000), Unit::AutoDecimal), "1.5 MB");
+        assert_eq!(format_vram(Bytes(999), Unit::AutoDecimal), "999 B");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_units_serialize_as_numbers() {
+        assert_eq!(
+            serde_json::to_string(&Bytes::from_mb(1)).unwrap(),
+            "1048576"
+        );
+        assert_eq!(serde_json::from_str::<Celsius>("65").unwrap(), Celsius(65));
+
+        // Renamed from the keys that held MB, so older readers see no value
+        // rather than a wrong one
+        let gpu = serde_json::to_value(crate::GPU::builder().vram_mb(1).build()).unwrap();
+        assert_eq!(gpu["vram_bytes"], 1048576);
+        assert!(gpu.get("vram").is_none());
+    }
+}
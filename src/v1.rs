@@ -0,0 +1,35 @@
+//! The stable API, for applications that want to upgrade without surprises.
+//!
+//! Everything re-exported here keeps its name, signature and meaning across
+//! semver-compatible releases, which `cargo semver-checks` verifies before
+//! each release. When an item has to change, the old form stays as a
+//! `#[deprecated]` shim pointing at its replacement for at least one
+//! release, so a build with warnings shows the migration before a build
+//! breaks. Shims are only removed in a semver-incompatible release.
+//!
+//! Enums and [`GPU`] are `#[non_exhaustive]`: new variants and fields are
+//! not breaking changes, so match with a wildcard arm and build GPUs with
+//! [`GPU::builder`].
+//!
+//! Items outside this module, such as the backend specific structs
+//! (`VulkanGpu`, `MetalGpu`, `DxgiAdapter`, ...) and everything behind a
+//! feature flag, follow the raw units and shapes of their API and change
+//! whenever that API does.
+//!
+//! ```no_run
+//! use gpu_info::v1::{retrieve_gpu_info, GPUKind};
+//!
+//! for gpu in retrieve_gpu_info()? {
+//!     let discrete = matches!(gpu.kind, GPUKind::Discrete);
+//!     println!("{} ({}, discrete: {discrete})", gpu.name, gpu.vram);
+//! }
+//! # Ok::<(), gpu_info::v1::Error>(())
+//! ```
+
+pub use crate::{
+    compare_performance, engine_utilization, memory_budget, primary_gpu, refresh_gpu,
+    retrieve_gpu_info, self_vram_usage, suggested_gpu_memory_budget, Bytes, Celsius, DisplayOutput,
+    EngineClass, EngineClassUtilization, Error, GPUKind, GPULocation, GpuBuilder, GpuList,
+    GpuPartition, GpuPreference, GpuQuery, HybridRole, MegaHertz, MemoryBudget, PciAddress,
+    ProductSegment, TemperatureLocation, TemperatureSensor, Watts, GPU,
+};
@@ -0,0 +1,91 @@
+use std::sync::OnceLock;
+
+use crate::GPUKind;
+
+/// Emulated and paravirtual display adapters, by PCI vendor and device id
+#[cfg(not(target_os = "macos"))]
+const PARAVIRTUAL_ADAPTERS: &[(u32, u32, &str)] = &[
+    (0x1AF4, 0x1050, "virtio-gpu"),
+    (0x15AD, 0x0405, "VMware SVGA II"),
+    (0x15AD, 0x0406, "VMware SVGA 3"),
+    (0x80EE, 0xBEEF, "VirtualBox Graphics Adapter"),
+    (0x1AB8, 0x4005, "Parallels Display Adapter"),
+    (0x1414, 0x5353, "Hyper-V Video"),
+    (0x1234, 0x1111, "QEMU Standard VGA"),
+    (0x1B36, 0x0100, "QXL"),
+    (0x1013, 0x00B8, "Cirrus CLGD 5446"),
+];
+
+/// Name of the hypervisor display adapter with these ids, `None` for
+/// everything else including GPUs passed through to a virtual machine
+#[cfg(not(target_os = "macos"))]
+pub(crate) fn paravirtual_adapter(vendor_id: u32, device_id: u32) -> Option<&'static str> {
+    PARAVIRTUAL_ADAPTERS
+        .iter()
+        .find(|&&(vendor, device, _)| vendor == vendor_id && device == device_id)
+        .map(|&(_, _, name)| name)
+}
+
+/// Whether this process runs in a virtual machine, from the CPUID hypervisor
+/// bit on x86 and the DMI system vendor on other Linux machines
+pub fn in_virtual_machine() -> bool {
+    static IN_VIRTUAL_MACHINE: OnceLock<bool> = OnceLock::new();
+    *IN_VIRTUAL_MACHINE.get_or_init(detect_virtual_machine)
+}
+
+#[cfg(target_arch = "x86_64")]
+fn detect_virtual_machine() -> bool {
+    std::arch::x86_64::__cpuid(1).ecx & (1 << 31) != 0
+}
+
+#[cfg(all(target_os = "linux", not(target_arch = "x86_64")))]
+fn detect_virtual_machine() -> bool {
+    const VENDORS: &[&str] = &[
+        "QEMU",
+        "VMware",
+        "innotek",
+        "Parallels",
+        "Xen",
+        "Amazon EC2",
+        "Google",
+    ];
+    std::fs::read_to_string("/sys/class/dmi/id/sys_vendor").is_ok_and(|vendor| {
+        VENDORS
+            .iter()
+            .any(|known| vendor.trim_start().starts_with(known))
+    }) || std::path::Path::new("/sys/hypervisor/type").exists()
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_os = "linux")))]
+fn detect_virtual_machine() -> bool {
+    false
+}
+
+/// `GPU::passthrough` for a GPU of the given kind
+pub(crate) fn passthrough(kind: &GPUKind) -> Option<bool> {
+    match kind {
+        GPUKind::Virtual => Some(false),
+        GPUKind::Integrated | GPUKind::Discrete if in_virtual_machine() => Some(true),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_passthrough() {
+        assert_eq!(passthrough(&GPUKind::Virtual), Some(false));
+        assert_eq!(passthrough(&GPUKind::Software), None);
+        assert_eq!(
+            passthrough(&GPUKind::Discrete),
+            in_virtual_machine().then_some(true)
+        );
+        #[cfg(not(target_os = "macos"))]
+        {
+            assert_eq!(paravirtual_adapter(0x1AF4, 0x1050), Some("virtio-gpu"));
+            assert_eq!(paravirtual_adapter(0x10DE, 0x2684), None);
+        }
+    }
+}
@@ -1,6 +1,13 @@
 use std::ffi::CStr;
+use std::time::{Duration, Instant};
 
-use crate::{GPUKind, GPU};
+#[cfg(feature = "bench")]
+use crate::bench::{self, Benchmark};
+#[cfg(feature = "counters")]
+use crate::counters::{Counter, CounterGroup, CounterUnit, CounterValue};
+use crate::icd::{icd_for_driver, retrieve_vulkan_icds, VulkanIcd};
+use crate::vm::paravirtual_adapter;
+use crate::{vendor_from_id, AllocationProbe, Bytes, GPUKind, Health, MemoryBudget, PciAddress};
 use ash::vk;
 
 #[derive(Debug, thiserror::Error)]
@@ -9,113 +16,1895 @@ pub enum VulkanError {
     NotSupported,
     #[error("Failed to perform Vulkan operation: {0}")]
     OperationFailed(String),
+    /// `VK_ERROR_DEVICE_LOST`, after a driver reset or the device was unplugged
+    #[error("Vulkan device lost: {0}")]
+    DeviceLost(String),
 }
 
 impl VulkanError {
     pub fn is_not_supported(&self) -> bool {
         matches!(self, VulkanError::NotSupported)
     }
+
+    pub fn is_device_lost(&self) -> bool {
+        matches!(self, VulkanError::DeviceLost(_))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VulkanGpu {
+    pub kind: GPUKind,
+    /// Invalid UTF-8 is replaced with `U+FFFD`
+    pub name: String,
+    /// The name as the driver reported it, for names in a legacy encoding,
+    /// e.g. GBK from some Chinese localized drivers
+    pub raw_name: Vec<u8>,
+    pub vendor: String,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    pub driver_version: String,
+    pub vram: Bytes,
+    /// System memory the GPU can address
+    pub shared_memory: Option<Bytes>,
+    pub queue_families: Vec<QueueFamily>,
+    /// Requires Vulkan 1.1
+    pub device_uuid: Option<[u8; 16]>,
+    /// Only reported on Windows, requires Vulkan 1.1
+    pub luid: Option<u64>,
+    /// Requires Vulkan 1.1 and `VK_EXT_pci_bus_info`
+    pub pci_address: Option<PciAddress>,
+    /// Every memory heap, `vram` only sums the device local ones
+    pub memory_heaps: Vec<HeapInfo>,
+    /// e.g. `radv` or `NVIDIA`. Requires Vulkan 1.2 or `VK_KHR_driver_properties`,
+    /// like `conformance_version` and `icd`.
+    pub driver_name: Option<String>,
+    /// Version of the conformance test suite the driver passed, e.g. `1.3.8.2`
+    pub conformance_version: Option<String>,
+    /// Manifest of the driver the loader picked for this device, recognized by
+    /// its library name
+    pub icd: Option<VulkanIcd>,
+    /// `VK_KHR_ray_tracing_pipeline` or `VK_KHR_ray_query`
+    pub ray_tracing: bool,
+    /// `VK_EXT_mesh_shader` or `VK_NV_mesh_shader`
+    pub mesh_shading: bool,
+    /// Physical devices in the device group of this one. Requires Vulkan 1.1
+    pub device_group_size: Option<u32>,
+    /// e.g. `Venus` when the driver implements Vulkan over another API.
+    /// Requires Vulkan 1.2 or `VK_KHR_driver_properties`.
+    pub translation_layer: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct QueueFamily {
+    pub queue_count: u32,
+    pub graphics: bool,
+    pub compute: bool,
+    pub transfer: bool,
+}
+
+/// A Vulkan memory heap and the memory types allocated from it. On APUs the
+/// carve-out and the GTT aperture can both be device local.
+#[derive(Debug, Clone)]
+pub struct HeapInfo {
+    pub size: Bytes,
+    pub device_local: bool,
+    pub memory_types: Vec<MemoryType>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MemoryType {
+    pub device_local: bool,
+    pub host_visible: bool,
+    pub host_coherent: bool,
+    pub host_cached: bool,
+}
+
+impl HeapInfo {
+    /// Whether the CPU can map memory from this heap
+    pub fn host_visible(&self) -> bool {
+        self.memory_types.iter().any(|ty| ty.host_visible)
+    }
+}
+
+impl From<vk::MemoryType> for MemoryType {
+    fn from(ty: vk::MemoryType) -> Self {
+        let flags = ty.property_flags;
+        Self {
+            device_local: flags.contains(vk::MemoryPropertyFlags::DEVICE_LOCAL),
+            host_visible: flags.contains(vk::MemoryPropertyFlags::HOST_VISIBLE),
+            host_coherent: flags.contains(vk::MemoryPropertyFlags::HOST_COHERENT),
+            host_cached: flags.contains(vk::MemoryPropertyFlags::HOST_CACHED),
+        }
+    }
+}
+
+fn memory_heaps(properties: &vk::PhysicalDeviceMemoryProperties) -> Vec<HeapInfo> {
+    let types = &properties.memory_types[..properties.memory_type_count as usize];
+    properties.memory_heaps[..properties.memory_heap_count as usize]
+        .iter()
+        .enumerate()
+        .map(|(index, heap)| HeapInfo {
+            size: Bytes(heap.size),
+            device_local: heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL),
+            memory_types: types
+                .iter()
+                .filter(|ty| ty.heap_index as usize == index)
+                .copied()
+                .map(MemoryType::from)
+                .collect(),
+        })
+        .collect()
+}
+
+/// `(vram, shared_memory)`. Integrated GPUs report system RAM as a device
+/// local heap, only AMD APUs expose their carve-out as a separate heap.
+fn split_memory(vendor_id: u32, kind: &GPUKind, heaps: &[HeapInfo]) -> (Bytes, Option<Bytes>) {
+    let device_local = heaps
+        .iter()
+        .filter(|heap| heap.device_local)
+        .map(|heap| heap.size)
+        .sum::<Bytes>();
+    let system = heaps
+        .iter()
+        .filter(|heap| !heap.device_local)
+        .map(|heap| heap.size)
+        .sum::<Bytes>();
+
+    match (kind, vendor_id) {
+        (GPUKind::Integrated, 0x1002) => (device_local, Some(system)),
+        // Every heap is a view of the same system RAM, e.g. the Intel GTT
+        (GPUKind::Integrated, _) => (Bytes(0), heaps.iter().map(|heap| heap.size).max()),
+        _ => (device_local, (system.0 > 0).then_some(system)),
+    }
+}
+
+impl From<vk::QueueFamilyProperties> for QueueFamily {
+    fn from(props: vk::QueueFamilyProperties) -> Self {
+        Self {
+            queue_count: props.queue_count,
+            graphics: props.queue_flags.contains(vk::QueueFlags::GRAPHICS),
+            compute: props.queue_flags.contains(vk::QueueFlags::COMPUTE),
+            transfer: props.queue_flags.contains(vk::QueueFlags::TRANSFER),
+        }
+    }
+}
+
+impl VulkanGpu {
+    /// Total number of queues that accept compute work, across all families.
+    pub fn compute_queue_count(&self) -> u32 {
+        self.queue_families
+            .iter()
+            .filter(|family| family.compute)
+            .map(|family| family.queue_count)
+            .sum()
+    }
+
+    /// Async compute is only practical with a dedicated compute family,
+    /// i.e. one that does not also accept graphics work.
+    pub fn has_async_compute(&self) -> bool {
+        self.queue_families
+            .iter()
+            .any(|family| family.compute && !family.graphics && family.queue_count > 0)
+    }
+}
+
+impl From<VulkanGpu> for super::GPU {
+    fn from(gpu: VulkanGpu) -> Self {
+        // Venus forwards Vulkan to the host and reports the host GPU's type
+        let kind = if paravirtual_adapter(gpu.vendor_id, gpu.device_id).is_some()
+            || gpu.driver_name.as_deref() == Some("venus")
+        {
+            GPUKind::Virtual
+        } else {
+            gpu.kind.clone()
+        };
+        Self {
+            passthrough: crate::vm::passthrough(&kind),
+            compute_queue_count: Some(gpu.compute_queue_count()),
+            async_compute: Some(gpu.has_async_compute()),
+            kind,
+            name: gpu.name,
+            vendor: gpu.vendor,
+            driver_version: gpu.driver_version,
+            vram: gpu.vram,
+            shared_memory: gpu.shared_memory,
+            clock_speed: None, // Vulkan does not provide clock speed
+            luid: gpu.luid,
+            pci_address: gpu.pci_address,
+            uuid: gpu.device_uuid.as_ref().map(format_uuid),
+            hardware_ray_tracing: Some(gpu.ray_tracing),
+            mesh_shading: Some(gpu.mesh_shading),
+            linked_node_count: gpu.device_group_size,
+            translation_layer: gpu.translation_layer,
+            #[cfg(feature = "extra")]
+            extra: crate::extra([
+                ("vulkan.vendor_id", gpu.vendor_id.into()),
+                ("vulkan.device_id", gpu.device_id.into()),
+                ("vulkan.driver_name", gpu.driver_name.into()),
+                ("vulkan.conformance_version", gpu.conformance_version.into()),
+                (
+                    "vulkan.icd_manifest",
+                    gpu.icd.map(|icd| icd.manifest_path).into(),
+                ),
+            ]),
+            ..Default::default()
+        }
+    }
 }
 
 pub fn is_vulkan_supported() -> bool {
     unsafe { ash::Entry::load().is_ok() }
 }
 
-pub fn retrieve_gpu_info_via_vk() -> Result<Vec<GPU>, VulkanError> {
-    let entry = unsafe { ash::Entry::load() }.map_err(|_| VulkanError::NotSupported)?;
-    let app_name = c"GPUInfoApp";
-    let app_info = vk::ApplicationInfo::default()
-        .application_name(app_name)
-        .application_version(0)
-        .engine_name(app_name)
-        .engine_version(0)
-        .api_version(vk::API_VERSION_1_0);
+/// The Vulkan GPUs as [`GPU`](super::GPU)s, without the Vulkan specific
+/// details of [`VulkanGpu`]
+#[deprecated(
+    since = "0.3.0",
+    note = "use `retrieve_vulkan_gpus` for the Vulkan details, or `retrieve_gpu_info`"
+)]
+pub fn retrieve_gpu_info_via_vk() -> Result<Vec<super::GPU>, VulkanError> {
+    Ok(retrieve_vulkan_gpus()?
+        .into_iter()
+        .map(Into::into)
+        .collect())
+}
 
-    let create_info = vk::InstanceCreateInfo::default().application_info(&app_info);
-    let instance = unsafe { entry.create_instance(&create_info, None) }
-        .map_err(|e| VulkanError::OperationFailed(e.to_string()))?;
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", err))]
+pub fn retrieve_vulkan_gpus() -> Result<Vec<VulkanGpu>, VulkanError> {
+    let entry = unsafe { ash::Entry::load() }.map_err(|_| VulkanError::NotSupported)?;
+    trace_debug!("vulkan loader loaded");
+    let (instance, api_version) = create_instance(&entry)?;
+    trace_debug!(
+        api_version = %format_api_version(api_version),
+        "vulkan instance created"
+    );
+    scopeguard::defer! {
+        unsafe { instance.destroy_instance(None) };
+    }
 
-    let physical_devices = unsafe { instance.enumerate_physical_devices() }
-        .map_err(|e| VulkanError::OperationFailed(e.to_string()))?;
+    let physical_devices = unsafe { enumerate_physical_devices(&instance) }
+        .map_err(operation_failed("vkEnumeratePhysicalDevices"))?;
 
     if physical_devices.is_empty() {
-        return Err(VulkanError::OperationFailed(
-            "No Vulkan-compatible GPUs found.".to_string(),
-        ));
+        log::info!("no vulkan devices found");
+        return Ok(vec![]);
     }
 
+    let device_groups = if api_version >= vk::API_VERSION_1_1 {
+        device_group_sizes(&instance)
+    } else {
+        vec![]
+    };
     let mut gpus = Vec::new();
+    let mut icds = None;
 
     for device in physical_devices {
         let properties = unsafe { instance.get_physical_device_properties(device) };
         let memory_properties = unsafe { instance.get_physical_device_memory_properties(device) };
+        let queue_families =
+            unsafe { instance.get_physical_device_queue_family_properties(device) }
+                .into_iter()
+                .map(QueueFamily::from)
+                .collect();
 
         // Extract GPU properties
-        let device_name = unsafe { CStr::from_ptr(properties.device_name.as_ptr()) }
-            .to_str()
-            .unwrap_or("Unknown")
-            .to_string();
+        let raw_name = name_bytes(&properties.device_name);
+        let device_name = String::from_utf8_lossy(&raw_name).into_owned();
 
         let vendor_id = properties.vendor_id;
-        let vendor_name = match vendor_id {
-            0x8086 => "Intel",
-            0x10DE => "NVIDIA",
-            0x1002 => "AMD",
-            _ => "Unknown",
-        }
-        .to_string();
+        let vendor_name = vendor_from_id(vendor_id).to_string();
 
-        let driver_version = format!(
-            "{}.{}.{}",
-            (properties.driver_version >> 22) & 0x3FF,
-            (properties.driver_version >> 12) & 0x3FF,
-            properties.driver_version & 0xFFF
-        );
+        let extended = if api_version >= vk::API_VERSION_1_1
+            && properties.api_version >= vk::API_VERSION_1_1
+        {
+            query_extended_properties(&instance, device)
+        } else {
+            ExtendedProperties::default()
+        };
+
+        let driver_version = decode_driver_version(vendor_id, properties.driver_version);
 
         let device_type = match properties.device_type {
             vk::PhysicalDeviceType::INTEGRATED_GPU => GPUKind::Integrated,
             vk::PhysicalDeviceType::DISCRETE_GPU => GPUKind::Discrete,
             vk::PhysicalDeviceType::VIRTUAL_GPU => GPUKind::Virtual,
-            vk::PhysicalDeviceType::CPU => GPUKind::CPU,
+            vk::PhysicalDeviceType::CPU => GPUKind::Software,
             _ => GPUKind::Unknown,
         };
 
-        let vram_size = memory_properties
-            .memory_heaps
-            .iter()
-            .take(memory_properties.memory_heap_count as usize)
-            .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
-            .map(|heap| heap.size)
-            .sum::<u64>();
+        let memory_heaps = memory_heaps(&memory_properties);
+        let (vram, shared_memory) = split_memory(vendor_id, &device_type, &memory_heaps);
+
+        let icd = extended.driver.as_ref().and_then(|driver| {
+            icd_for_driver(driver.id, icds.get_or_insert_with(retrieve_vulkan_icds)).cloned()
+        });
+        let translation_layer = extended
+            .driver
+            .as_ref()
+            .and_then(|driver| crate::translation::vulkan_translation_layer(driver.id))
+            .map(str::to_string);
+        let (driver_name, conformance_version) = extended
+            .driver
+            .map(|driver| (driver.name, driver.conformance_version))
+            .unzip();
 
-        // Populate GPU struct
-        let gpu = GPU {
+        let gpu = VulkanGpu {
             kind: device_type,
             name: device_name,
+            raw_name,
             vendor: vendor_name,
+            vendor_id,
+            device_id: properties.device_id,
             driver_version,
-            vram: vram_size / (1024 * 1024), // Convert to MB
-            clock_speed: None,               // Vulkan does not provide clock speed
-            temperature: None,               // Vulkan does not provide temperature natively
+            vram,
+            shared_memory,
+            queue_families,
+            device_uuid: extended.device_uuid,
+            luid: extended.luid,
+            pci_address: extended.pci_address,
+            memory_heaps,
+            driver_name,
+            conformance_version,
+            icd,
+            ray_tracing: extended.ray_tracing,
+            mesh_shading: extended.mesh_shading,
+            device_group_size: device_groups
+                .iter()
+                .find(|(group_device, _)| *group_device == device)
+                .map(|&(_, size)| size),
+            translation_layer,
         };
 
+        trace_debug!(
+            name = %gpu.name,
+            vendor_id = gpu.vendor_id,
+            device_id = gpu.device_id,
+            "found vulkan device"
+        );
         gpus.push(gpu);
     }
 
     Ok(gpus)
 }
 
+/// The size of the device group of each physical device. Linked GPUs form
+/// one group the application can create a single device across, every
+/// other GPU a group of its own.
+fn device_group_sizes(instance: &ash::Instance) -> Vec<(vk::PhysicalDevice, u32)> {
+    let Ok(count) = (unsafe { instance.enumerate_physical_device_groups_len() }) else {
+        return vec![];
+    };
+    let mut groups = vec![vk::PhysicalDeviceGroupProperties::default(); count];
+    if unsafe { instance.enumerate_physical_device_groups(&mut groups) }.is_err() {
+        return vec![];
+    }
+    groups
+        .iter()
+        .flat_map(|group| {
+            let size = group.physical_device_count;
+            group.physical_devices[..size as usize]
+                .iter()
+                .map(move |&device| (device, size))
+        })
+        .collect()
+}
+
+#[cfg(feature = "tracing")]
+fn format_api_version(version: u32) -> String {
+    format!(
+        "{}.{}.{}",
+        vk::api_version_major(version),
+        vk::api_version_minor(version),
+        vk::api_version_patch(version)
+    )
+}
+
+/// Per-process budget of the device with the given UUID, from `VK_EXT_memory_budget`.
+/// `None` if the device is not found or the driver lacks the extension.
+pub fn memory_budget_via_vk(device_uuid: &[u8; 16]) -> Result<Option<MemoryBudget>, VulkanError> {
+    let entry = unsafe { ash::Entry::load() }.map_err(|_| VulkanError::NotSupported)?;
+    let (instance, api_version) = create_instance(&entry)?;
+    scopeguard::defer! {
+        unsafe { instance.destroy_instance(None) };
+    }
+    if api_version < vk::API_VERSION_1_1 {
+        return Ok(None);
+    }
+
+    for device in unsafe { enumerate_physical_devices(&instance) }
+        .map_err(|e| VulkanError::OperationFailed(e.to_string()))?
+    {
+        if query_extended_properties(&instance, device)
+            .device_uuid
+            .as_ref()
+            != Some(device_uuid)
+        {
+            continue;
+        }
+        let extensions =
+            unsafe { instance.enumerate_device_extension_properties(device) }.unwrap_or_default();
+        if !extensions
+            .iter()
+            .any(|ext| ext.extension_name_as_c_str() == Ok(ash::ext::memory_budget::NAME))
+        {
+            return Ok(None);
+        }
+
+        let mut budget_properties = vk::PhysicalDeviceMemoryBudgetPropertiesEXT::default();
+        let mut properties2 =
+            vk::PhysicalDeviceMemoryProperties2::default().push_next(&mut budget_properties);
+        unsafe { instance.get_physical_device_memory_properties2(device, &mut properties2) };
+        let memory_properties = properties2.memory_properties;
+
+        let mut budget = MemoryBudget::default();
+        for (index, heap) in memory_properties
+            .memory_heaps
+            .iter()
+            .enumerate()
+            .take(memory_properties.memory_heap_count as usize)
+        {
+            if heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL) {
+                budget.budget.0 += budget_properties.heap_budget[index];
+                budget.usage.0 += budget_properties.heap_usage[index];
+            }
+        }
+        return Ok(Some(budget));
+    }
+
+    Ok(None)
+}
+
+/// Copy a pattern through `bytes` of device local memory of the device with the
+/// given UUID and read it back. `None` if the device is not found.
+pub fn probe_allocation_via_vk(
+    device_uuid: &[u8; 16],
+    bytes: u64,
+) -> Result<Option<AllocationProbe>, VulkanError> {
+    // Graphics and compute queues implicitly support transfers
+    let Some(queue) = QueueDevice::open(
+        device_uuid,
+        vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER,
+    )?
+    else {
+        return Ok(None);
+    };
+
+    let size = bytes.max(4);
+    let start = Instant::now();
+    let host_visible =
+        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT;
+    let upload = queue.create_buffer(size, vk::BufferUsageFlags::TRANSFER_SRC, host_visible)?;
+    let local = queue.create_buffer(
+        size,
+        vk::BufferUsageFlags::TRANSFER_SRC | vk::BufferUsageFlags::TRANSFER_DST,
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+    )?;
+    let readback = queue.create_buffer(size, vk::BufferUsageFlags::TRANSFER_DST, host_visible)?;
+
+    let pattern = |index: usize| (index as u8).wrapping_mul(31) ^ 0xA5;
+    upload.map(|data| {
+        for (index, byte) in data.iter_mut().enumerate() {
+            *byte = pattern(index);
+        }
+    })?;
+
+    let region = [vk::BufferCopy::default().size(size)];
+    let barrier = [vk::BufferMemoryBarrier::default()
+        .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+        .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+        .buffer(local.buffer)
+        .size(vk::WHOLE_SIZE)];
+    queue.submit(|device, commands| unsafe {
+        device.cmd_copy_buffer(commands, upload.buffer, local.buffer, &region);
+        device.cmd_pipeline_barrier(
+            commands,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::PipelineStageFlags::TRANSFER,
+            vk::DependencyFlags::empty(),
+            &[],
+            &barrier,
+            &[],
+        );
+        device.cmd_copy_buffer(commands, local.buffer, readback.buffer, &region);
+    })?;
+
+    let verified = readback.map(|data| {
+        data.iter()
+            .enumerate()
+            .all(|(index, &byte)| byte == pattern(index))
+    })?;
+
+    Ok(Some(AllocationProbe {
+        bytes: size,
+        latency: start.elapsed(),
+        verified,
+    }))
+}
+
+/// Submit an empty command buffer to the device with the given UUID and wait
+/// up to `timeout` for it. `None` if the device is not found.
+pub fn check_device_health_via_vk(
+    device_uuid: &[u8; 16],
+    timeout: Duration,
+) -> Result<Option<Health>, VulkanError> {
+    let Some(queue) = QueueDevice::open(
+        device_uuid,
+        vk::QueueFlags::GRAPHICS | vk::QueueFlags::COMPUTE | vk::QueueFlags::TRANSFER,
+    )?
+    else {
+        return Ok(None);
+    };
+    match queue.submit_with_timeout(|_, _| {}, timeout)? {
+        Ok(latency) => Ok(Some(Health::from_latency(latency, timeout))),
+        Err(vk::Result::TIMEOUT | vk::Result::ERROR_DEVICE_LOST) => Ok(Some(Health::Lost)),
+        Err(e) => Err(operation_failed("vkWaitForFences")(e)),
+    }
+}
+
+/// Whether any queue family of the device with the given UUID can present to
+/// the windowing system of the session: Win32 on Windows, and Wayland or X11
+/// on Linux, picked from `WAYLAND_DISPLAY` and `DISPLAY`. `None` if the device
+/// is not found, there is no windowing system to connect to, or the loader
+/// lacks its surface extension.
+pub fn presentation_support_via_vk(device_uuid: &[u8; 16]) -> Result<Option<bool>, VulkanError> {
+    let Some(session) = WindowSession::connect() else {
+        return Ok(None);
+    };
+    let entry = unsafe { ash::Entry::load() }.map_err(|_| VulkanError::NotSupported)?;
+    let available = unsafe { entry.enumerate_instance_extension_properties(None) }
+        .map_err(operation_failed("vkEnumerateInstanceExtensionProperties"))?;
+    let extensions = [ash::khr::surface::NAME, session.extension()];
+    if !extensions.iter().all(|&name| {
+        available
+            .iter()
+            .any(|ext| ext.extension_name_as_c_str() == Ok(name))
+    }) {
+        return Ok(None);
+    }
+
+    let extensions = extensions.map(CStr::as_ptr);
+    let (instance, api_version) = create_instance_with_extensions(&entry, &extensions)?;
+    scopeguard::defer! {
+        unsafe { instance.destroy_instance(None) };
+    }
+    if api_version < vk::API_VERSION_1_1 {
+        return Ok(None);
+    }
+    let Some(device) = unsafe { enumerate_physical_devices(&instance) }
+        .map_err(operation_failed("vkEnumeratePhysicalDevices"))?
+        .into_iter()
+        .find(|&device| {
+            query_extended_properties(&instance, device)
+                .device_uuid
+                .as_ref()
+                == Some(device_uuid)
+        })
+    else {
+        return Ok(None);
+    };
+
+    let family_count =
+        unsafe { instance.get_physical_device_queue_family_properties(device) }.len() as u32;
+    Ok(Some(unsafe {
+        session.supports(&entry, &instance, device, family_count)
+    }))
+}
+
+/// The windowing system presentation support is checked against
+#[cfg(windows)]
+struct WindowSession;
+
+#[cfg(windows)]
+impl WindowSession {
+    fn connect() -> Option<Self> {
+        Some(Self)
+    }
+
+    fn extension(&self) -> &'static CStr {
+        ash::khr::win32_surface::NAME
+    }
+
+    unsafe fn supports(
+        &self,
+        entry: &ash::Entry,
+        instance: &ash::Instance,
+        device: vk::PhysicalDevice,
+        family_count: u32,
+    ) -> bool {
+        let win32 = ash::khr::win32_surface::Instance::new(entry, instance);
+        (0..family_count)
+            .any(|family| win32.get_physical_device_win32_presentation_support(device, family))
+    }
+}
+
+/// A client connection to the Wayland compositor or X server, through the
+/// system libraries loaded at runtime
+#[cfg(target_os = "linux")]
+enum WindowSession {
+    Wayland {
+        display: *mut std::ffi::c_void,
+        disconnect: unsafe extern "C" fn(*mut std::ffi::c_void),
+        _lib: libloading::Library,
+    },
+    Xlib {
+        display: *mut std::ffi::c_void,
+        visual_id: std::ffi::c_ulong,
+        close: unsafe extern "C" fn(*mut std::ffi::c_void) -> std::ffi::c_int,
+        _lib: libloading::Library,
+    },
+}
+
+#[cfg(target_os = "linux")]
+impl WindowSession {
+    fn connect() -> Option<Self> {
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            if let Some(session) = Self::connect_wayland() {
+                return Some(session);
+            }
+        }
+        if std::env::var_os("DISPLAY").is_some() {
+            return Self::connect_xlib();
+        }
+        None
+    }
+
+    fn connect_wayland() -> Option<Self> {
+        use std::ffi::{c_char, c_void};
+
+        let lib = unsafe { libloading::Library::new("libwayland-client.so.0") }.ok()?;
+        let connect = *unsafe {
+            lib.get::<unsafe extern "C" fn(*const c_char) -> *mut c_void>(b"wl_display_connect\0")
+        }
+        .ok()?;
+        let disconnect =
+            *unsafe { lib.get::<unsafe extern "C" fn(*mut c_void)>(b"wl_display_disconnect\0") }
+                .ok()?;
+        // Null connects to `WAYLAND_DISPLAY`
+        let display = unsafe { connect(std::ptr::null()) };
+        (!display.is_null()).then_some(WindowSession::Wayland {
+            display,
+            disconnect,
+            _lib: lib,
+        })
+    }
+
+    fn connect_xlib() -> Option<Self> {
+        use std::ffi::{c_char, c_int, c_ulong, c_void};
+
+        let lib = unsafe { libloading::Library::new("libX11.so.6") }.ok()?;
+        unsafe {
+            let open = *lib
+                .get::<unsafe extern "C" fn(*const c_char) -> *mut c_void>(b"XOpenDisplay\0")
+                .ok()?;
+            let close = *lib
+                .get::<unsafe extern "C" fn(*mut c_void) -> c_int>(b"XCloseDisplay\0")
+                .ok()?;
+            let default_screen = *lib
+                .get::<unsafe extern "C" fn(*mut c_void) -> c_int>(b"XDefaultScreen\0")
+                .ok()?;
+            let default_visual = *lib
+                .get::<unsafe extern "C" fn(*mut c_void, c_int) -> *mut c_void>(b"XDefaultVisual\0")
+                .ok()?;
+            let visual_id = *lib
+                .get::<unsafe extern "C" fn(*mut c_void) -> c_ulong>(b"XVisualIDFromVisual\0")
+                .ok()?;
+
+            // Null opens `DISPLAY`
+            let display = open(std::ptr::null());
+            if display.is_null() {
+                return None;
+            }
+            let visual_id = visual_id(default_visual(display, default_screen(display)));
+            Some(WindowSession::Xlib {
+                display,
+                visual_id,
+                close,
+                _lib: lib,
+            })
+        }
+    }
+
+    fn extension(&self) -> &'static CStr {
+        match self {
+            WindowSession::Wayland { .. } => ash::khr::wayland_surface::NAME,
+            WindowSession::Xlib { .. } => ash::khr::xlib_surface::NAME,
+        }
+    }
+
+    unsafe fn supports(
+        &self,
+        entry: &ash::Entry,
+        instance: &ash::Instance,
+        device: vk::PhysicalDevice,
+        family_count: u32,
+    ) -> bool {
+        match *self {
+            WindowSession::Wayland { display, .. } => {
+                let wayland = ash::khr::wayland_surface::Instance::new(entry, instance);
+                (0..family_count).any(|family| {
+                    wayland.get_physical_device_wayland_presentation_support(
+                        device,
+                        family,
+                        &mut *display,
+                    )
+                })
+            }
+            WindowSession::Xlib {
+                display, visual_id, ..
+            } => {
+                let xlib = ash::khr::xlib_surface::Instance::new(entry, instance);
+                (0..family_count).any(|family| {
+                    xlib.get_physical_device_xlib_presentation_support(
+                        device,
+                        family,
+                        display,
+                        visual_id as vk::VisualID,
+                    )
+                })
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for WindowSession {
+    fn drop(&mut self) {
+        match *self {
+            WindowSession::Wayland {
+                display,
+                disconnect,
+                ..
+            } => unsafe { disconnect(display) },
+            WindowSession::Xlib { display, close, .. } => unsafe {
+                close(display);
+            },
+        }
+    }
+}
+
+/// No windowing system with a Vulkan presentation query
+#[cfg(not(any(windows, target_os = "linux")))]
+enum WindowSession {}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+impl WindowSession {
+    fn connect() -> Option<Self> {
+        None
+    }
+
+    fn extension(&self) -> &'static CStr {
+        match *self {}
+    }
+
+    unsafe fn supports(
+        &self,
+        _entry: &ash::Entry,
+        _instance: &ash::Instance,
+        _device: vk::PhysicalDevice,
+        _family_count: u32,
+    ) -> bool {
+        match *self {}
+    }
+}
+
+/// Copy bandwidth and FMA throughput of the device with the given UUID.
+/// `None` if the device is not found.
+#[cfg(feature = "bench")]
+pub(crate) fn benchmark_via_vk(device_uuid: &[u8; 16]) -> Result<Option<Benchmark>, VulkanError> {
+    let Some(queue) = QueueDevice::open(device_uuid, vk::QueueFlags::COMPUTE)? else {
+        return Ok(None);
+    };
+    let device = &queue.device;
+
+    let source = queue.create_buffer(
+        bench::COPY_BYTES,
+        vk::BufferUsageFlags::TRANSFER_SRC,
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+    )?;
+    let destination = queue.create_buffer(
+        bench::COPY_BYTES,
+        vk::BufferUsageFlags::TRANSFER_DST,
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+    )?;
+    // The copies are never read back, so they are not ordered against each other
+    let region = [vk::BufferCopy::default().size(bench::COPY_BYTES)];
+    let (copies, copy_time) = bench::measure(|count| {
+        queue.submit(|device, commands| {
+            for _ in 0..count {
+                unsafe {
+                    device.cmd_copy_buffer(commands, source.buffer, destination.buffer, &region)
+                };
+            }
+        })
+    })?;
+
+    let data = queue.create_buffer(
+        bench::FMA_ELEMENTS * 4,
+        vk::BufferUsageFlags::STORAGE_BUFFER,
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+    )?;
+    let binding = [vk::DescriptorSetLayoutBinding::default()
+        .binding(0)
+        .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+        .descriptor_count(1)
+        .stage_flags(vk::ShaderStageFlags::COMPUTE)];
+    let set_layout = unsafe {
+        device.create_descriptor_set_layout(
+            &vk::DescriptorSetLayoutCreateInfo::default().bindings(&binding),
+            None,
+        )
+    }
+    .map_err(operation_failed("vkCreateDescriptorSetLayout"))?;
+    scopeguard::defer! {
+        unsafe { device.destroy_descriptor_set_layout(set_layout, None) };
+    }
+    let set_layouts = [set_layout];
+    let pipeline_layout = unsafe {
+        device.create_pipeline_layout(
+            &vk::PipelineLayoutCreateInfo::default().set_layouts(&set_layouts),
+            None,
+        )
+    }
+    .map_err(operation_failed("vkCreatePipelineLayout"))?;
+    scopeguard::defer! {
+        unsafe { device.destroy_pipeline_layout(pipeline_layout, None) };
+    }
+    let code = bench::fma_shader();
+    let shader = unsafe {
+        device.create_shader_module(&vk::ShaderModuleCreateInfo::default().code(&code), None)
+    }
+    .map_err(operation_failed("vkCreateShaderModule"))?;
+    scopeguard::defer! {
+        unsafe { device.destroy_shader_module(shader, None) };
+    }
+    let pipeline_info = vk::ComputePipelineCreateInfo::default()
+        .stage(
+            vk::PipelineShaderStageCreateInfo::default()
+                .stage(vk::ShaderStageFlags::COMPUTE)
+                .module(shader)
+                .name(c"main"),
+        )
+        .layout(pipeline_layout);
+    let pipeline = unsafe {
+        device.create_compute_pipelines(vk::PipelineCache::null(), &[pipeline_info], None)
+    }
+    .map_err(|(_, e)| operation_failed("vkCreateComputePipelines")(e))?[0];
+    scopeguard::defer! {
+        unsafe { device.destroy_pipeline(pipeline, None) };
+    }
+
+    let pool_sizes = [vk::DescriptorPoolSize::default()
+        .ty(vk::DescriptorType::STORAGE_BUFFER)
+        .descriptor_count(1)];
+    let descriptor_pool = unsafe {
+        device.create_descriptor_pool(
+            &vk::DescriptorPoolCreateInfo::default()
+                .max_sets(1)
+                .pool_sizes(&pool_sizes),
+            None,
+        )
+    }
+    .map_err(operation_failed("vkCreateDescriptorPool"))?;
+    scopeguard::defer! {
+        unsafe { device.destroy_descriptor_pool(descriptor_pool, None) };
+    }
+    let descriptor_set = unsafe {
+        device.allocate_descriptor_sets(
+            &vk::DescriptorSetAllocateInfo::default()
+                .descriptor_pool(descriptor_pool)
+                .set_layouts(&set_layouts),
+        )
+    }
+    .map_err(operation_failed("vkAllocateDescriptorSets"))?[0];
+    let buffer_info = [vk::DescriptorBufferInfo::default()
+        .buffer(data.buffer)
+        .range(vk::WHOLE_SIZE)];
+    let write = vk::WriteDescriptorSet::default()
+        .dst_set(descriptor_set)
+        .dst_binding(0)
+        .descriptor_type(vk::DescriptorType::STORAGE_BUFFER)
+        .buffer_info(&buffer_info);
+    unsafe { device.update_descriptor_sets(&[write], &[]) };
+
+    // Every dispatch continues from the values the previous one wrote
+    let barrier = [vk::MemoryBarrier::default()
+        .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+        .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE)];
+    let groups = (bench::FMA_ELEMENTS / bench::FMA_GROUP_SIZE as u64) as u32;
+    let (dispatches, compute_time) = bench::measure(|count| {
+        queue.submit(|device, commands| unsafe {
+            device.cmd_bind_pipeline(commands, vk::PipelineBindPoint::COMPUTE, pipeline);
+            device.cmd_bind_descriptor_sets(
+                commands,
+                vk::PipelineBindPoint::COMPUTE,
+                pipeline_layout,
+                0,
+                &[descriptor_set],
+                &[],
+            );
+            for _ in 0..count {
+                device.cmd_dispatch(commands, groups, 1, 1);
+                device.cmd_pipeline_barrier(
+                    commands,
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                    vk::PipelineStageFlags::COMPUTE_SHADER,
+                    vk::DependencyFlags::empty(),
+                    &barrier,
+                    &[],
+                    &[],
+                );
+            }
+        })
+    })?;
+
+    Ok(Some(Benchmark::new(
+        copies,
+        copy_time,
+        dispatches,
+        compute_time,
+    )))
+}
+
+/// Queues whose performance counters are listed and sampled, the same that
+/// [`probe_allocation_via_vk`] copies on
+#[cfg(feature = "counters")]
+const COUNTER_QUEUE_FLAGS: vk::QueueFlags = vk::QueueFlags::from_raw(
+    vk::QueueFlags::GRAPHICS.as_raw()
+        | vk::QueueFlags::COMPUTE.as_raw()
+        | vk::QueueFlags::TRANSFER.as_raw(),
+);
+
+/// Performance counters of the device with the given UUID, grouped by
+/// category. `None` if the device is not found.
+#[cfg(feature = "counters")]
+pub(crate) fn counter_groups_via_vk(
+    device_uuid: &[u8; 16],
+) -> Result<Option<Vec<CounterGroup>>, VulkanError> {
+    let entry = unsafe { ash::Entry::load() }.map_err(|_| VulkanError::NotSupported)?;
+    let (instance, api_version) = create_instance(&entry)?;
+    scopeguard::defer! {
+        unsafe { instance.destroy_instance(None) };
+    }
+    if api_version < vk::API_VERSION_1_1 {
+        return Ok(None);
+    }
+    let Some(physical_device) = find_physical_device(&instance, device_uuid)? else {
+        return Ok(None);
+    };
+    let family = queue_family(&instance, physical_device, COUNTER_QUEUE_FLAGS)?;
+
+    let mut groups: Vec<CounterGroup> = vec![];
+    for counter in performance_counters(&entry, &instance, physical_device, family)? {
+        let counter_info = Counter {
+            name: counter.name,
+            description: Some(counter.description).filter(|description| !description.is_empty()),
+            unit: counter.unit,
+        };
+        match groups
+            .iter_mut()
+            .find(|group| group.name == counter.category)
+        {
+            Some(group) => group.counters.push(counter_info),
+            None => groups.push(CounterGroup {
+                name: counter.category,
+                counters: vec![counter_info],
+            }),
+        }
+    }
+    Ok(Some(groups))
+}
+
+/// Sample the counters of `group` on the device with the given UUID around
+/// a copy through device local memory. `None` if the device, its support
+/// for performance queries or the group is missing.
+#[cfg(feature = "counters")]
+pub(crate) fn sample_counters_via_vk(
+    device_uuid: &[u8; 16],
+    group: &str,
+) -> Result<Option<Vec<CounterValue>>, VulkanError> {
+    let Some(queue) = QueueDevice::open_with(device_uuid, COUNTER_QUEUE_FLAGS, true)? else {
+        return Ok(None);
+    };
+    let (indices, counters): (Vec<u32>, Vec<PerformanceCounter>) = performance_counters(
+        &queue.entry,
+        &queue.instance,
+        queue.physical_device,
+        queue.family,
+    )?
+    .into_iter()
+    .enumerate()
+    .filter(|(_, counter)| counter.category == group)
+    .map(|(index, counter)| (index as u32, counter))
+    .unzip();
+    if counters.is_empty() {
+        return Ok(None);
+    }
+
+    let device = &queue.device;
+    let loader = ash::khr::performance_query::Instance::new(&queue.entry, &queue.instance);
+    let mut performance_info = vk::QueryPoolPerformanceCreateInfoKHR::default()
+        .queue_family_index(queue.family)
+        .counter_indices(&indices);
+    let passes = unsafe {
+        loader.get_physical_device_queue_family_performance_query_passes(
+            queue.physical_device,
+            &performance_info,
+        )
+    };
+    let query_pool = unsafe {
+        device.create_query_pool(
+            &vk::QueryPoolCreateInfo::default()
+                .query_type(vk::QueryType::PERFORMANCE_QUERY_KHR)
+                .query_count(1)
+                .push_next(&mut performance_info),
+            None,
+        )
+    }
+    .map_err(operation_failed("vkCreateQueryPool"))?;
+    scopeguard::defer! {
+        unsafe { device.destroy_query_pool(query_pool, None) };
+    }
+
+    // Held while recording and submitting, other processes wait for it
+    let profiling = ash::khr::performance_query::Device::new(&queue.instance, device);
+    unsafe {
+        profiling.acquire_profiling_lock(
+            &vk::AcquireProfilingLockInfoKHR::default()
+                .timeout(Duration::from_secs(1).as_nanos() as u64),
+        )
+    }
+    .map_err(operation_failed("vkAcquireProfilingLockKHR"))?;
+    scopeguard::defer! {
+        unsafe { profiling.release_profiling_lock() };
+    }
+
+    let size = crate::counters::SAMPLE_COPY_BYTES;
+    let host_visible =
+        vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT;
+    let upload = queue.create_buffer(size, vk::BufferUsageFlags::TRANSFER_SRC, host_visible)?;
+    let local = queue.create_buffer(
+        size,
+        vk::BufferUsageFlags::TRANSFER_DST,
+        vk::MemoryPropertyFlags::DEVICE_LOCAL,
+    )?;
+    let region = [vk::BufferCopy::default().size(size)];
+
+    // A reset may not share a command buffer with the query it resets
+    queue.submit(|device, commands| unsafe {
+        device.cmd_reset_query_pool(commands, query_pool, 0, 1);
+    })?;
+    for pass in 0..passes {
+        queue
+            .submit_pass(
+                |device, commands| unsafe {
+                    device.cmd_begin_query(commands, query_pool, 0, vk::QueryControlFlags::empty());
+                    device.cmd_copy_buffer(commands, upload.buffer, local.buffer, &region);
+                    device.cmd_end_query(commands, query_pool, 0);
+                },
+                Duration::from_secs(10),
+                Some(pass),
+            )?
+            .map_err(operation_failed("vkWaitForFences"))?;
+    }
+
+    // ash sizes results per query, a performance query has one per counter
+    let mut results = vec![vk::PerformanceCounterResultKHR::default(); counters.len()];
+    let stride = std::mem::size_of_val(results.as_slice());
+    unsafe {
+        (device.fp_v1_0().get_query_pool_results)(
+            device.handle(),
+            query_pool,
+            0,
+            1,
+            stride,
+            results.as_mut_ptr().cast(),
+            stride as vk::DeviceSize,
+            vk::QueryResultFlags::WAIT,
+        )
+    }
+    .result()
+    .map_err(operation_failed("vkGetQueryPoolResults"))?;
+
+    Ok(Some(
+        counters
+            .into_iter()
+            .zip(results)
+            .map(|(counter, result)| CounterValue {
+                value: unsafe {
+                    match counter.storage {
+                        vk::PerformanceCounterStorageKHR::INT32 => result.int32 as f64,
+                        vk::PerformanceCounterStorageKHR::INT64 => result.int64 as f64,
+                        vk::PerformanceCounterStorageKHR::UINT32 => result.uint32 as f64,
+                        vk::PerformanceCounterStorageKHR::FLOAT32 => result.float32 as f64,
+                        vk::PerformanceCounterStorageKHR::FLOAT64 => result.float64,
+                        _ => result.uint64 as f64,
+                    }
+                },
+                name: counter.name,
+                unit: counter.unit,
+            })
+            .collect(),
+    ))
+}
+
+/// One counter of `VK_KHR_performance_query`, in the order the driver lists
+/// them, which counter indices refer to
+#[cfg(feature = "counters")]
+struct PerformanceCounter {
+    name: String,
+    category: String,
+    description: String,
+    unit: CounterUnit,
+    storage: vk::PerformanceCounterStorageKHR,
+}
+
+/// Counters of a queue family, empty without `VK_KHR_performance_query`
+#[cfg(feature = "counters")]
+fn performance_counters(
+    entry: &ash::Entry,
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    family: u32,
+) -> Result<Vec<PerformanceCounter>, VulkanError> {
+    if !has_device_extension(instance, physical_device, ash::khr::performance_query::NAME) {
+        return Ok(vec![]);
+    }
+    let loader = ash::khr::performance_query::Instance::new(entry, instance);
+    let failed =
+        operation_failed("vkEnumeratePhysicalDeviceQueueFamilyPerformanceQueryCountersKHR");
+    let count = unsafe {
+        loader.enumerate_physical_device_queue_family_performance_query_counters_len(
+            physical_device,
+            family,
+        )
+    }
+    .map_err(&failed)?;
+    let mut counters = vec![vk::PerformanceCounterKHR::default(); count];
+    let mut descriptions = vec![vk::PerformanceCounterDescriptionKHR::default(); count];
+    unsafe {
+        loader.enumerate_physical_device_queue_family_performance_query_counters(
+            physical_device,
+            family,
+            &mut counters,
+            &mut descriptions,
+        )
+    }
+    .map_err(&failed)?;
+
+    let text = |text: Result<&CStr, _>| {
+        text.map(|text| text.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    };
+    Ok(counters
+        .iter()
+        .zip(&descriptions)
+        .map(|(counter, description)| PerformanceCounter {
+            name: text(description.name_as_c_str()),
+            category: text(description.category_as_c_str()),
+            description: text(description.description_as_c_str()),
+            unit: match counter.unit {
+                vk::PerformanceCounterUnitKHR::PERCENTAGE => CounterUnit::Percentage,
+                vk::PerformanceCounterUnitKHR::NANOSECONDS => CounterUnit::Nanoseconds,
+                vk::PerformanceCounterUnitKHR::BYTES => CounterUnit::Bytes,
+                vk::PerformanceCounterUnitKHR::BYTES_PER_SECOND => CounterUnit::BytesPerSecond,
+                vk::PerformanceCounterUnitKHR::KELVIN => CounterUnit::Kelvin,
+                vk::PerformanceCounterUnitKHR::WATTS => CounterUnit::Watts,
+                vk::PerformanceCounterUnitKHR::VOLTS => CounterUnit::Volts,
+                vk::PerformanceCounterUnitKHR::AMPS => CounterUnit::Amps,
+                vk::PerformanceCounterUnitKHR::HERTZ => CounterUnit::Hertz,
+                vk::PerformanceCounterUnitKHR::CYCLES => CounterUnit::Cycles,
+                _ => CounterUnit::Generic,
+            },
+            storage: counter.storage,
+        })
+        .collect())
+}
+
+/// A logical device with one queue and a command pool on it, for work that is
+/// submitted rather than only queried
+struct QueueDevice {
+    instance: ash::Instance,
+    device: ash::Device,
+    queue: vk::Queue,
+    pool: vk::CommandPool,
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    #[cfg_attr(not(feature = "counters"), allow(dead_code))]
+    physical_device: vk::PhysicalDevice,
+    /// Index of the queue family of `queue`
+    #[cfg_attr(not(feature = "counters"), allow(dead_code))]
+    family: u32,
+    // Unloads the library, so it is dropped after the handles are destroyed
+    #[cfg_attr(not(feature = "counters"), allow(dead_code))]
+    entry: ash::Entry,
+}
+
+impl QueueDevice {
+    /// `None` if there is no device with the given UUID
+    fn open(
+        device_uuid: &[u8; 16],
+        queue_flags: vk::QueueFlags,
+    ) -> Result<Option<Self>, VulkanError> {
+        Self::open_with(device_uuid, queue_flags, false)
+    }
+
+    /// With `performance_query`, also enables the performance counter query
+    /// pools of `VK_KHR_performance_query`, and is `None` without them
+    fn open_with(
+        device_uuid: &[u8; 16],
+        queue_flags: vk::QueueFlags,
+        performance_query: bool,
+    ) -> Result<Option<Self>, VulkanError> {
+        let entry = unsafe { ash::Entry::load() }.map_err(|_| VulkanError::NotSupported)?;
+        let (instance, api_version) = create_instance(&entry)?;
+        let instance = scopeguard::guard(instance, |instance| unsafe {
+            instance.destroy_instance(None)
+        });
+        if api_version < vk::API_VERSION_1_1 {
+            return Ok(None);
+        }
+        let Some(physical_device) = find_physical_device(&instance, device_uuid)? else {
+            return Ok(None);
+        };
+        let family = queue_family(&instance, physical_device, queue_flags)?;
+
+        let mut extensions = vec![];
+        let mut performance_features = vk::PhysicalDevicePerformanceQueryFeaturesKHR::default();
+        if performance_query {
+            let mut features2 =
+                vk::PhysicalDeviceFeatures2::default().push_next(&mut performance_features);
+            unsafe { instance.get_physical_device_features2(physical_device, &mut features2) };
+            if !has_device_extension(
+                &instance,
+                physical_device,
+                ash::khr::performance_query::NAME,
+            ) || performance_features.performance_counter_query_pools != vk::TRUE
+            {
+                return Ok(None);
+            }
+            performance_features.performance_counter_multiple_query_pools = vk::FALSE;
+            extensions.push(ash::khr::performance_query::NAME.as_ptr());
+        }
+
+        let priorities = [1.0];
+        let queue_info = vk::DeviceQueueCreateInfo::default()
+            .queue_family_index(family)
+            .queue_priorities(&priorities);
+        let mut create_info = vk::DeviceCreateInfo::default()
+            .queue_create_infos(std::slice::from_ref(&queue_info))
+            .enabled_extension_names(&extensions);
+        if performance_query {
+            create_info = create_info.push_next(&mut performance_features);
+        }
+        let device = unsafe { instance.create_device(physical_device, &create_info, None) }
+            .map_err(operation_failed("vkCreateDevice"))?;
+        let device = scopeguard::guard(device, |device| unsafe { device.destroy_device(None) });
+        let pool = unsafe {
+            device.create_command_pool(
+                &vk::CommandPoolCreateInfo::default().queue_family_index(family),
+                None,
+            )
+        }
+        .map_err(operation_failed("vkCreateCommandPool"))?;
+
+        Ok(Some(QueueDevice {
+            queue: unsafe { device.get_device_queue(family, 0) },
+            pool,
+            memory_properties: unsafe {
+                instance.get_physical_device_memory_properties(physical_device)
+            },
+            physical_device,
+            family,
+            device: scopeguard::ScopeGuard::into_inner(device),
+            instance: scopeguard::ScopeGuard::into_inner(instance),
+            entry,
+        }))
+    }
+
+    fn create_buffer(
+        &self,
+        size: u64,
+        usage: vk::BufferUsageFlags,
+        flags: vk::MemoryPropertyFlags,
+    ) -> Result<Buffer<'_>, VulkanError> {
+        let device = &self.device;
+        let buffer = unsafe {
+            device.create_buffer(
+                &vk::BufferCreateInfo::default()
+                    .size(size)
+                    .usage(usage)
+                    .sharing_mode(vk::SharingMode::EXCLUSIVE),
+                None,
+            )
+        }
+        .map_err(operation_failed("vkCreateBuffer"))?;
+        let buffer = scopeguard::guard(buffer, |buffer| unsafe {
+            device.destroy_buffer(buffer, None)
+        });
+
+        let requirements = unsafe { device.get_buffer_memory_requirements(*buffer) };
+        let properties = &self.memory_properties;
+        let type_index = properties.memory_types[..properties.memory_type_count as usize]
+            .iter()
+            .enumerate()
+            .position(|(index, ty)| {
+                requirements.memory_type_bits & (1 << index) != 0
+                    && ty.property_flags.contains(flags)
+            })
+            .ok_or_else(|| VulkanError::OperationFailed(format!("no {flags:?} memory type")))?;
+        let memory = unsafe {
+            device.allocate_memory(
+                &vk::MemoryAllocateInfo::default()
+                    .allocation_size(requirements.size)
+                    .memory_type_index(type_index as u32),
+                None,
+            )
+        }
+        .map_err(operation_failed("vkAllocateMemory"))?;
+        let buffer = Buffer {
+            device,
+            buffer: scopeguard::ScopeGuard::into_inner(buffer),
+            memory,
+            size,
+        };
+        unsafe { device.bind_buffer_memory(buffer.buffer, memory, 0) }
+            .map_err(operation_failed("vkBindBufferMemory"))?;
+        Ok(buffer)
+    }
+
+    /// Record one command buffer, submit it and wait for it to finish.
+    /// Returns the time from submission to completion.
+    fn submit(
+        &self,
+        record: impl FnOnce(&ash::Device, vk::CommandBuffer),
+    ) -> Result<Duration, VulkanError> {
+        // A hung GPU never signals the fence
+        self.submit_with_timeout(record, Duration::from_secs(10))?
+            .map_err(operation_failed("vkWaitForFences"))
+    }
+
+    /// Like [`QueueDevice::submit`], but hands a timeout or lost device back
+    /// to the caller instead of failing
+    fn submit_with_timeout(
+        &self,
+        record: impl FnOnce(&ash::Device, vk::CommandBuffer),
+        timeout: Duration,
+    ) -> Result<Result<Duration, vk::Result>, VulkanError> {
+        self.submit_pass(record, timeout, None)
+    }
+
+    /// Submit as the given pass of the performance queries recorded, which
+    /// requires a device opened with `performance_query`
+    fn submit_pass(
+        &self,
+        record: impl FnOnce(&ash::Device, vk::CommandBuffer),
+        timeout: Duration,
+        counter_pass: Option<u32>,
+    ) -> Result<Result<Duration, vk::Result>, VulkanError> {
+        let device = &self.device;
+        let command_buffers = unsafe {
+            device.allocate_command_buffers(
+                &vk::CommandBufferAllocateInfo::default()
+                    .command_pool(self.pool)
+                    .command_buffer_count(1),
+            )
+        }
+        .map_err(operation_failed("vkAllocateCommandBuffers"))?;
+        scopeguard::defer! {
+            unsafe { device.free_command_buffers(self.pool, &command_buffers) };
+        }
+        let commands = command_buffers[0];
+        unsafe {
+            device
+                .begin_command_buffer(commands, &vk::CommandBufferBeginInfo::default())
+                .map_err(operation_failed("vkBeginCommandBuffer"))?;
+            record(device, commands);
+            device
+                .end_command_buffer(commands)
+                .map_err(operation_failed("vkEndCommandBuffer"))?;
+        }
+
+        let fence = unsafe { device.create_fence(&vk::FenceCreateInfo::default(), None) }
+            .map_err(operation_failed("vkCreateFence"))?;
+        scopeguard::defer! {
+            unsafe { device.destroy_fence(fence, None) };
+        }
+        let mut pass_info = vk::PerformanceQuerySubmitInfoKHR::default()
+            .counter_pass_index(counter_pass.unwrap_or_default());
+        let mut submit_info = vk::SubmitInfo::default().command_buffers(&command_buffers);
+        if counter_pass.is_some() {
+            submit_info = submit_info.push_next(&mut pass_info);
+        }
+        let start = Instant::now();
+        unsafe {
+            device
+                .queue_submit(self.queue, &[submit_info], fence)
+                .map_err(operation_failed("vkQueueSubmit"))?;
+            Ok(device
+                .wait_for_fences(&[fence], true, timeout.as_nanos() as u64)
+                .map(|()| start.elapsed()))
+        }
+    }
+}
+
+impl Drop for QueueDevice {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_command_pool(self.pool, None);
+            self.device.destroy_device(None);
+            self.instance.destroy_instance(None);
+        }
+    }
+}
+
+/// A buffer bound to its own allocation
+struct Buffer<'a> {
+    device: &'a ash::Device,
+    buffer: vk::Buffer,
+    memory: vk::DeviceMemory,
+    size: u64,
+}
+
+impl Buffer<'_> {
+    /// Only for buffers created with host visible and coherent memory
+    fn map<T>(&self, f: impl FnOnce(&mut [u8]) -> T) -> Result<T, VulkanError> {
+        let mapped = unsafe {
+            self.device
+                .map_memory(self.memory, 0, self.size, vk::MemoryMapFlags::empty())
+        }
+        .map_err(operation_failed("vkMapMemory"))?;
+        let result =
+            f(unsafe { std::slice::from_raw_parts_mut(mapped as *mut u8, self.size as usize) });
+        unsafe { self.device.unmap_memory(self.memory) };
+        Ok(result)
+    }
+}
+
+impl Drop for Buffer<'_> {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.destroy_buffer(self.buffer, None);
+            self.device.free_memory(self.memory, None);
+        }
+    }
+}
+
+/// The physical device with the given UUID, `None` if there is none
+fn find_physical_device(
+    instance: &ash::Instance,
+    device_uuid: &[u8; 16],
+) -> Result<Option<vk::PhysicalDevice>, VulkanError> {
+    Ok(unsafe { enumerate_physical_devices(instance) }
+        .map_err(operation_failed("vkEnumeratePhysicalDevices"))?
+        .into_iter()
+        .find(|&device| {
+            query_extended_properties(instance, device)
+                .device_uuid
+                .as_ref()
+                == Some(device_uuid)
+        }))
+}
+
+/// The first queue family with any of `queue_flags`
+fn queue_family(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    queue_flags: vk::QueueFlags,
+) -> Result<u32, VulkanError> {
+    unsafe { instance.get_physical_device_queue_family_properties(physical_device) }
+        .iter()
+        .position(|family| family.queue_count > 0 && family.queue_flags.intersects(queue_flags))
+        .map(|family| family as u32)
+        .ok_or_else(|| VulkanError::OperationFailed(format!("no {queue_flags:?} queue family")))
+}
+
+fn has_device_extension(
+    instance: &ash::Instance,
+    physical_device: vk::PhysicalDevice,
+    name: &CStr,
+) -> bool {
+    unsafe { instance.enumerate_device_extension_properties(physical_device) }
+        .unwrap_or_default()
+        .iter()
+        .any(|ext| ext.extension_name_as_c_str() == Ok(name))
+}
+
+fn operation_failed(call: &'static str) -> impl Fn(vk::Result) -> VulkanError {
+    move |e| match e {
+        vk::Result::ERROR_DEVICE_LOST => VulkanError::DeviceLost(call.to_string()),
+        e => VulkanError::OperationFailed(format!("{call}: {e}")),
+    }
+}
+
+/// Result of creating a logical device with a compute queue
+#[cfg(feature = "compute-runtimes")]
+pub(crate) struct ComputeProbe {
+    pub name: String,
+    pub pci_address: Option<PciAddress>,
+    pub result: Result<(), String>,
+}
+
+#[cfg(feature = "compute-runtimes")]
+pub(crate) fn probe_compute_devices() -> Result<Vec<ComputeProbe>, VulkanError> {
+    let entry = unsafe { ash::Entry::load() }.map_err(|_| VulkanError::NotSupported)?;
+    let (instance, api_version) = create_instance(&entry)?;
+    scopeguard::defer! {
+        unsafe { instance.destroy_instance(None) };
+    }
+
+    let devices = unsafe { enumerate_physical_devices(&instance) }
+        .map_err(|e| VulkanError::OperationFailed(e.to_string()))?;
+    Ok(devices
+        .into_iter()
+        .map(|device| {
+            let properties = unsafe { instance.get_physical_device_properties(device) };
+            let pci_address = if api_version >= vk::API_VERSION_1_1
+                && properties.api_version >= vk::API_VERSION_1_1
+            {
+                query_extended_properties(&instance, device).pci_address
+            } else {
+                None
+            };
+            ComputeProbe {
+                name: String::from_utf8_lossy(&name_bytes(&properties.device_name)).into_owned(),
+                pci_address,
+                result: create_compute_device(&instance, device),
+            }
+        })
+        .collect())
+}
+
+#[cfg(feature = "compute-runtimes")]
+fn create_compute_device(
+    instance: &ash::Instance,
+    device: vk::PhysicalDevice,
+) -> Result<(), String> {
+    let family = unsafe { instance.get_physical_device_queue_family_properties(device) }
+        .iter()
+        .position(|family| {
+            family.queue_flags.contains(vk::QueueFlags::COMPUTE) && family.queue_count > 0
+        })
+        .ok_or_else(|| "no compute queue family".to_string())?;
+
+    let priorities = [1.0];
+    let queue_info = vk::DeviceQueueCreateInfo::default()
+        .queue_family_index(family as u32)
+        .queue_priorities(&priorities);
+    let create_info =
+        vk::DeviceCreateInfo::default().queue_create_infos(std::slice::from_ref(&queue_info));
+    let logical_device =
+        unsafe { instance.create_device(device, &create_info, None) }.map_err(|e| e.to_string())?;
+    unsafe { logical_device.destroy_device(None) };
+    Ok(())
+}
+
+fn create_instance(entry: &ash::Entry) -> Result<(ash::Instance, u32), VulkanError> {
+    create_instance_with_extensions(entry, &[])
+}
+
+fn create_instance_with_extensions(
+    entry: &ash::Entry,
+    extensions: &[*const std::ffi::c_char],
+) -> Result<(ash::Instance, u32), VulkanError> {
+    // Vulkan 1.0 loaders reject instances asking for a newer api version
+    let api_version = match unsafe { entry.try_enumerate_instance_version() } {
+        Ok(Some(version)) if version >= vk::API_VERSION_1_1 => vk::API_VERSION_1_1,
+        _ => vk::API_VERSION_1_0,
+    };
+    let app_name = c"GPUInfoApp";
+    let app_info = vk::ApplicationInfo::default()
+        .application_name(app_name)
+        .application_version(0)
+        .engine_name(app_name)
+        .engine_version(0)
+        .api_version(api_version);
+
+    let create_info = vk::InstanceCreateInfo::default()
+        .application_info(&app_info)
+        .enabled_extension_names(extensions);
+    let result = match unsafe { entry.create_instance(&create_info, None) } {
+        Ok(instance) => Ok((instance, api_version)),
+        // Some drivers validate `VkApplicationInfo` strictly and fail on
+        // fields they do not expect, without it the instance is Vulkan 1.0
+        Err(e) if e != vk::Result::ERROR_INCOMPATIBLE_DRIVER && !is_out_of_memory(e) => {
+            let create_info = vk::InstanceCreateInfo::default().enabled_extension_names(extensions);
+            unsafe { entry.create_instance(&create_info, None) }
+                .map(|instance| (instance, vk::API_VERSION_1_0))
+                .map_err(|_| e)
+        }
+        Err(e) => Err(e),
+    };
+    result.map_err(|e| match e {
+        // The loader is installed but no driver (ICD) is
+        vk::Result::ERROR_INCOMPATIBLE_DRIVER => VulkanError::NotSupported,
+        e => VulkanError::OperationFailed(e.to_string()),
+    })
+}
+
+fn is_out_of_memory(result: vk::Result) -> bool {
+    matches!(
+        result,
+        vk::Result::ERROR_OUT_OF_HOST_MEMORY | vk::Result::ERROR_OUT_OF_DEVICE_MEMORY
+    )
+}
+
+/// How often to re-query when devices appear between the count and the list
+const MAX_ENUMERATION_ATTEMPTS: usize = 4;
+
+/// `vkEnumeratePhysicalDevices`, re-querying with the new count while the
+/// driver returns `VK_INCOMPLETE`, e.g. when an eGPU is plugged in or a
+/// device is hot-added to a VM between the two calls. Devices removed
+/// between the calls shrink the count the driver writes back. Should the
+/// list keep growing, the devices of the last attempt are returned.
+pub(crate) unsafe fn enumerate_physical_devices(
+    instance: &ash::Instance,
+) -> Result<Vec<vk::PhysicalDevice>, vk::Result> {
+    let enumerate = instance.fp_v1_0().enumerate_physical_devices;
+    let handle = instance.handle();
+    let mut devices = Vec::new();
+    for _ in 0..MAX_ENUMERATION_ATTEMPTS {
+        let mut count = 0;
+        enumerate(handle, &mut count, std::ptr::null_mut()).result()?;
+        devices = vec![vk::PhysicalDevice::null(); count as usize];
+        let result = enumerate(handle, &mut count, devices.as_mut_ptr());
+        devices.truncate(count as usize);
+        match result {
+            vk::Result::SUCCESS => return Ok(devices),
+            vk::Result::INCOMPLETE => continue,
+            e => return Err(e),
+        }
+    }
+    log::warn!(
+        "vkEnumeratePhysicalDevices kept returning VK_INCOMPLETE, some devices may be missing"
+    );
+    Ok(devices)
+}
+
+/// The bytes of a fixed size string up to its NUL, or all of them when a
+/// driver fills the array without one
+fn name_bytes(name: &[std::ffi::c_char]) -> Vec<u8> {
+    name.iter()
+        .map(|&c| c as u8)
+        .take_while(|&b| b != 0)
+        .collect()
+}
+
+/// `driverVersion` is vendor defined, only some drivers use the `VK_MAKE_API_VERSION` layout
+fn decode_driver_version(vendor_id: u32, version: u32) -> String {
+    match vendor_id {
+        // 10 bits major, 8 bits minor, 8 bits secondary, 6 bits tertiary
+        0x10DE => format!(
+            "{}.{}.{}.{}",
+            version >> 22,
+            (version >> 14) & 0xFF,
+            (version >> 6) & 0xFF,
+            version & 0x3F
+        ),
+        // The Windows driver packs 18 bits major and 14 bits minor, Mesa uses the Vulkan layout
+        0x8086 if cfg!(windows) => format!("{}.{}", version >> 14, version & 0x3FFF),
+        _ => format!(
+            "{}.{}.{}",
+            vk::api_version_major(version),
+            vk::api_version_minor(version),
+            vk::api_version_patch(version)
+        ),
+    }
+}
+
+/// Format as `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx`, matching NVML without its `GPU-` prefix
+fn format_uuid(uuid: &[u8; 16]) -> String {
+    let hex = uuid.iter().map(|b| format!("{b:02x}")).collect::<String>();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// Properties only reachable through `vkGetPhysicalDeviceProperties2`, and
+/// features read from the extension list enumerated for it
+#[derive(Default)]
+struct ExtendedProperties {
+    device_uuid: Option<[u8; 16]>,
+    luid: Option<u64>,
+    pci_address: Option<PciAddress>,
+    driver: Option<DriverProperties>,
+    ray_tracing: bool,
+    mesh_shading: bool,
+}
+
+struct DriverProperties {
+    id: vk::DriverId,
+    name: String,
+    conformance_version: String,
+}
+
+fn query_extended_properties(
+    instance: &ash::Instance,
+    device: vk::PhysicalDevice,
+) -> ExtendedProperties {
+    let extensions =
+        unsafe { instance.enumerate_device_extension_properties(device) }.unwrap_or_default();
+    let has_extension = |name: &CStr| {
+        extensions
+            .iter()
+            .any(|ext| ext.extension_name_as_c_str() == Ok(name))
+    };
+    let has_pci_bus_info = has_extension(ash::ext::pci_bus_info::NAME);
+    let has_driver_properties = has_extension(ash::khr::driver_properties::NAME)
+        || unsafe { instance.get_physical_device_properties(device) }.api_version
+            >= vk::API_VERSION_1_2;
+
+    let mut id_properties = vk::PhysicalDeviceIDProperties::default();
+    let mut pci_properties = vk::PhysicalDevicePCIBusInfoPropertiesEXT::default();
+    let mut driver_properties = vk::PhysicalDeviceDriverProperties::default();
+    let mut properties2 = vk::PhysicalDeviceProperties2::default().push_next(&mut id_properties);
+    if has_pci_bus_info {
+        properties2 = properties2.push_next(&mut pci_properties);
+    }
+    if has_driver_properties {
+        properties2 = properties2.push_next(&mut driver_properties);
+    }
+    unsafe { instance.get_physical_device_properties2(device, &mut properties2) };
+    let conformance = driver_properties.conformance_version;
+
+    ExtendedProperties {
+        device_uuid: Some(id_properties.device_uuid),
+        luid: (id_properties.device_luid_valid == vk::TRUE)
+            .then(|| u64::from_le_bytes(id_properties.device_luid)),
+        pci_address: has_pci_bus_info.then_some(PciAddress {
+            domain: pci_properties.pci_domain,
+            bus: pci_properties.pci_bus as u8,
+            device: pci_properties.pci_device as u8,
+            function: pci_properties.pci_function as u8,
+        }),
+        driver: has_driver_properties.then(|| DriverProperties {
+            id: driver_properties.driver_id,
+            name: driver_properties
+                .driver_name_as_c_str()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            conformance_version: format!(
+                "{}.{}.{}.{}",
+                conformance.major, conformance.minor, conformance.subminor, conformance.patch
+            ),
+        }),
+        ray_tracing: has_extension(ash::khr::ray_tracing_pipeline::NAME)
+            || has_extension(ash::khr::ray_query::NAME),
+        mesh_shading: has_extension(ash::ext::mesh_shader::NAME)
+            || has_extension(ash::nv::mesh_shader::NAME),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
     use test_log::test;
 
+    proptest! {
+        #[test]
+        fn test_decode_nvidia_driver_version(
+            major in 0u32..1024,
+            minor in 0u32..256,
+            secondary in 0u32..256,
+            tertiary in 0u32..64,
+        ) {
+            let version = major << 22 | minor << 14 | secondary << 6 | tertiary;
+            prop_assert_eq!(
+                decode_driver_version(0x10DE, version),
+                format!("{major}.{minor}.{secondary}.{tertiary}")
+            );
+        }
+
+        #[test]
+        fn test_decode_vulkan_driver_version(
+            vendor_id in prop_oneof![Just(0x1002u32), Just(0x13B5), Just(0x5143)],
+            major in 0u32..128,
+            minor in 0u32..1024,
+            patch in 0u32..4096,
+        ) {
+            let version = vk::make_api_version(0, major, minor, patch);
+            prop_assert_eq!(
+                decode_driver_version(vendor_id, version),
+                format!("{major}.{minor}.{patch}")
+            );
+        }
+
+        #[test]
+        fn test_decode_intel_driver_version(major in 0u32..(1 << 18), minor in 0u32..(1 << 14)) {
+            let version = major << 14 | minor;
+            let expected = if cfg!(windows) {
+                format!("{major}.{minor}")
+            } else {
+                format!(
+                    "{}.{}.{}",
+                    vk::api_version_major(version),
+                    vk::api_version_minor(version),
+                    vk::api_version_patch(version)
+                )
+            };
+            prop_assert_eq!(decode_driver_version(0x8086, version), expected);
+        }
+    }
+
+    #[test]
+    fn test_name_bytes() {
+        // "显卡" in GBK, then the unused tail of the array
+        let mut name = [0 as std::ffi::c_char; vk::MAX_PHYSICAL_DEVICE_NAME_SIZE];
+        for (c, b) in name.iter_mut().zip([0xCF, 0xD4, 0xBF, 0xA8]) {
+            *c = b as std::ffi::c_char;
+        }
+        let raw_name = name_bytes(&name);
+        assert_eq!(raw_name, [0xCF, 0xD4, 0xBF, 0xA8]);
+        assert!(!String::from_utf8_lossy(&raw_name).is_empty());
+        // Not terminated
+        assert_eq!(name_bytes(&[b'A' as std::ffi::c_char; 4]), b"AAAA");
+    }
+
+    #[test]
+    fn test_memory_heaps() {
+        // An APU with a 512 MB carve-out and a host visible GTT heap
+        let mut properties = vk::PhysicalDeviceMemoryProperties {
+            memory_heap_count: 2,
+            memory_type_count: 3,
+            ..Default::default()
+        };
+        properties.memory_heaps[0] = vk::MemoryHeap {
+            size: 512 << 20,
+            flags: vk::MemoryHeapFlags::DEVICE_LOCAL,
+        };
+        properties.memory_heaps[1] = vk::MemoryHeap {
+            size: 16 << 30,
+            flags: vk::MemoryHeapFlags::empty(),
+        };
+        properties.memory_types[0] = vk::MemoryType {
+            property_flags: vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            heap_index: 0,
+        };
+        properties.memory_types[1] = vk::MemoryType {
+            property_flags: vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_COHERENT,
+            heap_index: 1,
+        };
+        properties.memory_types[2] = vk::MemoryType {
+            property_flags: vk::MemoryPropertyFlags::HOST_VISIBLE
+                | vk::MemoryPropertyFlags::HOST_CACHED,
+            heap_index: 1,
+        };
+
+        let heaps = memory_heaps(&properties);
+        assert_eq!(heaps.len(), 2);
+        assert_eq!(heaps[0].size, Bytes::from_mb(512));
+        assert!(heaps[0].device_local && !heaps[0].host_visible());
+        assert_eq!(heaps[1].size, Bytes::from_mb(16 * 1024));
+        assert_eq!(heaps[1].memory_types.len(), 2);
+        assert!(heaps[1].host_visible() && heaps[1].memory_types[1].host_cached);
+
+        assert_eq!(
+            split_memory(0x1002, &GPUKind::Integrated, &heaps),
+            (Bytes::from_mb(512), Some(Bytes::from_mb(16 * 1024)))
+        );
+        assert_eq!(
+            split_memory(0x8086, &GPUKind::Integrated, &heaps),
+            (Bytes(0), Some(Bytes::from_mb(16 * 1024)))
+        );
+        assert_eq!(
+            split_memory(0x10DE, &GPUKind::Discrete, &heaps[..1]),
+            (Bytes::from_mb(512), None)
+        );
+    }
+
     #[test]
-    fn test_retrieve_gpu_info_via_vk() {
-        let result = retrieve_gpu_info_via_vk();
+    fn test_retrieve_vulkan_gpus() {
+        let result = retrieve_vulkan_gpus();
         eprintln!("{:#?}", result);
         assert!(match result {
-            Ok(gpus) => !gpus.is_empty(),
+            Ok(_) => true,
             Err(e) => e.is_not_supported(),
         });
     }
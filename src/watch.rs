@@ -0,0 +1,314 @@
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+use crate::{Alert, Error, GpuPreference, GpuQuery, GPU};
+
+/// How often [`wait_for_gpus`] enumerates
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Enumerate until at least `min_count` GPUs show up, for services started
+/// before the GPU driver finished loading, e.g. systemd units racing the
+/// NVIDIA kernel module. Returns as soon as enough GPUs are found, with the
+/// fields of [`GpuQuery::new`]. Enumeration errors are retried, as a
+/// driver still initializing may fail; the last one is part of the
+/// [`Error::GpusNotReady`] returned on timeout, with the display
+/// controllers no driver exposes on Linux.
+pub fn wait_for_gpus(min_count: usize, timeout: Duration) -> Result<Vec<GPU>, Error> {
+    wait_for_gpus_with(&GpuQuery::new(), min_count, timeout)
+}
+
+/// [`wait_for_gpus`] enumerating with `query`, so excluded GPUs do not count
+/// towards `min_count`
+pub fn wait_for_gpus_with(
+    query: &GpuQuery,
+    min_count: usize,
+    timeout: Duration,
+) -> Result<Vec<GPU>, Error> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let (found, error) = match query.run() {
+            Ok(gpus) if gpus.len() >= min_count => return Ok(gpus),
+            Ok(gpus) => (gpus.len(), None),
+            Err(e) => (0, Some(e)),
+        };
+        let now = Instant::now();
+        if now >= deadline {
+            let mut diagnostics: Vec<String> = error.iter().map(|e| e.to_string()).collect();
+            diagnostics.extend(unready_devices());
+            if diagnostics.is_empty() {
+                diagnostics.push(format!("no GPU appeared within {timeout:?}"));
+            }
+            return Err(Error::GpusNotReady {
+                min_count,
+                found,
+                diagnostics,
+            });
+        }
+        std::thread::sleep(WAIT_POLL_INTERVAL.min(deadline - now));
+    }
+}
+
+/// Display controllers on the PCI bus no Vulkan driver exposes yet
+#[cfg(target_os = "linux")]
+fn unready_devices() -> Vec<String> {
+    crate::retrieve_unexposed_gpus()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|gpu| {
+            let address = gpu.pci_address.map(|a| a.to_string()).unwrap_or_default();
+            match gpu.kernel_driver {
+                Some(driver) => format!("{address} {} bound to {driver} but not ready", gpu.name),
+                None => format!("{address} {} has no driver bound", gpu.name),
+            }
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+fn unready_devices() -> Vec<String> {
+    vec![]
+}
+
+/// A change to a GPU noticed by a [`DriverWatcher`] or a
+/// [`GpuMonitor`](crate::GpuMonitor)
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum GpuEvent {
+    /// The driver of `gpu` was updated or reloaded while the process ran,
+    /// e.g. by Windows Update or by reloading the kernel module on Linux.
+    /// Devices created before may be lost, and pipeline caches built by the
+    /// old driver rejected.
+    DriverChanged { gpu: GPU, previous_version: String },
+    /// `gpu`, as last enumerated, is gone: an eGPU was unplugged, or its
+    /// driver was unloaded or did not recover from a reset. Devices created
+    /// on it are lost and cached values about it are stale.
+    Removed { gpu: GPU },
+    /// A sample of `gpu` crossed the threshold of `alert`
+    AlertRaised { gpu: GPU, alert: Alert, value: f32 },
+    /// A sample of `gpu` fell far enough below the threshold of `alert`,
+    /// after it was raised
+    AlertCleared { gpu: GPU, alert: Alert, value: f32 },
+}
+
+/// Re-enumerates the GPUs on a background thread every `interval` and
+/// reports driver changes and removed GPUs. GPUs are matched across enumerations like a
+/// [`GpuPreference`], as a driver update can rename and reorder them. The
+/// thread stops when the watcher is dropped.
+pub struct DriverWatcher {
+    events: Receiver<GpuEvent>,
+    // Dropping the sender wakes the thread up to exit
+    _stop: Sender<()>,
+}
+
+impl DriverWatcher {
+    /// Takes the first snapshot before returning, so its errors surface
+    /// here. Later enumeration errors are logged and the check is skipped.
+    pub fn spawn(interval: Duration) -> Result<Self, Error> {
+        Self::spawn_with(GpuQuery::new(), interval)
+    }
+
+    /// Enumerate with `query`, e.g. to leave out the GPUs it excludes
+    pub fn spawn_with(query: GpuQuery, interval: Duration) -> Result<Self, Error> {
+        let mut snapshot = Snapshot::take(&query)?;
+        let (sender, events) = mpsc::channel();
+        let (stop, stopped) = mpsc::channel::<()>();
+
+        std::thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                // Enumeration that raced a removal succeeds when repeated
+                let current = match Snapshot::take(&query) {
+                    Err(e) if e.is_device_removed() => Snapshot::take(&query),
+                    result => result,
+                };
+                let current = match current {
+                    Ok(current) => current,
+                    Err(e) => {
+                        log::debug!("skipping driver check: {e}");
+                        continue;
+                    }
+                };
+                let events = driver_changes(&snapshot, &current);
+                for event in events.into_iter().chain(removals(&snapshot, &current)) {
+                    if sender.send(event).is_err() {
+                        return;
+                    }
+                }
+                snapshot = current;
+            }
+        });
+
+        Ok(Self {
+            events,
+            _stop: stop,
+        })
+    }
+
+    /// Events in the order they were noticed, e.g. `for event in watcher.events().try_iter()`
+    pub fn events(&self) -> &Receiver<GpuEvent> {
+        &self.events
+    }
+}
+
+struct Snapshot {
+    gpus: Vec<GPU>,
+    /// Identifies the loaded instance of each GPU's kernel module, which a
+    /// reload of the same build changes too
+    driver_loads: Vec<Option<u64>>,
+}
+
+impl Snapshot {
+    fn take(query: &GpuQuery) -> Result<Self, Error> {
+        let gpus = query.run()?;
+        let driver_loads = gpus.iter().map(driver_load).collect();
+        Ok(Self { gpus, driver_loads })
+    }
+}
+
+/// sysfs gives a module directory a new inode number each time it is loaded
+#[cfg(target_os = "linux")]
+fn driver_load(gpu: &GPU) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+
+    let address = gpu.pci_address?;
+    let module = format!("/sys/bus/pci/devices/{address}/driver/module");
+    Some(std::fs::metadata(module).ok()?.ino())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn driver_load(_gpu: &GPU) -> Option<u64> {
+    None
+}
+
+/// GPUs in `previous` that have no counterpart in `current`
+fn removals(previous: &Snapshot, current: &Snapshot) -> Vec<GpuEvent> {
+    previous
+        .gpus
+        .iter()
+        .filter(|gpu| GpuPreference::new(gpu).resolve(&current.gpus).is_none())
+        .map(|gpu| GpuEvent::Removed { gpu: gpu.clone() })
+        .collect()
+}
+
+/// GPUs in `current` whose driver version or kernel module load differs from
+/// their counterpart in `previous`. GPUs that appeared or disappeared are skipped.
+fn driver_changes(previous: &Snapshot, current: &Snapshot) -> Vec<GpuEvent> {
+    current
+        .gpus
+        .iter()
+        .zip(&current.driver_loads)
+        .filter_map(|(gpu, load)| {
+            let matched = GpuPreference::new(gpu).resolve(&previous.gpus)?;
+            let index = previous
+                .gpus
+                .iter()
+                .position(|previous| std::ptr::eq(previous, matched))?;
+            let reloaded = matches!(
+                (load, previous.driver_loads[index]),
+                (Some(load), Some(previous)) if *load != previous
+            );
+            (reloaded || gpu.driver_version != matched.driver_version).then(|| {
+                GpuEvent::DriverChanged {
+                    gpu: gpu.clone(),
+                    previous_version: matched.driver_version.clone(),
+                }
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_driver_changes() {
+        let gpu = |name: &str, driver_version: &str| {
+            GPU::builder()
+                .vendor("AMD")
+                .name(name)
+                .driver_version(driver_version)
+                .build()
+        };
+        let previous = Snapshot {
+            gpus: vec![
+                gpu("Radeon RX 7600", "2.0.294"),
+                gpu("Radeon 780M", "2.0.294"),
+            ],
+            driver_loads: vec![Some(41), Some(41)],
+        };
+
+        // Reordered, only the first enumerated GPU got a new driver
+        let current = Snapshot {
+            gpus: vec![
+                gpu("Radeon 780M", "2.0.301"),
+                gpu("Radeon RX 7600", "2.0.294"),
+            ],
+            driver_loads: vec![Some(41), Some(41)],
+        };
+        let events = driver_changes(&previous, &current);
+        assert_eq!(events.len(), 1);
+        let GpuEvent::DriverChanged {
+            gpu,
+            previous_version,
+        } = &events[0]
+        else {
+            panic!("expected a driver change, got {:?}", events[0]);
+        };
+        assert_eq!(gpu.name, "Radeon 780M");
+        assert_eq!(previous_version, "2.0.294");
+
+        // The same build loaded again
+        let reloaded = Snapshot {
+            gpus: previous.gpus.clone(),
+            driver_loads: vec![Some(57), None],
+        };
+        assert_eq!(driver_changes(&previous, &reloaded).len(), 1);
+        assert!(driver_changes(&previous, &previous).is_empty());
+    }
+
+    #[test]
+    fn test_removals() {
+        let gpu = |name: &str| GPU::builder().vendor("AMD").name(name).build();
+        let previous = Snapshot {
+            gpus: vec![gpu("Radeon RX 7600"), gpu("Radeon 780M")],
+            driver_loads: vec![None, None],
+        };
+        // The eGPU was unplugged
+        let current = Snapshot {
+            gpus: vec![gpu("Radeon 780M")],
+            driver_loads: vec![None],
+        };
+        let events = removals(&previous, &current);
+        assert!(matches!(
+            &events[..],
+            [GpuEvent::Removed { gpu }] if gpu.name == "Radeon RX 7600"
+        ));
+        assert!(removals(&current, &previous).is_empty());
+    }
+
+    #[test]
+    fn test_wait_for_gpus() {
+        assert!(wait_for_gpus(0, Duration::ZERO).is_ok());
+        assert!(wait_for_gpus_with(&GpuQuery::new(), 0, Duration::ZERO).is_ok());
+        match wait_for_gpus(usize::MAX, Duration::from_millis(300)) {
+            Err(Error::GpusNotReady {
+                min_count,
+                diagnostics,
+                ..
+            }) => {
+                assert_eq!(min_count, usize::MAX);
+                assert!(!diagnostics.is_empty());
+            }
+            other => panic!("expected a timeout, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_driver_watcher() {
+        let watcher = DriverWatcher::spawn(Duration::from_millis(10)).unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        // Nothing changes while the test runs
+        let events = watcher.events().try_iter().collect::<Vec<_>>();
+        assert!(events.is_empty(), "{events:?}");
+    }
+}
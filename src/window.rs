@@ -0,0 +1,30 @@
+use raw_window_handle::RawWindowHandle;
+
+use crate::{Error, GPU};
+
+/// The enumerated GPU presenting a window, i.e. the one driving the display the
+/// window is on. `None` for windowing systems without a lookup (X11, Wayland)
+/// or windows that are not on any display.
+///
+/// On macOS this must be called on the main thread.
+pub fn gpu_for_window(handle: RawWindowHandle) -> Result<Option<GPU>, Error> {
+    match handle {
+        #[cfg(windows)]
+        RawWindowHandle::Win32(handle) => {
+            let Some(luid) = crate::dxgi::adapter_luid_for_window(handle.hwnd.get())? else {
+                return Ok(None);
+            };
+            Ok(crate::retrieve_gpu_info()?
+                .into_iter()
+                .find(|gpu| gpu.luid == Some(luid)))
+        }
+        #[cfg(target_os = "macos")]
+        RawWindowHandle::AppKit(handle) => {
+            let Some(display_id) = crate::metal::display_id_for_view(handle.ns_view) else {
+                return Ok(None);
+            };
+            Ok(crate::metal::gpu_for_display(display_id)?.map(GPU::from))
+        }
+        _ => Ok(None),
+    }
+}
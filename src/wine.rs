@@ -0,0 +1,187 @@
+use std::ffi::{c_char, CStr};
+
+use windows::core::{HSTRING, PCWSTR};
+use windows::Win32::Storage::FileSystem::{
+    GetFileVersionInfoSizeW, GetFileVersionInfoW, VerQueryValueW, VS_FIXEDFILEINFO,
+};
+use windows::Win32::System::SystemInformation::GetSystemDirectoryW;
+
+use crate::dxgi::DxgiAdapter;
+use crate::vulkan::VulkanGpu;
+use crate::{Error, PciAddress};
+
+/// How Wine or Proton presents the host's GPUs to this Windows process, for
+/// game support tooling
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct WineEnvironment {
+    /// e.g. `9.0`
+    pub wine_version: String,
+    /// Kernel of the host, e.g. `Linux 6.8.0-45-generic`
+    pub host_system: Option<String>,
+    /// The Proton release that set up the prefix, e.g. `9.0-300`, `None`
+    /// outside of Steam
+    pub proton_version: Option<String>,
+    /// From the version resource of the DXVK `dxgi.dll`, `None` when Wine's
+    /// own DXGI is used or the build has no version resource
+    pub dxvk_version: Option<String>,
+    /// From the version resource of the vkd3d-proton `d3d12.dll`
+    pub vkd3d_version: Option<String>,
+    pub adapters: Vec<WineAdapter>,
+}
+
+/// A DXGI adapter and the host GPU it is backed by
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "specta", derive(specta::Type))]
+pub struct WineAdapter {
+    /// The adapter as Windows applications see it, names and ids may be
+    /// spoofed, e.g. by `DXVK_CONFIG` or `dxgi.customDeviceId`
+    pub name: String,
+    pub vendor_id: u32,
+    pub device_id: u32,
+    pub luid: u64,
+    /// The host GPU, e.g. `AMD Radeon RX 7900 XTX (RADV NAVI31)`, as its
+    /// Vulkan driver reports it through winevulkan
+    pub host_name: Option<String>,
+    /// The host's Vulkan driver, e.g. `radv` or `NVIDIA`
+    pub host_driver: Option<String>,
+    pub host_pci_address: Option<PciAddress>,
+}
+
+type WineGetVersion = unsafe extern "C" fn() -> *const c_char;
+type WineGetHostVersion = unsafe extern "C" fn(*mut *const c_char, *mut *const c_char);
+
+/// The Wine or Proton environment this process runs in, `None` on Windows
+/// itself. Adapters are matched to host GPUs by LUID, which DXVK takes from
+/// the Vulkan device.
+pub fn wine_environment() -> Result<Option<WineEnvironment>, Error> {
+    let Ok(ntdll) = libloading::os::windows::Library::open_already_loaded("ntdll.dll") else {
+        return Ok(None);
+    };
+    let Ok(get_version) = (unsafe { ntdll.get::<WineGetVersion>(b"wine_get_version\0") }) else {
+        return Ok(None);
+    };
+    let wine_version = unsafe { CStr::from_ptr(get_version()) }
+        .to_string_lossy()
+        .into_owned();
+    let host_system = unsafe { ntdll.get::<WineGetHostVersion>(b"wine_get_host_version\0") }
+        .ok()
+        .map(|get_host_version| {
+            let (mut sysname, mut release) = (std::ptr::null(), std::ptr::null());
+            unsafe { get_host_version(&mut sysname, &mut release) };
+            [sysname, release]
+                .into_iter()
+                .filter(|value| !value.is_null())
+                .map(|value| unsafe { CStr::from_ptr(value) }.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(" ")
+        });
+
+    let adapters = crate::dxgi::retrieve_gpu_info_via_dxgi()?;
+    let vk_gpus = match crate::vulkan::retrieve_vulkan_gpus() {
+        Ok(vk_gpus) => vk_gpus,
+        Err(e) if e.is_not_supported() => vec![],
+        Err(e) => return Err(e.into()),
+    };
+
+    Ok(Some(WineEnvironment {
+        wine_version,
+        host_system,
+        proton_version: proton_version(),
+        dxvk_version: crate::translation::wine_dll_is_native("dxgi")
+            .then(|| system_dll_version("dxgi.dll"))
+            .flatten(),
+        vkd3d_version: crate::translation::wine_dll_is_native("d3d12")
+            .then(|| system_dll_version("d3d12.dll"))
+            .flatten(),
+        adapters: map_adapters(adapters, &vk_gpus),
+    }))
+}
+
+fn map_adapters(adapters: Vec<DxgiAdapter>, vk_gpus: &[VulkanGpu]) -> Vec<WineAdapter> {
+    adapters
+        .into_iter()
+        .filter(|adapter| !adapter.is_software)
+        .map(|adapter| {
+            let host = vk_gpus.iter().find(|gpu| gpu.luid == Some(adapter.luid));
+            WineAdapter {
+                name: adapter.name,
+                vendor_id: adapter.vendor_id,
+                device_id: adapter.device_id,
+                luid: adapter.luid,
+                host_name: host.map(|gpu| gpu.name.clone()),
+                host_driver: host.and_then(|gpu| gpu.driver_name.clone()),
+                host_pci_address: host.and_then(|gpu| gpu.pci_address),
+            }
+        })
+        .collect()
+}
+
+/// Proton writes its version into the `version` file of the compatdata
+/// directory Steam passes, a host path reached through Wine's `Z:` drive
+fn proton_version() -> Option<String> {
+    let compat_data = std::env::var("STEAM_COMPAT_DATA_PATH").ok()?;
+    let path = format!("Z:{}\\version", compat_data.replace('/', "\\"));
+    let version = std::fs::read_to_string(path).ok()?;
+    // `9.0-300`, older releases put a timestamp before a `proton-` name
+    let version = version.split_whitespace().last()?;
+    Some(version.trim_start_matches("proton-").to_string())
+}
+
+/// The product version of a DLL in `system32`, e.g. `2.3.0.0`
+fn system_dll_version(dll: &str) -> Option<String> {
+    let mut buffer = [0u16; 260];
+    let len = unsafe { GetSystemDirectoryW(Some(&mut buffer)) } as usize;
+    if len == 0 || len > buffer.len() {
+        return None;
+    }
+    let path = HSTRING::from(format!(
+        "{}\\{dll}",
+        String::from_utf16_lossy(&buffer[..len])
+    ));
+    let path = PCWSTR(path.as_ptr());
+
+    let size = unsafe { GetFileVersionInfoSizeW(path, None) };
+    if size == 0 {
+        return None;
+    }
+    let mut info = vec![0u8; size as usize];
+    unsafe { GetFileVersionInfoW(path, None, size, info.as_mut_ptr().cast()) }.ok()?;
+    let mut fixed: *mut std::ffi::c_void = std::ptr::null_mut();
+    let mut fixed_len = 0;
+    let found = unsafe {
+        VerQueryValueW(
+            info.as_ptr().cast(),
+            windows::core::w!("\\"),
+            &mut fixed,
+            &mut fixed_len,
+        )
+    };
+    if !found.as_bool() || fixed.is_null() {
+        return None;
+    }
+    let fixed = unsafe { &*(fixed as *const VS_FIXEDFILEINFO) };
+    let (ms, ls) = (fixed.dwProductVersionMS, fixed.dwProductVersionLS);
+    Some(format!(
+        "{}.{}.{}.{}",
+        ms >> 16,
+        ms & 0xffff,
+        ls >> 16,
+        ls & 0xffff
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wine_environment() {
+        match wine_environment() {
+            Ok(environment) => eprintln!("{environment:#?}"),
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Invariants between backends that report the same GPUs. These need real
+//! hardware, run them with `cargo test --all-features -- --ignored`.
+
+#[allow(unused_imports)]
+use gpu_info::*;
+
+/// Backends round sizes differently and some reserve memory for the driver
+#[allow(dead_code)]
+fn assert_vram_close(name: &str, a: Bytes, b: Bytes) {
+    let (low, high) = (a.min(b), a.max(b));
+    assert!(
+        high.0 - low.0 <= high.0 / 10,
+        "{name}: VRAM differs by more than 10% ({a} vs {b})"
+    );
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+#[ignore = "requires GPU hardware"]
+fn test_vulkan_matches_sysfs() {
+    let vk_gpus = retrieve_vulkan_gpus().unwrap();
+    let pci_gpus = retrieve_gpu_info_via_pci().unwrap();
+
+    let hardware = vk_gpus
+        .iter()
+        .filter(|gpu| !matches!(gpu.kind, GPUKind::Software | GPUKind::Virtual))
+        .collect::<Vec<_>>();
+    assert!(
+        hardware.len() <= pci_gpus.len(),
+        "vulkan reports {} hardware GPUs but sysfs only {}",
+        hardware.len(),
+        pci_gpus.len()
+    );
+
+    for vk_gpu in hardware {
+        let pci_gpu = pci_gpus
+            .iter()
+            .find(|pci_gpu| match vk_gpu.pci_address {
+                Some(address) => pci_gpu.address == address,
+                None => {
+                    pci_gpu.vendor_id == vk_gpu.vendor_id && pci_gpu.device_id == vk_gpu.device_id
+                }
+            })
+            .unwrap_or_else(|| panic!("{} is not on the PCI bus", vk_gpu.name));
+        assert_eq!(vk_gpu.vendor_id, pci_gpu.vendor_id, "{}", vk_gpu.name);
+        assert_eq!(vk_gpu.vendor, pci_gpu.vendor, "{}", vk_gpu.name);
+    }
+}
+
+#[cfg(all(feature = "nvml", not(target_os = "macos")))]
+#[test]
+#[ignore = "requires an NVIDIA GPU"]
+fn test_vulkan_matches_nvml() {
+    let vk_gpus = retrieve_vulkan_gpus().unwrap();
+    let nvml_gpus = retrieve_gpu_info_via_nvml().unwrap();
+
+    let vk_nvidia = vk_gpus
+        .iter()
+        .filter(|gpu| gpu.vendor_id == 0x10DE)
+        .collect::<Vec<_>>();
+    assert_eq!(vk_nvidia.len(), nvml_gpus.len());
+
+    for nvml_gpu in &nvml_gpus {
+        let vk_gpu = vk_nvidia
+            .iter()
+            .find(|vk_gpu| {
+                vk_gpu.pci_address.is_some() && vk_gpu.pci_address == nvml_gpu.pci_address
+            })
+            .unwrap_or_else(|| panic!("{} is not exposed by vulkan", nvml_gpu.name));
+        assert_vram_close(&nvml_gpu.name, vk_gpu.vram, nvml_gpu.memory_total);
+    }
+}
+
+#[cfg(windows)]
+#[test]
+#[ignore = "requires GPU hardware"]
+fn test_vulkan_matches_dxgi() {
+    let vk_gpus = retrieve_vulkan_gpus().unwrap();
+    let adapters = retrieve_gpu_info_via_dxgi().unwrap();
+
+    for vk_gpu in vk_gpus.iter().filter(|gpu| gpu.kind != GPUKind::Software) {
+        let adapter = adapters
+            .iter()
+            .find(|adapter| Some(adapter.luid) == vk_gpu.luid)
+            .unwrap_or_else(|| panic!("{} has no dxgi adapter", vk_gpu.name));
+        assert_eq!(vk_gpu.vendor_id, adapter.vendor_id, "{}", vk_gpu.name);
+        if vk_gpu.kind == GPUKind::Discrete {
+            assert_vram_close(&vk_gpu.name, vk_gpu.vram, adapter.dedicated_video_memory);
+        }
+    }
+
+    // Merging by LUID leaves one GPU per hardware adapter
+    let gpus = retrieve_gpu_info().unwrap();
+    let hardware_adapters = adapters
+        .iter()
+        .filter(|adapter| !adapter.is_software)
+        .count();
+    assert_eq!(
+        gpus.iter()
+            .filter(|gpu| gpu.kind != GPUKind::Software)
+            .count(),
+        hardware_adapters
+    );
+}
+
+#[cfg(all(feature = "system-profiler", target_os = "macos"))]
+#[test]
+#[ignore = "requires GPU hardware"]
+fn test_metal_matches_system_profiler() {
+    let gpus = retrieve_gpu_info_via_metal().unwrap();
+    let discrepancies = system_profiler::verify_with_system_profiler(&gpus).unwrap();
+    assert!(discrepancies.is_empty(), "{discrepancies:#?}");
+}